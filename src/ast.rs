@@ -5,19 +5,24 @@ use std::{
 
 use crate::common::{AllocMode, StructKind};
 
+pub mod visit;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     pub line: u32,
     pub col: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Range {
     pub from: Position,
     pub to: Position,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Located<T> {
     pub range: Range,
     pub value: T,
@@ -96,11 +101,18 @@ impl<T> Located<Box<T>> {
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryOp {
     Add,
     Sub,
     Mul,
     Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
     Equals,
     NotEquals,
     LessThan,
@@ -109,18 +121,56 @@ pub enum BinaryOp {
     GreaterThanOrEquals,
 }
 
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Mod => "%",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Equals => "==",
+            BinaryOp::NotEquals => "!=",
+            BinaryOp::LessThan => "<",
+            BinaryOp::LessThanOrEquals => "<=",
+            BinaryOp::GreaterThan => ">",
+            BinaryOp::GreaterThanOrEquals => ">=",
+        })
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryOp {
     Not,
+    Neg,
+    BitNot,
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UnaryOp::Not => "not",
+            UnaryOp::Neg => "-",
+            UnaryOp::BitNot => "~",
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MultiOp {
     And,
     Or,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CallExpr {
     pub name: String,
     pub generic_args: Option<Vec<Located<UnresolvedType>>>,
@@ -128,40 +178,75 @@ pub struct CallExpr {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SizeOfExpr {
     pub ty: Located<UnresolvedType>,
 }
 
+// 可変長引数の次の値を指定した型で読み出す
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VaArgExpr {
+    pub ty: Located<UnresolvedType>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableRefExpr {
     pub name: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct NumberLiteralExpr {
     pub value: String,
+    // 10u8 の u8 のような型接尾辞
+    pub suffix: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FloatLiteralExpr {
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CharLiteralExpr {
+    pub value: u8,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StringLiteralExpr {
     pub value: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BoolLiteralExpr {
     pub value: bool,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructLiteralExpr {
     pub name: String,
     pub generic_args: Option<Vec<Located<UnresolvedType>>>,
     pub fields: Vec<(String, LocatedExpr)>,
 }
 
+// (1, 2u8)
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TupleLiteralExpr {
+    pub elements: Vec<LocatedExpr>,
+}
+
 pub type LocatedExpr = Located<Box<Expression>>;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinaryExpr {
     pub op: BinaryOp,
     pub lhs: LocatedExpr,
@@ -169,35 +254,64 @@ pub struct BinaryExpr {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct UnaryExpr {
     pub op: UnaryOp,
     pub operand: LocatedExpr,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MultiExpr {
     pub op: MultiOp,
     pub operands: Vec<LocatedExpr>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DerefExpr {
     pub target: LocatedExpr,
 }
 
+// (cast<u64> x)
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CastExpr {
+    pub target_ty: Located<UnresolvedType>,
+    pub expr: LocatedExpr,
+}
+
+// x.len() のようなメソッド呼び出し
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MethodCallExpr {
+    pub receiver: LocatedExpr,
+    pub name: String,
+    pub args: Vec<LocatedExpr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AddressOfExpr {
+    pub target: LocatedExpr,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IndexAccessExpr {
     pub target: LocatedExpr,
     pub index: LocatedExpr,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldAccessExpr {
     pub target: LocatedExpr,
     pub field_name: String,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfExpr {
     pub cond: LocatedExpr,
     pub then: LocatedExpr,
@@ -205,12 +319,31 @@ pub struct IfExpr {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WhenExpr {
     pub cond: LocatedExpr,
     pub then: LocatedExpr,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WhileExpr {
+    pub cond: LocatedExpr,
+    pub body: Vec<LocatedExpr>,
+}
+
+// (for init cond step body...)
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForExpr {
+    pub init: Option<LocatedExpr>,
+    pub cond: Option<LocatedExpr>,
+    pub step: Option<LocatedExpr>,
+    pub body: Vec<LocatedExpr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AssignExpr {
     pub deref_count: u32,
     pub index_access: Option<LocatedExpr>,
@@ -219,48 +352,80 @@ pub struct AssignExpr {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableDecl {
     pub name: String,
     pub ty: Option<Located<UnresolvedType>>,
     pub value: LocatedExpr,
 }
 
+// (const SIZE: usize (* 4 2))
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstDecl {
+    pub name: String,
+    pub ty: Option<Located<UnresolvedType>>,
+    pub value: LocatedExpr,
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VariableDeclsExpr {
     pub decls: Vec<Located<VariableDecl>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expression {
     SizeOf(SizeOfExpr),
+    VaArg(VaArgExpr),
     VariableRef(VariableRefExpr),
     NumberLiteral(NumberLiteralExpr),
+    FloatLiteral(FloatLiteralExpr),
     StringLiteral(StringLiteralExpr),
+    CharLiteral(CharLiteralExpr),
     BoolLiteral(BoolLiteralExpr),
+    NullLiteral,
     StructLiteral(StructLiteralExpr),
+    TupleLiteral(TupleLiteralExpr),
     Binary(BinaryExpr),
     Unary(UnaryExpr),
     Multi(MultiExpr),
     Call(CallExpr),
+    MethodCall(MethodCallExpr),
     DerefExpr(DerefExpr),
+    AddressOf(AddressOfExpr),
+    Cast(CastExpr),
     IndexAccess(IndexAccessExpr),
     FieldAccess(FieldAccessExpr),
     If(IfExpr),
     When(WhenExpr),
+    While(WhileExpr),
+    For(ForExpr),
+    Break,
+    Continue,
     Assignment(AssignExpr),
     VariableDecl(VariableDeclsExpr),
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeRef {
     pub name: String,
     pub generic_args: Option<Vec<Located<UnresolvedType>>>,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnresolvedType {
     TypeRef(TypeRef),
     Ptr(Box<Located<UnresolvedType>>),
+    // [T; N]。負の長さもresolverでエラーにするために符号付きで持つ
+    Array(Box<Located<UnresolvedType>>, i64),
+    // fn(i32, i32): i32
+    FnPtr(Vec<Located<UnresolvedType>>, Box<Located<UnresolvedType>>),
+    // (i32, u8)
+    Tuple(Vec<Located<UnresolvedType>>),
     Infer,
 }
 
@@ -282,6 +447,29 @@ impl Display for UnresolvedType {
                 write!(f, "{}", inner_type.value)?;
                 f.write_char(']')?;
             }
+            UnresolvedType::Array(inner_type, len) => {
+                write!(f, "[{}; {}]", inner_type.value, len)?;
+            }
+            UnresolvedType::FnPtr(params, return_type) => {
+                f.write_str("fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", param.value)?;
+                }
+                write!(f, "): {}", return_type.value)?;
+            }
+            UnresolvedType::Tuple(elements) => {
+                f.write_char('(')?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{}", element.value)?;
+                }
+                f.write_char(')')?;
+            }
             UnresolvedType::Infer => {
                 f.write_str("_")?;
             }
@@ -291,22 +479,47 @@ impl Display for UnresolvedType {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReturnStatement {
     pub expression: Option<Located<Expression>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EffectStatement {
     pub expression: Located<Expression>,
 }
 
+// caseの後ろに続く文の並び。次のcaseに落ちることはない
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SwitchStatement {
+    pub scrutinee: Located<Expression>,
+    pub cases: Vec<(Located<Expression>, Vec<Located<Statement>>)>,
+    pub default: Option<Vec<Located<Statement>>>,
+}
+
+// 本体を実行してからcondを評価する。本体で宣言した変数はcondからは見えない
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DoWhileStatement {
+    pub body: Vec<Located<Statement>>,
+    pub cond: Located<Expression>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
     Return(ReturnStatement),
     Effect(EffectStatement),
+    // 独自のスコープを持つ { ... }
+    Block(Vec<Located<Statement>>),
+    Switch(SwitchStatement),
+    DoWhile(DoWhileStatement),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Restriction {
     Interface(String),
 }
@@ -320,6 +533,7 @@ impl Display for Restriction {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GenericArgument {
     pub name: String,
     pub restrictions: Vec<Restriction>,
@@ -341,14 +555,38 @@ impl Display for GenericArgument {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ArgumentAttribute {
+    Unused,
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Argument {
     VarArgs,
-    Normal(Located<UnresolvedType>, String),
+    Normal {
+        name: Located<String>,
+        ty: Located<UnresolvedType>,
+        attributes: Vec<ArgumentAttribute>,
+        // 呼び出しで省略されたときに使う値
+        default: Option<LocatedExpr>,
+    },
+}
+
+// 複数のファイルをまとめてコンパイルする場合、pubを付けた関数と型だけが他のファイルから見える
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Visibility {
+    #[default]
+    Private,
+    Public,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionDecl {
+    pub visibility: Visibility,
     pub alloc_mode: Option<AllocMode>,
     pub name: String,
     pub generic_args: Option<Vec<Located<GenericArgument>>>,
@@ -357,7 +595,22 @@ pub struct FunctionDecl {
     pub is_intrinsic: bool,
 }
 
+impl FunctionDecl {
+    pub fn is_var_args(&self) -> bool {
+        self.args.last() == Some(&Argument::VarArgs)
+    }
+
+    // 呼び出しで省略できない引数の数
+    pub fn required_arg_count(&self) -> usize {
+        self.args
+            .iter()
+            .filter(|arg| matches!(arg, Argument::Normal { default: None, .. }))
+            .count()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Interface {
     pub name: String,
     pub generic_args: Vec<Located<GenericArgument>>,
@@ -366,12 +619,16 @@ pub struct Interface {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Function {
     pub decl: FunctionDecl,
     pub body: Vec<Located<Statement>>,
+    // 宣言部分の位置
+    pub range: Range,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ImplementationDecl {
     pub alloc_mode: Option<AllocMode>,
     pub name: String,
@@ -382,38 +639,133 @@ pub struct ImplementationDecl {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Implementation {
     pub decl: ImplementationDecl,
     pub body: Vec<Located<Statement>>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructLikeTypeDef {
     pub struct_kind: StructKind,
     pub generic_args: Option<Vec<Located<GenericArgument>>>,
     pub fields: Vec<(String, Located<UnresolvedType>)>,
+    // 定義の後の impl { ... } に書いたメソッド
+    pub methods: Vec<Function>,
+}
+
+// impl Point { fn len(self): usize { ... } }
+// メソッドの最初の引数selfは型を書かず、レシーバーへのポインタになる
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MethodImpl {
+    pub target: String,
+    pub methods: Vec<Function>,
+}
+
+// 値を省略したバリアントは直前のバリアントの値+1になる
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnumTypeDef {
+    pub variants: Vec<(String, Option<i64>)>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TypeDefKind {
     StructLike(StructLikeTypeDef),
+    Enum(EnumTypeDef),
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct TypeDef {
+    pub visibility: Visibility,
     pub name: String,
     pub kind: TypeDefKind,
 }
 
+// (import "lib.hr")
+// パスはimportを書いたファイルのディレクトリからの相対パス
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Import {
+    pub path: String,
+}
+
+// type MyInt = u64
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TypeAlias {
+    pub visibility: Visibility,
+    pub name: String,
+    pub target: Located<UnresolvedType>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TopLevel {
     Function(Function),
     Implemantation(Implementation),
+    MethodImpl(MethodImpl),
     TypeDef(TypeDef),
     Interface(Interface),
+    GlobalVariables(VariableDeclsExpr),
+    Const(ConstDecl),
+    TypeAlias(TypeAlias),
+    Import(Import),
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Module {
     pub toplevels: Vec<Located<TopLevel>>,
+    // 読み飛ばして解析を続けた文のエラー。空でなければモジュールは不完全
+    pub errors: Vec<Located<String>>,
+}
+
+// 外部のツールが構文木を読めるように、JSONに変換する
+#[cfg(feature = "serde")]
+impl Module {
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use nom::Finish;
+
+    use super::*;
+    use crate::parser::parse_module;
+
+    #[test]
+    fn test_json_round_trip() {
+        let (_, module) = parse_module(
+            "
+pub struct Point {
+    x: i32,
+    y: *u8,
+}
+type Id = u64
+(:= counter: i64 10)
+fn add(@unused a: i32, b: i32 = 2): i32 {
+    (:= p Point { x: 1, y: null })
+    (for (:= i 0) (< i 10) (:=< i (+ i 1))
+        (printf \"%d\\n\" (cast<u64> p.x)))
+    return (+ a b)
+}
+"
+            .into(),
+        )
+        .finish()
+        .unwrap();
+        let json = module.to_json().unwrap();
+        assert_eq!(Module::from_json(&json).unwrap(), module);
+    }
 }
@@ -0,0 +1,224 @@
+use super::*;
+
+// ASTを辿る処理の共通部分。必要なメソッドだけを上書きし、子を辿るにはwalk_*を呼ぶ
+pub trait Visitor {
+    fn visit_toplevel(&mut self, toplevel: &Located<TopLevel>) {
+        walk_toplevel(self, toplevel);
+    }
+
+    fn visit_function(&mut self, func: &Function) {
+        walk_function(self, func);
+    }
+
+    fn visit_statement(&mut self, statement: &Located<Statement>) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expr: Located<&Expression>) {
+        walk_expression(self, expr);
+    }
+}
+
+pub fn walk_module<V: Visitor + ?Sized>(visitor: &mut V, module: &Module) {
+    for toplevel in &module.toplevels {
+        visitor.visit_toplevel(toplevel);
+    }
+}
+
+pub fn walk_toplevel<V: Visitor + ?Sized>(visitor: &mut V, toplevel: &Located<TopLevel>) {
+    match &toplevel.value {
+        TopLevel::Function(func) => visitor.visit_function(func),
+        TopLevel::Implemantation(implementation) => {
+            walk_statements(visitor, &implementation.body);
+        }
+        TopLevel::GlobalVariables(variable_decls) => {
+            for decl in &variable_decls.decls {
+                visitor.visit_expression(decl.value.value.as_inner_deref());
+            }
+        }
+        TopLevel::Const(const_decl) => visitor.visit_expression(const_decl.value.as_inner_deref()),
+        TopLevel::MethodImpl(method_impl) => {
+            for method in &method_impl.methods {
+                visitor.visit_function(method);
+            }
+        }
+        TopLevel::TypeDef(TypeDef {
+            kind: TypeDefKind::StructLike(struct_def),
+            ..
+        }) => {
+            for method in &struct_def.methods {
+                visitor.visit_function(method);
+            }
+        }
+        TopLevel::TypeDef(_)
+        | TopLevel::Interface(_)
+        | TopLevel::TypeAlias(_)
+        | TopLevel::Import(_) => {}
+    }
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, func: &Function) {
+    for arg in &func.decl.args {
+        if let Argument::Normal {
+            default: Some(default),
+            ..
+        } = arg
+        {
+            visitor.visit_expression(default.as_inner_deref());
+        }
+    }
+    walk_statements(visitor, &func.body);
+}
+
+pub fn walk_statements<V: Visitor + ?Sized>(visitor: &mut V, statements: &[Located<Statement>]) {
+    for statement in statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &Located<Statement>) {
+    match &statement.value {
+        Statement::Return(ReturnStatement { expression }) => {
+            if let Some(expression) = expression {
+                visitor.visit_expression(expression.as_ref());
+            }
+        }
+        Statement::Effect(EffectStatement { expression }) => {
+            visitor.visit_expression(expression.as_ref());
+        }
+        Statement::Block(statements) => walk_statements(visitor, statements),
+        Statement::Switch(switch) => {
+            visitor.visit_expression(switch.scrutinee.as_ref());
+            for (label, statements) in &switch.cases {
+                visitor.visit_expression(label.as_ref());
+                walk_statements(visitor, statements);
+            }
+            if let Some(statements) = &switch.default {
+                walk_statements(visitor, statements);
+            }
+        }
+        Statement::DoWhile(do_while) => {
+            walk_statements(visitor, &do_while.body);
+            visitor.visit_expression(do_while.cond.as_ref());
+        }
+    }
+}
+
+fn walk_exprs<'a, V: Visitor + ?Sized>(
+    visitor: &mut V,
+    exprs: impl IntoIterator<Item = &'a LocatedExpr>,
+) {
+    for expr in exprs {
+        visitor.visit_expression(expr.as_inner_deref());
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expr: Located<&Expression>) {
+    match expr.value {
+        Expression::SizeOf(_)
+        | Expression::VaArg(_)
+        | Expression::VariableRef(_)
+        | Expression::NumberLiteral(_)
+        | Expression::FloatLiteral(_)
+        | Expression::StringLiteral(_)
+        | Expression::CharLiteral(_)
+        | Expression::BoolLiteral(_)
+        | Expression::NullLiteral
+        | Expression::Break
+        | Expression::Continue => {}
+        Expression::StructLiteral(struct_literal) => {
+            walk_exprs(visitor, struct_literal.fields.iter().map(|(_, expr)| expr));
+        }
+        Expression::TupleLiteral(tuple_literal) => walk_exprs(visitor, &tuple_literal.elements),
+        Expression::Binary(binary) => walk_exprs(visitor, [&binary.lhs, &binary.rhs]),
+        Expression::Unary(unary) => walk_exprs(visitor, [&unary.operand]),
+        Expression::Multi(multi) => walk_exprs(visitor, &multi.operands),
+        Expression::Call(call) => walk_exprs(visitor, &call.args),
+        Expression::MethodCall(method_call) => {
+            walk_exprs(visitor, [&method_call.receiver]);
+            walk_exprs(visitor, &method_call.args);
+        }
+        Expression::DerefExpr(deref) => walk_exprs(visitor, [&deref.target]),
+        Expression::AddressOf(address_of) => walk_exprs(visitor, [&address_of.target]),
+        Expression::Cast(cast) => walk_exprs(visitor, [&cast.expr]),
+        Expression::IndexAccess(index_access) => {
+            walk_exprs(visitor, [&index_access.target, &index_access.index]);
+        }
+        Expression::FieldAccess(field_access) => walk_exprs(visitor, [&field_access.target]),
+        Expression::If(if_expr) => {
+            walk_exprs(visitor, [&if_expr.cond, &if_expr.then, &if_expr.els]);
+        }
+        Expression::When(when_expr) => walk_exprs(visitor, [&when_expr.cond, &when_expr.then]),
+        Expression::While(while_expr) => {
+            walk_exprs(visitor, [&while_expr.cond]);
+            walk_exprs(visitor, &while_expr.body);
+        }
+        Expression::For(for_expr) => {
+            walk_exprs(
+                visitor,
+                [&for_expr.init, &for_expr.cond, &for_expr.step]
+                    .into_iter()
+                    .flatten(),
+            );
+            walk_exprs(visitor, &for_expr.body);
+        }
+        Expression::Assignment(assignment) => {
+            walk_exprs(visitor, &assignment.index_access);
+            walk_exprs(visitor, [&assignment.value]);
+        }
+        Expression::VariableDecl(variable_decls) => {
+            walk_exprs(
+                visitor,
+                variable_decls.decls.iter().map(|decl| &decl.value.value),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::Finish;
+
+    use super::*;
+    use crate::parser::parse_module;
+
+    #[derive(Default)]
+    struct CallCounter {
+        count: usize,
+    }
+
+    impl Visitor for CallCounter {
+        fn visit_expression(&mut self, expr: Located<&Expression>) {
+            if let Expression::Call(_) = expr.value {
+                self.count += 1;
+            }
+            walk_expression(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_count_calls() {
+        let (_, module) = parse_module(
+            "
+(:= counter: i32 (f))
+fn f(): i32 {
+    1
+}
+fn main(): void {
+    (:= x (+ (f) (f)))
+    {
+        (while (< x (f))
+            (printf \"%d\" (f)))
+    }
+    return (f)
+}
+"
+            .into(),
+        )
+        .finish()
+        .unwrap();
+        let mut counter = CallCounter::default();
+        walk_module(&mut counter, &module);
+        assert_eq!(counter.count, 7);
+    }
+}
@@ -0,0 +1,127 @@
+use inkwell::{
+    debug_info::{
+        AsDIScope, DICompileUnit, DIFlags, DIFlagsConstants, DISubprogram, DWARFEmissionKind,
+        DWARFSourceLanguage, DebugInfoBuilder,
+    },
+    module::FlagBehavior,
+    values::FunctionValue,
+};
+
+use super::*;
+use crate::concrete_ast::*;
+
+// DWARFのデバッグ情報を生成するための状態
+pub struct DebugInfo<'a> {
+    builder: DebugInfoBuilder<'a>,
+    compile_unit: DICompileUnit<'a>,
+    // 生成中の関数。文の位置はこの関数のスコープに属する
+    subprogram: Cell<Option<DISubprogram<'a>>>,
+}
+
+impl<'a> LLVMCodeGenerator<'a> {
+    // 関数と文にソースファイル上の位置を付ける。gen_moduleより前に呼ぶ
+    pub fn enable_debug_info(&mut self, source_path: &Path) {
+        let file_name = source_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let directory = source_path
+            .parent()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (builder, compile_unit) = self.llvm_module.create_debug_info_builder(
+            true,
+            DWARFSourceLanguage::C,
+            &file_name,
+            &directory,
+            "hirou_compiler",
+            false,
+            "",
+            0,
+            "",
+            DWARFEmissionKind::Full,
+            0,
+            false,
+            false,
+            "",
+            "",
+        );
+        self.llvm_module.add_basic_value_flag(
+            "Debug Info Version",
+            FlagBehavior::Warning,
+            self.llvm_context.i32_type().const_int(3, false),
+        );
+        self.debug_info = Some(DebugInfo {
+            builder,
+            compile_unit,
+            subprogram: Cell::new(None),
+        });
+    }
+
+    pub(super) fn begin_debug_function(
+        &self,
+        function: &Function,
+        function_value: FunctionValue<'a>,
+    ) {
+        if let Some(debug_info) = &self.debug_info {
+            let file = debug_info.compile_unit.get_file();
+            let line = function.range.from.line;
+            // 引数と戻り値の型情報は出力せず、行番号だけを対応付ける
+            let subroutine_type =
+                debug_info
+                    .builder
+                    .create_subroutine_type(file, None, &[], DIFlags::PUBLIC);
+            let subprogram = debug_info.builder.create_function(
+                debug_info.compile_unit.as_debug_info_scope(),
+                &function.decl.name,
+                None,
+                file,
+                line,
+                subroutine_type,
+                false,
+                true,
+                line,
+                DIFlags::PUBLIC,
+                false,
+            );
+            function_value.set_subprogram(subprogram);
+            debug_info.subprogram.set(Some(subprogram));
+        }
+        // 引数を受け取る命令は関数の宣言の位置にする
+        self.set_debug_location(function.range);
+    }
+
+    pub(super) fn end_debug_function(&self) {
+        // 最後の文の後に生成したreturnなどは、最後の文の位置にする
+        self.record_source_range(None);
+        if let Some(debug_info) = &self.debug_info {
+            debug_info.subprogram.set(None);
+            self.llvm_builder.unset_current_debug_location();
+        }
+    }
+
+    // 以降に生成する命令にrangeの位置を付ける
+    pub(super) fn set_debug_location(&self, range: Range) {
+        self.record_source_range(Some(range));
+        let Some(debug_info) = &self.debug_info else {
+            return;
+        };
+        let Some(subprogram) = debug_info.subprogram.get() else {
+            return;
+        };
+        let location = debug_info.builder.create_debug_location(
+            self.llvm_context,
+            range.from.line,
+            range.from.col as u32,
+            subprogram.as_debug_info_scope(),
+            None,
+        );
+        self.llvm_builder.set_current_debug_location(location);
+    }
+
+    pub(super) fn finalize_debug_info(&self) {
+        if let Some(debug_info) = &self.debug_info {
+            debug_info.builder.finalize();
+        }
+    }
+}
@@ -1,45 +1,339 @@
+use inkwell::{intrinsics::Intrinsic, values::IntValue};
+
 use super::*;
-use crate::{ast::BinaryOp, common::binary::get_cast_type};
+use crate::{ast::BinaryOp, builder::ArithmeticMode, common::binary::get_cast_type};
 
 impl LLVMCodeGenerator<'_> {
     pub(crate) fn gen_try_cast<'ctx>(
         &'ctx self,
         value: BasicValueEnum<'ctx>,
+        from: &ConcreteType,
         ty: &ConcreteType,
     ) -> BasicValueEnum<'ctx> {
-        dbg!(ty, value);
-        let value = value.into_int_value();
-        match ty {
-            ConcreteType::I32 => self
-                .llvm_builder
-                .build_int_cast_sign_flag(value, self.llvm_context.i32_type(), true, "(i32)")
-                .unwrap()
-                .as_basic_value_enum(),
-            ConcreteType::U32 => self
-                .llvm_builder
-                .build_int_cast_sign_flag(value, self.llvm_context.i32_type(), false, "(u32)")
-                .unwrap()
-                .as_basic_value_enum(),
-            ConcreteType::U64 => self
-                .llvm_builder
-                .build_int_cast_sign_flag(value, self.llvm_context.i64_type(), false, "(u64)")
+        if from == ty {
+            return value;
+        }
+        if ty.is_float_type() {
+            let float_type = self.type_to_basic_type_enum(ty).unwrap().into_float_type();
+            let value = if from.is_float_type() {
+                self.llvm_builder
+                    .build_float_cast(value.into_float_value(), float_type, "")
+            } else if from.is_signed_integer_type() {
+                self.llvm_builder
+                    .build_signed_int_to_float(value.into_int_value(), float_type, "")
+            } else {
+                self.llvm_builder.build_unsigned_int_to_float(
+                    value.into_int_value(),
+                    float_type,
+                    "",
+                )
+            };
+            return value.unwrap().as_basic_value_enum();
+        }
+        if let ConcreteType::Ptr(_) = ty {
+            // opaque pointerなのでポインタ同士の変換は不要
+            if from.is_pointer_type() {
+                return value;
+            }
+            let ptr_type = self
+                .type_to_basic_type_enum(ty)
                 .unwrap()
-                .as_basic_value_enum(),
-            ConcreteType::U8 => self
+                .into_pointer_type();
+            return self
                 .llvm_builder
-                .build_int_cast_sign_flag(value, self.llvm_context.i8_type(), false, "(u8)")
+                .build_int_to_ptr(value.into_int_value(), ptr_type, "")
                 .unwrap()
-                .as_basic_value_enum(),
-            ConcreteType::I64 => self
+                .as_basic_value_enum();
+        }
+        // ここから先は整数型への変換
+        let int_type = self.type_to_basic_type_enum(ty).unwrap().into_int_type();
+        let value = if from.is_float_type() {
+            if ty.is_signed_integer_type() {
+                self.llvm_builder
+                    .build_float_to_signed_int(value.into_float_value(), int_type, "")
+            } else {
+                self.llvm_builder.build_float_to_unsigned_int(
+                    value.into_float_value(),
+                    int_type,
+                    "",
+                )
+            }
+        } else if from.is_pointer_type() {
+            self.llvm_builder
+                .build_ptr_to_int(value.into_pointer_value(), int_type, "")
+        } else {
+            // 拡張するときは変換元の符号でsext/zextを選ぶ。boolは0か1に拡張する
+            self.llvm_builder.build_int_cast_sign_flag(
+                value.into_int_value(),
+                int_type,
+                from.is_signed_integer_type(),
+                "",
+            )
+        };
+        value.unwrap().as_basic_value_enum()
+    }
+    // シフトは左辺の型のまま計算し、シフト量だけを左辺の幅に合わせる
+    fn eval_shift_expr<'a>(
+        &'a self,
+        binary_expr: &BinaryExpr,
+        left: BasicValueEnum<'a>,
+        right: BasicValueEnum<'a>,
+    ) -> Result<BasicValueEnum<'a>, BuilderError> {
+        let left = left.into_int_value();
+        let right = self.llvm_builder.build_int_cast_sign_flag(
+            right.into_int_value(),
+            left.get_type(),
+            binary_expr.rhs.ty.is_signed_integer_type(),
+            "",
+        )?;
+        // ビット幅以上のシフトはpoisonになるので、マスクするモードではシフト量を幅未満に丸める
+        let right = if self.mask_shift_amount {
+            let int_type = left.get_type();
+            let mask = int_type.const_int(u64::from(int_type.get_bit_width() - 1), false);
+            self.llvm_builder.build_and(right, mask, "")?
+        } else {
+            right
+        };
+        let value = match binary_expr.op {
+            BinaryOp::Shl => self.llvm_builder.build_left_shift(left, right, "")?,
+            // 符号付きは算術シフト、符号なしは論理シフト
+            BinaryOp::Shr => self.llvm_builder.build_right_shift(
+                left,
+                right,
+                binary_expr.lhs.ty.is_signed_integer_type(),
+                "",
+            )?,
+            _ => unreachable!(),
+        };
+        Ok(value.as_basic_value_enum())
+    }
+    // resolverが通すのは ptr ± int と int + ptr、同じ型のポインタ同士の差だけ
+    fn eval_pointer_arithmetic<'a>(
+        &'a self,
+        binary_expr: &BinaryExpr,
+        left: BasicValueEnum<'a>,
+        right: BasicValueEnum<'a>,
+    ) -> Result<BasicValueEnum<'a>, BuilderError> {
+        let usize_type = self
+            .llvm_context
+            .ptr_sized_int_type(&self.target_data, None);
+        // *voidはバイト単位で計算する
+        let pointee_type = |pointee: &ConcreteType| {
+            self.type_to_basic_type_enum(pointee)
+                .unwrap_or(self.llvm_context.i8_type().into())
+        };
+        if let (ConcreteType::Ptr(pointee), ConcreteType::Ptr(_)) =
+            (&binary_expr.lhs.ty, &binary_expr.rhs.ty)
+        {
+            let diff = self.llvm_builder.build_ptr_diff(
+                pointee_type(pointee),
+                left.into_pointer_value(),
+                right.into_pointer_value(),
+                "",
+            )?;
+            return Ok(self
                 .llvm_builder
-                .build_int_cast(value, self.llvm_context.i64_type(), "(i64)")
-                .unwrap()
-                .as_basic_value_enum(),
-            ConcreteType::Ptr(_) => unreachable!(),
-            ConcreteType::Void => unreachable!(),
-            ConcreteType::StructLike(_) => unreachable!(),
-            ConcreteType::Bool => unreachable!(),
+                .build_int_cast_sign_flag(diff, usize_type, true, "")?
+                .as_basic_value_enum());
         }
+        // int + ptr は ptr + int として扱う
+        let (ptr, pointee, offset, offset_ty) = match (&binary_expr.lhs.ty, &binary_expr.rhs.ty) {
+            (ConcreteType::Ptr(pointee), offset_ty) => (left, pointee, right, offset_ty),
+            (offset_ty, ConcreteType::Ptr(pointee)) => (right, pointee, left, offset_ty),
+            _ => unreachable!(),
+        };
+        let mut offset = self.llvm_builder.build_int_cast_sign_flag(
+            offset.into_int_value(),
+            usize_type,
+            offset_ty.is_signed_integer_type(),
+            "",
+        )?;
+        if let BinaryOp::Sub = binary_expr.op {
+            offset = self.llvm_builder.build_int_neg(offset, "")?;
+        }
+        let value = unsafe {
+            self.llvm_builder.build_gep(
+                pointee_type(pointee),
+                ptr.into_pointer_value(),
+                &[offset],
+                "",
+            )?
+        };
+        Ok(value.as_basic_value_enum())
+    }
+    // llvm.trapで実行を止める。後に続く命令には到達しない
+    pub(crate) fn gen_trap(&self) -> Result<(), BuilderError> {
+        let trap = Intrinsic::find("llvm.trap")
+            .unwrap()
+            .get_declaration(&self.llvm_module, &[])
+            .unwrap();
+        self.llvm_builder.build_call(trap, &[], "")?;
+        self.llvm_builder.build_unreachable()?;
+        Ok(())
+    }
+    // llvm.*.with.overflowで演算し、オーバーフローしていればtrapのブロックへ分岐する
+    fn gen_checked_int_arithmetic<'a>(
+        &'a self,
+        op: BinaryOp,
+        is_signed: bool,
+        left: IntValue<'a>,
+        right: IntValue<'a>,
+    ) -> Result<IntValue<'a>, BuilderError> {
+        let name = match (op, is_signed) {
+            (BinaryOp::Add, true) => "llvm.sadd.with.overflow",
+            (BinaryOp::Add, false) => "llvm.uadd.with.overflow",
+            (BinaryOp::Sub, true) => "llvm.ssub.with.overflow",
+            (BinaryOp::Sub, false) => "llvm.usub.with.overflow",
+            (BinaryOp::Mul, true) => "llvm.smul.with.overflow",
+            (BinaryOp::Mul, false) => "llvm.umul.with.overflow",
+            _ => unreachable!(),
+        };
+        let function = Intrinsic::find(name)
+            .unwrap()
+            .get_declaration(&self.llvm_module, &[left.get_type().into()])
+            .unwrap();
+        // 結果は{ 値, オーバーフローしたか }の構造体で返る
+        let result = self
+            .llvm_builder
+            .build_call(function, &[left.into(), right.into()], "")?
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_struct_value();
+        let value = self
+            .llvm_builder
+            .build_extract_value(result, 0, "")?
+            .into_int_value();
+        let overflowed = self
+            .llvm_builder
+            .build_extract_value(result, 1, "")?
+            .into_int_value();
+        self.gen_trap_if(overflowed, "overflow")?;
+        Ok(value)
+    }
+    // condが真ならnameのブロックでtrapし、偽なら続きのブロックから生成を続ける
+    fn gen_trap_if<'a>(&'a self, cond: IntValue<'a>, name: &str) -> Result<(), BuilderError> {
+        let current_function = self
+            .llvm_builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let trap_block = self.llvm_context.append_basic_block(current_function, name);
+        let continue_block = self
+            .llvm_context
+            .append_basic_block(current_function, &format!("no_{}", name));
+        self.llvm_builder
+            .build_conditional_branch(cond, trap_block, continue_block)?;
+        self.llvm_builder.position_at_end(trap_block);
+        self.gen_trap()?;
+        self.llvm_builder.position_at_end(continue_block);
+        Ok(())
+    }
+    // wrapping_*は普通の整数演算に、saturating_*はllvm.*.satにする。両辺はresolverで同じ整数型に揃っている。
+    // arithmetic_modeに関わらず、名前の通りの演算をする
+    pub(super) fn gen_arithmetic_builtin<'a>(
+        &'a self,
+        builtin: ArithmeticBuiltin,
+        ty: &ConcreteType,
+        left: IntValue<'a>,
+        right: IntValue<'a>,
+    ) -> Result<BasicValueEnum<'a>, BuilderError> {
+        if !builtin.saturating {
+            let value = match builtin.op {
+                BinaryOp::Add => self.llvm_builder.build_int_add(left, right, "")?,
+                BinaryOp::Sub => self.llvm_builder.build_int_sub(left, right, "")?,
+                BinaryOp::Mul => self.llvm_builder.build_int_mul(left, right, "")?,
+                _ => unreachable!(),
+            };
+            return Ok(value.as_basic_value_enum());
+        }
+        // 乗算の飽和演算は、固定小数点数の乗算を小数部の桁数0で使う
+        let name = match (builtin.op, ty.is_signed_integer_type()) {
+            (BinaryOp::Add, true) => "llvm.sadd.sat",
+            (BinaryOp::Add, false) => "llvm.uadd.sat",
+            (BinaryOp::Sub, true) => "llvm.ssub.sat",
+            (BinaryOp::Sub, false) => "llvm.usub.sat",
+            (BinaryOp::Mul, true) => "llvm.smul.fix.sat",
+            (BinaryOp::Mul, false) => "llvm.umul.fix.sat",
+            _ => unreachable!(),
+        };
+        let function = Intrinsic::find(name)
+            .unwrap()
+            .get_declaration(&self.llvm_module, &[left.get_type().into()])
+            .unwrap();
+        let mut args: Vec<BasicMetadataValueEnum> = vec![left.into(), right.into()];
+        if builtin.op == BinaryOp::Mul {
+            args.push(self.llvm_context.i32_type().const_zero().into());
+        }
+        Ok(self
+            .llvm_builder
+            .build_call(function, &args, "")?
+            .try_as_basic_value()
+            .left()
+            .unwrap())
+    }
+    // 比較は既定では順序付き(ordered)の述語を使うので、NaNとの比較は!=以外false。
+    // use_unordered_float_compareを呼んだ場合は順序なし(unordered)にし、NaNとの比較は全てtrueになる
+    fn eval_float_binary_expr<'a>(
+        &'a self,
+        op: BinaryOp,
+        left: BasicValueEnum<'a>,
+        right: BasicValueEnum<'a>,
+    ) -> Result<BasicValueEnum<'a>, BuilderError> {
+        let (left, right) = (left.into_float_value(), right.into_float_value());
+        let predicate = match op {
+            BinaryOp::Add => {
+                return Ok(self
+                    .llvm_builder
+                    .build_float_add(left, right, "")?
+                    .as_basic_value_enum())
+            }
+            BinaryOp::Sub => {
+                return Ok(self
+                    .llvm_builder
+                    .build_float_sub(left, right, "")?
+                    .as_basic_value_enum())
+            }
+            BinaryOp::Mul => {
+                return Ok(self
+                    .llvm_builder
+                    .build_float_mul(left, right, "")?
+                    .as_basic_value_enum())
+            }
+            BinaryOp::Div => {
+                return Ok(self
+                    .llvm_builder
+                    .build_float_div(left, right, "")?
+                    .as_basic_value_enum())
+            }
+            BinaryOp::Equals if self.unordered_float_compare => inkwell::FloatPredicate::UEQ,
+            BinaryOp::LessThan if self.unordered_float_compare => inkwell::FloatPredicate::ULT,
+            BinaryOp::LessThanOrEquals if self.unordered_float_compare => {
+                inkwell::FloatPredicate::ULE
+            }
+            BinaryOp::GreaterThan if self.unordered_float_compare => inkwell::FloatPredicate::UGT,
+            BinaryOp::GreaterThanOrEquals if self.unordered_float_compare => {
+                inkwell::FloatPredicate::UGE
+            }
+            BinaryOp::Equals => inkwell::FloatPredicate::OEQ,
+            BinaryOp::NotEquals => inkwell::FloatPredicate::UNE,
+            BinaryOp::LessThan => inkwell::FloatPredicate::OLT,
+            BinaryOp::LessThanOrEquals => inkwell::FloatPredicate::OLE,
+            BinaryOp::GreaterThan => inkwell::FloatPredicate::OGT,
+            BinaryOp::GreaterThanOrEquals => inkwell::FloatPredicate::OGE,
+            // resolverで整数以外を拒否している
+            BinaryOp::Mod
+            | BinaryOp::BitAnd
+            | BinaryOp::BitOr
+            | BinaryOp::BitXor
+            | BinaryOp::Shl
+            | BinaryOp::Shr => unreachable!(),
+        };
+        Ok(self
+            .llvm_builder
+            .build_float_compare(predicate, left, right, "")?
+            .as_basic_value_enum())
     }
     pub(super) fn eval_binary_expr(
         &self,
@@ -48,24 +342,88 @@ impl LLVMCodeGenerator<'_> {
         let mut left = self.gen_expression(&binary_expr.lhs)?.unwrap();
         let mut right = self.gen_expression(&binary_expr.rhs)?.unwrap();
 
+        if let BinaryOp::Shl | BinaryOp::Shr = binary_expr.op {
+            return self.eval_shift_expr(binary_expr, left, right);
+        }
+        if let BinaryOp::Add | BinaryOp::Sub = binary_expr.op {
+            if binary_expr.lhs.ty.is_pointer_type() || binary_expr.rhs.ty.is_pointer_type() {
+                return self.eval_pointer_arithmetic(binary_expr, left, right);
+            }
+        }
+        // ポインタ同士の比較はアドレスを符号なし整数として比べる
+        if binary_expr.lhs.ty.is_pointer_type() && binary_expr.rhs.ty.is_pointer_type() {
+            let predicate = match binary_expr.op {
+                BinaryOp::Equals => inkwell::IntPredicate::EQ,
+                BinaryOp::NotEquals => inkwell::IntPredicate::NE,
+                BinaryOp::LessThan => inkwell::IntPredicate::ULT,
+                BinaryOp::LessThanOrEquals => inkwell::IntPredicate::ULE,
+                BinaryOp::GreaterThan => inkwell::IntPredicate::UGT,
+                BinaryOp::GreaterThanOrEquals => inkwell::IntPredicate::UGE,
+                _ => unreachable!(),
+            };
+            return Ok(self
+                .llvm_builder
+                .build_int_compare(
+                    predicate,
+                    left.into_pointer_value(),
+                    right.into_pointer_value(),
+                    "",
+                )?
+                .as_basic_value_enum());
+        }
+
         let (lhs_cast_type, rhs_cast_type) =
             get_cast_type(&binary_expr.lhs.ty, &binary_expr.rhs.ty);
 
-        let mut result_type = ConcreteType::I32;
+        // キャストが不要な場合は左辺と右辺の型が一致している
+        let mut result_type = binary_expr.lhs.ty.clone();
         if let Some(lhs_cast_type) = lhs_cast_type {
-            left = self.gen_try_cast(left, &lhs_cast_type);
+            left = self.gen_try_cast(left, &binary_expr.lhs.ty, &lhs_cast_type);
             result_type = lhs_cast_type;
         }
         if let Some(rhs_cast_type) = rhs_cast_type {
-            right = self.gen_try_cast(right, &rhs_cast_type);
+            right = self.gen_try_cast(right, &binary_expr.rhs.ty, &rhs_cast_type);
             result_type = rhs_cast_type;
         };
 
+        if result_type.is_float_type() {
+            return self.eval_float_binary_expr(binary_expr.op, left, right);
+        }
+
+        if let (ArithmeticMode::Checked, BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul) =
+            (self.arithmetic_mode, binary_expr.op)
+        {
+            return Ok(self
+                .gen_checked_int_arithmetic(
+                    binary_expr.op,
+                    result_type.is_signed_integer_type(),
+                    left.into_int_value(),
+                    right.into_int_value(),
+                )?
+                .as_basic_value_enum());
+        }
+
+        // 0での整数の除算はLLVMでは未定義動作なので、有効にした場合は除数を検査する
+        if let (true, BinaryOp::Div | BinaryOp::Mod) = (self.guard_division_by_zero, binary_expr.op)
+        {
+            let divisor = right.into_int_value();
+            let is_zero = self.llvm_builder.build_int_compare(
+                inkwell::IntPredicate::EQ,
+                divisor,
+                divisor.get_type().const_zero(),
+                "",
+            )?;
+            self.gen_trap_if(is_zero, "division_by_zero")?;
+        }
+
         let value = match binary_expr.op {
             BinaryOp::Add => {
                 match result_type {
-                    ConcreteType::I32
+                    ConcreteType::I8
+                    | ConcreteType::I16
+                    | ConcreteType::I32
                     | ConcreteType::U8
+                    | ConcreteType::U16
                     | ConcreteType::U32
                     | ConcreteType::I64
                     | ConcreteType::U64 => {}
@@ -122,13 +480,47 @@ impl LLVMCodeGenerator<'_> {
                     unimplemented!()
                 }
             }
+            BinaryOp::Mod => {
+                if result_type.is_integer_type() {
+                    if result_type.is_signed_integer_type() {
+                        self.llvm_builder.build_int_signed_rem(
+                            left.into_int_value(),
+                            right.into_int_value(),
+                            "",
+                        )?
+                    } else {
+                        self.llvm_builder.build_int_unsigned_rem(
+                            left.into_int_value(),
+                            right.into_int_value(),
+                            "",
+                        )?
+                    }
+                } else {
+                    unimplemented!()
+                }
+            }
+            BinaryOp::BitAnd | BinaryOp::BitOr | BinaryOp::BitXor => {
+                if result_type.is_integer_type() {
+                    let (left, right) = (left.into_int_value(), right.into_int_value());
+                    match binary_expr.op {
+                        BinaryOp::BitAnd => self.llvm_builder.build_and(left, right, "")?,
+                        BinaryOp::BitOr => self.llvm_builder.build_or(left, right, "")?,
+                        BinaryOp::BitXor => self.llvm_builder.build_xor(left, right, "")?,
+                        _ => unreachable!(),
+                    }
+                } else {
+                    unimplemented!()
+                }
+            }
+            BinaryOp::Shl | BinaryOp::Shr => unreachable!(),
             BinaryOp::Equals
             | BinaryOp::NotEquals
             | BinaryOp::LessThan
             | BinaryOp::LessThanOrEquals
             | BinaryOp::GreaterThan
             | BinaryOp::GreaterThanOrEquals => {
-                if result_type.is_integer_type() {
+                // boolはi1なので整数と同じように比較できる
+                if result_type.is_integer_type() || result_type == ConcreteType::Bool {
                     let predicate = if result_type.is_signed_integer_type() {
                         match binary_expr.op {
                             BinaryOp::Equals => inkwell::IntPredicate::EQ,
@@ -165,3 +557,540 @@ impl LLVMCodeGenerator<'_> {
         Ok(value.as_basic_value_enum())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::{
+            tests::{compile_to_ir, compile_to_verified_ir, generate, CompileTarget},
+            ArithmeticMode,
+        },
+        common::target::TargetPlatform,
+    };
+
+    #[test]
+    fn test_pointer_add() {
+        let ir = compile_to_ir(
+            "
+fn main(): void {
+    (:= x: i32 1)
+    (:= p &x)
+    (:= _q (+ p 1))
+}
+",
+        );
+        assert!(ir.contains("getelementptr i32, ptr"), "{}", ir);
+    }
+
+    #[test]
+    fn test_pointer_diff() {
+        let ir = compile_to_ir(
+            "
+fn main(): void {
+    (:= x: i64 1)
+    (:= p &x)
+    (:= q (+ p 2))
+    (:= _n (- q p))
+}
+",
+        );
+        assert!(ir.contains("ptrtoint ptr"), "{}", ir);
+        // 差をi64の大きさで割って要素数にする
+        assert!(ir.contains("sdiv exact i64"), "{}", ir);
+    }
+
+    #[test]
+    fn test_sub_with_mixed_widths() {
+        let ir = compile_to_ir(
+            "
+fn sub(a: i32, b: i64): i64 {
+    (- a b)
+}
+fn main(): void {
+    (sub 1 2)
+}
+",
+        );
+        assert!(ir.contains("sext i32"), "{}", ir);
+        assert!(ir.contains("sub i64"), "{}", ir);
+    }
+
+    #[test]
+    fn test_mul_with_mixed_widths() {
+        let ir = compile_to_ir(
+            "
+fn mul(a: u32, b: u64): u64 {
+    (* a b)
+}
+fn main(): void {
+    (mul 1 2)
+}
+",
+        );
+        assert!(ir.contains("zext i32"), "{}", ir);
+        assert!(ir.contains("mul i64"), "{}", ir);
+    }
+
+    #[test]
+    fn test_u16_promotes_to_u32() {
+        let ir = compile_to_ir(
+            "
+fn add(a: u16, b: u32): u32 {
+    (+ a b)
+}
+fn main(): void {
+    (add 1 2)
+}
+",
+        );
+        assert!(ir.contains("define i32 @add(i16 %a, i32 %b)"), "{}", ir);
+        assert!(ir.contains("zext i16"), "{}", ir);
+        assert!(ir.contains("add i32"), "{}", ir);
+    }
+
+    #[test]
+    fn test_signed_div() {
+        let ir = compile_to_ir(
+            "
+fn div(a: i32, b: i64): i64 {
+    (/ a b)
+}
+fn main(): void {
+    (div 1 2)
+}
+",
+        );
+        assert!(ir.contains("sdiv i64"), "{}", ir);
+        assert!(!ir.contains("udiv"), "{}", ir);
+    }
+
+    #[test]
+    fn test_unsigned_div() {
+        let ir = compile_to_ir(
+            "
+fn div(a: u8, b: u32): u32 {
+    (/ a b)
+}
+fn div64(a: u64, b: u64): u64 {
+    (/ a b)
+}
+fn main(): void {
+    (div 1 2)
+    (div64 1 2)
+}
+",
+        );
+        assert!(ir.contains("zext i8"), "{}", ir);
+        assert!(ir.contains("udiv i32"), "{}", ir);
+        // 同じ型同士の場合もresult_typeが符号なしになる
+        assert!(ir.contains("udiv i64"), "{}", ir);
+        assert!(!ir.contains("sdiv"), "{}", ir);
+    }
+
+    #[test]
+    fn test_signed_less_than() {
+        let ir = compile_to_ir(
+            "
+fn lt(a: i32, b: i32): void {
+    (:= c (< a b))
+}
+fn main(): void {
+    (lt 1 2)
+}
+",
+        );
+        assert!(ir.contains("icmp slt i32"), "{}", ir);
+    }
+
+    #[test]
+    fn test_unsigned_less_than() {
+        let ir = compile_to_ir(
+            "
+fn lt(a: u32, b: u32): void {
+    (:= c (< a b))
+}
+fn main(): void {
+    (lt 4294967295u32 1u32)
+}
+",
+        );
+        // 符号付きで比べると4294967295が-1になり、大小が逆になる
+        assert!(ir.contains("icmp ult i32"), "{}", ir);
+        assert!(!ir.contains("icmp slt"), "{}", ir);
+    }
+
+    #[test]
+    fn test_compare_with_mixed_widths() {
+        let ir = compile_to_ir(
+            "
+fn lt(a: i64, b: i32): bool {
+    (< a b)
+}
+fn main(): void {
+    (lt 1 2)
+}
+",
+        );
+        assert!(ir.contains("sext i32"), "{}", ir);
+        assert!(ir.contains("icmp slt i64"), "{}", ir);
+    }
+
+    #[test]
+    fn test_unsigned_greater_than_or_equals() {
+        let ir = compile_to_ir(
+            "
+fn ge(a: u64, b: u64): void {
+    (:= c (>= a b))
+}
+fn main(): void {
+    (ge 1 2)
+}
+",
+        );
+        assert!(ir.contains("icmp uge i64"), "{}", ir);
+    }
+
+    #[test]
+    fn test_bool_equals() {
+        let ir = compile_to_ir(
+            "
+fn same(a: i32, b: i32): void {
+    (:= c (== (< a b) (< b a)))
+}
+fn main(): void {
+    (same 1 2)
+}
+",
+        );
+        assert!(ir.contains("icmp eq i1"), "{}", ir);
+    }
+
+    #[test]
+    fn test_signed_mod() {
+        let ir = compile_to_ir(
+            "
+fn rem(x: i32, y: i32): i32 {
+    (% x y)
+}
+fn main(): void {
+    (rem 7 3)
+}
+",
+        );
+        assert!(ir.contains("srem i32"), "{}", ir);
+    }
+
+    #[test]
+    fn test_unsigned_mod() {
+        let ir = compile_to_ir(
+            "
+fn rem(x: u32, y: u32): u32 {
+    (% x y)
+}
+fn main(): void {
+    (rem 7 3)
+}
+",
+        );
+        assert!(ir.contains("urem i32"), "{}", ir);
+        assert!(!ir.contains("srem"), "{}", ir);
+    }
+
+    #[test]
+    fn test_bitwise_operators() {
+        let ir = compile_to_ir(
+            "
+fn mask(a: u32, b: u32, c: u32): u32 {
+    (^ (| (& a b) c) a)
+}
+fn main(): void {
+    (mask 1 2 3)
+}
+",
+        );
+        let and_at = ir.find(" and i32").expect(&ir);
+        let or_at = ir.find(" or i32").expect(&ir);
+        let xor_at = ir.find(" xor i32").expect(&ir);
+        assert!(and_at < or_at && or_at < xor_at, "{}", ir);
+    }
+
+    #[test]
+    fn test_shift_right_signedness() {
+        let ir = compile_to_ir(
+            "
+fn sshr(x: i32): i32 {
+    (>> x 2)
+}
+fn ushr(x: u32): u32 {
+    (>> x 2)
+}
+fn shl(x: u64): u64 {
+    (<< x 2)
+}
+fn main(): void {
+    (sshr 1)
+    (ushr 1)
+    (shl 1)
+}
+",
+        );
+        assert!(ir.contains("ashr i32"), "{}", ir);
+        assert!(ir.contains("lshr i32"), "{}", ir);
+        // シフト量は左辺の幅に揃えられる
+        assert!(ir.contains("shl i64"), "{}", ir);
+    }
+
+    #[test]
+    fn test_mask_shift_amount() {
+        let source = "
+fn shl33(x: i32): i32 {
+    (<< x 33)
+}
+fn shr(x: u64, n: u64): u64 {
+    (>> x n)
+}
+fn main(): void {
+    (shl33 1)
+    (shr 1 2)
+}
+";
+        let ir = generate(
+            source,
+            CompileTarget::Platform(TargetPlatform::DarwinArm64),
+            |llvm_codegenerator| llvm_codegenerator.enable_shift_amount_masking(),
+            |llvm_codegenerator| {
+                llvm_codegenerator.verify().unwrap();
+                llvm_codegenerator.emit_ir_string()
+            },
+        );
+        // 定数のシフト量は 33 & 31 = 1 に畳み込まれる
+        assert!(
+            ir.lines()
+                .any(|line| line.contains("shl i32") && line.ends_with(", 1")),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("and i64 %"), "{}", ir);
+        assert!(ir.contains(", 63"), "{}", ir);
+
+        // 既定ではシフト量をそのまま使う
+        let ir = compile_to_ir(source);
+        assert!(
+            ir.lines()
+                .any(|line| line.contains("shl i32") && line.ends_with(", 33")),
+            "{}",
+            ir
+        );
+        assert!(!ir.contains("and i64"), "{}", ir);
+    }
+
+    #[test]
+    fn test_int_promoted_to_float() {
+        let ir = compile_to_ir(
+            "
+fn add(a: f64, b: i32): f64 {
+    (+ a b)
+}
+fn literal(): f64 {
+    (+ 1.0 2)
+}
+fn main(): void {
+    (add 1.0 2)
+    (literal)
+}
+",
+        );
+        assert!(ir.contains("sitofp i32"), "{}", ir);
+        assert!(ir.contains("fadd double"), "{}", ir);
+        // 定数同士は畳み込まれる
+        assert!(ir.contains("ret double 3.000000e+00"), "{}", ir);
+    }
+
+    #[test]
+    fn test_float_div_and_compare() {
+        let ir = compile_to_ir(
+            "
+fn div(a: f64, b: f64): f64 {
+    (/ a b)
+}
+fn lt(a: f64, b: f64): bool {
+    (< a b)
+}
+fn main(): void {
+    (div 1.0 2.0)
+    (lt 1.0 2.0)
+}
+",
+        );
+        assert!(ir.contains("fdiv double"), "{}", ir);
+        assert!(ir.contains("fcmp olt double"), "{}", ir);
+    }
+
+    #[test]
+    fn test_compare_int_with_float() {
+        let ir = compile_to_ir(
+            "
+fn lt(a: f64, b: i32): bool {
+    (< a b)
+}
+fn gt(a: f64): bool {
+    (> a 2)
+}
+fn main(): void {
+    (lt 1.0 2)
+    (gt 1.0)
+}
+",
+        );
+        assert!(ir.contains("sitofp i32"), "{}", ir);
+        assert!(ir.contains("fcmp olt double"), "{}", ir);
+        // リテラルはf64として生成される
+        assert!(ir.contains("fcmp ogt double %a, 2.000000e+00"), "{}", ir);
+    }
+
+    #[test]
+    fn test_unordered_float_compare() {
+        let source = "
+fn lt(a: f64, b: f64): bool {
+    (< a b)
+}
+fn main(): void {
+    (lt 1.0 2.0)
+}
+";
+        let target = CompileTarget::Platform(TargetPlatform::DarwinArm64);
+        let ir = generate(
+            source,
+            target,
+            |llvm_codegenerator| llvm_codegenerator.use_unordered_float_compare(),
+            |llvm_codegenerator| llvm_codegenerator.emit_ir_string(),
+        );
+        assert!(ir.contains("fcmp ult double"), "{}", ir);
+        assert!(!ir.contains("fcmp olt"), "{}", ir);
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let source = "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn mul(a: u8, b: u8): u8 {
+    (* a b)
+}
+fn main(): void {
+    (add 1 2)
+    (mul 3u8 4u8)
+}
+";
+        let ir = generate(
+            source,
+            CompileTarget::Platform(TargetPlatform::DarwinArm64),
+            |llvm_codegenerator| llvm_codegenerator.set_arithmetic_mode(ArithmeticMode::Checked),
+            |llvm_codegenerator| {
+                llvm_codegenerator.verify().unwrap();
+                llvm_codegenerator.emit_ir_string()
+            },
+        );
+        assert!(
+            ir.contains("call { i32, i1 } @llvm.sadd.with.overflow.i32("),
+            "{}",
+            ir
+        );
+        assert!(
+            ir.contains("call { i8, i1 } @llvm.umul.with.overflow.i8("),
+            "{}",
+            ir
+        );
+        // オーバーフローしたらtrapする
+        assert!(ir.contains("br i1 %"), "{}", ir);
+        assert!(ir.contains("call void @llvm.trap()"), "{}", ir);
+        assert!(!ir.contains("add i32"), "{}", ir);
+
+        // 既定ではラップアラウンドする普通の命令になる
+        let ir = compile_to_ir(source);
+        assert!(ir.contains("add i32"), "{}", ir);
+        assert!(!ir.contains("with.overflow"), "{}", ir);
+    }
+
+    #[test]
+    fn test_wrapping_arithmetic_builtins() {
+        let ir = compile_to_verified_ir(
+            "
+fn f(a: u8, b: u8): u8 {
+    (wrapping_mul (wrapping_sub (wrapping_add a b) 1) b)
+}
+fn main(): void {
+    (f 1u8 2u8)
+}
+",
+        );
+        // 普通の整数演算になり、関数としては宣言しない
+        assert!(ir.contains("add i8"), "{}", ir);
+        assert!(ir.contains("sub i8"), "{}", ir);
+        assert!(ir.contains("mul i8"), "{}", ir);
+        assert!(!ir.contains("@wrapping_"), "{}", ir);
+    }
+
+    #[test]
+    fn test_saturating_arithmetic_builtins() {
+        let ir = compile_to_verified_ir(
+            "
+fn signed(a: i32, b: i32): i32 {
+    (saturating_mul (saturating_sub (saturating_add a b) b) a)
+}
+fn unsigned(a: u16, b: u16): u16 {
+    (saturating_mul (saturating_sub (saturating_add a b) b) a)
+}
+fn main(): void {
+    (signed 1 2)
+    (unsigned 1u16 2u16)
+}
+",
+        );
+        assert!(ir.contains("call i32 @llvm.sadd.sat.i32("), "{}", ir);
+        assert!(ir.contains("call i32 @llvm.ssub.sat.i32("), "{}", ir);
+        assert!(ir.contains("call i32 @llvm.smul.fix.sat.i32("), "{}", ir);
+        assert!(ir.contains("call i16 @llvm.uadd.sat.i16("), "{}", ir);
+        assert!(ir.contains("call i16 @llvm.usub.sat.i16("), "{}", ir);
+        assert!(ir.contains("call i16 @llvm.umul.fix.sat.i16("), "{}", ir);
+        assert!(!ir.contains("@saturating_"), "{}", ir);
+    }
+
+    #[test]
+    fn test_division_by_zero_guard() {
+        let source = "
+fn div(a: i32, b: i32): i32 {
+    (/ a b)
+}
+fn rem(a: u64, b: u64): u64 {
+    (% a b)
+}
+fn main(): void {
+    (div 1 2)
+    (rem 1 2)
+}
+";
+        let ir = generate(
+            source,
+            CompileTarget::Platform(TargetPlatform::DarwinArm64),
+            |llvm_codegenerator| llvm_codegenerator.enable_division_by_zero_guard(),
+            |llvm_codegenerator| {
+                llvm_codegenerator.verify().unwrap();
+                llvm_codegenerator.emit_ir_string()
+            },
+        );
+        // 除数が0ならtrapのブロックへ分岐してから割る
+        assert!(ir.contains("icmp eq i32 %"), "{}", ir);
+        assert!(ir.contains("icmp eq i64 %"), "{}", ir);
+        assert_eq!(ir.matches("\ndivision_by_zero:").count(), 2, "{}", ir);
+        assert!(ir.contains("call void @llvm.trap()"), "{}", ir);
+        assert!(ir.contains("sdiv i32"), "{}", ir);
+        assert!(ir.contains("urem i64"), "{}", ir);
+
+        // 既定では検査しない
+        let ir = compile_to_ir(source);
+        assert!(!ir.contains("division_by_zero"), "{}", ir);
+        assert!(!ir.contains("@llvm.trap"), "{}", ir);
+    }
+}
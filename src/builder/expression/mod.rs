@@ -3,20 +3,33 @@ mod multi;
 mod unary;
 
 use super::*;
-use crate::concrete_ast::*;
+use crate::{common::builtin::ArithmeticBuiltin, concrete_ast::*};
 use inkwell::{
     builder::BuilderError,
+    module::Linkage,
     types::BasicType,
-    values::{BasicMetadataValueEnum, BasicValue, BasicValueEnum},
+    values::{BasicMetadataValueEnum, BasicValue, BasicValueEnum, CallSiteValue},
     AddressSpace,
 };
 
+// リテラルが型の範囲に収まることはresolverで検査済み
+// 符号付きの型は符号拡張した64bitの値を、符号なしの型はゼロ拡張した値をconst_intに渡す
 impl LLVMCodeGenerator<'_> {
     fn eval_u8(&self, value_str: &str) -> BasicValueEnum {
         let n = value_str.parse::<u8>().unwrap();
+        let int_value = self.llvm_context.i8_type().const_int(n as u64, false);
+        int_value.into()
+    }
+    fn eval_i8(&self, value_str: &str) -> BasicValueEnum {
+        let n = value_str.parse::<i8>().unwrap();
         let int_value = self.llvm_context.i8_type().const_int(n as u64, true);
         int_value.into()
     }
+    fn eval_i16(&self, value_str: &str) -> BasicValueEnum {
+        let n = value_str.parse::<i16>().unwrap();
+        let int_value = self.llvm_context.i16_type().const_int(n as u64, true);
+        int_value.into()
+    }
     fn eval_i32(&self, value_str: &str) -> BasicValueEnum {
         let n = value_str.parse::<i32>().unwrap();
         let int_value = self.llvm_context.i32_type().const_int(n as u64, true);
@@ -27,16 +40,31 @@ impl LLVMCodeGenerator<'_> {
         let int_value = self.llvm_context.i64_type().const_int(n as u64, true);
         int_value.into()
     }
+    fn eval_u16(&self, value_str: &str) -> BasicValueEnum {
+        let n = value_str.parse::<u16>().unwrap();
+        let int_value = self.llvm_context.i16_type().const_int(n as u64, false);
+        int_value.into()
+    }
     fn eval_u32(&self, value_str: &str) -> BasicValueEnum {
         let n = value_str.parse::<u32>().unwrap();
-        let int_value = self.llvm_context.i32_type().const_int(n as u64, true);
+        let int_value = self.llvm_context.i32_type().const_int(n as u64, false);
         int_value.into()
     }
     fn eval_u64(&self, value_str: &str) -> BasicValueEnum {
         let n = value_str.parse::<u64>().unwrap();
-        let int_value = self.llvm_context.i64_type().const_int(n, true);
+        let int_value = self.llvm_context.i64_type().const_int(n, false);
         int_value.into()
     }
+    fn eval_f32(&self, value_str: &str) -> BasicValueEnum {
+        let n = value_str.parse::<f32>().unwrap();
+        let float_value = self.llvm_context.f32_type().const_float(n as f64);
+        float_value.into()
+    }
+    fn eval_f64(&self, value_str: &str) -> BasicValueEnum {
+        let n = value_str.parse::<f64>().unwrap();
+        let float_value = self.llvm_context.f64_type().const_float(n);
+        float_value.into()
+    }
     fn eval_number_literal(
         &self,
         integer_literal: &NumberLiteral,
@@ -44,17 +72,36 @@ impl LLVMCodeGenerator<'_> {
     ) -> Result<BasicValueEnum, BuilderError> {
         let value_str = &integer_literal.value;
         Ok(match ty {
+            ConcreteType::I8 => self.eval_i8(value_str),
+            ConcreteType::I16 => self.eval_i16(value_str),
             ConcreteType::U8 => self.eval_u8(value_str),
+            ConcreteType::U16 => self.eval_u16(value_str),
             ConcreteType::U32 => self.eval_u32(value_str),
             ConcreteType::I32 => self.eval_i32(value_str),
             ConcreteType::I64 => self.eval_i64(value_str),
             ConcreteType::U64 => self.eval_u64(value_str),
+            ConcreteType::F32 => self.eval_f32(value_str),
+            ConcreteType::F64 => self.eval_f64(value_str),
             ConcreteType::Ptr(_) => unreachable!(),
+            ConcreteType::Array(_, _) => unreachable!(),
+            ConcreteType::FnPtr(_, _) => unreachable!(),
             ConcreteType::Void => unreachable!(),
             ConcreteType::StructLike(_) => unreachable!(),
             ConcreteType::Bool => unreachable!(),
         })
     }
+    fn eval_float_literal(
+        &self,
+        float_literal: &FloatLiteral,
+        ty: &ConcreteType,
+    ) -> Result<BasicValueEnum, BuilderError> {
+        let value_str = &float_literal.value;
+        Ok(match ty {
+            ConcreteType::F32 => self.eval_f32(value_str),
+            ConcreteType::F64 => self.eval_f64(value_str),
+            _ => unreachable!(),
+        })
+    }
     fn eval_bool_literal(
         &self,
         bool_literal: &BoolLiteral,
@@ -67,11 +114,18 @@ impl LLVMCodeGenerator<'_> {
         &self,
         string_literal: &StringLiteral,
     ) -> Result<BasicValueEnum, BuilderError> {
-        let value = string_literal.value.as_str();
-        let string = self
-            .llvm_builder
-            .build_global_string_ptr(value, "string_literal")?;
-        Ok(string.as_basic_value_enum())
+        // build_global_string_ptrは\0で切れてしまうので、バイト列からグローバル定数を作る
+        let value = self
+            .llvm_context
+            .const_string(string_literal.value.as_bytes(), true);
+        let global = self
+            .llvm_module
+            .add_global(value.get_type(), None, "string_literal");
+        global.set_initializer(&value);
+        global.set_constant(true);
+        global.set_linkage(Linkage::Private);
+        global.set_unnamed_addr(true);
+        Ok(global.as_pointer_value().as_basic_value_enum())
     }
     fn eval_struct_literal(
         &self,
@@ -81,7 +135,14 @@ impl LLVMCodeGenerator<'_> {
         let ty = self.type_to_basic_type_enum(ty).unwrap();
         let ptr = self.llvm_builder.build_alloca(ty, "")?;
         for (i, (_name, field_expr)) in struct_literal.fields.iter().enumerate() {
-            let value = self.gen_expression(field_expr)?.unwrap();
+            let mut value = self.gen_expression(field_expr)?.unwrap();
+            // struct型の値はポインタで表しているので、フィールドには中身をロードして格納する
+            if field_expr.ty.is_struct_type() {
+                let field_ty = self.type_to_basic_type_enum(&field_expr.ty).unwrap();
+                value = self
+                    .llvm_builder
+                    .build_load(field_ty, value.into_pointer_value(), "")?;
+            }
             let ptr = self.llvm_builder.build_struct_gep(ty, ptr, i as u32, "")?;
             self.llvm_builder.build_store(ptr, value)?;
         }
@@ -94,41 +155,48 @@ impl LLVMCodeGenerator<'_> {
     ) -> Result<BasicValueEnum, BuilderError> {
         let ptr = self.get_variable(&variable_ref.name);
         let pointee_ty = self.type_to_basic_type_enum(ty).unwrap();
-        if ty.is_struct_type() {
+        // structと配列はロードせず、変数の領域を指すポインタのまま扱う
+        if ty.is_struct_type() || ty.is_array_type() {
             Ok(ptr.as_basic_value_enum())
         } else {
             Ok(self.llvm_builder.build_load(pointee_ty, ptr, "")?)
         }
     }
-    fn eval_index_access(
+    fn gen_index_access_ptr(
         &self,
         index_access: &IndexAccessExpr,
         ty: &ConcreteType,
-    ) -> Result<BasicValueEnum, BuilderError> {
+    ) -> Result<PointerValue, BuilderError> {
         let ptr = self
             .gen_expression(&index_access.target)?
             .unwrap()
             .into_pointer_value();
         let pointee_ty = self.type_to_basic_type_enum(ty).unwrap();
         let index = self.gen_expression(&index_access.index)?.unwrap();
-        let ptr = unsafe {
+        unsafe {
             self.llvm_builder
-                .build_in_bounds_gep(pointee_ty, ptr, &[index.into_int_value()], "")?
-        };
+                .build_in_bounds_gep(pointee_ty, ptr, &[index.into_int_value()], "")
+        }
+    }
+    fn eval_index_access(
+        &self,
+        index_access: &IndexAccessExpr,
+        ty: &ConcreteType,
+    ) -> Result<BasicValueEnum, BuilderError> {
+        let ptr = self.gen_index_access_ptr(index_access, ty)?;
         if ty.is_struct_type() {
             Ok(ptr.as_basic_value_enum())
         } else {
+            let pointee_ty = self.type_to_basic_type_enum(ty).unwrap();
             let value = self.llvm_builder.build_load(pointee_ty, ptr, "")?;
             Ok(value)
         }
     }
-    fn eval_field_access(
+    fn gen_field_access_ptr(
         &self,
         field_access: &FieldAccessExpr,
-        ty: &ConcreteType,
-    ) -> Result<BasicValueEnum, BuilderError> {
+    ) -> Result<PointerValue, BuilderError> {
         if let ConcreteType::StructLike(struct_ty) = &field_access.target.ty {
-            let ty_enum = self.type_to_basic_type_enum(ty).unwrap();
             let index: usize = struct_ty
                 .fields
                 .iter()
@@ -138,28 +206,62 @@ impl LLVMCodeGenerator<'_> {
                 .gen_expression(&field_access.target)?
                 .unwrap()
                 .into_pointer_value();
-            let field_ptr = self.llvm_builder.build_struct_gep(
+            self.llvm_builder.build_struct_gep(
                 self.type_to_basic_type_enum(&field_access.target.ty)
                     .unwrap(),
                 struct_ptr,
                 index as u32,
                 "",
-            )?;
-            let value = self
-                .llvm_builder
-                .build_load(ty_enum, field_ptr, "")
-                .unwrap();
-            Ok(value)
+            )
         } else {
             unreachable!()
         }
     }
+    fn eval_field_access(
+        &self,
+        field_access: &FieldAccessExpr,
+        ty: &ConcreteType,
+    ) -> Result<BasicValueEnum, BuilderError> {
+        let field_ptr = self.gen_field_access_ptr(field_access)?;
+        // struct型や配列のフィールドはポインタのまま返し、a.b.c や a.b[0] のように続けてアクセスできるようにする
+        if ty.is_struct_type() || ty.is_array_type() {
+            return Ok(field_ptr.as_basic_value_enum());
+        }
+        let ty_enum = self.type_to_basic_type_enum(ty).unwrap();
+        let value = self
+            .llvm_builder
+            .build_load(ty_enum, field_ptr, "")
+            .unwrap();
+        Ok(value)
+    }
+    // 値をロードせずに、左辺値が指しているポインタをそのまま返す
+    fn eval_address_of(&self, address_of: &AddressOfExpr) -> Result<BasicValueEnum, BuilderError> {
+        let target = &address_of.target;
+        let ptr = match &target.kind {
+            ExpressionKind::VariableRef(variable_ref) => self.get_variable(&variable_ref.name),
+            ExpressionKind::IndexAccess(index_access) => {
+                self.gen_index_access_ptr(index_access, &target.ty)?
+            }
+            ExpressionKind::FieldAccess(field_access) => self.gen_field_access_ptr(field_access)?,
+            ExpressionKind::Deref(deref) => self
+                .gen_expression(&deref.target)?
+                .unwrap()
+                .into_pointer_value(),
+            // 左辺値であることはresolverで保証されている
+            _ => unreachable!(),
+        };
+        Ok(ptr.as_basic_value_enum())
+    }
     fn eval_deref(
         &self,
         deref: &DerefExpr,
         ty: &ConcreteType,
     ) -> Result<BasicValueEnum, BuilderError> {
         let ptr = self.gen_expression(&deref.target)?.unwrap();
+        // structと配列は値もポインタで持つので、ポインタをそのまま返す
+        if ty.is_struct_type() || ty.is_array_type() {
+            return Ok(ptr);
+        }
         let pointee_ty = self
             .type_to_basic_type_enum(ty)
             .unwrap_or(self.type_to_basic_type_enum(&ConcreteType::U8).unwrap());
@@ -168,17 +270,30 @@ impl LLVMCodeGenerator<'_> {
             .build_load(pointee_ty, ptr.into_pointer_value(), "")?;
         Ok(value)
     }
-    fn eval_sizeof(&self, ty: &ConcreteType) -> BasicValueEnum {
-        let size = self.type_to_basic_type_enum(ty).unwrap().size_of().unwrap();
-        size.as_basic_value_enum()
+    // ターゲットのデータレイアウトからサイズを求め、ポインタサイズの整数の定数にする
+    // va_listはresolverで可変長引数の関数の中でだけ使われることが保証されている
+    fn eval_va_arg(&self, ty: &ConcreteType) -> Result<BasicValueEnum, BuilderError> {
+        let va_list = self.va_list.get().unwrap();
+        let ty = self.type_to_basic_type_enum(ty).unwrap();
+        self.llvm_builder.build_va_arg(va_list, ty, "va_arg")
     }
-    pub(super) fn eval_call_expr<'a>(
+
+    fn eval_sizeof(&self, ty: &ConcreteType, result_ty: &ConcreteType) -> BasicValueEnum {
+        let size = self
+            .target_data
+            .get_abi_size(&self.type_to_basic_type_enum(ty).unwrap());
+        self.type_to_basic_type_enum(result_ty)
+            .unwrap()
+            .into_int_type()
+            .const_int(size, false)
+            .as_basic_value_enum()
+    }
+    // 引数を評価する。struct型の引数は値渡しするのでロードしておく
+    fn gen_call_args<'a>(
         &'a self,
-        call_expr: &CallExpr,
-    ) -> Result<Option<BasicValueEnum<'a>>, BuilderError> {
-        let mut args = call_expr
-            .args
-            .iter()
+        args: &[ConcreteExpression],
+    ) -> Result<Vec<BasicMetadataValueEnum<'a>>, BuilderError> {
+        args.iter()
             .map(|arg| {
                 self.gen_expression(arg).map(|x| {
                     if arg.ty.is_struct_type() {
@@ -192,23 +307,92 @@ impl LLVMCodeGenerator<'_> {
                     }
                 })
             })
-            .collect::<Result<Vec<BasicMetadataValueEnum>, _>>()?;
-
-        let function = *self.function_by_name.get(&call_expr.callee).unwrap();
-        let func = self.gen_or_get_function(function);
-        // 構造体を返す関数を呼ぶ場合、第一引数にスタックポインタを渡す
-        if let ConcreteType::StructLike(_) = &function.decl.return_type {
-            let return_ty = self
-                .type_to_basic_type_enum(&function.decl.return_type)
-                .unwrap();
-            let ptr = self.llvm_builder.build_alloca(return_ty, "")?;
+            .collect()
+    }
+    // 直接の呼び出しと関数ポインタの呼び出しで共通の、戻り値の受け取り方。buildで呼び出し命令を作る
+    fn build_call_with_return<'a>(
+        &'a self,
+        return_type: &ConcreteType,
+        mut args: Vec<BasicMetadataValueEnum<'a>>,
+        build: impl FnOnce(&[BasicMetadataValueEnum<'a>]) -> Result<CallSiteValue<'a>, BuilderError>,
+    ) -> Result<Option<BasicValueEnum<'a>>, BuilderError> {
+        // 大きな構造体を返す関数を呼ぶ場合、第一引数に結果を書き込む領域を渡す
+        if self.returns_via_sret(return_type) {
+            let return_ty = self.type_to_basic_type_enum(return_type).unwrap();
+            let ptr = self.build_entry_block_alloca(return_ty, "")?;
             args.insert(0, ptr.into());
-            self.llvm_builder.build_call(func, &args, "")?;
-            // let value = self.llvm_builder.build_load(return_ty, ptr, "")?;
+            build(&args)?;
             return Ok(Some(ptr.as_basic_value_enum()));
         }
-        let value = self.llvm_builder.build_call(func, &args, "").unwrap();
-        Ok(value.try_as_basic_value().left())
+        let value = build(&args)?;
+        let value = value.try_as_basic_value().left();
+        // 値で返された構造体は、他のstructと同じくポインタで扱えるように格納し直す
+        if let (Some(value), true) = (value, return_type.is_struct_type()) {
+            let ptr = self.build_entry_block_alloca(value.get_type(), "")?;
+            self.llvm_builder.build_store(ptr, value)?;
+            return Ok(Some(ptr.as_basic_value_enum()));
+        }
+        Ok(value)
+    }
+    pub(super) fn eval_call_expr<'a>(
+        &'a self,
+        call_expr: &CallExpr,
+    ) -> Result<Option<BasicValueEnum<'a>>, BuilderError> {
+        let args = self.gen_call_args(&call_expr.args)?;
+
+        // 構造体のコピーと同じllvm.memcpyを使い、宣言を一つにまとめる
+        if call_expr.callee == MEMCPY_FUNCTION_NAME {
+            self.llvm_builder.build_memcpy(
+                args[0].into_pointer_value(),
+                1,
+                args[1].into_pointer_value(),
+                1,
+                args[2].into_int_value(),
+            )?;
+            return Ok(None);
+        }
+
+        // 同名の関数が定義されていなければ、resolverで組み込み関数として解決されている
+        let Some(function) = self.function_by_name.get(&call_expr.callee).copied() else {
+            let builtin = ArithmeticBuiltin::from_name(&call_expr.callee).unwrap();
+            return self
+                .gen_arithmetic_builtin(
+                    builtin,
+                    &call_expr.args[0].ty,
+                    args[0].into_int_value(),
+                    args[1].into_int_value(),
+                )
+                .map(Some);
+        };
+        let func = self.gen_or_get_function(function);
+        self.build_call_with_return(&function.decl.return_type, args, |args| {
+            self.llvm_builder.build_call(func, args, "")
+        })
+    }
+    fn eval_indirect_call<'a>(
+        &'a self,
+        indirect_call: &IndirectCallExpr,
+    ) -> Result<Option<BasicValueEnum<'a>>, BuilderError> {
+        let ConcreteType::FnPtr(param_types, return_type) = &indirect_call.callee.ty else {
+            unreachable!()
+        };
+        let callee = self
+            .gen_expression(&indirect_call.callee)?
+            .unwrap()
+            .into_pointer_value();
+        let args = self.gen_call_args(&indirect_call.args)?;
+        let fn_type = self.function_type(param_types, false, return_type);
+        self.build_call_with_return(return_type, args, |args| {
+            self.llvm_builder
+                .build_indirect_call(fn_type, callee, args, "")
+        })
+    }
+    fn eval_function_ref(&self, function_ref: &FunctionRefExpr) -> BasicValueEnum {
+        let function = *self.function_by_name.get(&function_ref.name).unwrap();
+        self.gen_or_get_function(function)
+            .as_global_value()
+            .as_pointer_value()
+            .as_basic_value_enum()
     }
     pub(super) fn eval_if_expr<'a>(
         &'a self,
@@ -232,22 +416,26 @@ impl LLVMCodeGenerator<'_> {
         self.llvm_builder
             .build_conditional_branch(cond, then_block, else_block)?;
         self.llvm_builder.position_at_end(then_block);
-        let then_value = self.gen_expression(&if_expr.then)?.unwrap();
+        let then_value = self.gen_expression(&if_expr.then)?;
         self.llvm_builder.build_unconditional_branch(merge_block)?;
         let then_block = self.llvm_builder.get_insert_block().unwrap();
         self.llvm_builder.position_at_end(else_block);
-        let else_value = self.gen_expression(&if_expr.els)?.unwrap();
+        let else_value = self.gen_expression(&if_expr.els)?;
         self.llvm_builder.build_unconditional_branch(merge_block)?;
         let else_block = self.llvm_builder.get_insert_block().unwrap();
-        if matches!(ty, ConcreteType::Void) {
-            Ok(None)
-        } else {
-            self.llvm_builder.position_at_end(merge_block);
-            let phi = self
-                .llvm_builder
-                .build_phi(self.type_to_basic_type_enum(ty).unwrap(), "iftmp")?;
-            phi.add_incoming(&[(&then_value, then_block), (&else_value, else_block)]);
-            Ok(Some(phi.as_basic_value()))
+        // 値を返さない場合も、後続の処理はmerge_blockに続ける
+        self.llvm_builder.position_at_end(merge_block);
+        match (ty, then_value, else_value) {
+            (ConcreteType::Void, _, _) => Ok(None),
+            (_, Some(then_value), Some(else_value)) => {
+                // 構造体はポインタで扱っているので、値の型からphiの型を決める
+                let phi = self
+                    .llvm_builder
+                    .build_phi(then_value.get_type(), "iftmp")?;
+                phi.add_incoming(&[(&then_value, then_block), (&else_value, else_block)]);
+                Ok(Some(phi.as_basic_value()))
+            }
+            _ => unreachable!(),
         }
     }
     pub(super) fn eval_when_expr<'a>(
@@ -266,24 +454,194 @@ impl LLVMCodeGenerator<'_> {
             .get_parent()
             .unwrap();
         let then_block = self.llvm_context.append_basic_block(function, "then");
-        let else_block = self.llvm_context.append_basic_block(function, "else");
         let merge_block = self.llvm_context.append_basic_block(function, "ifcont");
         self.llvm_builder
-            .build_conditional_branch(cond, then_block, else_block)?;
+            .build_conditional_branch(cond, then_block, merge_block)?;
         self.llvm_builder.position_at_end(then_block);
-        let _then_value = self.gen_expression(&when_expr.then)?.unwrap();
-        self.llvm_builder.build_unconditional_branch(merge_block)?;
-        self.llvm_builder.position_at_end(else_block);
+        self.gen_expression(&when_expr.then)?;
         self.llvm_builder.build_unconditional_branch(merge_block)?;
         self.llvm_builder.position_at_end(merge_block);
         Ok(None)
     }
+    pub(super) fn eval_while_expr(&self, while_expr: &WhileExpr) -> Result<(), BuilderError> {
+        let function = self
+            .llvm_builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let header_block = self
+            .llvm_context
+            .append_basic_block(function, "loop_header");
+        let body_block = self.llvm_context.append_basic_block(function, "loop_body");
+        let exit_block = self.llvm_context.append_basic_block(function, "loop_exit");
+        self.llvm_builder.build_unconditional_branch(header_block)?;
+
+        self.llvm_builder.position_at_end(header_block);
+        // condがboolであることはresolverで保証されている
+        let cond = self
+            .gen_expression(&while_expr.cond)?
+            .unwrap()
+            .into_int_value();
+        self.llvm_builder
+            .build_conditional_branch(cond, body_block, exit_block)?;
+
+        self.llvm_builder.position_at_end(body_block);
+        self.push_scope(Scope::new(ScopeKind::Block));
+        self.loops.borrow_mut().push(LoopBlocks {
+            continue_block: header_block,
+            exit_block,
+        });
+        for expr in &while_expr.body {
+            self.gen_expression(expr)?;
+        }
+        self.loops.borrow_mut().pop();
+        self.pop_scope();
+        self.llvm_builder.build_unconditional_branch(header_block)?;
+
+        self.llvm_builder.position_at_end(exit_block);
+        Ok(())
+    }
+    // whileと同じブロック構成で、ヘッダーへ戻る前にstepを実行する
+    pub(super) fn eval_for_expr(&self, for_expr: &ForExpr) -> Result<(), BuilderError> {
+        let function = self
+            .llvm_builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        self.push_scope(Scope::new(ScopeKind::Block));
+        if let Some(init) = &for_expr.init {
+            self.gen_expression(init)?;
+        }
+        let header_block = self
+            .llvm_context
+            .append_basic_block(function, "loop_header");
+        let body_block = self.llvm_context.append_basic_block(function, "loop_body");
+        let step_block = self.llvm_context.append_basic_block(function, "loop_step");
+        let exit_block = self.llvm_context.append_basic_block(function, "loop_exit");
+        self.llvm_builder.build_unconditional_branch(header_block)?;
+
+        self.llvm_builder.position_at_end(header_block);
+        // 条件が省略された場合は無限ループになる
+        if let Some(cond) = &for_expr.cond {
+            let cond = self.gen_expression(cond)?.unwrap().into_int_value();
+            self.llvm_builder
+                .build_conditional_branch(cond, body_block, exit_block)?;
+        } else {
+            self.llvm_builder.build_unconditional_branch(body_block)?;
+        }
+
+        self.llvm_builder.position_at_end(body_block);
+        self.push_scope(Scope::new(ScopeKind::Block));
+        // continueでもstepは実行する
+        self.loops.borrow_mut().push(LoopBlocks {
+            continue_block: step_block,
+            exit_block,
+        });
+        for expr in &for_expr.body {
+            self.gen_expression(expr)?;
+        }
+        self.loops.borrow_mut().pop();
+        self.pop_scope();
+        self.llvm_builder.build_unconditional_branch(step_block)?;
+
+        self.llvm_builder.position_at_end(step_block);
+        if let Some(step) = &for_expr.step {
+            self.gen_expression(step)?;
+        }
+        self.llvm_builder.build_unconditional_branch(header_block)?;
+        self.pop_scope();
+
+        self.llvm_builder.position_at_end(exit_block);
+        Ok(())
+    }
+    // ループの中にあることはresolverで保証されている
+    pub(super) fn eval_loop_jump(&self, is_break: bool) -> Result<(), BuilderError> {
+        let loop_blocks = *self.loops.borrow().last().unwrap();
+        self.llvm_builder.build_unconditional_branch(if is_break {
+            loop_blocks.exit_block
+        } else {
+            loop_blocks.continue_block
+        })?;
+        // 後続の命令には到達しないが、ブロックを終端した後に書き出せないので新しいブロックに置く
+        let function = self
+            .llvm_builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let unreachable_block = self.llvm_context.append_basic_block(function, "after_jump");
+        self.llvm_builder.position_at_end(unreachable_block);
+        Ok(())
+    }
+    // 配列を関数の先頭で確保し、全要素に同じ値を格納する。
+    // 要素数によらずコードの大きさが変わらないように、格納はループで行う
+    fn gen_array_fill(
+        &self,
+        array_fill: &ArrayFill,
+        ty: &ConcreteType,
+        name: &str,
+    ) -> Result<PointerValue, BuilderError> {
+        let ConcreteType::Array(element_ty, len) = ty else {
+            unreachable!()
+        };
+        let array_ty = self.type_to_basic_type_enum(ty).unwrap();
+        let element_ty = self.type_to_basic_type_enum(element_ty).unwrap();
+        let ptr = self.build_entry_block_alloca(array_ty, name)?;
+        let mut value = self.gen_expression(&array_fill.value)?.unwrap();
+        if array_fill.value.ty.is_struct_type() {
+            value = self
+                .llvm_builder
+                .build_load(element_ty, value.into_pointer_value(), "")?;
+        }
+
+        let entry_block = self.llvm_builder.get_insert_block().unwrap();
+        let function = entry_block.get_parent().unwrap();
+        let fill_block = self.llvm_context.append_basic_block(function, "array_fill");
+        let exit_block = self
+            .llvm_context
+            .append_basic_block(function, "array_fill_exit");
+        self.llvm_builder.build_unconditional_branch(fill_block)?;
+
+        self.llvm_builder.position_at_end(fill_block);
+        let index_ty = self.llvm_context.i64_type();
+        let index = self.llvm_builder.build_phi(index_ty, "index")?;
+        let current = index.as_basic_value().into_int_value();
+        let element_ptr = unsafe {
+            self.llvm_builder
+                .build_in_bounds_gep(element_ty, ptr, &[current], "")?
+        };
+        self.llvm_builder.build_store(element_ptr, value)?;
+        let next = self
+            .llvm_builder
+            .build_int_add(current, index_ty.const_int(1, false), "")?;
+        let done = self.llvm_builder.build_int_compare(
+            inkwell::IntPredicate::EQ,
+            next,
+            index_ty.const_int(*len as u64, false),
+            "",
+        )?;
+        self.llvm_builder
+            .build_conditional_branch(done, exit_block, fill_block)?;
+        index.add_incoming(&[(&index_ty.const_zero(), entry_block), (&next, fill_block)]);
+
+        self.llvm_builder.position_at_end(exit_block);
+        Ok(ptr)
+    }
     pub(super) fn eval_variable_decls(&self, decls: &VariableDecls) -> Result<(), BuilderError> {
         for decl in &decls.decls {
+            // 確保した配列をそのまま変数の領域にする
+            if let ExpressionKind::ArrayFill(array_fill) = &decl.value.kind {
+                let ptr = self.gen_array_fill(array_fill, &decl.value.ty, &decl.name)?;
+                self.add_variable(&decl.name, ptr);
+                continue;
+            }
             let ty = self.type_to_basic_type_enum(&decl.value.ty).unwrap();
             let value = self.gen_expression(&decl.value)?.unwrap();
-            if ty.is_struct_type() {
-                let ptr = self.llvm_builder.build_alloca(ty, "")?;
+            // 配列もstructと同じくポインタで持っているので、中身をコピーする
+            if ty.is_struct_type() || ty.is_array_type() {
+                let ptr = self.build_entry_block_alloca(ty, &decl.name)?;
                 self.llvm_builder.build_memcpy(
                     ptr,
                     8,
@@ -293,7 +651,7 @@ impl LLVMCodeGenerator<'_> {
                 )?;
                 self.add_variable(&decl.name, ptr);
             } else {
-                let ptr = self.llvm_builder.build_alloca(ty, "")?;
+                let ptr = self.build_entry_block_alloca(ty, &decl.name)?;
                 self.llvm_builder.build_store(ptr, value)?;
                 self.add_variable(&decl.name, ptr);
             }
@@ -302,38 +660,47 @@ impl LLVMCodeGenerator<'_> {
     }
     pub(super) fn eval_assignment(&self, assignment: &Assignment) -> Result<(), BuilderError> {
         let value = self.gen_expression(&assignment.value)?.unwrap();
-        let pointee_type = value.get_type();
+        let value_type = self.type_to_basic_type_enum(&assignment.value.ty).unwrap();
+        let ptr_type = self
+            .llvm_context
+            .i8_type()
+            .ptr_type(AddressSpace::default());
+        // 代入先のポインタを、デリファレンスの回数だけロードして辿る
         let mut ptr = self.get_variable(&assignment.name);
         for _ in 0..assignment.deref_count {
             ptr = self
                 .llvm_builder
-                .build_load(pointee_type, ptr, "")
-                .unwrap()
+                .build_load(ptr_type, ptr, "")?
                 .into_pointer_value();
         }
         if let Some(index_access) = &assignment.index_access {
             let index = self.gen_expression(index_access)?.unwrap();
-            ptr = self
-                .llvm_builder
-                .build_load(pointee_type.ptr_type(AddressSpace::default()), ptr, "")
-                .unwrap()
-                .into_pointer_value();
-
+            // 配列は変数の領域に要素が並んでいるので、ポインタをロードしない
+            if !assignment.indexes_array {
+                ptr = self
+                    .llvm_builder
+                    .build_load(ptr_type, ptr, "")?
+                    .into_pointer_value();
+            }
             ptr = unsafe {
-                self.llvm_builder
-                    .build_in_bounds_gep(pointee_type, ptr, &[index.into_int_value()], "")
-                    .unwrap()
-            };
-            if assignment.value.ty.is_struct_type() {
-                self.llvm_builder.build_memcpy(
+                self.llvm_builder.build_in_bounds_gep(
+                    value_type,
                     ptr,
-                    8,
-                    value.into_pointer_value(),
-                    8,
-                    pointee_type.size_of().unwrap(),
-                )?;
-                return Ok(());
-            }
+                    &[index.into_int_value()],
+                    "",
+                )?
+            };
+        }
+        // struct型の値はポインタで表しているので中身をコピーする
+        if assignment.value.ty.is_struct_type() {
+            self.llvm_builder.build_memcpy(
+                ptr,
+                8,
+                value.into_pointer_value(),
+                8,
+                value_type.size_of().unwrap(),
+            )?;
+            return Ok(());
         }
         self.llvm_builder.build_store(ptr, value)?;
         Ok(())
@@ -346,6 +713,9 @@ impl LLVMCodeGenerator<'_> {
             ExpressionKind::NumberLiteral(number_literal) => {
                 self.eval_number_literal(number_literal, &expr.ty).map(Some)
             }
+            ExpressionKind::FloatLiteral(float_literal) => {
+                self.eval_float_literal(float_literal, &expr.ty).map(Some)
+            }
             ExpressionKind::VariableRef(variable_ref) => {
                 self.eval_variable_ref(variable_ref, &expr.ty).map(Some)
             }
@@ -353,17 +723,34 @@ impl LLVMCodeGenerator<'_> {
                 self.eval_index_access(index_access, &expr.ty).map(Some)
             }
             ExpressionKind::Deref(deref) => self.eval_deref(deref, &expr.ty).map(Some),
+            ExpressionKind::AddressOf(address_of) => self.eval_address_of(address_of).map(Some),
+            ExpressionKind::Cast(cast_expr) => {
+                let value = self.gen_expression(&cast_expr.target)?.unwrap();
+                Ok(Some(self.gen_try_cast(
+                    value,
+                    &cast_expr.target.ty,
+                    &expr.ty,
+                )))
+            }
             ExpressionKind::Binary(binary_expr) => self.eval_binary_expr(binary_expr).map(Some),
             ExpressionKind::Unary(unary_expr) => self.eval_unary_expr(unary_expr).map(Some),
             ExpressionKind::Multi(multi_expr) => self.eval_multi_expr(multi_expr).map(Some),
             ExpressionKind::CallExpr(call_expr) => self.eval_call_expr(call_expr),
+            ExpressionKind::IndirectCall(indirect_call) => self.eval_indirect_call(indirect_call),
+            ExpressionKind::FunctionRef(function_ref) => {
+                Ok(Some(self.eval_function_ref(function_ref)))
+            }
             ExpressionKind::StringLiteral(string_literal) => {
                 self.eval_string_literal(string_literal).map(Some)
             }
             ExpressionKind::StructLiteral(struct_literal) => {
                 self.eval_struct_literal(struct_literal, &expr.ty).map(Some)
             }
-            ExpressionKind::SizeOf(ty) => Ok(Some(self.eval_sizeof(ty))),
+            ExpressionKind::ArrayFill(array_fill) => self
+                .gen_array_fill(array_fill, &expr.ty, "")
+                .map(|ptr| Some(ptr.as_basic_value_enum())),
+            ExpressionKind::SizeOf(ty) => Ok(Some(self.eval_sizeof(ty, &expr.ty))),
+            ExpressionKind::VaArg => self.eval_va_arg(&expr.ty).map(Some),
             ExpressionKind::FieldAccess(field_access_expr) => self
                 .eval_field_access(field_access_expr, &expr.ty)
                 .map(Some),
@@ -371,8 +758,31 @@ impl LLVMCodeGenerator<'_> {
             ExpressionKind::BoolLiteral(bool_literal) => {
                 self.eval_bool_literal(bool_literal).map(Some)
             }
+            ExpressionKind::NullLiteral => Ok(Some(
+                self.type_to_basic_type_enum(&expr.ty)
+                    .unwrap()
+                    .into_pointer_type()
+                    .const_null()
+                    .as_basic_value_enum(),
+            )),
             ExpressionKind::If(if_expr) => self.eval_if_expr(if_expr, &expr.ty),
             ExpressionKind::When(when_expr) => self.eval_when_expr(when_expr),
+            ExpressionKind::While(while_expr) => {
+                self.eval_while_expr(while_expr)?;
+                Ok(None)
+            }
+            ExpressionKind::For(for_expr) => {
+                self.eval_for_expr(for_expr)?;
+                Ok(None)
+            }
+            ExpressionKind::Break => {
+                self.eval_loop_jump(true)?;
+                Ok(None)
+            }
+            ExpressionKind::Continue => {
+                self.eval_loop_jump(false)?;
+                Ok(None)
+            }
             ExpressionKind::VariableDecls(decls) => {
                 self.eval_variable_decls(decls)?;
                 Ok(None)
@@ -383,3 +793,600 @@ impl LLVMCodeGenerator<'_> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::tests::{compile_to_ir, compile_to_ir_for, compile_to_verified_ir, CompileTarget},
+        common::target::TargetPlatform,
+    };
+
+    #[test]
+    fn test_bool_variable_is_i1() {
+        let ir = compile_to_ir(
+            "
+fn is_less(a: i32, b: i32): bool {
+    (:= c: bool (< a b))
+    c
+}
+fn main(): void {
+    (is_less 1 2)
+}
+",
+        );
+        assert!(ir.contains("define i1 @is_less"), "{}", ir);
+        assert!(ir.contains("alloca i1"), "{}", ir);
+        assert!(ir.contains("store i1"), "{}", ir);
+        assert!(ir.contains("load i1"), "{}", ir);
+    }
+
+    #[test]
+    fn test_void_if_continues_in_merge_block() {
+        let ir = compile_to_ir(
+            r#"
+fn print_sign(n: i32): void {
+    (if (< n 0) (printf "negative\n") (printf "positive\n"))
+    (printf "done\n")
+}
+fn main(): void {
+    (print_sign 1)
+}
+"#,
+        );
+        assert!(ir.contains("ifcont:"), "{}", ir);
+        // merge_blockの後にprintfが続く
+        let merge_at = ir.find("ifcont:").unwrap();
+        assert!(
+            ir[merge_at..].contains("call i32 (ptr, ...) @printf"),
+            "{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_nested_if() {
+        let ir = compile_to_ir(
+            r#"
+fn classify(n: i32): i32 {
+    (if (< n 0) 0 (if (= n 0) 1 2))
+}
+fn report(n: i32): void {
+    (if (< n 10) (if (< n 5) (printf "small") (printf "medium")))
+}
+fn main(): void {
+    (classify 1)
+    (report 1)
+}
+"#,
+        );
+        // printfはi32を返すので、reportの内側のifもphiになる
+        assert_eq!(ir.matches("phi i32").count(), 3, "{}", ir);
+        assert!(ir.matches("ifcont").count() >= 4, "{}", ir);
+    }
+
+    #[test]
+    fn test_counting_while_loop() {
+        let ir = compile_to_ir(
+            "
+fn count(n: i32): i32 {
+    (:= i 0)
+    (while (< i n)
+        (:= next (+ i 1))
+        (:=< i next))
+    i
+}
+fn main(): void {
+    (count 10)
+}
+",
+        );
+        assert!(ir.contains("loop_header:"), "{}", ir);
+        assert!(ir.contains("loop_body:"), "{}", ir);
+        assert!(ir.contains("loop_exit:"), "{}", ir);
+        // entryからとループの末尾から、ヘッダーへ分岐する
+        assert_eq!(ir.matches("br label %loop_header").count(), 2, "{}", ir);
+        // ループ内の変数もエントリーブロックで確保される
+        let body_at = ir.find("loop_body:").unwrap();
+        assert!(!ir[body_at..].contains("alloca"), "{}", ir);
+    }
+
+    #[test]
+    fn test_sum_with_for_loop() {
+        let ir = compile_to_ir(
+            "
+fn sum(): i32 {
+    (:= total 0)
+    (for (:= i 0) (< i 10) (:=< i (+ i 1))
+        (:=< total (+ total i)))
+    total
+}
+fn main(): void {
+    (sum)
+}
+",
+        );
+        assert!(ir.contains("loop_step:"), "{}", ir);
+        // bodyの末尾はstepへ、stepの末尾はヘッダーへ分岐する
+        assert!(ir.contains("br label %loop_step"), "{}", ir);
+        assert_eq!(ir.matches("br label %loop_header").count(), 2, "{}", ir);
+        assert!(ir.contains("icmp slt i32"), "{}", ir);
+    }
+
+    #[test]
+    fn test_for_loop_without_clauses_is_infinite() {
+        let ir = compile_to_ir(
+            "
+fn spin(): void {
+    (for () () () (printf \"x\"))
+}
+fn main(): void {
+    (spin)
+}
+",
+        );
+        assert!(ir.contains("br label %loop_body"), "{}", ir);
+        assert!(!ir.contains("br i1"), "{}", ir);
+    }
+
+    #[test]
+    fn test_float_variable_decl_and_return() {
+        let ir = compile_to_ir(
+            "
+fn half(): f64 {
+    (:= x: f64 2)
+    x
+}
+fn single(): f32 {
+    (:= y: f32 1)
+    y
+}
+fn main(): void {
+    (half)
+    (single)
+}
+",
+        );
+        assert!(ir.contains("store double 2.000000e+00"), "{}", ir);
+        assert!(ir.contains("ret double"), "{}", ir);
+        assert!(ir.contains("store float 1.000000e+00"), "{}", ir);
+        assert!(ir.contains("ret float"), "{}", ir);
+    }
+
+    #[test]
+    fn test_float_literal() {
+        let ir = compile_to_ir(
+            "
+fn pi(): f32 {
+    (:= x: f32 3.5)
+    x
+}
+fn main(): void {
+    (:= y 2.5e-1)
+    (pi)
+}
+",
+        );
+        assert!(ir.contains("store float 3.500000e+00"), "{}", ir);
+        // アノテーションがない場合はf64になる
+        assert!(ir.contains("store double 2.500000e-01"), "{}", ir);
+    }
+
+    #[test]
+    fn test_char_literal() {
+        let ir = compile_to_ir(
+            "
+fn main(): void {
+    (:= a 'a')
+    (:= nl '\\n')
+}
+",
+        );
+        assert!(ir.contains("store i8 97"), "{}", ir);
+        assert!(ir.contains("store i8 10"), "{}", ir);
+    }
+
+    #[test]
+    fn test_string_literal_escapes() {
+        let ir = compile_to_ir(
+            "
+fn main(): void {
+    (printf \"a\\nb\")
+    (printf \"a\\0b\")
+}
+",
+        );
+        // LLVMは改行を\0Aと表示する
+        assert!(ir.contains("c\"a\\0Ab\\00\""), "{}", ir);
+        // 埋め込まれた\0の後ろも切り捨てられない
+        assert!(ir.contains("[4 x i8] c\"a\\00b\\00\""), "{}", ir);
+    }
+
+    #[test]
+    fn test_field_access() {
+        let ir = compile_to_ir(
+            "
+struct Point {
+    x: i32,
+    y: i32,
+}
+fn get_y(): i32 {
+    (:= p Point { x: 1, y: 2 })
+    p.y
+}
+fn main(): void {
+    (get_y)
+}
+",
+        );
+        assert!(ir.contains("%Point = type { i32, i32 }"), "{}", ir);
+        assert!(
+            ir.contains("getelementptr inbounds %Point, ptr %p, i32 0, i32 1"),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("load i32"), "{}", ir);
+    }
+
+    #[test]
+    fn test_nested_struct_literal() {
+        let ir = compile_to_ir(
+            "
+struct Point {
+    x: i32,
+    y: i32,
+}
+struct Line {
+    start: Point,
+    end: Point,
+}
+fn end_x(): i32 {
+    (:= line Line { start: Point { x: 1, y: 2 }, end: Point { x: 3, y: 4 } })
+    line.end.x
+}
+fn main(): void {
+    (end_x)
+}
+",
+        );
+        assert!(ir.contains("%Line = type { %Point, %Point }"), "{}", ir);
+        // 内側のstructはポインタではなく値として格納される
+        assert!(ir.contains("load %Point"), "{}", ir);
+        assert!(ir.contains("store %Point"), "{}", ir);
+        assert!(
+            ir.contains("getelementptr inbounds %Line, ptr %line, i32 0, i32 1"),
+            "{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_doubly_nested_field_access() {
+        let ir = compile_to_ir(
+            "
+type Point = struct { x: i32, y: i32 }
+type Line = struct { start: Point, end: Point }
+fn end_y(line: Line): i32 {
+    line.end.y
+}
+fn main(): void {
+    (end_y Line { start: Point { x: 1, y: 2 }, end: Point { x: 3, y: 4 } })
+}
+",
+        );
+        let end_y = ir
+            .split("define i32 @end_y")
+            .nth(1)
+            .and_then(|body| body.split("\n}\n").next())
+            .unwrap();
+        // Lineの中のPointを指してから、そのyを指す
+        assert_eq!(end_y.matches("getelementptr").count(), 2, "{}", ir);
+        assert!(
+            end_y.contains("getelementptr inbounds %Line, ptr %line1, i32 0, i32 1"),
+            "{}",
+            ir
+        );
+        assert!(
+            end_y.contains("getelementptr inbounds %Point, ptr %0, i32 0, i32 1"),
+            "{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_address_of_variable() {
+        let ir = compile_to_ir(
+            "
+fn main(): void {
+    (:= x 1)
+    (:= p &x)
+}
+",
+        );
+        // &xはxをロードせずにallocaのポインタをそのまま格納する
+        assert!(ir.contains("store ptr %x, ptr %p"), "{}", ir);
+        assert!(!ir.contains("load i32"), "{}", ir);
+    }
+
+    #[test]
+    fn test_integer_constant_encoding() {
+        let ir = compile_to_ir(
+            "
+(const NEGATIVE: i32 (- 5))
+fn main(): void {
+    (:= _a NEGATIVE)
+    (:= _b: u8 200)
+    (:= _c: u64 18446744073709551615)
+}
+",
+        );
+        assert!(ir.contains("store i32 -5, ptr %_a"), "{}", ir);
+        // LLVMは整数を符号付きで表示するので、200は-56と表示される
+        assert!(ir.contains("store i8 -56, ptr %_b"), "{}", ir);
+        assert!(ir.contains("store i64 -1, ptr %_c"), "{}", ir);
+    }
+
+    #[test]
+    fn test_small_signed_integer_types() {
+        let ir = compile_to_ir(
+            "
+fn small(): i8 {
+    (:= x: i8 100)
+    x
+}
+fn medium(): i16 {
+    (:= y: i16 30000)
+    y
+}
+fn widen(a: i8, b: i16): i16 {
+    (+ a b)
+}
+fn main(): void {
+    (small)
+    (medium)
+    (widen 1 2)
+}
+",
+        );
+        assert!(ir.contains("define i8 @small()"), "{}", ir);
+        assert!(ir.contains("ret i8"), "{}", ir);
+        assert!(ir.contains("define i16 @medium()"), "{}", ir);
+        assert!(ir.contains("store i16 30000, ptr %y"), "{}", ir);
+        assert!(ir.contains("sext i8"), "{}", ir);
+        assert!(ir.contains("add i16"), "{}", ir);
+    }
+
+    #[test]
+    fn test_null_literal() {
+        let ir = compile_to_ir(
+            "
+fn main(): void {
+    (:= p: *i32 null)
+    (:= _b (== p null))
+}
+",
+        );
+        assert!(ir.contains("store ptr null, ptr %p"), "{}", ir);
+        assert!(ir.contains("icmp eq ptr"), "{}", ir);
+    }
+
+    #[test]
+    fn test_assignment_through_deref() {
+        let ir = compile_to_ir(
+            "
+fn set(p: *i32): void {
+    (:=< *p 5)
+}
+fn main(): void {
+    (:= x 1)
+    (set &x)
+}
+",
+        );
+        assert!(ir.contains("load ptr, ptr %p1"), "{}", ir);
+        assert!(ir.contains("store i32 5, ptr %0"), "{}", ir);
+    }
+
+    #[test]
+    fn test_assignment_through_index() {
+        let ir = compile_to_ir(
+            "
+fn set_at(a: *i32): void {
+    (:=< a[2] 7)
+}
+fn main(): void {
+    (:= x 1)
+    (set_at &x)
+}
+",
+        );
+        assert!(ir.contains("load ptr, ptr %a1"), "{}", ir);
+        assert!(
+            ir.contains("getelementptr inbounds i32, ptr %0, i64 2"),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("store i32 7, ptr %1"), "{}", ir);
+    }
+
+    #[test]
+    fn test_array_variable() {
+        let ir = compile_to_verified_ir(
+            "
+fn second(): u8 {
+    (:= a: [u8; 4] 0)
+    (:=< a[1] 5)
+    a[1]
+}
+fn main(): void {
+    (second)
+}
+",
+        );
+        assert!(ir.contains("%a = alloca [4 x i8]"), "{}", ir);
+        // 全要素をループで初期化する
+        assert!(ir.contains("store i8 0, ptr"), "{}", ir);
+        // 配列の要素は変数の領域を直接指す
+        assert!(
+            ir.contains("getelementptr inbounds i8, ptr %a, i64 1"),
+            "{}",
+            ir
+        );
+        assert!(!ir.contains("load ptr, ptr %a"), "{}", ir);
+    }
+
+    #[test]
+    fn test_sizeof_is_constant() {
+        let ir = compile_to_ir(
+            "
+struct Point {
+    x: i32,
+    y: i32,
+}
+struct Padded {
+    a: u8,
+    b: i64,
+}
+fn main(): void {
+    (:= a (sizeof u64))
+    (:= b (sizeof *u8))
+    (:= c (sizeof Point))
+    (:= d (sizeof Padded))
+}
+",
+        );
+        assert!(ir.contains("store i64 8, ptr %a"), "{}", ir);
+        assert!(ir.contains("store i64 8, ptr %b"), "{}", ir);
+        assert!(ir.contains("store i64 8, ptr %c"), "{}", ir);
+        assert!(ir.contains("store i64 16, ptr %d"), "{}", ir);
+    }
+
+    #[test]
+    fn test_sizeof_on_32_bit_target() {
+        let ir = compile_to_ir_for(
+            "
+fn main(): void {
+    (:= a (sizeof usize))
+    (:= b (sizeof *u8))
+    (:= c: usize 1)
+}
+",
+            CompileTarget::Platform(TargetPlatform::Linux386),
+        );
+        // sizeofの結果もusizeなのでi32になる
+        assert!(ir.contains("store i32 4, ptr %a"), "{}", ir);
+        assert!(ir.contains("store i32 4, ptr %b"), "{}", ir);
+        assert!(ir.contains("store i32 1, ptr %c"), "{}", ir);
+    }
+
+    #[test]
+    fn test_cast_expressions() {
+        let ir = compile_to_ir(
+            "
+fn widen(a: i32, b: u32): i64 {
+    (:= c (cast<u64> b))
+    (cast<i64> a)
+}
+fn narrow(a: i64): u8 {
+    (cast<u8> a)
+}
+fn round_trip(p: *u8): *u8 {
+    (:= address (cast<u64> p))
+    (cast<*u8> address)
+}
+fn to_float(a: i32): f64 {
+    (:= f (cast<f64> a))
+    (:= i (cast<i32> f))
+    f
+}
+fn main(): void {
+    (widen 1 2)
+    (narrow 1)
+    (round_trip \"abc\")
+    (to_float 1)
+}
+",
+        );
+        // 引数のロードは名前なしなので、命令と型だけを確認する
+        for instruction in [
+            "zext i32",
+            "sext i32",
+            "trunc i64",
+            "ptrtoint ptr",
+            "inttoptr i64",
+            "sitofp i32",
+            "fptosi double",
+        ] {
+            assert!(ir.contains(instruction), "{}\n{}", instruction, ir);
+        }
+    }
+
+    #[test]
+    fn test_enum_variant_is_constant() {
+        let ir = compile_to_ir(
+            "
+type Color = enum { Red, Green = 5, Blue }
+fn main(): void {
+    (:= red Color.Red)
+    (:= blue: Color Color.Blue)
+}
+",
+        );
+        assert!(ir.contains("store i32 0, ptr %red"), "{}", ir);
+        assert!(ir.contains("store i32 6, ptr %blue"), "{}", ir);
+    }
+
+    #[test]
+    fn test_break_exits_loop() {
+        let ir = compile_to_ir(
+            "
+fn find(limit: i32): i32 {
+    (:= i 0)
+    (while true
+        (when (== i limit) (break))
+        (:=< i (+ i 1)))
+    i
+}
+fn main(): void {
+    (find 3)
+}
+",
+        );
+        // 条件分岐とは別に、breakからループの出口へ飛ぶ
+        assert_eq!(ir.matches("br label %loop_exit").count(), 1, "{}", ir);
+    }
+
+    #[test]
+    fn test_continue_runs_step() {
+        let ir = compile_to_ir(
+            "
+fn sum_odd(n: i32): i32 {
+    (:= sum 0)
+    (for (:= i 0) (< i n) (:=< i (+ i 1))
+        (when (== (% i 2) 0) (continue))
+        (:=< sum (+ sum i)))
+    sum
+}
+fn main(): void {
+    (sum_odd 10)
+}
+",
+        );
+        // 本体の末尾とcontinueの両方からstepへ飛び、stepを通ってからヘッダーに戻る
+        assert_eq!(ir.matches("br label %loop_step").count(), 2, "{}", ir);
+    }
+
+    #[test]
+    fn test_ternary_expression() {
+        let ir = compile_to_ir(
+            "
+fn max(a: i64, b: i64): i64 {
+    (? (> a b) a b)
+}
+fn main(): void {
+    (max 1 2)
+}
+",
+        );
+        assert!(ir.contains("phi i64"), "{}", ir);
+    }
+}
@@ -2,28 +2,119 @@ use super::*;
 use crate::ast::MultiOp;
 
 impl LLVMCodeGenerator<'_> {
+    // 結果が確定した時点で残りのオペランドを評価せずにmerge_blockへ分岐する
     pub(super) fn eval_multi_expr(
         &self,
         multi_expr: &MultiExpr,
     ) -> Result<BasicValueEnum, BuilderError> {
-        let operands = multi_expr
-            .operands
-            .iter()
-            .map(|operand| self.gen_expression(operand))
-            .collect::<Result<Vec<_>, _>>()?;
-
         match multi_expr.op {
             MultiOp::And | MultiOp::Or => {
-                let mut result = self.llvm_context.bool_type().const_int(1, false);
-                for operand in operands {
-                    let operand = operand.unwrap().into_int_value();
-                    result = match multi_expr.op {
-                        MultiOp::And => self.llvm_builder.build_and(result, operand, "and")?,
-                        MultiOp::Or => self.llvm_builder.build_or(result, operand, "or")?,
-                    };
+                let (rhs_block_name, merge_block_name, short_circuit_value) = match multi_expr.op {
+                    MultiOp::And => ("and_rhs", "and_merge", 0),
+                    MultiOp::Or => ("or_rhs", "or_merge", 1),
+                };
+                let short_circuit_value = self
+                    .llvm_context
+                    .bool_type()
+                    .const_int(short_circuit_value, false);
+                let function = self
+                    .llvm_builder
+                    .get_insert_block()
+                    .unwrap()
+                    .get_parent()
+                    .unwrap();
+                let merge_block = self
+                    .llvm_context
+                    .append_basic_block(function, merge_block_name);
+                let mut incomings = Vec::new();
+                let last_index = multi_expr.operands.len() - 1;
+                for (i, operand) in multi_expr.operands.iter().enumerate() {
+                    // operandがboolであることはresolverで保証されている
+                    let value = self.gen_expression(operand)?.unwrap().into_int_value();
+                    // operandの評価中にブロックが変わることがあるので、分岐元は評価後に取得する
+                    let current_block = self.llvm_builder.get_insert_block().unwrap();
+                    if i == last_index {
+                        self.llvm_builder.build_unconditional_branch(merge_block)?;
+                        incomings.push((value, current_block));
+                    } else {
+                        let rhs_block = self
+                            .llvm_context
+                            .insert_basic_block_after(current_block, rhs_block_name);
+                        match multi_expr.op {
+                            MultiOp::And => self.llvm_builder.build_conditional_branch(
+                                value,
+                                rhs_block,
+                                merge_block,
+                            )?,
+                            MultiOp::Or => self.llvm_builder.build_conditional_branch(
+                                value,
+                                merge_block,
+                                rhs_block,
+                            )?,
+                        };
+                        incomings.push((short_circuit_value, current_block));
+                        self.llvm_builder.position_at_end(rhs_block);
+                    }
+                }
+                self.llvm_builder.position_at_end(merge_block);
+                let phi = self
+                    .llvm_builder
+                    .build_phi(self.llvm_context.bool_type(), merge_block_name)?;
+                for (value, block) in &incomings {
+                    phi.add_incoming(&[(value as &dyn BasicValue, *block)]);
                 }
-                Ok(result.as_basic_value_enum())
+                Ok(phi.as_basic_value())
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::tests::compile_to_ir;
+
+    #[test]
+    fn test_and_short_circuits() {
+        let ir = compile_to_ir(
+            "
+fn check(): bool {
+    (< (printf \"rhs\") 0)
+}
+fn both(a: i32, b: i32): void {
+    (:= c (&& (< a b) (check)))
+}
+fn main(): void {
+    (both 1 2)
+}
+",
+        );
+        // checkの呼び出しはand_rhsブロックの中でだけ行われる
+        let branch_at = ir.find("br i1").expect(&ir);
+        let rhs_at = ir.find("and_rhs:").expect(&ir);
+        let call_at = ir.find("call i1 @check").expect(&ir);
+        assert!(branch_at < rhs_at && rhs_at < call_at, "{}", ir);
+        assert!(ir.contains("label %and_rhs, label %and_merge"), "{}", ir);
+        assert!(ir.contains("phi i1 [ false, %entry ]"), "{}", ir);
+        assert!(!ir.contains(" and i1"), "{}", ir);
+    }
+
+    #[test]
+    fn test_or_short_circuits() {
+        let ir = compile_to_ir(
+            "
+fn check(): bool {
+    (< (printf \"rhs\") 0)
+}
+fn either(a: i32, b: i32): void {
+    (:= c (|| (< a b) (check)))
+}
+fn main(): void {
+    (either 1 2)
+}
+",
+        );
+        assert!(ir.contains("label %or_merge, label %or_rhs"), "{}", ir);
+        assert!(ir.contains("phi i1 [ true, %entry ]"), "{}", ir);
+        assert!(!ir.contains(" or i1"), "{}", ir);
+    }
+}
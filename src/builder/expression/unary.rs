@@ -13,6 +13,62 @@ impl LLVMCodeGenerator<'_> {
                 .llvm_builder
                 .build_not(operand.into_int_value(), "not")?
                 .into()),
+            UnaryOp::BitNot => Ok(self
+                .llvm_builder
+                .build_not(operand.into_int_value(), "bitnot")?
+                .into()),
+            UnaryOp::Neg => Ok(self
+                .llvm_builder
+                .build_int_neg(operand.into_int_value(), "neg")?
+                .into()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::tests::compile_to_ir;
+
+    #[test]
+    fn test_double_negation() {
+        let ir = compile_to_ir(
+            "
+fn negneg(x: i32): i32 {
+    (- (- x))
+}
+fn main(): void {
+    (negneg 1)
+}
+",
+        );
+        assert_eq!(ir.matches("sub i32 0,").count(), 2, "{}", ir);
+    }
+
+    #[test]
+    fn test_double_not() {
+        let ir = compile_to_ir(
+            "
+fn double_not(a: i32, b: i32): bool {
+    (! (not (< a b)))
+}
+fn main(): void {
+    (double_not 1 2)
+}
+",
+        );
+        assert_eq!(ir.matches("xor i1").count(), 2, "{}", ir);
+    }
+
+    #[test]
+    fn test_bit_not_u8_is_folded() {
+        let ir = compile_to_ir(
+            "
+fn main(): void {
+    (:= a: u8 (~ 0))
+}
+",
+        );
+        // LLVMはi8の255を-1と表示する
+        assert!(ir.contains("store i8 -1"), "{}", ir);
+    }
+}
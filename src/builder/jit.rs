@@ -0,0 +1,285 @@
+use std::ffi::{c_char, c_int, c_void};
+
+use inkwell::{
+    types::BasicMetadataTypeEnum,
+    values::{BasicValueEnum, FunctionValue},
+};
+
+use super::*;
+
+// JIT実行の入口として生成する関数。引数を埋め込んで呼び出し、戻り値をi64に揃えて返す
+const JIT_ENTRY_FUNCTION_NAME: &str = "__jit_entry";
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+    fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+    fn memset(dst: *mut c_void, value: c_int, size: usize) -> *mut c_void;
+    fn strlen(s: *const c_char) -> usize;
+    fn strcmp(s1: *const c_char, s2: *const c_char) -> c_int;
+    fn strcpy(dst: *mut c_char, src: *const c_char) -> *mut c_char;
+    fn strcat(dst: *mut c_char, src: *const c_char) -> *mut c_char;
+    fn printf(format: *const c_char, ...) -> c_int;
+}
+
+// 組み込み関数と、それを実装するlibcの関数のアドレス。memcpyはllvm.memcpyになるので含まない
+fn libc_functions() -> [(&'static str, usize); 9] {
+    [
+        ("malloc", malloc as usize),
+        ("realloc", realloc as usize),
+        ("free", free as usize),
+        ("memset", memset as usize),
+        ("strlen", strlen as usize),
+        ("strcmp", strcmp as usize),
+        ("strcpy", strcpy as usize),
+        ("strcat", strcat as usize),
+        ("printf", printf as usize),
+    ]
+}
+
+fn jit_error(message: String) -> CompileError {
+    CompileError::new(Range::default(), CompileErrorKind::CannotRunJit { message })
+}
+
+impl<'a> LLVMCodeGenerator<'a> {
+    // entryを引数argsで呼び出す関数を生成する。戻り値がvoidなら0を返す
+    fn gen_jit_entry(
+        &self,
+        entry: FunctionValue<'a>,
+        args: &[i64],
+        is_signed_return: bool,
+    ) -> Result<FunctionValue<'a>, BuilderError> {
+        let i64_type = self.llvm_context.i64_type();
+        let function = self.llvm_module.add_function(
+            JIT_ENTRY_FUNCTION_NAME,
+            i64_type.fn_type(&[], false),
+            None,
+        );
+        self.llvm_builder
+            .position_at_end(self.llvm_context.append_basic_block(function, "entry"));
+        // 最後に生成した関数のデバッグ情報の位置が残っていると検証に失敗する
+        self.llvm_builder.unset_current_debug_location();
+        let call_args = entry
+            .get_type()
+            .get_param_types()
+            .iter()
+            .zip(args)
+            .map(|(param_type, arg)| {
+                param_type
+                    .into_int_type()
+                    .const_int(*arg as u64, true)
+                    .into()
+            })
+            .collect::<Vec<_>>();
+        let result = self
+            .llvm_builder
+            .build_call(entry, &call_args, "result")?
+            .try_as_basic_value()
+            .left();
+        let result =
+            match result {
+                Some(BasicValueEnum::IntValue(value)) => self
+                    .llvm_builder
+                    .build_int_cast_sign_flag(value, i64_type, is_signed_return, "result")?,
+                _ => i64_type.const_zero(),
+            };
+        self.llvm_builder.build_return(Some(&result))?;
+        Ok(function)
+    }
+
+    // モジュールを検証してから、entryの関数をJITで実行して戻り値を返す。
+    // 引数と戻り値は整数か真偽値に限り、戻り値はi64に拡張する。
+    // モジュールは実行エンジンに渡されるので、一度しか実行できない
+    pub fn jit_run(&self, entry: &str, args: &[i64]) -> Result<i64, CompileError> {
+        self.verify()?;
+        let Some(entry_function) = self.llvm_module.get_function(entry) else {
+            return Err(jit_error(format!("Function `{}` is not defined", entry)));
+        };
+        let entry_type = entry_function.get_type();
+        let param_types = entry_type.get_param_types();
+        if entry_type.is_var_arg() || param_types.len() != args.len() {
+            return Err(jit_error(format!(
+                "Function `{}` expects {} arguments, but {} were given",
+                entry,
+                param_types.len(),
+                args.len()
+            )));
+        }
+        let has_int_params = param_types
+            .iter()
+            .all(|ty| matches!(ty, BasicMetadataTypeEnum::IntType(_)));
+        let has_int_return = entry_type
+            .get_return_type()
+            .map_or(true, |ty| ty.is_int_type());
+        if !has_int_params || !has_int_return {
+            return Err(jit_error(format!(
+                "Function `{}` must take and return only integers",
+                entry
+            )));
+        }
+        let is_signed_return = self
+            .function_by_name
+            .get(entry)
+            .map_or(true, |func| func.decl.return_type.is_signed_integer_type());
+        self.gen_jit_entry(entry_function, args, is_signed_return)
+            .map_err(|error| jit_error(error.to_string()))?;
+
+        let execution_engine = self
+            .llvm_module
+            .create_jit_execution_engine(OptimizationLevel::None)
+            .map_err(|message| jit_error(message.to_string()))?;
+        for (name, address) in libc_functions() {
+            if let Some(function) = self.llvm_module.get_function(name) {
+                execution_engine.add_global_mapping(&function, address);
+            }
+        }
+        unsafe {
+            let function = execution_engine
+                .get_function::<unsafe extern "C" fn() -> i64>(JIT_ENTRY_FUNCTION_NAME)
+                .map_err(|error| jit_error(error.to_string()))?;
+            Ok(function.call())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::tests::{generate, CompileTarget};
+
+    fn run(source: &str, entry: &str, args: &[i64]) -> Result<i64, CompileError> {
+        // 実行するマシン向けにコードを生成する
+        let triple = TargetMachine::get_default_triple()
+            .as_str()
+            .to_string_lossy()
+            .into_owned();
+        generate(
+            source,
+            CompileTarget::Triple(&triple),
+            |_| {},
+            |llvm_codegenerator| llvm_codegenerator.jit_run(entry, args),
+        )
+    }
+
+    #[test]
+    fn test_jit_run() {
+        let source = "
+fn main(): i32 {
+    (+ 40 2)
+}
+";
+        assert_eq!(run(source, "main", &[]).unwrap(), 42);
+    }
+
+    #[test]
+    fn test_jit_switch() {
+        let source = "
+fn classify(x: i32): i32 {
+    (:= result 0)
+    switch (x) {
+        case 1: (:=< result 10)
+        case 2:
+            (:=< result 20)
+            return (+ result 1)
+        default: (:=< result 99)
+    }
+    result
+}
+fn main(): void {
+    (classify 1)
+}
+";
+        assert_eq!(run(source, "classify", &[1]).unwrap(), 10);
+        // caseの文の後に次のcaseへ落ちることはない
+        assert_eq!(run(source, "classify", &[2]).unwrap(), 21);
+        assert_eq!(run(source, "classify", &[5]).unwrap(), 99);
+    }
+
+    #[test]
+    fn test_jit_do_while_runs_body_once() {
+        let source = "
+fn count(n: i32): i32 {
+    (:= runs 0)
+    do {
+        (:=< runs (+ runs 1))
+    } while (< runs n)
+    runs
+}
+fn main(): void {
+    (count 0)
+}
+";
+        // 条件が最初から偽でも本体は1回実行される
+        assert_eq!(run(source, "count", &[0]).unwrap(), 1);
+        assert_eq!(run(source, "count", &[3]).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_jit_call_through_function_pointer() {
+        let source = "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn mul(a: i32, b: i32): i32 {
+    (* a b)
+}
+fn apply(use_add: bool, x: i32): i32 {
+    (:= op: fn(i32, i32): i32 &mul)
+    (when use_add (:=< op &add))
+    (op x 10)
+}
+fn main(): void {
+    (apply true 1)
+}
+";
+        assert_eq!(run(source, "apply", &[1, 3]).unwrap(), 13);
+        assert_eq!(run(source, "apply", &[0, 3]).unwrap(), 30);
+    }
+
+    #[test]
+    fn test_jit_run_with_args() {
+        let source = "
+fn sub(a: i32, b: u8): i32 {
+    (- a b)
+}
+fn main(): void {
+    (:= buf (malloc 8))
+    (free buf)
+    (sub 1 2)
+}
+";
+        assert_eq!(run(source, "sub", &[1, 3]).unwrap(), -2);
+        assert!(matches!(
+            run(source, "sub", &[1]).unwrap_err().kind,
+            CompileErrorKind::CannotRunJit { .. }
+        ));
+    }
+
+    #[test]
+    fn test_jit_nan_compare() {
+        let source = "
+fn nan_lt(): i32 {
+    (:= zero 0.0)
+    (:= nan (/ zero zero))
+    (if (< nan 1.0) 1 0)
+}
+fn main(): void {
+    (nan_lt)
+}
+";
+        // 既定の順序付きの比較では、NaNとの比較はfalseになる
+        assert_eq!(run(source, "nan_lt", &[]).unwrap(), 0);
+
+        let triple = TargetMachine::get_default_triple()
+            .as_str()
+            .to_string_lossy()
+            .into_owned();
+        let result = generate(
+            source,
+            CompileTarget::Triple(&triple),
+            |llvm_codegenerator| llvm_codegenerator.use_unordered_float_compare(),
+            |llvm_codegenerator| llvm_codegenerator.jit_run("nan_lt", &[]),
+        );
+        assert_eq!(result.unwrap(), 1);
+    }
+}
@@ -1,24 +1,43 @@
+mod debug_info;
 mod expression;
+mod jit;
+mod source_map;
 mod statement;
 mod toplevel;
 mod ty;
 
 use inkwell::OptimizationLevel;
 
-use crate::common::target::TargetPlatform;
+use self::debug_info::DebugInfo;
+use self::source_map::SourceMap;
+
+use crate::ast::Range;
+use crate::common::target::{PointerSizedIntWidth, TargetPlatform};
 use crate::concrete_ast::*;
-use inkwell::builder::Builder as LLVMBuilder;
+use crate::resolver::error::{CompileError, CompileErrorKind};
+use inkwell::basic_block::BasicBlock;
+use inkwell::builder::{Builder as LLVMBuilder, BuilderError};
 use inkwell::context::Context as LLVMContext;
 use inkwell::module::Module as LLVMModule;
-use inkwell::targets::{InitializationConfig, Target};
+use inkwell::passes::PassBuilderOptions;
+use inkwell::targets::{
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetData, TargetMachine,
+    TargetTriple,
+};
+use inkwell::types::BasicTypeEnum;
 use inkwell::values::PointerValue;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::path::Path;
+
+// 組み込み関数のmemcpy。呼び出しはllvm.memcpyに置き換える
+const MEMCPY_FUNCTION_NAME: &str = "memcpy";
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ScopeKind {
     Global,
     Function,
+    Block,
 }
 
 #[derive(Debug)]
@@ -36,47 +55,147 @@ impl Scope<'_> {
     }
 }
 
+// breakとcontinueの飛び先
+#[derive(Debug, Clone, Copy)]
+pub struct LoopBlocks<'a> {
+    pub continue_block: BasicBlock<'a>,
+    pub exit_block: BasicBlock<'a>,
+}
+
+// 整数の加減乗算でオーバーフローしたときの扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArithmeticMode {
+    // 型のビット幅でラップアラウンドする
+    #[default]
+    Wrapping,
+    // llvm.*.with.overflowで検査し、オーバーフローしたらllvm.trapで止める。デバッグ用
+    Checked,
+}
+
 pub struct LLVMCodeGenerator<'a> {
     llvm_module: LLVMModule<'a>,
     llvm_builder: LLVMBuilder<'a>,
     llvm_context: &'a LLVMContext,
-    scopes: Vec<RefCell<Scope<'a>>>,
+    scopes: RefCell<Vec<Scope<'a>>>,
+    // 生成中のループ。内側のループが末尾に来る
+    loops: RefCell<Vec<LoopBlocks<'a>>>,
+    // 生成中の関数が可変長引数を取る場合のva_list
+    va_list: Cell<Option<PointerValue<'a>>>,
     function_by_name: HashMap<String, &'a Function>,
+    // sizeofをコンパイル時に定数にするために使う
+    target_data: TargetData,
+    // with_targetで指定したターゲットトリプル
+    target_triple: Option<String>,
+    // enable_debug_infoを呼んだ場合だけ持つ
+    debug_info: Option<DebugInfo<'a>>,
+    // enable_source_mapを呼んだ場合だけ持つ
+    source_map: Option<SourceMap<'a>>,
+    // trueなら浮動小数点数の比較に順序なし(unordered)の述語を使い、NaNとの比較をtrueにする
+    unordered_float_compare: bool,
+    arithmetic_mode: ArithmeticMode,
+    // trueなら整数の/と%の前に除数が0でないか検査し、0ならllvm.trapで止める
+    guard_division_by_zero: bool,
+    // trueならシフト量を(ビット幅-1)とのandで丸め、ビット幅以上のシフトでも結果を決める
+    mask_shift_amount: bool,
+}
+
+fn initialize_targets() {
+    Target::initialize_all(&InitializationConfig {
+        asm_parser: false,
+        asm_printer: false,
+        base: true,
+        disassembler: false,
+        info: true,
+        machine_code: true,
+    });
+}
+
+fn create_target_machine(
+    triple: &str,
+    optimization_level: OptimizationLevel,
+) -> Result<TargetMachine, String> {
+    initialize_targets();
+    let target_triple = TargetTriple::create(triple);
+    Target::from_triple(&target_triple)
+        .map_err(|message| message.to_string())?
+        .create_target_machine(
+            &target_triple,
+            "generic",
+            "",
+            optimization_level,
+            RelocMode::Default,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| format!("Unsupported target `{}`", triple))
+}
+
+fn create_target_data(
+    triple: &str,
+    optimization_level: OptimizationLevel,
+) -> Result<TargetData, String> {
+    Ok(create_target_machine(triple, optimization_level)?.get_target_data())
+}
+
+fn pointer_sized_int_width_of(target_data: &TargetData) -> PointerSizedIntWidth {
+    match target_data.get_pointer_byte_size(None) {
+        4 => PointerSizedIntWidth::ThirtyTwo,
+        _ => PointerSizedIntWidth::SixtyFour,
+    }
+}
+
+// usizeの幅はresolverで決まるので、コード生成の前にターゲットのデータレイアウトから求めておく
+pub fn pointer_sized_int_width(triple: &str) -> Result<PointerSizedIntWidth, String> {
+    let target_data = create_target_data(triple, OptimizationLevel::None)?;
+    Ok(pointer_sized_int_width_of(&target_data))
 }
 
 impl<'a> LLVMCodeGenerator<'a> {
     pub fn new(
         llvm_context: &'a LLVMContext,
-        _target: TargetPlatform,
+        target: TargetPlatform,
         _optimization_level: OptimizationLevel,
         module: &'a ConcreteModule,
     ) -> Self {
-        let llvm_module = llvm_context.create_module("main");
-        let llvm_builder = llvm_context.create_builder();
+        initialize_targets();
 
-        Target::initialize_all(&InitializationConfig {
-            asm_parser: false,
-            asm_printer: false,
-            base: true,
-            disassembler: false,
-            info: true,
-            machine_code: true,
+        let metrics = target.metrics();
+        // データレイアウトが未定義のターゲットは、ポインタのサイズだけ合わせたレイアウトを使う
+        let target_data = TargetData::create(match metrics.target_data_layout {
+            "" if metrics.word_size == 4 => "e-p:32:32",
+            "" => "e",
+            layout => layout,
         });
 
-        // let triple = TargetTriple::create(target.metrics().target_triplet);
-        // let target = Target::from_triple(&triple).unwrap();
-        // let host_cpu = TargetMachine::get_host_cpu_name();
-        // let features = TargetMachine::get_host_cpu_features();
-        // let target_machine = target
-        //     .create_target_machine(
-        //         &triple,
-        //         host_cpu.to_str().unwrap(),
-        //         features.to_str().unwrap(),
-        //         optimization_level,
-        //         RelocMode::Default,
-        //         CodeModel::Default,
-        //     )
-        //     .unwrap();
+        Self::with_target_data(llvm_context, target_data, None, module)
+    }
+
+    // ターゲットトリプルを指定して生成する。モジュールにもトリプルとデータレイアウトを設定する
+    pub fn with_target(
+        llvm_context: &'a LLVMContext,
+        triple: &str,
+        optimization_level: OptimizationLevel,
+        module: &'a ConcreteModule,
+    ) -> Result<Self, String> {
+        let target_data = create_target_data(triple, optimization_level)?;
+        let generator =
+            Self::with_target_data(llvm_context, target_data, Some(triple.to_string()), module);
+        generator
+            .llvm_module
+            .set_triple(&TargetTriple::create(triple));
+        generator
+            .llvm_module
+            .set_data_layout(&generator.target_data.get_data_layout());
+        Ok(generator)
+    }
+
+    fn with_target_data(
+        llvm_context: &'a LLVMContext,
+        target_data: TargetData,
+        target_triple: Option<String>,
+        module: &'a ConcreteModule,
+    ) -> Self {
+        let llvm_module = llvm_context.create_module("main");
+        let llvm_builder = llvm_context.create_builder();
 
         let mut function_by_name = HashMap::new();
         for toplevel in &module.toplevels {
@@ -84,6 +203,7 @@ impl<'a> LLVMCodeGenerator<'a> {
                 TopLevel::Function(func) => {
                     function_by_name.insert(func.decl.name.clone(), func);
                 }
+                TopLevel::GlobalVariable(_) => {}
             }
         }
 
@@ -91,13 +211,155 @@ impl<'a> LLVMCodeGenerator<'a> {
             llvm_module,
             llvm_builder,
             llvm_context,
-            scopes: Vec::new(),
+            scopes: RefCell::new(Vec::new()),
+            loops: RefCell::new(Vec::new()),
+            va_list: Cell::new(None),
             function_by_name,
+            target_data,
+            target_triple,
+            debug_info: None,
+            source_map: None,
+            unordered_float_compare: false,
+            arithmetic_mode: ArithmeticMode::default(),
+            guard_division_by_zero: false,
+            mask_shift_amount: false,
         }
     }
+
+    // 浮動小数点数の比較を順序なしにする。gen_moduleより前に呼ぶ
+    pub fn use_unordered_float_compare(&mut self) {
+        self.unordered_float_compare = true;
+    }
+
+    // 整数の加減乗算のオーバーフローの扱いを変える。gen_moduleより前に呼ぶ
+    pub fn set_arithmetic_mode(&mut self, arithmetic_mode: ArithmeticMode) {
+        self.arithmetic_mode = arithmetic_mode;
+    }
+
+    // 整数の除算と剰余で、0で割る前にtrapするようにする。gen_moduleより前に呼ぶ
+    pub fn enable_division_by_zero_guard(&mut self) {
+        self.guard_division_by_zero = true;
+    }
+
+    // シフト量を実行時にビット幅未満へ丸めるようにする。gen_moduleより前に呼ぶ
+    pub fn enable_shift_amount_masking(&mut self) {
+        self.mask_shift_amount = true;
+    }
+
+    // 生成先のusizeの幅
+    pub fn ptr_sized_int_type(&self) -> PointerSizedIntWidth {
+        pointer_sized_int_width_of(&self.target_data)
+    }
+
+    pub fn target_triple(&self) -> Option<&str> {
+        self.target_triple.as_deref()
+    }
+
+    // 検証に失敗するモジュールでも、調査できるようにIRを返す
+    pub fn emit_ir_string(&self) -> String {
+        self.llvm_module.print_to_string().to_string()
+    }
+
+    pub fn verify(&self) -> Result<(), CompileError> {
+        self.llvm_module.verify().map_err(|message| {
+            CompileError::new(
+                Range::default(),
+                CompileErrorKind::InvalidModule {
+                    message: message.to_string(),
+                },
+            )
+        })
+    }
+
+    // with_targetで生成していなければ、実行中のマシン向けのTargetMachineを作る。
+    // モジュールのトリプルとデータレイアウトもそれに合わせる
+    fn create_module_target_machine(
+        &self,
+        optimization_level: OptimizationLevel,
+    ) -> Result<TargetMachine, CompileError> {
+        let triple = match &self.target_triple {
+            Some(triple) => triple.clone(),
+            None => TargetMachine::get_default_triple()
+                .as_str()
+                .to_string_lossy()
+                .into_owned(),
+        };
+        let target_machine =
+            create_target_machine(&triple, optimization_level).map_err(|message| {
+                CompileError::new(
+                    Range::default(),
+                    CompileErrorKind::CannotEmitCode { message },
+                )
+            })?;
+        self.llvm_module.set_triple(&target_machine.get_triple());
+        self.llvm_module
+            .set_data_layout(&target_machine.get_target_data().get_data_layout());
+        Ok(target_machine)
+    }
+
+    // 最適化レベルに応じたLLVMの標準パイプラインをモジュールに適用する。Noneなら何もしない
+    pub fn optimize(&self, optimization_level: OptimizationLevel) -> Result<(), CompileError> {
+        let passes = match optimization_level {
+            OptimizationLevel::None => return Ok(()),
+            OptimizationLevel::Less => "default<O1>",
+            OptimizationLevel::Default => "default<O2>",
+            OptimizationLevel::Aggressive => "default<O3>",
+        };
+        let target_machine = self.create_module_target_machine(optimization_level)?;
+        self.llvm_module
+            .run_passes(passes, &target_machine, PassBuilderOptions::create())
+            .map_err(|message| {
+                CompileError::new(
+                    Range::default(),
+                    CompileErrorKind::OptimizationFailed {
+                        message: message.to_string(),
+                    },
+                )
+            })
+    }
+
+    // モジュールを検証してから、オブジェクトファイルを書き出す
+    pub fn write_object_file(
+        &self,
+        path: &Path,
+        optimization_level: OptimizationLevel,
+    ) -> Result<(), CompileError> {
+        self.verify()?;
+        self.create_module_target_machine(optimization_level)?
+            .write_to_file(&self.llvm_module, FileType::Object, path)
+            .map_err(|message| {
+                CompileError::new(
+                    Range::default(),
+                    CompileErrorKind::CannotEmitCode {
+                        message: message.to_string(),
+                    },
+                )
+            })
+    }
+
+    // モジュールを検証してから、アセンブリを文字列で返す
+    pub fn emit_assembly(
+        &self,
+        optimization_level: OptimizationLevel,
+    ) -> Result<String, CompileError> {
+        self.verify()?;
+        let buffer = self
+            .create_module_target_machine(optimization_level)?
+            .write_to_memory_buffer(&self.llvm_module, FileType::Assembly)
+            .map_err(|message| {
+                CompileError::new(
+                    Range::default(),
+                    CompileErrorKind::CannotEmitCode {
+                        message: message.to_string(),
+                    },
+                )
+            })?;
+        Ok(String::from_utf8_lossy(buffer.as_slice()).into_owned())
+    }
+
     pub fn gen_module(&mut self, module: &'a ConcreteModule) {
-        self.scopes
-            .push(RefCell::new(Scope::new(ScopeKind::Global)));
+        // グローバル変数はこのスコープに登録されるので、どの関数からも参照できる
+        self.push_scope(Scope::new(ScopeKind::Global));
 
         // self.gen_intrinsic_functions_on_llvm();
         for top in &module.toplevels {
@@ -107,36 +369,299 @@ impl<'a> LLVMCodeGenerator<'a> {
         for top in &module.toplevels {
             match top {
                 TopLevel::Function(func) => self.gen_function_body(func).unwrap(),
+                TopLevel::GlobalVariable(_) => {}
             }
         }
 
-        self.scopes.pop();
-    }
-    pub fn get_module(self) -> LLVMModule<'a> {
-        self.llvm_module
+        self.finalize_debug_info();
+        self.pop_scope();
     }
     fn add_variable(&self, name: &str, value: PointerValue<'a>) {
         self.scopes
-            .last()
-            .unwrap()
             .borrow_mut()
+            .last_mut()
+            .unwrap()
             .values
             .insert(name.into(), value);
     }
     fn get_variable(&self, name: &str) -> PointerValue<'a> {
         *self
             .scopes
-            .last()
-            .unwrap()
             .borrow()
-            .values
-            .get(name)
+            .iter()
+            .rev()
+            .find_map(|scope| scope.values.get(name))
             .unwrap()
     }
-    fn pop_scope(&mut self) {
-        self.scopes.pop();
+    fn pop_scope(&self) {
+        self.scopes.borrow_mut().pop();
     }
-    fn push_scope(&mut self, scope: Scope<'a>) {
-        self.scopes.push(RefCell::new(scope));
+    fn push_scope(&self, scope: Scope<'a>) {
+        self.scopes.borrow_mut().push(scope);
+    }
+    // ループの中で宣言された変数でスタックが伸び続けないように、allocaは関数の先頭で行う
+    fn build_entry_block_alloca(
+        &self,
+        ty: BasicTypeEnum<'a>,
+        name: &str,
+    ) -> Result<PointerValue<'a>, BuilderError> {
+        let builder = self.llvm_context.create_builder();
+        let entry_block = self
+            .llvm_builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap()
+            .get_first_basic_block()
+            .unwrap();
+        match entry_block.get_first_instruction() {
+            Some(instruction) => builder.position_before(&instruction),
+            None => builder.position_at_end(entry_block),
+        }
+        builder.build_alloca(ty, name)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use nom::Finish;
+
+    use super::*;
+    use crate::{
+        concretizer::{concretize_module, ConcretizerContext},
+        parser::parse_module,
+        resolver::{resolve_module, ResolverContext},
+    };
+
+    #[derive(Clone, Copy)]
+    pub(crate) enum CompileTarget<'s> {
+        Platform(TargetPlatform),
+        Triple(&'s str),
+    }
+
+    // ソースをコード生成まで進め、生成し終えたLLVMCodeGeneratorをfに渡す。
+    // configureはコード生成の前に呼ばれる
+    pub(crate) fn generate<T>(
+        source: &str,
+        target: CompileTarget,
+        configure: impl FnOnce(&mut LLVMCodeGenerator<'_>),
+        f: impl FnOnce(LLVMCodeGenerator<'_>) -> T,
+    ) -> T {
+        let (_, module) = parse_module(source.into()).finish().unwrap();
+        let ptr_sized_int_type = match target {
+            CompileTarget::Platform(platform) => PointerSizedIntWidth::from(platform),
+            CompileTarget::Triple(triple) => pointer_sized_int_width(triple).unwrap(),
+        };
+        let resolver_context = ResolverContext::new(ptr_sized_int_type);
+        let resolved_module = resolve_module(&resolver_context, &module, true).unwrap();
+        assert!(resolver_context.errors.borrow().is_empty());
+        let concretizer_context =
+            ConcretizerContext::from_resolved_module(&resolver_context, resolved_module);
+        let concrete_module = concretize_module(&concretizer_context);
+        let llvm_context = LLVMContext::create();
+        let mut llvm_codegenerator = match target {
+            CompileTarget::Platform(platform) => LLVMCodeGenerator::new(
+                &llvm_context,
+                platform,
+                OptimizationLevel::None,
+                &concrete_module,
+            ),
+            CompileTarget::Triple(triple) => LLVMCodeGenerator::with_target(
+                &llvm_context,
+                triple,
+                OptimizationLevel::None,
+                &concrete_module,
+            )
+            .unwrap(),
+        };
+        assert_eq!(llvm_codegenerator.ptr_sized_int_type(), ptr_sized_int_type);
+        configure(&mut llvm_codegenerator);
+        llvm_codegenerator.gen_module(&concrete_module);
+        f(llvm_codegenerator)
+    }
+
+    fn compile(source: &str, target: CompileTarget, verify: bool) -> String {
+        generate(
+            source,
+            target,
+            |_| {},
+            |llvm_codegenerator| {
+                let ir = llvm_codegenerator.emit_ir_string();
+                if verify {
+                    if let Err(error) = llvm_codegenerator.verify() {
+                        panic!("{}\n{}", error, ir);
+                    }
+                }
+                ir
+            },
+        )
+    }
+
+    pub(crate) fn compile_to_ir(source: &str) -> String {
+        compile_to_ir_for(source, CompileTarget::Platform(TargetPlatform::DarwinArm64))
+    }
+
+    pub(crate) fn compile_to_ir_for(source: &str, target: CompileTarget) -> String {
+        compile(source, target, false)
+    }
+
+    // 生成したIRがLLVMの検証を通ることも確かめる
+    pub(crate) fn compile_to_verified_ir(source: &str) -> String {
+        compile(
+            source,
+            CompileTarget::Platform(TargetPlatform::DarwinArm64),
+            true,
+        )
+    }
+
+    #[test]
+    fn test_usize_follows_target_triple() {
+        let triple = "i686-unknown-linux-gnu";
+        assert_eq!(
+            pointer_sized_int_width(triple).unwrap(),
+            PointerSizedIntWidth::ThirtyTwo
+        );
+        let ir = compile(
+            "
+fn main(): void {
+    (:= size: usize (sizeof *u8))
+    (:= buf (malloc size))
+}
+",
+            CompileTarget::Triple(triple),
+            true,
+        );
+        assert!(
+            ir.contains("target triple = \"i686-unknown-linux-gnu\""),
+            "{}",
+            ir
+        );
+        // usizeとポインタは4バイトになる
+        assert!(ir.contains("store i32 4, ptr %size"), "{}", ir);
+        assert!(ir.contains("@malloc(i32"), "{}", ir);
+    }
+
+    #[test]
+    fn test_unknown_target_triple_is_error() {
+        assert!(pointer_sized_int_width("unknown-target").is_err());
+    }
+
+    #[test]
+    fn test_write_object_file() {
+        let path = std::env::temp_dir().join("hirou_compiler_test_write_object_file.o");
+        generate(
+            "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn main(): void {
+    (add 1 2)
+}
+",
+            CompileTarget::Triple("x86_64-unknown-linux-gnu"),
+            |_| {},
+            |llvm_codegenerator| {
+                llvm_codegenerator
+                    .write_object_file(&path, OptimizationLevel::Default)
+                    .unwrap()
+            },
+        );
+        let object = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        // ELFのマジックナンバー
+        assert_eq!(&object[..4], b"\x7fELF");
+    }
+
+    #[test]
+    fn test_emit_ir_and_assembly() {
+        let source = "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn main(): void {
+    (add 1 2)
+}
+";
+        let (ir, assembly) = generate(
+            source,
+            CompileTarget::Triple("x86_64-unknown-linux-gnu"),
+            |_| {},
+            |llvm_codegenerator| {
+                (
+                    llvm_codegenerator.emit_ir_string(),
+                    llvm_codegenerator
+                        .emit_assembly(OptimizationLevel::None)
+                        .unwrap(),
+                )
+            },
+        );
+        assert!(ir.contains("define i32 @add(i32 %a, i32 %b)"), "{}", ir);
+        assert!(assembly.contains("add:"), "{}", assembly);
+        assert!(assembly.contains("main:"), "{}", assembly);
+    }
+
+    #[test]
+    fn test_optimize_folds_constants() {
+        let source = "
+fn five(): i32 {
+    (:= a 2)
+    (:= b 3)
+    (+ a b)
+}
+fn main(): void {
+    (five)
+}
+";
+        let optimize = |optimization_level| {
+            generate(
+                source,
+                CompileTarget::Triple("x86_64-unknown-linux-gnu"),
+                |_| {},
+                |llvm_codegenerator| {
+                    llvm_codegenerator.optimize(optimization_level).unwrap();
+                    llvm_codegenerator.emit_ir_string()
+                },
+            )
+        };
+        // O0ではローカル変数がそのまま残る
+        let ir = optimize(OptimizationLevel::None);
+        assert!(ir.contains("alloca i32"), "{}", ir);
+        assert!(!ir.contains("ret i32 5"), "{}", ir);
+
+        let ir = optimize(OptimizationLevel::Default);
+        assert!(ir.contains("ret i32 5"), "{}", ir);
+        assert!(!ir.contains("alloca"), "{}", ir);
+    }
+
+    #[test]
+    fn test_debug_info() {
+        let ir = generate(
+            "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn main(): void {
+    (add 1 2)
+}
+",
+            CompileTarget::Triple("x86_64-unknown-linux-gnu"),
+            |llvm_codegenerator| {
+                llvm_codegenerator.enable_debug_info(Path::new("/tmp/sample/add.hr"))
+            },
+            |llvm_codegenerator| {
+                llvm_codegenerator.verify().unwrap();
+                llvm_codegenerator.emit_ir_string()
+            },
+        );
+        assert!(ir.contains("!llvm.dbg.cu"), "{}", ir);
+        assert!(ir.contains("!dbg"), "{}", ir);
+        assert!(
+            ir.contains("DIFile(filename: \"add.hr\", directory: \"/tmp/sample\")"),
+            "{}",
+            ir
+        );
+        // 関数の宣言の行と、文の行と列
+        assert!(ir.contains("name: \"add\""), "{}", ir);
+        assert!(ir.contains("DILocation(line: 3, column: 5"), "{}", ir);
     }
 }
@@ -0,0 +1,159 @@
+use std::fmt::Write;
+
+use inkwell::{
+    basic_block::BasicBlock,
+    values::{FunctionValue, InstructionValue},
+};
+
+use super::*;
+
+// 生成した命令とソースの位置の対応
+pub struct SourceMap<'a> {
+    // 直前にset_debug_locationで付けた位置。以降に生成した命令はこの位置に対応する
+    current_range: Cell<Option<Range>>,
+    ranges: RefCell<HashMap<InstructionValue<'a>, Range>>,
+    // ブロックごとの、対応付けを済ませた最後の命令。命令は常にブロックの末尾に追加するので、
+    // これより後の命令だけを見ればよい
+    last_recorded: RefCell<HashMap<BasicBlock<'a>, InstructionValue<'a>>>,
+}
+
+fn instructions<'a>(function: FunctionValue<'a>) -> impl Iterator<Item = InstructionValue<'a>> {
+    function.get_basic_blocks().into_iter().flat_map(|block| {
+        std::iter::successors(block.get_first_instruction(), |instruction| {
+            instruction.get_next_instruction()
+        })
+    })
+}
+
+// 関数名はマングルした名前なので、JSONの文字列として書けるようにエスケープする
+fn json_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+impl<'a> LLVMCodeGenerator<'a> {
+    // 命令ごとのソースの位置を記録する。gen_moduleより前に呼ぶ
+    pub fn enable_source_map(&mut self) {
+        self.source_map = Some(SourceMap {
+            current_range: Cell::new(None),
+            ranges: RefCell::new(HashMap::new()),
+            last_recorded: RefCell::new(HashMap::new()),
+        });
+    }
+
+    // 前に位置を付けてから生成した命令を、その位置に対応付けてから、以降の位置をrangeにする
+    pub(super) fn record_source_range(&self, range: Option<Range>) {
+        let Some(source_map) = &self.source_map else {
+            return;
+        };
+        let function = self
+            .llvm_builder
+            .get_insert_block()
+            .and_then(|block| block.get_parent());
+        if let (Some(function), Some(current_range)) = (function, source_map.current_range.get()) {
+            let mut ranges = source_map.ranges.borrow_mut();
+            let mut last_recorded = source_map.last_recorded.borrow_mut();
+            for block in function.get_basic_blocks() {
+                let first = match last_recorded.get(&block) {
+                    Some(last) => last.get_next_instruction(),
+                    None => block.get_first_instruction(),
+                };
+                let new_instructions =
+                    std::iter::successors(first, |instruction| instruction.get_next_instruction());
+                for instruction in new_instructions {
+                    ranges.insert(instruction, current_range);
+                    last_recorded.insert(block, instruction);
+                }
+            }
+        }
+        source_map.current_range.set(range);
+    }
+
+    // 命令ごとのソースの位置をJSONの配列で返す。命令は「関数名#関数内での順番」のidで表す。
+    // 最適化の前に呼べば、emit_ir_stringが出力する命令と同じ順番になる
+    pub fn emit_source_map(&self) -> String {
+        let mut entries = Vec::new();
+        if let Some(source_map) = &self.source_map {
+            let ranges = source_map.ranges.borrow();
+            for function in self.llvm_module.get_functions() {
+                let name = function.get_name().to_string_lossy();
+                for (i, instruction) in instructions(function).enumerate() {
+                    let Some(range) = ranges.get(&instruction) else {
+                        continue;
+                    };
+                    entries.push(format!(
+                        "{{\"id\": {}, \"function\": {}, \"opcode\": \"{:?}\", \"line\": {}, \"col\": {}, \"end_line\": {}, \"end_col\": {}}}",
+                        json_string(&format!("{}#{}", name, i)),
+                        json_string(&name),
+                        instruction.get_opcode(),
+                        range.from.line,
+                        range.from.col,
+                        range.to.line,
+                        range.to.col
+                    ));
+                }
+            }
+        }
+        if entries.is_empty() {
+            return "[]\n".to_string();
+        }
+        format!("[\n  {}\n]\n", entries.join(",\n  "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::tests::{generate, CompileTarget};
+
+    #[test]
+    fn test_emit_source_map() {
+        let source_map = generate(
+            "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn main(): void {
+    (add 1 2)
+}
+",
+            CompileTarget::Platform(TargetPlatform::DarwinArm64),
+            |llvm_codegenerator| llvm_codegenerator.enable_source_map(),
+            |llvm_codegenerator| llvm_codegenerator.emit_source_map(),
+        );
+        // (+ a b) の加算と戻り値は3行目の文に対応する
+        assert!(
+            source_map.contains(
+                "\"function\": \"add\", \"opcode\": \"Add\", \"line\": 3, \"col\": 5, \"end_line\": 3"
+            ),
+            "{}",
+            source_map
+        );
+        assert!(
+            source_map.contains("\"function\": \"main\", \"opcode\": \"Call\", \"line\": 6"),
+            "{}",
+            source_map
+        );
+        assert!(source_map.contains("\"id\": \"add#"), "{}", source_map);
+
+        // 有効にしなければ何も記録しない
+        let source_map = generate(
+            "
+fn main(): void {}
+",
+            CompileTarget::Platform(TargetPlatform::DarwinArm64),
+            |_| {},
+            |llvm_codegenerator| llvm_codegenerator.emit_source_map(),
+        );
+        assert_eq!(source_map, "[]\n");
+    }
+}
@@ -1,51 +1,245 @@
-use inkwell::{
-    builder::BuilderError,
-    types::BasicType,
-    values::{BasicValue, InstructionValue},
-};
+use inkwell::{builder::BuilderError, types::BasicType, values::InstructionValue};
 
 use super::*;
 use crate::concrete_ast::*;
 
 impl LLVMCodeGenerator<'_> {
     pub(super) fn gen_return(&mut self, ret: &Return) -> Result<InstructionValue, BuilderError> {
-        if let Some(expression) = &ret.expression {
-            let value = self.gen_expression(expression)?.unwrap();
-            let ptr = self.llvm_builder.build_alloca(value.get_type(), "")?;
-            if value.is_struct_value() {
-                dbg!("value is struct type");
-                self.llvm_builder.build_call(
-                    self.llvm_module.get_function("memcpy").unwrap(),
-                    &[value
-                        .get_type()
-                        .size_of()
-                        .unwrap()
-                        .as_basic_value_enum()
-                        .into()],
-                    "memcpy",
-                )?;
-            } else {
-                self.llvm_builder.build_store(ptr, value)?;
-            }
-            self.llvm_builder.build_return(Some(&value))
-        } else {
+        let Some(expression) = &ret.expression else {
+            self.gen_va_end()?;
+            return self.llvm_builder.build_return(None);
+        };
+        // voidの式を返すときは、式を評価してから値を持たないreturnにする
+        let Some(value) = self.gen_expression(expression)? else {
+            self.gen_va_end()?;
+            return self.llvm_builder.build_return(None);
+        };
+        if !expression.ty.is_struct_type() {
+            self.gen_va_end()?;
+            return self.llvm_builder.build_return(Some(&value));
+        }
+
+        // structは関数内ではポインタで扱っているので、返す前に中身を取り出す
+        let struct_ptr = value.into_pointer_value();
+        let struct_ty = self.type_to_basic_type_enum(&expression.ty).unwrap();
+        if self.returns_via_sret(&expression.ty) {
+            let sret_ptr = self
+                .llvm_builder
+                .get_insert_block()
+                .unwrap()
+                .get_parent()
+                .unwrap()
+                .get_first_param()
+                .unwrap()
+                .into_pointer_value();
+            let align = self.target_data.get_abi_alignment(&struct_ty);
+            self.llvm_builder.build_memcpy(
+                sret_ptr,
+                align,
+                struct_ptr,
+                align,
+                struct_ty.size_of().unwrap(),
+            )?;
+            self.gen_va_end()?;
             self.llvm_builder.build_return(None)
+        } else {
+            let struct_value = self.llvm_builder.build_load(struct_ty, struct_ptr, "")?;
+            self.gen_va_end()?;
+            self.llvm_builder.build_return(Some(&struct_value))
         }
     }
     pub(super) fn gen_effect(&self, effect: &Effect) -> Result<(), BuilderError> {
         self.gen_expression(&effect.expression)?;
         Ok(())
     }
+    // caseごとにブロックを作り、終端していなければswitch_endへ抜ける
+    pub(super) fn gen_switch(&mut self, switch: &Switch) -> Result<(), BuilderError> {
+        // scrutineeが整数でラベルが整数の定数であることはresolverで保証されている
+        let value = self
+            .gen_expression(&switch.scrutinee)?
+            .unwrap()
+            .into_int_value();
+        let function = self
+            .llvm_builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let case_blocks = switch
+            .cases
+            .iter()
+            .map(|_| {
+                self.llvm_context
+                    .append_basic_block(function, "switch_case")
+            })
+            .collect::<Vec<_>>();
+        let default_block = switch.default.as_ref().map(|_| {
+            self.llvm_context
+                .append_basic_block(function, "switch_default")
+        });
+        let merge_block = self.llvm_context.append_basic_block(function, "switch_end");
+        let mut cases = Vec::new();
+        for ((label, _), block) in switch.cases.iter().zip(&case_blocks) {
+            let label = self.gen_expression(label)?.unwrap().into_int_value();
+            cases.push((label, *block));
+        }
+        self.llvm_builder
+            .build_switch(value, default_block.unwrap_or(merge_block), &cases)?;
+
+        let bodies = switch
+            .cases
+            .iter()
+            .map(|(_, statements)| statements)
+            .zip(case_blocks)
+            .chain(switch.default.iter().zip(default_block));
+        for (statements, block) in bodies {
+            self.llvm_builder.position_at_end(block);
+            self.push_scope(Scope::new(ScopeKind::Block));
+            for statement in statements {
+                self.gen_statement(statement)?;
+            }
+            self.pop_scope();
+            let terminated = self
+                .llvm_builder
+                .get_insert_block()
+                .and_then(|block| block.get_terminator())
+                .is_some();
+            if !terminated {
+                self.llvm_builder.build_unconditional_branch(merge_block)?;
+            }
+        }
+        self.llvm_builder.position_at_end(merge_block);
+        // すべての分岐がreturnした場合、switch_endには到達しない
+        if merge_block.get_first_use().is_none() {
+            self.llvm_builder.build_unreachable()?;
+        }
+        Ok(())
+    }
+    // 本体を先に置き、condが真なら本体へ戻る
+    pub(super) fn gen_do_while(&mut self, do_while: &DoWhile) -> Result<(), BuilderError> {
+        let function = self
+            .llvm_builder
+            .get_insert_block()
+            .unwrap()
+            .get_parent()
+            .unwrap();
+        let body_block = self.llvm_context.append_basic_block(function, "loop_body");
+        let cond_block = self.llvm_context.append_basic_block(function, "loop_cond");
+        let exit_block = self.llvm_context.append_basic_block(function, "loop_exit");
+        self.llvm_builder.build_unconditional_branch(body_block)?;
+
+        self.llvm_builder.position_at_end(body_block);
+        self.push_scope(Scope::new(ScopeKind::Block));
+        // continueでもcondは評価する
+        self.loops.borrow_mut().push(LoopBlocks {
+            continue_block: cond_block,
+            exit_block,
+        });
+        for statement in &do_while.body {
+            self.gen_statement(statement)?;
+        }
+        self.loops.borrow_mut().pop();
+        self.pop_scope();
+        let terminated = self
+            .llvm_builder
+            .get_insert_block()
+            .and_then(|block| block.get_terminator())
+            .is_some();
+        if !terminated {
+            self.llvm_builder.build_unconditional_branch(cond_block)?;
+        }
+
+        self.llvm_builder.position_at_end(cond_block);
+        if cond_block.get_first_use().is_none() {
+            // 本体が必ずreturnし、continueもない場合はcondを評価することはない
+            self.llvm_builder.build_unreachable()?;
+        } else {
+            // condがboolであることはresolverで保証されている
+            let cond = self
+                .gen_expression(&do_while.cond)?
+                .unwrap()
+                .into_int_value();
+            self.llvm_builder
+                .build_conditional_branch(cond, body_block, exit_block)?;
+        }
+
+        self.llvm_builder.position_at_end(exit_block);
+        // condを評価せず、breakもなければloop_exitには到達しない
+        if exit_block.get_first_use().is_none() {
+            self.llvm_builder.build_unreachable()?;
+        }
+        Ok(())
+    }
     pub(super) fn gen_statement(
         &mut self,
         statement: &Statement,
     ) -> Result<Option<InstructionValue>, BuilderError> {
         match &statement {
-            Statement::Return(ret) => self.gen_return(ret).map(Some),
+            Statement::Return(ret) => {
+                self.set_debug_location(ret.range);
+                self.gen_return(ret).map(Some)
+            }
             Statement::Effect(effect) => {
+                self.set_debug_location(effect.range);
                 self.gen_effect(effect)?;
                 Ok(None)
             }
+            Statement::Block(statements) => {
+                self.push_scope(Scope::new(ScopeKind::Block));
+                let mut result = Ok(None);
+                for statement in statements {
+                    result = self.gen_statement(statement);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                self.pop_scope();
+                result
+            }
+            Statement::Switch(switch) => {
+                self.set_debug_location(switch.range);
+                self.gen_switch(switch)?;
+                Ok(None)
+            }
+            Statement::DoWhile(do_while) => {
+                self.set_debug_location(do_while.range);
+                self.gen_do_while(do_while)?;
+                Ok(None)
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::tests::compile_to_verified_ir;
+
+    #[test]
+    fn test_do_while_body_precedes_condition() {
+        let ir = compile_to_verified_ir(
+            "
+fn count(n: i32): i32 {
+    (:= i 0)
+    do {
+        (:=< i (+ i 1))
+    } while (< i n)
+    i
+}
+fn main(): void {
+    (count 10)
+}
+",
+        );
+        // entryからは条件を評価せずに本体へ進み、条件が真なら本体へ戻る
+        let body_at = ir.find("loop_body:").unwrap();
+        let cond_at = ir.find("loop_cond:").unwrap();
+        assert!(body_at < cond_at, "{}", ir);
+        assert!(ir[..body_at].contains("br label %loop_body"), "{}", ir);
+        assert!(!ir[..cond_at].contains("icmp"), "{}", ir);
+        assert!(
+            ir[cond_at..].contains("label %loop_body, label %loop_exit"),
+            "{}",
+            ir
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use inkwell::{
     builder::BuilderError,
-    types::{AnyType, BasicMetadataTypeEnum, BasicType},
-    values::FunctionValue,
+    types::{AnyType, BasicMetadataTypeEnum, BasicType, FunctionType},
+    values::{BasicValue, FunctionValue},
     AddressSpace,
 };
 
@@ -9,56 +9,61 @@ use super::*;
 use crate::concrete_ast::*;
 
 impl<'a> LLVMCodeGenerator<'a> {
-    pub(super) fn gen_or_get_function(&self, function: &Function) -> FunctionValue {
-        if let Some(ret) = self.llvm_module.get_function(&function.decl.name) {
-            return ret;
-        }
-
-        let returns_struct = match function.decl.return_type {
-            ConcreteType::StructLike(_) => true,
-            _ => false,
-        };
-
-        let mut has_var_args = false;
-        let mut param_types: Vec<BasicMetadataTypeEnum> = Vec::new();
+    // 関数の型。関数ポインタの呼び出しでも同じ型を使う
+    pub(super) fn function_type<'t>(
+        &self,
+        param_types: impl IntoIterator<Item = &'t ConcreteType>,
+        has_var_args: bool,
+        return_type: &ConcreteType,
+    ) -> FunctionType<'a> {
+        let returns_struct = self.returns_via_sret(return_type);
+        let mut llvm_param_types: Vec<BasicMetadataTypeEnum> = Vec::new();
         // structを返す関数の場合は第一引数にポインタを追加する
         if returns_struct {
             let param = self
                 .llvm_context
                 .i8_type()
                 .ptr_type(AddressSpace::default());
-            param_types.push(param.into());
+            llvm_param_types.push(param.into());
+        }
+        // struct型の引数はLLVMの集約型として値渡しする。
+        // 呼び出し側でロードし、関数の先頭でallocaに格納し直すので、関数内では他のstructと同じくポインタで扱える
+        for ty in param_types {
+            if let Some(ty) = self.type_to_basic_metadata_type_enum(ty) {
+                llvm_param_types.push(ty);
+            }
+        }
+        match self.type_to_basic_type_enum(return_type) {
+            Some(return_ty) if !returns_struct => {
+                return_ty.fn_type(&llvm_param_types, has_var_args)
+            }
+            _ => self
+                .llvm_context
+                .void_type()
+                .fn_type(&llvm_param_types, has_var_args),
+        }
+    }
+
+    pub(super) fn gen_or_get_function(&self, function: &Function) -> FunctionValue {
+        if let Some(ret) = self.llvm_module.get_function(&function.decl.name) {
+            return ret;
         }
 
+        let return_type = &function.decl.return_type;
+        let returns_struct = self.returns_via_sret(return_type);
+        let mut has_var_args = false;
+        let mut param_types = Vec::new();
         for arg in &function.decl.args {
             match arg {
                 Argument::VarArgs => {
                     has_var_args = true;
                 }
-                Argument::Normal(ty, _) => {
-                    if let Some(ty) = self.type_to_basic_metadata_type_enum(ty) {
-                        param_types.push(ty);
-                    }
-                }
+                Argument::Normal(ty, _) => param_types.push(ty),
             }
         }
-
-        let return_ty = self.type_to_basic_type_enum(&function.decl.return_type);
         let function = self.llvm_module.add_function(
             &function.decl.name,
-            if let Some(return_ty) = return_ty {
-                if returns_struct {
-                    self.llvm_context
-                        .void_type()
-                        .fn_type(&param_types, has_var_args)
-                } else {
-                    return_ty.fn_type(&param_types, has_var_args)
-                }
-            } else {
-                self.llvm_context
-                    .void_type()
-                    .fn_type(&param_types, has_var_args)
-            },
+            self.function_type(param_types, has_var_args, return_type),
             None,
         );
 
@@ -76,7 +81,9 @@ impl<'a> LLVMCodeGenerator<'a> {
                 inkwell::attributes::AttributeLoc::Param(0),
                 self.llvm_context.create_type_attribute(
                     inkwell::attributes::Attribute::get_named_enum_kind_id("sret"),
-                    return_ty.unwrap().as_any_type_enum(),
+                    self.type_to_basic_type_enum(return_type)
+                        .unwrap()
+                        .as_any_type_enum(),
                 ),
             );
         }
@@ -88,10 +95,7 @@ impl<'a> LLVMCodeGenerator<'a> {
         if function.body.is_empty() {
             return Ok(());
         }
-        let returns_struct = match function.decl.return_type {
-            ConcreteType::StructLike(_) => true,
-            _ => false,
-        };
+        let returns_struct = self.returns_via_sret(&function.decl.return_type);
         let function_value = self.llvm_module.get_function(&function.decl.name).unwrap();
         let entry_basic_block = self
             .llvm_context
@@ -101,6 +105,7 @@ impl<'a> LLVMCodeGenerator<'a> {
         self.push_scope(scope);
         {
             self.llvm_builder.position_at_end(entry_basic_block);
+            self.begin_debug_function(function, function_value);
 
             // Set parameters in function body
             // Generate function body
@@ -114,9 +119,9 @@ impl<'a> LLVMCodeGenerator<'a> {
                 .decl
                 .args
                 .iter()
-                .map(|x| match x {
-                    Argument::VarArgs => unimplemented!(),
-                    Argument::Normal(ty, name) => (ty, name),
+                .filter_map(|x| match x {
+                    Argument::VarArgs => None,
+                    Argument::Normal(ty, name) => Some((ty, name)),
                 })
                 .enumerate()
             {
@@ -133,51 +138,514 @@ impl<'a> LLVMCodeGenerator<'a> {
                 self.add_variable(name, allocated_pointer);
             }
 
+            if function.decl.args.last() == Some(&Argument::VarArgs) {
+                self.gen_va_start()?;
+            }
+
             // Generate function body
-            for (i, statement) in function.body.iter().enumerate() {
-                if i == function.body.len() - 1 {
-                    // 構造体を返す場合、最後のreturn文はreturn voidにする
-                    if returns_struct {
-                        match statement {
-                            Statement::Return(return_stmt) => {
-                                let value = self
-                                    .gen_expression(return_stmt.expression.as_ref().unwrap())?
-                                    .unwrap();
-                                let first_param_ptr = function_value
-                                    .get_first_param()
-                                    .unwrap()
-                                    .into_pointer_value();
-                                let struct_ptr = value.into_pointer_value();
-                                let struct_ty = self
-                                    .type_to_basic_type_enum(
-                                        &return_stmt.expression.as_ref().unwrap().ty,
-                                    )
-                                    .unwrap()
-                                    .into_struct_type();
-                                self.llvm_builder.build_memcpy(
-                                    first_param_ptr,
-                                    8,
-                                    struct_ptr,
-                                    8,
-                                    struct_ty.size_of().unwrap(),
-                                )?;
-                                self.llvm_builder.build_return(None)?;
-                                continue;
-                            }
-                            _ => {}
-                        }
-                    }
-                }
+            for statement in &function.body {
                 self.gen_statement(statement)?;
             }
+            // voidの関数は末尾のreturnを省略できる
+            let terminated = self
+                .llvm_builder
+                .get_insert_block()
+                .and_then(|block| block.get_terminator())
+                .is_some();
+            if !terminated && function.decl.return_type == ConcreteType::Void {
+                self.gen_va_end()?;
+                self.llvm_builder.build_return(None)?;
+            }
         }
+        self.va_list.set(None);
+        self.end_debug_function();
         self.pop_scope();
         Ok(())
     }
 
+    fn get_or_declare_va_intrinsic(&self, name: &str) -> FunctionValue<'a> {
+        if let Some(function) = self.llvm_module.get_function(name) {
+            return function;
+        }
+        let fn_type = self.llvm_context.void_type().fn_type(
+            &[self
+                .llvm_context
+                .i8_type()
+                .ptr_type(AddressSpace::default())
+                .into()],
+            false,
+        );
+        self.llvm_module.add_function(name, fn_type, None)
+    }
+
+    // va_listの大きさはターゲットごとに異なるので、主要なターゲットで最大のAArch64(32バイト)に合わせて確保する
+    fn gen_va_start(&self) -> Result<(), BuilderError> {
+        let va_list_ty = self.llvm_context.i8_type().array_type(32);
+        let va_list = self.llvm_builder.build_alloca(va_list_ty, "va_list")?;
+        va_list
+            .as_instruction_value()
+            .unwrap()
+            .set_alignment(16)
+            .unwrap();
+        self.llvm_builder.build_call(
+            self.get_or_declare_va_intrinsic("llvm.va_start"),
+            &[va_list.into()],
+            "",
+        )?;
+        self.va_list.set(Some(va_list));
+        Ok(())
+    }
+
+    // 可変長引数を取る関数では、returnの前に必ずva_endを呼ぶ
+    pub(super) fn gen_va_end(&self) -> Result<(), BuilderError> {
+        if let Some(va_list) = self.va_list.get() {
+            self.llvm_builder.build_call(
+                self.get_or_declare_va_intrinsic("llvm.va_end"),
+                &[va_list.into()],
+                "",
+            )?;
+        }
+        Ok(())
+    }
+
+    // 初期値はresolverで定数式であることが保証されているので、命令を生成せずに評価できる
+    fn gen_global_variable(&self, global_variable: &GlobalVariable) -> Result<(), BuilderError> {
+        let ty = self
+            .type_to_basic_type_enum(&global_variable.value.ty)
+            .unwrap();
+        let value = self.gen_expression(&global_variable.value)?.unwrap();
+        let global = self.llvm_module.add_global(ty, None, &global_variable.name);
+        global.set_initializer(&value);
+        self.add_variable(&global_variable.name, global.as_pointer_value());
+        Ok(())
+    }
+
     pub(super) fn gen_toplevel(&mut self, top: &'a TopLevel) {
         match top {
-            TopLevel::Function(func) => self.gen_or_get_function(func),
+            // memcpyの呼び出しはllvm.memcpyで生成するので、Cのmemcpyは宣言しない
+            TopLevel::Function(func) if func.decl.name == MEMCPY_FUNCTION_NAME => {}
+            TopLevel::Function(func) => {
+                self.gen_or_get_function(func);
+            }
+            TopLevel::GlobalVariable(global_variable) => {
+                self.gen_global_variable(global_variable).unwrap();
+            }
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::tests::{compile_to_ir, compile_to_verified_ir};
+
+    #[test]
+    fn test_struct_parameter() {
+        let ir = compile_to_ir(
+            "
+struct Point {
+    x: i32,
+    y: i32,
+}
+fn get_y(p: Point): i32 {
+    p.y
+}
+fn main(): void {
+    (:= p Point { x: 1, y: 2 })
+    (get_y p)
+}
+",
+        );
+        assert!(ir.contains("define i32 @get_y(%Point %p)"), "{}", ir);
+        assert!(ir.contains("store %Point %p, ptr %p1"), "{}", ir);
+        assert!(ir.contains("call i32 @get_y(%Point"), "{}", ir);
+    }
+
+    #[test]
+    fn test_empty_void_function() {
+        let ir = compile_to_verified_ir(
+            "
+fn nop(): void {}
+fn main(): void {
+    (nop)
+}
+",
+        );
+        // 本体が空でも暗黙のreturnだけを持つ関数になる
+        assert!(
+            ir.contains("define void @nop() {\nentry:\n  ret void\n}"),
+            "{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_void_function_with_effects() {
+        let ir = compile_to_verified_ir(
+            "
+fn greet(times: i32): void {
+    (printf \"hello\\n\")
+    (:= doubled (* times 2))
+    (printf \"%d\\n\" doubled)
+}
+fn main(): void {
+    (greet 2)
+}
+",
+        );
+        let greet = ir
+            .split("define void @greet(i32 %times)")
+            .nth(1)
+            .and_then(|body| body.split("\n}\n").next())
+            .unwrap();
+        // 最後の文の値は捨て、暗黙のret voidで終わる
+        assert_eq!(greet.matches("ret ").count(), 1, "{}", ir);
+        assert!(greet.ends_with("ret void"), "{}", ir);
+        assert!(ir.contains("call void @greet(i32 2)"), "{}", ir);
+    }
+
+    #[test]
+    fn test_heap_allocation() {
+        let ir = compile_to_verified_ir(
+            "
+fn main(): void {
+    (:= values: *i32 (malloc (* 4 (sizeof i32))))
+    (:=< values[3] 42)
+    (:= grown: *i32 (realloc values (* 8 (sizeof i32))))
+    (free grown)
+}
+",
+        );
+        assert!(ir.contains("declare ptr @malloc(i64)"), "{}", ir);
+        assert!(ir.contains("declare ptr @realloc(ptr, i64)"), "{}", ir);
+        assert!(ir.contains("declare void @free(ptr)"), "{}", ir);
+        assert!(ir.contains("call void @free(ptr"), "{}", ir);
+    }
+
+    #[test]
+    fn test_global_variable() {
+        let ir = compile_to_ir(
+            "
+(:= counter: i64 10)
+fn increment(): void {
+    (:=< counter (+ counter 1))
+}
+fn get(): i64 {
+    counter
+}
+fn main(): void {
+    (increment)
+    (get)
+}
+",
+        );
+        assert!(ir.contains("@counter = global i64 10"), "{}", ir);
+        // どちらの関数もグローバル変数を直接読み書きする
+        assert!(ir.contains("load i64, ptr @counter"), "{}", ir);
+        assert!(ir.contains("store i64 %1, ptr @counter"), "{}", ir);
+    }
+
+    #[test]
+    fn test_const_is_inlined() {
+        let ir = compile_to_ir(
+            "
+(const SIZE: usize (* 4 2))
+fn main(): void {
+    (:= buf (malloc (* SIZE 4)))
+}
+",
+        );
+        // constはグローバル変数にならず、値がそのまま埋め込まれる
+        assert!(!ir.contains("@SIZE"), "{}", ir);
+        assert!(ir.contains("@malloc(i64 32)"), "{}", ir);
+    }
+
+    #[test]
+    fn test_var_args_function() {
+        let ir = compile_to_ir(
+            "
+fn log(count: i32, ...): void {
+    (for (:= i 0) (< i count) (+= i 1)
+        (printf \"%d\\n\" (va_arg i32)))
+}
+fn main(): void {
+    (log 2 10 20)
+}
+",
+        );
+        assert!(ir.contains("define void @log(i32 %count, ...)"), "{}", ir);
+        assert!(
+            ir.contains("call void @llvm.va_start(ptr %va_list)"),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("va_arg ptr %va_list, i32"), "{}", ir);
+        assert!(
+            ir.contains("call void @llvm.va_end(ptr %va_list)"),
+            "{}",
+            ir
+        );
+        assert!(
+            ir.contains("call void (i32, ...) @log(i32 2, i32 10, i32 20)"),
+            "{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_return_struct_by_value() {
+        let ir = compile_to_verified_ir(
+            "
+struct Point {
+    x: i32,
+    y: i32,
+}
+fn make_point(x: i32, y: i32): Point {
+    (:= p Point { x: x, y: y })
+    p
+}
+fn main(): void {
+    (:= p (make_point 1 2))
+    (printf \"%d\\n\" p.y)
+}
+",
+        );
+        // 小さなstructはLLVMの集約型として値で返す
+        assert!(
+            ir.contains("define %Point @make_point(i32 %x, i32 %y)"),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("ret %Point"), "{}", ir);
+    }
+
+    #[test]
+    fn test_return_tuple() {
+        let ir = compile_to_verified_ir(
+            "
+fn divmod(a: i32, b: i32): (i32, i32) {
+    return ((/ a b), (% a b))
+}
+fn main(): void {
+    (:= result (divmod 7 2))
+    (:= pair (1, 2u8))
+    (printf \"%d %d %d\\n\" result.0 result.1 pair.1)
+}
+",
+        );
+        // タプルは要素をフィールドに持つ名前のないstructとして、structと同じく値で返す
+        assert!(ir.contains("%\"(i32, i32)\" = type { i32, i32 }"), "{}", ir);
+        assert!(ir.contains("%\"(i32, u8)\" = type { i32, i8 }"), "{}", ir);
+        assert!(
+            ir.contains("define %\"(i32, i32)\" @divmod(i32 %a, i32 %b)"),
+            "{}",
+            ir
+        );
+        assert!(ir.contains("ret %\"(i32, i32)\""), "{}", ir);
+        assert!(ir.contains("call %\"(i32, i32)\" @divmod("), "{}", ir);
+    }
+
+    #[test]
+    fn test_method_call() {
+        let ir = compile_to_verified_ir(
+            "
+type Point = struct { x: i32, y: i32 } impl {
+    fn sum(self): i32 {
+        (+ self.x self.y)
+    }
+}
+fn main(): void {
+    (:= p Point { x: 1, y: 2 })
+    (printf \"%d\\n\" p.sum())
+}
+",
+        );
+        // メソッドはレシーバーへのポインタを最初の引数に受け取る関数になる
+        assert!(ir.contains("define i32 @Point.sum(ptr %self)"), "{}", ir);
+        assert!(ir.contains("call i32 @Point.sum(ptr"), "{}", ir);
+    }
+
+    #[test]
+    fn test_return_large_struct_via_sret() {
+        let ir = compile_to_verified_ir(
+            "
+struct Rect {
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+}
+fn make_rect(width: i64, height: i64): Rect {
+    return Rect { x: 0, y: 0, width: width, height: height }
+}
+fn main(): void {
+    (:= r (make_rect 3 4))
+    (printf \"%ld\\n\" r.width)
+}
+",
+        );
+        assert!(ir.contains("define void @make_rect(ptr"), "{}", ir);
+        assert!(ir.contains("sret(%Rect)"), "{}", ir);
+        assert!(ir.contains("call void @llvm.memcpy"), "{}", ir);
+        assert!(!ir.contains("ret %Rect"), "{}", ir);
+    }
+
+    #[test]
+    fn test_memcpy_call() {
+        let ir = compile_to_verified_ir(
+            "
+fn main(): void {
+    (:= src \"hello\")
+    (:= dst: *u8 (malloc 6))
+    (memcpy dst src 6)
+}
+",
+        );
+        assert!(
+            ir.contains("call void @llvm.memcpy.p0.p0.i64(ptr align 1 %"),
+            "{}",
+            ir
+        );
+        assert!(!ir.contains("@memcpy"), "{}", ir);
+    }
+
+    #[test]
+    fn test_memcpy_is_declared_once() {
+        let ir = compile_to_verified_ir(
+            "
+struct Rect {
+    x: i64,
+    y: i64,
+    width: i64,
+    height: i64,
+}
+fn copy(dst: *Rect, src: *Rect): void {
+    (memcpy dst src (sizeof Rect))
+}
+fn main(): void {
+    (:= a Rect { x: 0, y: 0, width: 3, height: 4 })
+    (:= b a)
+    (copy &b &a)
+}
+",
+        );
+        // 構造体のコピーとmemcpyの呼び出しが同じ宣言を使う
+        assert_eq!(ir.matches("declare void @llvm.memcpy").count(), 1, "{}", ir);
+        let copy = &ir[ir.find("define void @copy").unwrap()..];
+        let copy = &copy[..copy.find('}').unwrap()];
+        assert!(copy.contains("call void @llvm.memcpy"), "{}", ir);
+        let main = &ir[ir.find("define void @main").unwrap()..];
+        assert!(main.contains("call void @llvm.memcpy"), "{}", ir);
+    }
+
+    #[test]
+    fn test_overloaded_function_symbols() {
+        let ir = compile_to_verified_ir(
+            "
+fn print(n: i32): void {
+    (printf \"%d\\n\" n)
+}
+fn print(s: *u8): void {
+    (printf s)
+}
+fn main(): void {
+    (print 1)
+    (print \"hello\")
+}
+",
+        );
+        assert!(
+            ir.contains("define void @\"print(i32)->void\"(i32 %n)"),
+            "{}",
+            ir
+        );
+        assert!(
+            ir.contains("define void @\"print([u8])->void\"(ptr %s)"),
+            "{}",
+            ir
+        );
+        assert!(
+            ir.contains("call void @\"print(i32)->void\"(i32 1)"),
+            "{}",
+            ir
+        );
+    }
+
+    #[test]
+    fn test_recursive_struct_through_pointer() {
+        let ir = compile_to_verified_ir(
+            "
+struct Node {
+    value: i32,
+    next: *Node,
+}
+fn second(head: *Node): i32 {
+    (:= next head[0].next)
+    next[0].value
+}
+fn main(): void {
+    (:= tail Node { value: 2, next: null })
+    (:= head Node { value: 1, next: &tail })
+    (printf \"%d\\n\" (second &head))
+}
+",
+        );
+        assert!(ir.contains("%Node = type { i32, ptr }"), "{}", ir);
+    }
+
+    #[test]
+    fn test_generic_function_specialization() {
+        let ir = compile_to_verified_ir(
+            "
+fn id<T>(x: T): T {
+    x
+}
+fn main(): void {
+    (printf \"%d\\n\" (id<i32> 5))
+    (printf \"%lu\\n\" (id<u64> 5))
+    (printf \"%d\\n\" (id<i32> 6))
+}
+",
+        );
+        assert!(
+            ir.contains("define i32 @\"id(i32)->i32\"(i32 %x)"),
+            "{}",
+            ir
+        );
+        assert!(
+            ir.contains("define i64 @\"id(u64)->u64\"(i64 %x)"),
+            "{}",
+            ir
+        );
+        assert_eq!(ir.matches("define").count(), 3, "{}", ir);
+        assert!(ir.contains("call i32 @\"id(i32)->i32\"(i32 6)"), "{}", ir);
+    }
+
+    #[test]
+    fn test_generic_struct_instance() {
+        let ir = compile_to_verified_ir(
+            "
+struct Pair<T, U> {
+    first: T,
+    second: U,
+}
+fn main(): void {
+    (:= p Pair<i32, u8> { first: 1, second: 2 })
+    (:= q Pair<u8, i32> { first: 3, second: 4 })
+    (:= r Pair<i32, u8> { first: 5, second: 6 })
+    (printf \"%d %d %d\\n\" p.second q.second r.first)
+}
+",
+        );
+        assert!(
+            ir.contains("%\"Pair<i32, u8>\" = type { i32, i8 }"),
+            "{}",
+            ir
+        );
+        assert!(
+            ir.contains("%\"Pair<u8, i32>\" = type { i8, i32 }"),
+            "{}",
+            ir
+        );
+        assert_eq!(ir.matches(" = type {").count(), 2, "{}", ir);
+    }
+}
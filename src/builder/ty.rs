@@ -7,7 +7,18 @@ use crate::concrete_ast::{ConcreteStructType, ConcreteType};
 
 use super::LLVMCodeGenerator;
 
+// これより大きいstructは値で返さず、呼び出し側が確保した領域に書き込んで返す
+const MAX_STRUCT_RETURN_SIZE: u64 = 16;
+
 impl<'a> LLVMCodeGenerator<'a> {
+    // 関数が第一引数のsretポインタ経由でstructを返すか
+    pub(super) fn returns_via_sret(&self, ty: &ConcreteType) -> bool {
+        ty.is_struct_type()
+            && self
+                .target_data
+                .get_abi_size(&self.type_to_basic_type_enum(ty).unwrap())
+                > MAX_STRUCT_RETURN_SIZE
+    }
     pub fn type_to_basic_type_enum(&self, ty: &ConcreteType) -> Option<BasicTypeEnum<'a>> {
         self.type_to_basic_metadata_type_enum(ty)
             .map(|x| x.try_into().unwrap())
@@ -17,11 +28,16 @@ impl<'a> LLVMCodeGenerator<'a> {
         ty: &ConcreteType,
     ) -> Option<BasicMetadataTypeEnum<'a>> {
         Some(match ty {
+            ConcreteType::I8 => BasicMetadataTypeEnum::IntType(self.llvm_context.i8_type()),
+            ConcreteType::I16 => BasicMetadataTypeEnum::IntType(self.llvm_context.i16_type()),
             ConcreteType::I32 => BasicMetadataTypeEnum::IntType(self.llvm_context.i32_type()),
             ConcreteType::U8 => BasicMetadataTypeEnum::IntType(self.llvm_context.i8_type()),
+            ConcreteType::U16 => BasicMetadataTypeEnum::IntType(self.llvm_context.i16_type()),
             ConcreteType::U32 => BasicMetadataTypeEnum::IntType(self.llvm_context.i32_type()),
             ConcreteType::U64 => BasicMetadataTypeEnum::IntType(self.llvm_context.i64_type()),
             ConcreteType::I64 => BasicMetadataTypeEnum::IntType(self.llvm_context.i64_type()),
+            ConcreteType::F32 => BasicMetadataTypeEnum::FloatType(self.llvm_context.f32_type()),
+            ConcreteType::F64 => BasicMetadataTypeEnum::FloatType(self.llvm_context.f64_type()),
             ConcreteType::Ptr(inner) => BasicMetadataTypeEnum::PointerType(
                 if let Some(t) = self.type_to_basic_type_enum(inner) {
                     t.ptr_type(AddressSpace::default())
@@ -32,6 +48,15 @@ impl<'a> LLVMCodeGenerator<'a> {
                         .ptr_type(AddressSpace::default())
                 },
             ),
+            ConcreteType::Array(inner, len) => BasicMetadataTypeEnum::ArrayType(
+                self.type_to_basic_type_enum(inner)?.array_type(*len as u32),
+            ),
+            // 関数ポインタも、LLVM上は他のポインタと同じ型になる
+            ConcreteType::FnPtr(_, _) => BasicMetadataTypeEnum::PointerType(
+                self.llvm_context
+                    .i8_type()
+                    .ptr_type(AddressSpace::default()),
+            ),
             ConcreteType::Bool => BasicMetadataTypeEnum::IntType(self.llvm_context.bool_type()),
             ConcreteType::Void => return None,
             ConcreteType::StructLike(ConcreteStructType {
@@ -2,56 +2,100 @@ use core::panic;
 
 use crate::concrete_ast::ConcreteType;
 
+fn integer_bit_width(ty: &ConcreteType) -> u32 {
+    match ty {
+        ConcreteType::I8 | ConcreteType::U8 => 8,
+        ConcreteType::I16 | ConcreteType::U16 => 16,
+        ConcreteType::I32 | ConcreteType::U32 => 32,
+        ConcreteType::I64 | ConcreteType::U64 => 64,
+        _ => panic!("{} is not an integer type", ty),
+    }
+}
+
+// 整数同士の演算では幅の大きい方の型に揃える。
+// 同じ幅で符号の有無だけが異なる場合は、Cと同じく符号なしの型に揃える
+fn common_integer_type(lhs: &ConcreteType, rhs: &ConcreteType) -> ConcreteType {
+    let rank = |ty: &ConcreteType| (integer_bit_width(ty), !ty.is_signed_integer_type());
+    if rank(lhs) >= rank(rhs) {
+        lhs.clone()
+    } else {
+        rhs.clone()
+    }
+}
+
+// 二項演算の左辺と右辺をそれぞれ何の型にキャストするかを返す。キャストが不要な側はNone
 pub(crate) fn get_cast_type(
     lhs: &ConcreteType,
     rhs: &ConcreteType,
 ) -> (Option<ConcreteType>, Option<ConcreteType>) {
-    match lhs {
-        ConcreteType::I32 => match rhs {
-            ConcreteType::I32 => (None, None),
-            ConcreteType::I64 => (Some(ConcreteType::I64), None),
-            ConcreteType::U32 => (None, Some(ConcreteType::I32)),
-            ConcreteType::U64 => (Some(ConcreteType::I64), Some(ConcreteType::I64)),
-            ConcreteType::U8 => (None, Some(ConcreteType::I32)),
-            _ => panic!("Invalid type for binary expression"),
-        },
-        ConcreteType::I64 => match rhs {
-            ConcreteType::I32 => (None, Some(ConcreteType::I64)),
-            ConcreteType::I64 => (None, None),
-            ConcreteType::U32 => (None, Some(ConcreteType::I64)),
-            ConcreteType::U64 => (None, Some(ConcreteType::I64)),
-            ConcreteType::U8 => (None, Some(ConcreteType::I64)),
-            _ => panic!("Invalid type for binary expression"),
-        },
-        ConcreteType::U32 => match rhs {
-            ConcreteType::I32 => (Some(ConcreteType::I32), None),
-            ConcreteType::I64 => (Some(ConcreteType::I64), None),
-            ConcreteType::U32 => (None, None),
-            ConcreteType::U64 => (Some(ConcreteType::U64), None),
-            ConcreteType::U8 => (Some(ConcreteType::U32), None),
-            _ => panic!("Invalid type for binary expression"),
-        },
-        ConcreteType::U64 => match rhs {
-            ConcreteType::I32 => (Some(ConcreteType::I64), None),
-            ConcreteType::I64 => (Some(ConcreteType::I64), None),
-            ConcreteType::U32 => (None, Some(ConcreteType::U64)),
-            ConcreteType::U64 => (None, None),
-            _ => panic!("Invalid type for binary expression"),
-        },
-        ConcreteType::U8 => match rhs {
-            ConcreteType::I32 => (Some(ConcreteType::I32), None),
-            ConcreteType::I64 => (Some(ConcreteType::I64), None),
-            ConcreteType::U32 => (Some(ConcreteType::U32), None),
-            ConcreteType::U64 => (Some(ConcreteType::U64), None),
-            ConcreteType::U8 => (None, None),
-            _ => panic!("Invalid type for binary expression"),
-        },
-        ConcreteType::Bool => match rhs {
-            ConcreteType::Bool => (None, None),
-            _ => panic!("Invalid type for binary expression"),
-        },
-        ConcreteType::Ptr(_) => panic!("Invalid type for binary expression"),
-        ConcreteType::Void => panic!("Invalid type for binary expression"),
-        ConcreteType::StructLike(_) => panic!("Invalid type for binary expression"),
+    let is_numeric = |ty: &ConcreteType| ty.is_integer_type() || ty.is_float_type();
+    let common_type = match (lhs, rhs) {
+        (ConcreteType::Bool, ConcreteType::Bool) => return (None, None),
+        (lhs, rhs) if lhs.is_integer_type() && rhs.is_integer_type() => {
+            common_integer_type(lhs, rhs)
+        }
+        // 整数と浮動小数点数の演算では、整数を浮動小数点数に変換する
+        (lhs, rhs) if is_numeric(lhs) && is_numeric(rhs) => {
+            if *lhs == ConcreteType::F64 || *rhs == ConcreteType::F64 {
+                ConcreteType::F64
+            } else {
+                ConcreteType::F32
+            }
+        }
+        _ => panic!("Invalid type for binary expression"),
+    };
+    let cast = |ty: &ConcreteType| (*ty != common_type).then(|| common_type.clone());
+    (cast(lhs), cast(rhs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolved_ast::ResolvedType;
+
+    #[test]
+    fn test_integer_cast_rules() {
+        use ConcreteType::*;
+        let types = [
+            ResolvedType::I32,
+            ResolvedType::I64,
+            ResolvedType::U8,
+            ResolvedType::U32,
+            ResolvedType::U64,
+            ResolvedType::USize,
+        ];
+        // 行が左辺、列が右辺の型で、演算に使われる型。usizeは64bitのターゲットでu64になる
+        let expected = [
+            [I32, I64, I32, U32, U64, U64],
+            [I64, I64, I64, I64, U64, U64],
+            [I32, I64, U8, U32, U64, U64],
+            [U32, I64, U32, U32, U64, U64],
+            [U64, U64, U64, U64, U64, U64],
+            [U64, U64, U64, U64, U64, U64],
+        ];
+        for (lhs, row) in types.iter().zip(&expected) {
+            for (rhs, expected) in types.iter().zip(row) {
+                let lhs = lhs.unwrap_primitive_into_concrete_type(true);
+                let rhs = rhs.unwrap_primitive_into_concrete_type(true);
+                let expected_cast = |ty: &ConcreteType| (ty != expected).then(|| expected.clone());
+                assert_eq!(
+                    get_cast_type(&lhs, &rhs),
+                    (expected_cast(&lhs), expected_cast(&rhs)),
+                    "{} and {}",
+                    lhs,
+                    rhs
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_16_bit_cast_rules() {
+        use ConcreteType::*;
+        assert_eq!(get_cast_type(&U16, &U32), (Some(U32), None));
+        assert_eq!(get_cast_type(&U8, &U16), (Some(U16), None));
+        // 同じ幅なら符号なしに揃える
+        assert_eq!(get_cast_type(&I16, &U16), (Some(U16), None));
+        assert_eq!(get_cast_type(&U16, &I32), (Some(I32), None));
     }
 }
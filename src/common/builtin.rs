@@ -0,0 +1,26 @@
+use crate::ast::BinaryOp;
+
+// (wrapping_add a b) のような整数演算の組み込み関数。両辺は同じ整数型で、結果もその型になる。
+// wrappingは型のビット幅でラップアラウンドし、saturatingは型の最大値か最小値に張り付く
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArithmeticBuiltin {
+    pub op: BinaryOp,
+    pub saturating: bool,
+}
+
+impl ArithmeticBuiltin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let (saturating, op) = match name.split_once('_')? {
+            ("wrapping", op) => (false, op),
+            ("saturating", op) => (true, op),
+            _ => return None,
+        };
+        let op = match op {
+            "add" => BinaryOp::Add,
+            "sub" => BinaryOp::Sub,
+            "mul" => BinaryOp::Mul,
+            _ => return None,
+        };
+        Some(Self { op, saturating })
+    }
+}
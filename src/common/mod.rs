@@ -1,14 +1,17 @@
 pub mod binary;
+pub mod builtin;
 pub mod target;
 pub mod typename;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StructKind {
     Struct,
     Record,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AllocMode {
     Heap,
     Stack,
@@ -1,9 +1,14 @@
 pub const VOID_TYPE_NAME: &str = "void";
 pub const U8_TYPE_NAME: &str = "u8";
+pub const U16_TYPE_NAME: &str = "u16";
 pub const U32_TYPE_NAME: &str = "u32";
 pub const U64_TYPE_NAME: &str = "u64";
+pub const I8_TYPE_NAME: &str = "i8";
+pub const I16_TYPE_NAME: &str = "i16";
 pub const I32_TYPE_NAME: &str = "i32";
 pub const I64_TYPE_NAME: &str = "i64";
+pub const F32_TYPE_NAME: &str = "f32";
+pub const F64_TYPE_NAME: &str = "f64";
 pub const USIZE_TYPE_NAME: &str = "usize";
 pub const BOOL_TYPE_NAME: &str = "bool";
 pub const UNKNOWN_TYPE_NAME: &str = "unknown";
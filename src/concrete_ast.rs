@@ -1,7 +1,7 @@
 use std::fmt::{Display, Write};
 
 use crate::{
-    ast::{BinaryOp, MultiOp, UnaryOp},
+    ast::{BinaryOp, MultiOp, Range, UnaryOp},
     common::typename::*,
     resolved_ast::ResolvedType,
 };
@@ -31,13 +31,21 @@ pub struct ConcreteGenericType {
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum ConcreteType {
+    I8,
+    I16,
     I32,
     I64,
+    U16,
     U32,
     U64,
     U8,
+    F32,
+    F64,
     Bool,
     Ptr(Box<ConcreteType>),
+    Array(Box<ConcreteType>, usize),
+    // 引数の型と戻り値の型
+    FnPtr(Vec<ConcreteType>, Box<ConcreteType>),
     Void,
     StructLike(ConcreteStructType),
 }
@@ -45,19 +53,31 @@ pub enum ConcreteType {
 impl ConcreteType {
     pub fn is_integer_type(&self) -> bool {
         match self {
+            ConcreteType::I8 => true,
+            ConcreteType::I16 => true,
             ConcreteType::I32 => true,
             ConcreteType::U8 => true,
+            ConcreteType::U16 => true,
             ConcreteType::U32 => true,
             ConcreteType::I64 => true,
             ConcreteType::U64 => true,
+            ConcreteType::F32 => false,
+            ConcreteType::F64 => false,
             ConcreteType::Ptr(_) => false,
+            ConcreteType::Array(_, _) => false,
+            ConcreteType::FnPtr(_, _) => false,
             ConcreteType::Void => false,
             ConcreteType::StructLike(_) => false,
             ConcreteType::Bool => false,
         }
     }
+    pub fn is_float_type(&self) -> bool {
+        matches!(self, ConcreteType::F32 | ConcreteType::F64)
+    }
     pub fn is_signed_integer_type(&self) -> bool {
         match self {
+            ConcreteType::I8 => true,
+            ConcreteType::I16 => true,
             ConcreteType::I32 => true,
             ConcreteType::I64 => true,
             _ => false,
@@ -77,13 +97,21 @@ impl ConcreteType {
             false
         }
     }
+    pub fn is_array_type(&self) -> bool {
+        matches!(self, ConcreteType::Array(_, _))
+    }
     pub fn unwrap_primitive_into_resolved_type(&self) -> ResolvedType {
         match self {
+            ConcreteType::I8 => ResolvedType::I8,
+            ConcreteType::I16 => ResolvedType::I16,
             ConcreteType::I32 => ResolvedType::I32,
             ConcreteType::I64 => ResolvedType::I64,
+            ConcreteType::U16 => ResolvedType::U16,
             ConcreteType::U32 => ResolvedType::U32,
             ConcreteType::U64 => ResolvedType::U64,
             ConcreteType::U8 => ResolvedType::U8,
+            ConcreteType::F32 => ResolvedType::F32,
+            ConcreteType::F64 => ResolvedType::F64,
             ConcreteType::Bool => ResolvedType::Bool,
             ConcreteType::Void => ResolvedType::Void,
             ConcreteType::Ptr(inner) => {
@@ -111,16 +139,36 @@ impl Display for ConcreteType {
                 f,
                 "{}",
                 match self {
+                    ConcreteType::I8 => I8_TYPE_NAME,
+                    ConcreteType::I16 => I16_TYPE_NAME,
                     ConcreteType::I32 => I32_TYPE_NAME,
                     ConcreteType::I64 => I64_TYPE_NAME,
+                    ConcreteType::U16 => U16_TYPE_NAME,
                     ConcreteType::U32 => U32_TYPE_NAME,
                     ConcreteType::U64 => U64_TYPE_NAME,
                     ConcreteType::U8 => U8_TYPE_NAME,
+                    ConcreteType::F32 => F32_TYPE_NAME,
+                    ConcreteType::F64 => F64_TYPE_NAME,
                     ConcreteType::Bool => BOOL_TYPE_NAME,
                     ConcreteType::Void => VOID_TYPE_NAME,
                     ConcreteType::Ptr(inner) => {
                         return write!(f, "*{}", inner);
                     }
+                    ConcreteType::Array(inner, len) => {
+                        return write!(f, "[{}; {}]", inner, len);
+                    }
+                    ConcreteType::FnPtr(params, return_type) => {
+                        return write!(
+                            f,
+                            "fn({}): {}",
+                            params
+                                .iter()
+                                .map(|x| x.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            return_type
+                        );
+                    }
                     ConcreteType::StructLike(ConcreteStructType {
                         name,
                         fields: _,
@@ -141,6 +189,19 @@ pub struct CallExpr {
     pub generic_args: Option<Vec<ConcreteType>>,
 }
 
+// 関数ポインタを値に持つ式を呼び出す
+#[derive(Debug, Clone)]
+pub struct IndirectCallExpr {
+    pub callee: Box<ConcreteExpression>,
+    pub args: Vec<ConcreteExpression>,
+}
+
+// &fで得られる関数のアドレス
+#[derive(Debug, Clone)]
+pub struct FunctionRefExpr {
+    pub name: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct VariableRefExpr {
     pub name: String,
@@ -152,6 +213,11 @@ pub struct NumberLiteral {
     pub value: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct FloatLiteral {
+    pub value: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct StringLiteral {
     pub value: String,
@@ -167,6 +233,11 @@ pub struct StructLiteral {
     pub fields: Vec<(String, ConcreteExpression)>,
 }
 
+#[derive(Debug, Clone)]
+pub struct ArrayFill {
+    pub value: Box<ConcreteExpression>,
+}
+
 #[derive(Debug, Clone)]
 pub struct BinaryExpr {
     pub op: BinaryOp,
@@ -191,6 +262,17 @@ pub struct DerefExpr {
     pub target: Box<ConcreteExpression>,
 }
 
+// 変換先の型は式の型として持つ
+#[derive(Debug, Clone)]
+pub struct CastExpr {
+    pub target: Box<ConcreteExpression>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AddressOfExpr {
+    pub target: Box<ConcreteExpression>,
+}
+
 #[derive(Debug, Clone)]
 pub struct IndexAccessExpr {
     pub target: Box<ConcreteExpression>,
@@ -216,23 +298,49 @@ pub struct WhenExpr {
     pub then: Box<ConcreteExpression>,
 }
 
+#[derive(Debug, Clone)]
+pub struct WhileExpr {
+    pub cond: Box<ConcreteExpression>,
+    pub body: Vec<ConcreteExpression>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForExpr {
+    pub init: Option<Box<ConcreteExpression>>,
+    pub cond: Option<Box<ConcreteExpression>>,
+    pub step: Option<Box<ConcreteExpression>>,
+    pub body: Vec<ConcreteExpression>,
+}
+
 #[derive(Debug, Clone)]
 pub enum ExpressionKind {
     SizeOf(ConcreteType),
+    VaArg,
     VariableRef(VariableRefExpr),
     NumberLiteral(NumberLiteral),
+    FloatLiteral(FloatLiteral),
     StringLiteral(StringLiteral),
     StructLiteral(StructLiteral),
+    ArrayFill(ArrayFill),
     BoolLiteral(BoolLiteral),
+    NullLiteral,
     Binary(BinaryExpr),
     Unary(UnaryExpr),
     Multi(MultiExpr),
     CallExpr(CallExpr),
+    IndirectCall(IndirectCallExpr),
+    FunctionRef(FunctionRefExpr),
     Deref(DerefExpr),
+    AddressOf(AddressOfExpr),
+    Cast(CastExpr),
     IndexAccess(IndexAccessExpr),
     FieldAccess(FieldAccessExpr),
     If(IfExpr),
     When(WhenExpr),
+    While(WhileExpr),
+    For(ForExpr),
+    Break,
+    Continue,
     VariableDecls(VariableDecls),
     Assignment(Assignment),
     Unknown,
@@ -250,6 +358,7 @@ pub struct Assignment {
     pub value: Box<ConcreteExpression>,
     pub deref_count: usize,
     pub index_access: Option<Box<ConcreteExpression>>,
+    pub indexes_array: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -266,17 +375,37 @@ pub struct VariableDecls {
 #[derive(Debug, Clone)]
 pub struct Return {
     pub expression: Option<ConcreteExpression>,
+    pub range: Range,
 }
 
 #[derive(Debug, Clone)]
 pub struct Effect {
     pub expression: ConcreteExpression,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone)]
+pub struct Switch {
+    pub scrutinee: ConcreteExpression,
+    pub cases: Vec<(ConcreteExpression, Vec<Statement>)>,
+    pub default: Option<Vec<Statement>>,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone)]
+pub struct DoWhile {
+    pub body: Vec<Statement>,
+    pub cond: ConcreteExpression,
+    pub range: Range,
 }
 
 #[derive(Debug, Clone)]
 pub enum Statement {
     Return(Return),
     Effect(Effect),
+    Block(Vec<Statement>),
+    Switch(Switch),
+    DoWhile(DoWhile),
 }
 
 #[derive(Debug, Clone)]
@@ -296,11 +425,20 @@ pub struct FunctionDecl {
 pub struct Function {
     pub decl: FunctionDecl,
     pub body: Vec<Statement>,
+    // デバッグ情報に使う宣言部分の位置
+    pub range: Range,
+}
+
+#[derive(Debug, Clone)]
+pub struct GlobalVariable {
+    pub name: String,
+    pub value: ConcreteExpression,
 }
 
 #[derive(Debug, Clone)]
 pub enum TopLevel {
     Function(Function),
+    GlobalVariable(GlobalVariable),
 }
 
 #[derive(Debug)]
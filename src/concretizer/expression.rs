@@ -0,0 +1,217 @@
+use crate::{
+    concrete_ast::{self, ConcreteExpression, ExpressionKind},
+    resolved_ast::{self, ResolvedExpression},
+};
+
+use super::{ty::concretize_type, ConcretizerContext};
+
+fn concretize_boxed_expression(
+    context: &ConcretizerContext,
+    expr: &ResolvedExpression,
+) -> Box<ConcreteExpression> {
+    Box::new(concretize_expression(context, expr))
+}
+
+pub(super) fn concretize_expression(
+    context: &ConcretizerContext,
+    expr: &ResolvedExpression,
+) -> ConcreteExpression {
+    let kind = match &expr.kind {
+        resolved_ast::ExpressionKind::SizeOf(ty) => {
+            ExpressionKind::SizeOf(concretize_type(context, ty))
+        }
+        resolved_ast::ExpressionKind::VariableRef(variable_ref) => {
+            ExpressionKind::VariableRef(concrete_ast::VariableRefExpr {
+                name: variable_ref.name.clone(),
+            })
+        }
+        resolved_ast::ExpressionKind::NumberLiteral(number_literal) => {
+            ExpressionKind::NumberLiteral(concrete_ast::NumberLiteral {
+                value: number_literal.value.clone(),
+            })
+        }
+        resolved_ast::ExpressionKind::FloatLiteral(float_literal) => {
+            ExpressionKind::FloatLiteral(concrete_ast::FloatLiteral {
+                value: float_literal.value.clone(),
+            })
+        }
+        resolved_ast::ExpressionKind::StringLiteral(string_literal) => {
+            ExpressionKind::StringLiteral(concrete_ast::StringLiteral {
+                value: string_literal.value.clone(),
+            })
+        }
+        resolved_ast::ExpressionKind::StructLiteral(struct_literal) => {
+            ExpressionKind::StructLiteral(concrete_ast::StructLiteral {
+                fields: struct_literal
+                    .fields
+                    .iter()
+                    .map(|(name, field)| (name.clone(), concretize_expression(context, field)))
+                    .collect(),
+            })
+        }
+        resolved_ast::ExpressionKind::ArrayFill(array_fill) => {
+            ExpressionKind::ArrayFill(concrete_ast::ArrayFill {
+                value: concretize_boxed_expression(context, &array_fill.value),
+            })
+        }
+        resolved_ast::ExpressionKind::BoolLiteral(bool_literal) => {
+            ExpressionKind::BoolLiteral(concrete_ast::BoolLiteral {
+                value: bool_literal.value,
+            })
+        }
+        resolved_ast::ExpressionKind::NullLiteral => ExpressionKind::NullLiteral,
+        resolved_ast::ExpressionKind::Binary(binary_expr) => {
+            ExpressionKind::Binary(concrete_ast::BinaryExpr {
+                op: binary_expr.op,
+                lhs: concretize_boxed_expression(context, &binary_expr.lhs),
+                rhs: concretize_boxed_expression(context, &binary_expr.rhs),
+            })
+        }
+        resolved_ast::ExpressionKind::Unary(unary_expr) => {
+            ExpressionKind::Unary(concrete_ast::UnaryExpr {
+                op: unary_expr.op,
+                operand: concretize_boxed_expression(context, &unary_expr.operand),
+            })
+        }
+        resolved_ast::ExpressionKind::Multi(multi_expr) => {
+            ExpressionKind::Multi(concrete_ast::MultiExpr {
+                op: multi_expr.op,
+                operands: multi_expr
+                    .operands
+                    .iter()
+                    .map(|operand| concretize_expression(context, operand))
+                    .collect(),
+            })
+        }
+        resolved_ast::ExpressionKind::CallExpr(call_expr) => {
+            ExpressionKind::CallExpr(concrete_ast::CallExpr {
+                callee: call_expr.callee.clone(),
+                args: call_expr
+                    .args
+                    .iter()
+                    .map(|arg| concretize_expression(context, arg))
+                    .collect(),
+                generic_args: call_expr.generic_args.as_ref().map(|generic_args| {
+                    generic_args
+                        .iter()
+                        .map(|ty| concretize_type(context, ty))
+                        .collect()
+                }),
+            })
+        }
+        resolved_ast::ExpressionKind::IndirectCall(indirect_call) => {
+            ExpressionKind::IndirectCall(concrete_ast::IndirectCallExpr {
+                callee: concretize_boxed_expression(context, &indirect_call.callee),
+                args: indirect_call
+                    .args
+                    .iter()
+                    .map(|arg| concretize_expression(context, arg))
+                    .collect(),
+            })
+        }
+        resolved_ast::ExpressionKind::FunctionRef(function_ref) => {
+            ExpressionKind::FunctionRef(concrete_ast::FunctionRefExpr {
+                name: function_ref.name.clone(),
+            })
+        }
+        resolved_ast::ExpressionKind::Deref(deref_expr) => {
+            ExpressionKind::Deref(concrete_ast::DerefExpr {
+                target: concretize_boxed_expression(context, &deref_expr.target),
+            })
+        }
+        resolved_ast::ExpressionKind::AddressOf(address_of) => {
+            ExpressionKind::AddressOf(concrete_ast::AddressOfExpr {
+                target: concretize_boxed_expression(context, &address_of.target),
+            })
+        }
+        resolved_ast::ExpressionKind::Cast(cast_expr) => {
+            ExpressionKind::Cast(concrete_ast::CastExpr {
+                target: concretize_boxed_expression(context, &cast_expr.target),
+            })
+        }
+        resolved_ast::ExpressionKind::IndexAccess(index_access) => {
+            ExpressionKind::IndexAccess(concrete_ast::IndexAccessExpr {
+                target: concretize_boxed_expression(context, &index_access.target),
+                index: concretize_boxed_expression(context, &index_access.index),
+            })
+        }
+        resolved_ast::ExpressionKind::FieldAccess(field_access) => {
+            ExpressionKind::FieldAccess(concrete_ast::FieldAccessExpr {
+                target: concretize_boxed_expression(context, &field_access.target),
+                field_name: field_access.field_name.clone(),
+            })
+        }
+        resolved_ast::ExpressionKind::If(if_expr) => ExpressionKind::If(concrete_ast::IfExpr {
+            cond: concretize_boxed_expression(context, &if_expr.cond),
+            then: concretize_boxed_expression(context, &if_expr.then),
+            els: concretize_boxed_expression(context, &if_expr.els),
+        }),
+        resolved_ast::ExpressionKind::When(when_expr) => {
+            ExpressionKind::When(concrete_ast::WhenExpr {
+                cond: concretize_boxed_expression(context, &when_expr.cond),
+                then: concretize_boxed_expression(context, &when_expr.then),
+            })
+        }
+        resolved_ast::ExpressionKind::While(while_expr) => {
+            ExpressionKind::While(concrete_ast::WhileExpr {
+                cond: concretize_boxed_expression(context, &while_expr.cond),
+                body: while_expr
+                    .body
+                    .iter()
+                    .map(|expr| concretize_expression(context, expr))
+                    .collect(),
+            })
+        }
+        resolved_ast::ExpressionKind::For(for_expr) => ExpressionKind::For(concrete_ast::ForExpr {
+            init: for_expr
+                .init
+                .as_ref()
+                .map(|init| concretize_boxed_expression(context, init)),
+            cond: for_expr
+                .cond
+                .as_ref()
+                .map(|cond| concretize_boxed_expression(context, cond)),
+            step: for_expr
+                .step
+                .as_ref()
+                .map(|step| concretize_boxed_expression(context, step)),
+            body: for_expr
+                .body
+                .iter()
+                .map(|expr| concretize_expression(context, expr))
+                .collect(),
+        }),
+        resolved_ast::ExpressionKind::VariableDecls(variable_decls) => {
+            ExpressionKind::VariableDecls(concrete_ast::VariableDecls {
+                decls: variable_decls
+                    .decls
+                    .iter()
+                    .map(|decl| concrete_ast::VariableDecl {
+                        name: decl.name.clone(),
+                        value: concretize_boxed_expression(context, &decl.value),
+                    })
+                    .collect(),
+            })
+        }
+        resolved_ast::ExpressionKind::Assignment(assignment) => {
+            ExpressionKind::Assignment(concrete_ast::Assignment {
+                name: assignment.name.clone(),
+                value: concretize_boxed_expression(context, &assignment.value),
+                deref_count: assignment.deref_count,
+                index_access: assignment
+                    .index_access
+                    .as_ref()
+                    .map(|index| concretize_boxed_expression(context, index)),
+                indexes_array: assignment.indexes_array,
+            })
+        }
+        resolved_ast::ExpressionKind::VaArg => ExpressionKind::VaArg,
+        resolved_ast::ExpressionKind::Break => ExpressionKind::Break,
+        resolved_ast::ExpressionKind::Continue => ExpressionKind::Continue,
+        resolved_ast::ExpressionKind::Unknown => ExpressionKind::Unknown,
+    };
+    ConcreteExpression {
+        ty: concretize_type(context, &expr.ty),
+        kind,
+    }
+}
@@ -1,18 +1,29 @@
+mod expression;
+mod ty;
+
 use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use crate::{
     common::target::PointerSizedIntWidth,
-    concrete_ast::ConcreteModule,
+    concrete_ast::{self, ConcreteModule},
     resolved_ast::{self, ResolvedModule},
     resolver::ResolverContext,
 };
 
+use self::{expression::concretize_expression, ty::concretize_type};
+
 pub struct ConcretizerContext {
     pub resolved_types: Rc<RefCell<HashMap<String, resolved_ast::ResolvedType>>>,
     pub function_by_name: Rc<RefCell<HashMap<String, resolved_ast::Function>>>,
     pub interface_by_name: Rc<RefCell<HashMap<String, resolved_ast::Interface>>>,
     pub impls_by_name: Rc<RefCell<HashMap<String, Vec<resolved_ast::Implementation>>>>,
+    // 宣言された順に並べる
+    pub global_variables: Rc<RefCell<Vec<resolved_ast::GlobalVariable>>>,
     pub ptr_sized_int_type: PointerSizedIntWidth,
+    // resolverで解決したstruct。フィールドを持たない型をここから補う
+    pub resolved_structs: HashMap<String, resolved_ast::ResolvedStructType>,
+    // フィールドを変換中のstruct。ポインタの先の自身は補わずに、フィールドを持たないまま残す
+    pub concretizing_structs: RefCell<Vec<String>>,
 }
 
 impl ConcretizerContext {
@@ -25,7 +36,10 @@ impl ConcretizerContext {
             function_by_name: Default::default(),
             interface_by_name: Default::default(),
             impls_by_name: Default::default(),
+            global_variables: Default::default(),
             ptr_sized_int_type: context.ptr_sized_int_type,
+            resolved_structs: context.resolved_structs.borrow().clone(),
+            concretizing_structs: Default::default(),
         };
         for toplevel in resolved_module.toplevels {
             match toplevel {
@@ -45,12 +59,124 @@ impl ConcretizerContext {
                         .borrow_mut()
                         .insert(name, interface.clone());
                 }
+                resolved_ast::TopLevel::GlobalVariable(global_variable) => {
+                    ret.global_variables.borrow_mut().push(global_variable);
+                }
             }
         }
         ret
     }
+    pub fn is_64_bit(&self) -> bool {
+        self.ptr_sized_int_type == PointerSizedIntWidth::SixtyFour
+    }
+}
+
+fn concretize_statement(
+    context: &ConcretizerContext,
+    statement: &resolved_ast::Statement,
+) -> concrete_ast::Statement {
+    match statement {
+        resolved_ast::Statement::Return(ret) => {
+            concrete_ast::Statement::Return(concrete_ast::Return {
+                expression: ret
+                    .expression
+                    .as_ref()
+                    .map(|expr| concretize_expression(context, expr)),
+                range: ret.range,
+            })
+        }
+        resolved_ast::Statement::Effect(effect) => {
+            concrete_ast::Statement::Effect(concrete_ast::Effect {
+                expression: concretize_expression(context, &effect.expression),
+                range: effect.range,
+            })
+        }
+        resolved_ast::Statement::Block(statements) => {
+            concrete_ast::Statement::Block(concretize_statements(context, statements))
+        }
+        resolved_ast::Statement::Switch(switch) => {
+            concrete_ast::Statement::Switch(concrete_ast::Switch {
+                scrutinee: concretize_expression(context, &switch.scrutinee),
+                cases: switch
+                    .cases
+                    .iter()
+                    .map(|(label, statements)| {
+                        (
+                            concretize_expression(context, label),
+                            concretize_statements(context, statements),
+                        )
+                    })
+                    .collect(),
+                default: switch
+                    .default
+                    .as_ref()
+                    .map(|statements| concretize_statements(context, statements)),
+                range: switch.range,
+            })
+        }
+        resolved_ast::Statement::DoWhile(do_while) => {
+            concrete_ast::Statement::DoWhile(concrete_ast::DoWhile {
+                body: concretize_statements(context, &do_while.body),
+                cond: concretize_expression(context, &do_while.cond),
+                range: do_while.range,
+            })
+        }
+    }
+}
+
+fn concretize_statements(
+    context: &ConcretizerContext,
+    statements: &[resolved_ast::Statement],
+) -> Vec<concrete_ast::Statement> {
+    statements
+        .iter()
+        .map(|statement| concretize_statement(context, statement))
+        .collect()
+}
+
+fn concretize_function(
+    context: &ConcretizerContext,
+    function: &resolved_ast::Function,
+) -> concrete_ast::Function {
+    concrete_ast::Function {
+        decl: concrete_ast::FunctionDecl {
+            name: function.decl.name.clone(),
+            args: function
+                .decl
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    resolved_ast::Argument::VarArgs => concrete_ast::Argument::VarArgs,
+                    resolved_ast::Argument::Normal(ty, name) => {
+                        concrete_ast::Argument::Normal(concretize_type(context, ty), name.clone())
+                    }
+                })
+                .collect(),
+            return_type: concretize_type(context, &function.decl.return_type),
+        },
+        body: concretize_statements(context, &function.body),
+        range: function.range,
+    }
 }
 
 pub fn concretize_module(context: &ConcretizerContext) -> ConcreteModule {
-    todo!()
+    let function_by_name = context.function_by_name.borrow();
+    // 出力されるIRが実行ごとに変わらないように名前順に並べる
+    let mut names = function_by_name.keys().collect::<Vec<_>>();
+    names.sort();
+    let mut toplevels: Vec<_> = context
+        .global_variables
+        .borrow()
+        .iter()
+        .map(|global_variable| {
+            concrete_ast::TopLevel::GlobalVariable(concrete_ast::GlobalVariable {
+                name: global_variable.name.clone(),
+                value: concretize_expression(context, &global_variable.value),
+            })
+        })
+        .collect();
+    toplevels.extend(names.into_iter().map(|name| {
+        concrete_ast::TopLevel::Function(concretize_function(context, &function_by_name[name]))
+    }));
+    ConcreteModule { toplevels }
 }
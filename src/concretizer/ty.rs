@@ -0,0 +1,58 @@
+use crate::{
+    concrete_ast::{ConcreteStructType, ConcreteType},
+    resolved_ast::{ResolvedStructType, ResolvedType},
+};
+
+use super::ConcretizerContext;
+
+pub(super) fn concretize_type(context: &ConcretizerContext, ty: &ResolvedType) -> ConcreteType {
+    match ty {
+        // 比較の相手などで型の決まらないnullは*voidとして扱う
+        ResolvedType::Ptr(inner) if **inner == ResolvedType::Unknown => {
+            ConcreteType::Ptr(Box::new(ConcreteType::Void))
+        }
+        ResolvedType::Ptr(inner) => ConcreteType::Ptr(Box::new(concretize_type(context, inner))),
+        ResolvedType::Array(inner, len) => {
+            ConcreteType::Array(Box::new(concretize_type(context, inner)), *len)
+        }
+        ResolvedType::FnPtr(params, return_type) => ConcreteType::FnPtr(
+            params
+                .iter()
+                .map(|param| concretize_type(context, param))
+                .collect(),
+            Box::new(concretize_type(context, return_type)),
+        ),
+        ResolvedType::StructLike(ResolvedStructType {
+            name,
+            non_generic_name,
+            fields,
+            generic_args: _,
+        }) => {
+            // ポインタの先で自身を参照するフィールドの型はフィールドを持たないので、解決済みのstructから補う。
+            // 補ったフィールドの中の自身は、builderが名前で引けるのでフィールドを持たないままにする
+            let is_concretizing = context.concretizing_structs.borrow().contains(name);
+            let fields = match context.resolved_structs.get(name) {
+                Some(resolved) if fields.is_empty() && !is_concretizing => &resolved.fields,
+                _ => fields,
+            };
+            context.concretizing_structs.borrow_mut().push(name.clone());
+            let fields = fields
+                .iter()
+                .map(|(field_name, field_ty)| {
+                    (field_name.clone(), concretize_type(context, field_ty))
+                })
+                .collect();
+            context.concretizing_structs.borrow_mut().pop();
+            ConcreteType::StructLike(ConcreteStructType {
+                name: name.clone(),
+                non_generic_name: non_generic_name.clone(),
+                fields,
+            })
+        }
+        // resolverでエラーになっているはずなので、ここには来ない
+        ResolvedType::Unknown | ResolvedType::Generics(_) => {
+            unreachable!("{} cannot be concretized", ty)
+        }
+        _ => ty.unwrap_primitive_into_concrete_type(context.is_64_bit()),
+    }
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::HashSet,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use nom::Finish;
+use thiserror::Error;
+
+use crate::{
+    ast::{Module, TopLevel},
+    parser::{format_parse_error, parse_module},
+};
+
+// 読み込んだファイル。診断の表示に元のソースを使う
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub source: String,
+    pub module: Module,
+}
+
+#[derive(Debug, Error)]
+pub enum LoadError {
+    #[error("Cannot read `{}`: {message}", .path.display())]
+    CannotRead { path: PathBuf, message: String },
+    #[error("Failed to parse `{}`:\n{message}", .path.display())]
+    ParseFailed { path: PathBuf, message: String },
+}
+
+fn parse_file(path: PathBuf) -> Result<SourceFile, LoadError> {
+    let source = read_to_string(&path).map_err(|error| LoadError::CannotRead {
+        path: path.clone(),
+        message: error.to_string(),
+    })?;
+    let input = source.as_str().into();
+    let module = match parse_module(input).finish() {
+        Ok((_, module)) => module,
+        Err(err) => {
+            let message = format_parse_error(&source, err);
+            return Err(LoadError::ParseFailed { path, message });
+        }
+    };
+    // 読み飛ばした文があれば、まとめて報告する
+    if !module.errors.is_empty() {
+        let message = module
+            .errors
+            .iter()
+            .map(|error| {
+                format!(
+                    "{}:{}: {}",
+                    error.range.from.line, error.range.from.col, error.value
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        return Err(LoadError::ParseFailed { path, message });
+    }
+    Ok(SourceFile {
+        path,
+        source,
+        module,
+    })
+}
+
+fn load(
+    path: &Path,
+    loaded: &mut HashSet<PathBuf>,
+    files: &mut Vec<SourceFile>,
+) -> Result<(), LoadError> {
+    let path = path.canonicalize().map_err(|error| LoadError::CannotRead {
+        path: path.to_path_buf(),
+        message: error.to_string(),
+    })?;
+    if !loaded.insert(path.clone()) {
+        return Ok(());
+    }
+    let file = parse_file(path)?;
+    let dir = file.path.parent().unwrap().to_path_buf();
+    let imports = file
+        .module
+        .toplevels
+        .iter()
+        .filter_map(|toplevel| match &toplevel.value {
+            TopLevel::Import(import) => Some(dir.join(&import.path)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    files.push(file);
+    for import in imports {
+        load(&import, loaded, files)?;
+    }
+    Ok(())
+}
+
+// entryとそこからimportで辿れるファイルを読み込み、entryを先頭にして返す。
+// 同じファイルは一度だけ読み込むので、互いにimportし合うファイルも扱える
+pub fn load_files(entry: &Path) -> Result<Vec<SourceFile>, LoadError> {
+    let mut files = Vec::new();
+    load(entry, &mut HashSet::new(), &mut files)?;
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        common::target::PointerSizedIntWidth,
+        resolver::{resolve_modules, ResolverContext},
+    };
+
+    #[test]
+    fn test_import() {
+        let dir = std::env::temp_dir().join("hirou_compiler_test_import");
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("main.hr"),
+            "
+(import \"lib/math.hr\")
+(import \"lib/../lib/math.hr\")
+fn main(): i32 {
+    (square 6)
+}
+",
+        )
+        .unwrap();
+        // mainを逆にimportしても循環しない
+        std::fs::write(
+            dir.join("lib/math.hr"),
+            "
+(import \"../main.hr\")
+pub fn square(x: i32): i32 {
+    (* x x)
+}
+",
+        )
+        .unwrap();
+        let files = load_files(&dir.join("main.hr"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        let files = files.unwrap();
+        assert_eq!(files.len(), 2);
+        assert!(files[0].path.ends_with("main.hr"));
+        assert!(files[1].path.ends_with("lib/math.hr"));
+
+        let context = ResolverContext::new(PointerSizedIntWidth::SixtyFour);
+        let modules = files.into_iter().map(|file| file.module).collect();
+        resolve_modules(&context, modules, true).unwrap();
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+        assert!(context.resolved_functions.borrow().contains_key("square"));
+    }
+
+    #[test]
+    fn test_import_missing_file() {
+        let dir = std::env::temp_dir().join("hirou_compiler_test_import_missing_file");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.hr"), "(import \"missing.hr\")\n").unwrap();
+        let result = load_files(&dir.join("main.hr"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(
+            result,
+            Err(LoadError::CannotRead { path, .. }) if path.ends_with("missing.hr")
+        ));
+    }
+}
@@ -1,9 +1,10 @@
-use std::{fs::read_to_string, path::Path};
+use std::{path::Path, process::ExitCode};
 mod ast;
 mod builder;
 mod common;
 mod concrete_ast;
 mod concretizer;
+mod loader;
 mod parser;
 mod resolved_ast;
 mod resolver;
@@ -11,12 +12,16 @@ mod resolver;
 use clap::{command, Parser};
 use common::target::{PointerSizedIntWidth, TargetPlatform};
 use inkwell::{context::Context as LLVMContext, OptimizationLevel};
-use nom::{
-    error::{convert_error, VerboseError},
-    Finish,
-};
 use resolver::ResolverContext;
 
+// --emitで出力する形式
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Emit {
+    Ir,
+    Asm,
+    Obj,
+}
+
 #[derive(clap::Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -24,82 +29,254 @@ struct Args {
     target: String,
     #[clap(short, long)]
     output: Option<String>,
+    // 指定した場合はJIT実行せず、その形式で出力する。
+    // irとasmは-oがなければ標準出力に、objは-oがなければ入力と同じ名前の.oに書き出す
+    #[clap(long, value_enum)]
+    emit: Option<Emit>,
     #[clap(long)]
     parse: bool,
+    // 構文解析はせず、入力ファイルのトークンを位置つきで1行ずつ出力する
+    #[clap(long)]
+    tokens: bool,
+    // 指定した場合はそのターゲット向けにout.llを出力するだけで、JIT実行はしない
+    #[clap(long)]
+    triple: Option<String>,
+    #[clap(short = 'O', long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=3))]
+    opt_level: u8,
+    // DWARFのデバッグ情報を出力する
+    #[clap(short = 'g', long)]
+    debug_info: bool,
+    // 浮動小数点数の比較を順序なしにし、NaNとの比較をtrueにする
+    #[clap(long)]
+    unordered_float_compare: bool,
+    // 整数の加減乗算のオーバーフローを検査し、オーバーフローしたらtrapする
+    #[clap(long)]
+    checked_arithmetic: bool,
+    // 整数を0で割る前に検査し、0ならtrapする
+    #[clap(long)]
+    guard_division_by_zero: bool,
+    // シフト量を amount & (width-1) に丸め、ビット幅以上のシフトの結果を決める
+    #[clap(long)]
+    mask_shift_amount: bool,
+    // 生成したIRの命令とソースの位置の対応を、JSONでこのファイルに書き出す
+    #[clap(long)]
+    source_map: Option<String>,
 }
 
-fn main() {
+// irとasmの出力先。-oがなければ標準出力に書き出す
+fn write_text(output: Option<&str>, text: &str) -> Result<(), String> {
+    match output {
+        Some(output) => std::fs::write(output, text).map_err(|error| error.to_string()),
+        None => {
+            print!("{}", text);
+            Ok(())
+        }
+    }
+}
+
+// 診断は標準エラーに書き出し、エラーがあれば失敗の終了コードを返す
+fn main() -> ExitCode {
     let args = Args::parse();
     let path = Path::new(&args.target);
-    let input = read_to_string(path).unwrap();
-    let input = input.as_str().into();
-    let module = match parser::parse_module(input).finish() {
-        Ok((_, module)) => module,
-        Err(err) => {
-            // using workaround to convert Span -> &str
-            // ref: https://github.com/fflorent/nom_locate/issues/36#issuecomment-1013469728
-            let errors = err
-                .errors
-                .into_iter()
-                .map(|(input, error)| (*input.fragment(), error))
-                .collect();
-
-            let error_message = convert_error(*input, VerboseError { errors });
-            println!("{}", error_message);
-            return;
+    if args.tokens {
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) => {
+                eprintln!("{}: {}", path.display(), error);
+                return ExitCode::FAILURE;
+            }
+        };
+        for token in parser::tokenize(&source) {
+            println!(
+                "{}:{}-{}:{} {:?}",
+                token.range.from.line,
+                token.range.from.col,
+                token.range.to.line,
+                token.range.to.col,
+                token.value
+            );
+        }
+        return ExitCode::SUCCESS;
+    }
+    let files = match loader::load_files(path) {
+        Ok(files) => files,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
         }
     };
     if args.parse {
-        dbg!(module);
-        return;
+        for file in &files {
+            dbg!(&file.module);
+        }
+        return ExitCode::SUCCESS;
+    }
+    // デバッグ情報にはentryのファイルの絶対パスを書く
+    let absolute_path = files[0].path.clone();
+    let current_dir = std::env::current_dir().unwrap();
+    // 診断はそれを出したファイルのパスとソースで表示する。
+    // カレントディレクトリの外のファイルは絶対パスで表示する
+    let mut sources = Vec::new();
+    let mut modules = Vec::new();
+    for file in files {
+        let relative_path = file.path.strip_prefix(&current_dir).unwrap_or(&file.path);
+        sources.push((relative_path.to_str().unwrap().to_string(), file.source));
+        modules.push(file.module);
     }
 
     let llvm_context: LLVMContext = LLVMContext::create();
     let target_platform = TargetPlatform::DarwinArm64;
-    let resolver_context = ResolverContext::new(PointerSizedIntWidth::from(target_platform));
-    let resolved_module = match resolver::resolve_module(&resolver_context, &module, true) {
+    let ptr_sized_int_type = match &args.triple {
+        Some(triple) => match builder::pointer_sized_int_width(triple) {
+            Ok(width) => width,
+            Err(message) => {
+                eprintln!("{}", message);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => PointerSizedIntWidth::from(target_platform),
+    };
+    let resolver_context = ResolverContext::new(ptr_sized_int_type);
+    let resolved_module = match resolver::resolve_modules(&resolver_context, modules, true) {
         Ok(module) => module,
         Err(err) => {
             dbg!(err);
-            return;
+            return ExitCode::FAILURE;
         }
     };
-    if !resolver_context.errors.borrow().is_empty() {
-        let absolute_path = path.canonicalize().unwrap();
-        let current_dir = std::env::current_dir().unwrap();
-        let relative_path = absolute_path.strip_prefix(current_dir).unwrap();
-        let mut stdout = std::io::stdout();
+    let mut stderr = std::io::stderr();
+    #[cfg(feature = "color")]
+    {
+        use std::io::IsTerminal;
+        let renderer = resolver::report::DiagnosticRenderer::new(stderr.is_terminal());
+        for warning in resolver_context.warnings.borrow().iter() {
+            let (path, source) = &sources[warning.file()];
+            renderer
+                .render_warning(&mut stderr, warning, path, source)
+                .unwrap();
+        }
         for error in resolver_context.errors.borrow().iter() {
-            error
-                .fmt_with_source(
-                    &mut stdout,
-                    relative_path.to_str().unwrap(),
-                    input.fragment(),
-                )
+            let (path, source) = &sources[error.file()];
+            renderer
+                .render_error(&mut stderr, error, path, source)
                 .unwrap();
         }
-        return;
+    }
+    #[cfg(not(feature = "color"))]
+    {
+        for warning in resolver_context.warnings.borrow().iter() {
+            let (path, source) = &sources[warning.file()];
+            warning.fmt_with_source(&mut stderr, path, source).unwrap();
+        }
+        for error in resolver_context.errors.borrow().iter() {
+            let (path, source) = &sources[error.file()];
+            error.fmt_with_source(&mut stderr, path, source).unwrap();
+        }
+    }
+    if !resolver_context.errors.borrow().is_empty() {
+        return ExitCode::FAILURE;
     }
     let concretizer_context =
         concretizer::ConcretizerContext::from_resolved_module(&resolver_context, resolved_module);
     let concrete_module = concretizer::concretize_module(&concretizer_context);
-    let mut llvm_codegenerator = builder::LLVMCodeGenerator::new(
-        &llvm_context,
-        target_platform,
-        OptimizationLevel::None,
-        &concrete_module,
-    );
+    let optimization_level = match args.opt_level {
+        0 => OptimizationLevel::None,
+        1 => OptimizationLevel::Less,
+        2 => OptimizationLevel::Default,
+        _ => OptimizationLevel::Aggressive,
+    };
+    let mut llvm_codegenerator = match &args.triple {
+        Some(triple) => match builder::LLVMCodeGenerator::with_target(
+            &llvm_context,
+            triple,
+            optimization_level,
+            &concrete_module,
+        ) {
+            Ok(llvm_codegenerator) => llvm_codegenerator,
+            Err(message) => {
+                eprintln!("{}", message);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => builder::LLVMCodeGenerator::new(
+            &llvm_context,
+            target_platform,
+            optimization_level,
+            &concrete_module,
+        ),
+    };
+    // 解決に使った整数の幅とターゲットの幅が食い違えば、正しいコードを生成できない
+    if llvm_codegenerator.ptr_sized_int_type() != ptr_sized_int_type {
+        eprintln!(
+            "The pointer-sized integer width of the target does not match the one used for type checking"
+        );
+        return ExitCode::FAILURE;
+    }
+    let has_explicit_target = llvm_codegenerator.target_triple().is_some();
+    if args.debug_info {
+        llvm_codegenerator.enable_debug_info(&absolute_path);
+    }
+    if args.unordered_float_compare {
+        llvm_codegenerator.use_unordered_float_compare();
+    }
+    if args.checked_arithmetic {
+        llvm_codegenerator.set_arithmetic_mode(builder::ArithmeticMode::Checked);
+    }
+    if args.guard_division_by_zero {
+        llvm_codegenerator.enable_division_by_zero_guard();
+    }
+    if args.mask_shift_amount {
+        llvm_codegenerator.enable_shift_amount_masking();
+    }
+    if args.source_map.is_some() {
+        llvm_codegenerator.enable_source_map();
+    }
     llvm_codegenerator.gen_module(&concrete_module);
-    let module = llvm_codegenerator.get_module();
-
-    module.print_to_file(Path::new("out.ll")).unwrap();
-    let execution_engine = &module
-        .create_jit_execution_engine(inkwell::OptimizationLevel::None)
-        .unwrap();
-    unsafe {
-        execution_engine
-            .get_function::<unsafe extern "C" fn()>("main")
-            .unwrap()
-            .call();
+    // 最適化で命令が変わる前の対応を書き出す
+    if let Some(source_map_path) = &args.source_map {
+        if let Err(error) = std::fs::write(source_map_path, llvm_codegenerator.emit_source_map()) {
+            eprintln!("{}: {}", source_map_path, error);
+            return ExitCode::FAILURE;
+        }
+    }
+    if let Err(error) = llvm_codegenerator.optimize(optimization_level) {
+        eprintln!("{}", error);
+        return ExitCode::FAILURE;
+    }
+    let output = args.output.as_deref();
+    let result = match (args.emit, output) {
+        (Some(Emit::Ir), output) => llvm_codegenerator
+            .verify()
+            .map_err(|error| error.to_string())
+            .and_then(|_| write_text(output, &llvm_codegenerator.emit_ir_string())),
+        (Some(Emit::Asm), output) => llvm_codegenerator
+            .emit_assembly(optimization_level)
+            .map_err(|error| error.to_string())
+            .and_then(|assembly| write_text(output, &assembly)),
+        // 出力先を指定した場合はオブジェクトファイルを書き出すだけで、JIT実行はしない
+        (Some(Emit::Obj), _) | (None, Some(_)) => {
+            let output = output.map_or_else(|| path.with_extension("o"), Into::into);
+            llvm_codegenerator
+                .write_object_file(&output, optimization_level)
+                .map_err(|error| error.to_string())
+        }
+        (None, None) => {
+            std::fs::write("out.ll", llvm_codegenerator.emit_ir_string()).unwrap();
+            if has_explicit_target {
+                return ExitCode::SUCCESS;
+            }
+            // mainの戻り値をそのまま終了コードにする
+            match llvm_codegenerator.jit_run("main", &[]) {
+                Ok(code) => std::process::exit(code as i32),
+                Err(error) => Err(error.to_string()),
+            }
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
     }
 }
@@ -1,14 +1,14 @@
 use nom::{
     branch::alt,
-    bytes::complete::tag,
-    character::complete::{digit1, none_of},
-    combinator::{cut, opt},
-    error::context,
-    multi::many0,
+    bytes::complete::{tag, take},
+    character::complete::{char, digit1, multispace0, none_of, one_of},
+    combinator::{cut, not, opt, recognize, verify},
+    error::{context, VerboseError, VerboseErrorKind},
+    multi::{many0, many1},
     sequence::{pair, preceded, terminated, tuple},
 };
 
-use crate::ast::*;
+use crate::{ast::*, common::typename::*};
 
 use super::{
     token::*,
@@ -17,12 +17,121 @@ use super::{
     *,
 };
 
-fn parse_number_literal(input: Span) -> NotLocatedParseResult<Expression> {
-    map(digit1, |str: Span| {
-        Expression::NumberLiteral(NumberLiteralExpr {
-            value: str.to_string(),
+// 1_000_000 のように数字の間にだけ_を書ける。_1, 1_, 1__0 は受け付けない
+fn parse_digits(input: Span) -> NotLocatedParseResult<Span> {
+    terminated(
+        recognize(pair(digit1, many0(pair(char('_'), digit1)))),
+        not(char('_')),
+    )(input)
+}
+
+// 10u8 のような接尾辞で型を固定できる。usizeをu8より先に試す
+fn parse_integer_suffix(input: Span) -> NotLocatedParseResult<Span> {
+    alt((
+        tag(I32_TYPE_NAME),
+        tag(I64_TYPE_NAME),
+        tag(U16_TYPE_NAME),
+        tag(U32_TYPE_NAME),
+        tag(U64_TYPE_NAME),
+        tag(USIZE_TYPE_NAME),
+        tag(U8_TYPE_NAME),
+        tag(I16_TYPE_NAME),
+        tag(I8_TYPE_NAME),
+    ))(input)
+}
+
+pub(super) fn parse_number_literal(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        pair(parse_digits, opt(parse_integer_suffix)),
+        |(str, suffix): (Span, Option<Span>)| {
+            Expression::NumberLiteral(NumberLiteralExpr {
+                value: str.replace('_', ""),
+                suffix: suffix.map(|suffix| suffix.to_string()),
+            })
+        },
+    )(input)
+}
+
+#[test]
+fn test_parse_number_literal_with_separators() {
+    for (source, value) in [("1_000_000", "1000000"), ("12", "12"), ("1_2_3", "123")] {
+        let (rest, expr) = parse_number_literal(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "");
+        assert_eq!(
+            expr,
+            Expression::NumberLiteral(NumberLiteralExpr {
+                value: value.into(),
+                suffix: None
+            })
+        );
+    }
+    for (source, value, suffix) in [("10u8", "10", "u8"), ("1_000usize", "1000", "usize")] {
+        let (rest, expr) = parse_number_literal(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "");
+        assert_eq!(
+            expr,
+            Expression::NumberLiteral(NumberLiteralExpr {
+                value: value.into(),
+                suffix: Some(suffix.into()),
+            })
+        );
+    }
+    for source in ["_1", "1_", "1__0"] {
+        assert!(
+            parse_number_literal(Span::new(source)).is_err(),
+            "{}",
+            source
+        );
+    }
+    let (rest, expr) = parse_float_literal(Span::new("1_000.000_5e1_0")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(
+        expr,
+        Expression::FloatLiteral(FloatLiteralExpr {
+            value: "1000.0005e10".into()
         })
-    })(input)
+    );
+    assert!(parse_float_literal(Span::new("1_.5")).is_err());
+}
+
+// 3.14, 1e10, 2.5e-3 のような形式。.5 や 1. は受け付けない
+pub(super) fn parse_float_literal(input: Span) -> NotLocatedParseResult<Expression> {
+    let exponent = |input| recognize(tuple((one_of("eE"), opt(one_of("+-")), parse_digits)))(input);
+    map(
+        recognize(tuple((
+            parse_digits,
+            alt((
+                recognize(pair(preceded(tag("."), parse_digits), opt(exponent))),
+                exponent,
+            )),
+        ))),
+        |str: Span| {
+            Expression::FloatLiteral(FloatLiteralExpr {
+                value: str.replace('_', ""),
+            })
+        },
+    )(input)
+}
+
+#[test]
+fn test_parse_float_literal() {
+    for source in ["3.14", "1.0", "1e10", "2.5e-3", "6.02E+23"] {
+        let (rest, expr) = parse_float_literal(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "");
+        assert_eq!(
+            expr,
+            Expression::FloatLiteral(FloatLiteralExpr {
+                value: source.into()
+            })
+        );
+    }
+    assert!(parse_float_literal(Span::new(".5")).is_err());
+    assert!(parse_float_literal(Span::new("1.")).is_err());
+    assert!(parse_float_literal(Span::new("12")).is_err());
+    // 整数リテラルより先に試すので3に切り詰められない
+    let (rest, expr) = parse_boxed_expression(Span::new("3.14")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert!(matches!(*expr.value, Expression::FloatLiteral(_)));
 }
 
 fn parse_variable_ref(input: Span) -> NotLocatedParseResult<Expression> {
@@ -46,6 +155,50 @@ fn parse_arguments(input: Span) -> NotLocatedParseResult<Vec<LocatedExpr>> {
     Ok((s, args))
 }
 
+// 二項演算子の記号。前から順に試すので、他の記号を接頭辞に持つ記号を先に置く
+const BINARY_OPERATORS: &[(&str, BinaryOp)] = &[
+    ("<<", BinaryOp::Shl),
+    (">>", BinaryOp::Shr),
+    ("<=", BinaryOp::LessThanOrEquals),
+    (">=", BinaryOp::GreaterThanOrEquals),
+    ("==", BinaryOp::Equals),
+    ("!=", BinaryOp::NotEquals),
+    ("<", BinaryOp::LessThan),
+    (">", BinaryOp::GreaterThan),
+    ("=", BinaryOp::Equals),
+    ("+", BinaryOp::Add),
+    ("-", BinaryOp::Sub),
+    ("*", BinaryOp::Mul),
+    ("/", BinaryOp::Div),
+    ("%", BinaryOp::Mod),
+    ("&", BinaryOp::BitAnd),
+    ("|", BinaryOp::BitOr),
+    ("^", BinaryOp::BitXor),
+];
+
+fn parse_binary_operator(input: Span) -> NotLocatedParseResult<BinaryOp> {
+    let (s, _) = skip0(input)?;
+    for (symbol, op) in BINARY_OPERATORS {
+        if s.fragment().starts_with(symbol) {
+            let (rest, _) = take(symbol.len())(s)?;
+            return Ok((rest, *op));
+        }
+    }
+    Err(nom::Err::Error(VerboseError {
+        errors: vec![(s, VerboseErrorKind::Context("binary_operator"))],
+    }))
+}
+
+#[test]
+fn test_parse_binary_operator() {
+    for (symbol, op) in BINARY_OPERATORS {
+        let source = format!("{} a", symbol);
+        let (rest, parsed) = parse_binary_operator(Span::new(&source)).unwrap();
+        assert_eq!(parsed, *op, "{}", symbol);
+        assert_eq!(rest.to_string().as_str(), " a", "{}", symbol);
+    }
+}
+
 pub(super) fn parse_intrinsic_binop_expression(input: Span) -> NotLocatedParseResult<Expression> {
     map(
         delimited(
@@ -53,18 +206,8 @@ pub(super) fn parse_intrinsic_binop_expression(input: Span) -> NotLocatedParseRe
             delimited(
                 skip0,
                 tuple((
-                    alt((
-                        map(plus, |_| BinaryOp::Add),
-                        map(minus, |_| BinaryOp::Sub),
-                        map(asterisk, |_| BinaryOp::Mul),
-                        map(slash, |_| BinaryOp::Div),
-                        map(eq_token, |_| BinaryOp::Equals),
-                        map(neq_token, |_| BinaryOp::NotEquals),
-                        map(lte_token, |_| BinaryOp::LessThanOrEquals),
-                        map(lt_token, |_| BinaryOp::LessThan),
-                        map(gte_token, |_| BinaryOp::GreaterThanOrEquals),
-                        map(gt_token, |_| BinaryOp::GreaterThan),
-                    )),
+                    // && は論理演算、&x はアドレス演算子なので、&& をビットANDとして読まない
+                    terminated(parse_binary_operator, not(char('&'))),
                     parse_boxed_expression,
                     parse_boxed_expression,
                 )),
@@ -81,18 +224,149 @@ pub(super) fn parse_intrinsic_binop_expression(input: Span) -> NotLocatedParseRe
         },
     )(input)
 }
+
+#[test]
+fn test_parse_mod_expression() {
+    let (rest, expr) = parse_intrinsic_binop_expression(Span::new("(% 7 3)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(
+        expr,
+        Expression::Binary(BinaryExpr {
+            op: BinaryOp::Mod,
+            lhs: Located {
+                range: Range {
+                    from: Position { line: 1, col: 4 },
+                    to: Position { line: 1, col: 5 },
+                },
+                value: Box::new(Expression::NumberLiteral(NumberLiteralExpr {
+                    value: "7".into(),
+                    suffix: None
+                })),
+            },
+            rhs: Located {
+                range: Range {
+                    from: Position { line: 1, col: 6 },
+                    to: Position { line: 1, col: 7 },
+                },
+                value: Box::new(Expression::NumberLiteral(NumberLiteralExpr {
+                    value: "3".into(),
+                    suffix: None
+                })),
+            },
+        })
+    );
+}
+
+#[test]
+fn test_parse_bitwise_expression() {
+    // S式なので優先順位は括弧の入れ子で決まる: a & b | c
+    let (rest, expr) = parse_boxed_expression(Span::new("(| (& a b) c)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match *expr.value {
+        Expression::Binary(bit_or) => {
+            assert_eq!(bit_or.op, BinaryOp::BitOr);
+            match *bit_or.lhs.value {
+                Expression::Binary(bit_and) => assert_eq!(bit_and.op, BinaryOp::BitAnd),
+                _ => panic!("unexpected expression type"),
+            }
+        }
+        _ => panic!("unexpected expression type"),
+    }
+    let (_, expr) = parse_boxed_expression(Span::new("(^ a b)")).unwrap();
+    assert!(matches!(
+        *expr.value,
+        Expression::Binary(BinaryExpr {
+            op: BinaryOp::BitXor,
+            ..
+        })
+    ));
+    // && と || は論理演算として扱う
+    let (_, expr) = parse_boxed_expression(Span::new("(&& a b)")).unwrap();
+    assert!(matches!(*expr.value, Expression::Multi(_)));
+    let (_, expr) = parse_boxed_expression(Span::new("(|| a b)")).unwrap();
+    assert!(matches!(*expr.value, Expression::Multi(_)));
+}
+
+#[test]
+fn test_parse_shift_expression() {
+    for (source, op) in [("(<< a b)", BinaryOp::Shl), ("(>> a b)", BinaryOp::Shr)] {
+        let (rest, expr) = parse_intrinsic_binop_expression(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "");
+        match expr {
+            Expression::Binary(binary_expr) => assert_eq!(binary_expr.op, op, "{}", source),
+            _ => panic!("unexpected expression type"),
+        }
+    }
+}
+
+#[test]
+fn test_parse_expression_with_block_comments() {
+    let (rest, expr) =
+        parse_boxed_expression(Span::new("(+ /* lhs */ 1 /* 複数行の\ncomment */ 2)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match *expr.value {
+        Expression::Binary(binary_expr) => {
+            assert_eq!(binary_expr.op, BinaryOp::Add);
+            assert_eq!(binary_expr.rhs.range.from, Position { line: 2, col: 12 });
+        }
+        _ => panic!("unexpected expression type"),
+    }
+}
+
+#[test]
+fn test_parse_comparison_expression() {
+    for (source, op) in [
+        ("(= a b)", BinaryOp::Equals),
+        ("(== a b)", BinaryOp::Equals),
+        ("(!= a b)", BinaryOp::NotEquals),
+        ("(< a b)", BinaryOp::LessThan),
+        ("(<= a b)", BinaryOp::LessThanOrEquals),
+        ("(> a b)", BinaryOp::GreaterThan),
+        ("(>= a b)", BinaryOp::GreaterThanOrEquals),
+    ] {
+        let (rest, expr) = parse_intrinsic_binop_expression(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "");
+        match expr {
+            Expression::Binary(binary_expr) => {
+                assert_eq!(binary_expr.op, op, "{}", source);
+                assert_eq!(
+                    *binary_expr.lhs.value,
+                    Expression::VariableRef(VariableRefExpr { name: "a".into() })
+                );
+                assert_eq!(
+                    *binary_expr.rhs.value,
+                    Expression::VariableRef(VariableRefExpr { name: "b".into() })
+                );
+            }
+            _ => panic!("unexpected expression: {}", source),
+        }
+    }
+}
+
 pub(super) fn parse_intrinsic_unary_op_expression(
     input: Span,
 ) -> NotLocatedParseResult<Expression> {
     map(
         preceded(
             lparen,
-            tuple((
-                alt((map(not_token, |_| {
-                    // panic!();
-                    UnaryOp::Not
-                }),)),
-                cut(terminated(parse_boxed_expression, rparen)),
+            alt((
+                pair(
+                    map(not_token, |_| UnaryOp::Not),
+                    cut(terminated(parse_boxed_expression, rparen)),
+                ),
+                pair(
+                    map(tilde, |_| UnaryOp::BitNot),
+                    cut(terminated(parse_boxed_expression, rparen)),
+                ),
+                // (!= a b) や (- a b) は二項演算なので、ここではcutしない
+                pair(
+                    map(bang, |_| UnaryOp::Not),
+                    terminated(parse_boxed_expression, rparen),
+                ),
+                pair(
+                    map(minus, |_| UnaryOp::Neg),
+                    terminated(parse_boxed_expression, rparen),
+                ),
             )),
         ),
         |(op, target)| {
@@ -104,6 +378,30 @@ pub(super) fn parse_intrinsic_unary_op_expression(
     )(input)
 }
 
+#[test]
+fn test_parse_negation_expression() {
+    for (source, op) in [
+        ("(- 5)", UnaryOp::Neg),
+        ("(- (- x))", UnaryOp::Neg),
+        ("(! (< a b))", UnaryOp::Not),
+        ("(not (< a b))", UnaryOp::Not),
+        ("(~ 0)", UnaryOp::BitNot),
+    ] {
+        let (rest, expr) = parse_boxed_expression(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "");
+        match *expr.value {
+            Expression::Unary(unary_expr) => assert_eq!(unary_expr.op, op, "{}", source),
+            _ => panic!("unexpected expression type: {}", source),
+        }
+    }
+    // 引数が2つの場合は二項演算になる
+    for source in ["(- a b)", "(!= a b)"] {
+        let (rest, expr) = parse_boxed_expression(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "");
+        assert!(matches!(*expr.value, Expression::Binary(_)), "{}", source);
+    }
+}
+
 #[test]
 fn parse_test_intrinsic_unary_op_expression() {
     assert!(parse_boxed_expression("(not a b)".into()).is_err());
@@ -119,7 +417,9 @@ pub(super) fn parse_intrinsic_multi_op_expression(
             pair(
                 alt((
                     map(and_token, |_| MultiOp::And),
+                    map(double_ampersand_token, |_| MultiOp::And),
                     map(or_token, |_| MultiOp::Or),
+                    map(double_pipe_token, |_| MultiOp::Or),
                 )),
                 cut(many1(parse_boxed_expression)),
             ),
@@ -128,6 +428,27 @@ pub(super) fn parse_intrinsic_multi_op_expression(
         |(op, operands)| Expression::Multi(MultiExpr { op, operands }),
     )(input)
 }
+
+#[test]
+fn test_parse_logical_expression() {
+    for (source, op) in [
+        ("(and a b)", MultiOp::And),
+        ("(&& a b)", MultiOp::And),
+        ("(or a b)", MultiOp::Or),
+        ("(|| a b)", MultiOp::Or),
+    ] {
+        let (rest, expr) = parse_intrinsic_multi_op_expression(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "");
+        match expr {
+            Expression::Multi(multi_expr) => {
+                assert_eq!(multi_expr.op, op, "{}", source);
+                assert_eq!(multi_expr.operands.len(), 2);
+            }
+            _ => panic!("unexpected expression type"),
+        }
+    }
+}
+
 pub(super) fn parse_function_call_expression(input: Span) -> NotLocatedParseResult<Expression> {
     map(
         delimited(
@@ -149,6 +470,15 @@ pub(super) fn parse_function_call_expression(input: Span) -> NotLocatedParseResu
     )(input)
 }
 
+#[test]
+fn test_parse_function_call_with_trailing_comma() {
+    let (_, expr) = parse_function_call_expression("(f<i32, u8> 1, 2)".into()).unwrap();
+    let (rest, expr_with_comma) =
+        parse_function_call_expression("(f<i32, u8> 1, 2,)".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(expr_with_comma, expr);
+}
+
 #[test]
 fn test_parse_function_call_expression() {
     // write test
@@ -186,6 +516,27 @@ fn parse_if_expression(input: Span) -> NotLocatedParseResult<Expression> {
                 if_token,
                 parse_boxed_expression,
                 parse_boxed_expression,
+                opt(parse_boxed_expression),
+            )),
+            rparen,
+        ),
+        |(_, cond, then, els)| match els {
+            Some(els) => Expression::If(IfExpr { cond, then, els }),
+            // elseが無いifはwhenと同じ
+            None => Expression::When(WhenExpr { cond, then }),
+        },
+    )(input)
+}
+
+// (? cond a b) はelseを省略できないifとして扱う
+fn parse_ternary_expression(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        delimited(
+            lparen,
+            tuple((
+                pair(question, skip1),
+                parse_boxed_expression,
+                parse_boxed_expression,
                 parse_boxed_expression,
             )),
             rparen,
@@ -194,6 +545,165 @@ fn parse_if_expression(input: Span) -> NotLocatedParseResult<Expression> {
     )(input)
 }
 
+#[test]
+fn test_parse_ternary_expression() {
+    let (rest, expr) =
+        parse_boxed_expression(Span::new("(? (< a b) (? flag 1 2) (+ a 1))")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match *expr.value {
+        Expression::If(if_expr) => {
+            assert!(matches!(*if_expr.cond.value, Expression::Binary(_)));
+            // 入れ子の三項演算子はthenの中に収まる
+            assert!(matches!(*if_expr.then.value, Expression::If(_)));
+            assert!(matches!(*if_expr.els.value, Expression::Binary(_)));
+        }
+        _ => panic!("unexpected expression type"),
+    }
+    // elseは省略できない
+    assert!(parse_ternary_expression(Span::new("(? flag 1)")).is_err());
+}
+
+#[test]
+fn test_parse_if_expression_without_else() {
+    let (rest, expr) = parse_if_expression(Span::new("(if a b)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert!(matches!(expr, Expression::When(_)));
+
+    let (rest, expr) = parse_if_expression(Span::new("(if a (if b c d))")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match expr {
+        Expression::When(when_expr) => {
+            assert!(matches!(*when_expr.then.value, Expression::If(_)));
+        }
+        _ => panic!("unexpected expression type"),
+    }
+}
+
+fn parse_while_expression(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        delimited(
+            lparen,
+            tuple((
+                while_token,
+                parse_boxed_expression,
+                many0(parse_boxed_expression),
+            )),
+            rparen,
+        ),
+        |(_, cond, body)| Expression::While(WhileExpr { cond, body }),
+    )(input)
+}
+
+#[test]
+fn test_parse_while_expression() {
+    let (rest, expr) = parse_while_expression(Span::new(
+        "(while (< i 10) (printf \"%d\" i) (:=< i (+ i 1)))",
+    ))
+    .unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match expr {
+        Expression::While(while_expr) => {
+            assert!(matches!(*while_expr.cond.value, Expression::Binary(_)));
+            assert_eq!(while_expr.body.len(), 2);
+        }
+        _ => panic!("unexpected expression type"),
+    }
+    assert!(parse_while_expression(Span::new("(while true)")).is_ok());
+}
+
+// 空の節は()で書く
+// (break) と (continue)
+fn parse_loop_control_expression(input: Span) -> NotLocatedParseResult<Expression> {
+    delimited(
+        lparen,
+        alt((
+            map(break_token, |_| Expression::Break),
+            map(continue_token, |_| Expression::Continue),
+        )),
+        rparen,
+    )(input)
+}
+
+#[test]
+fn test_parse_loop_control_expression() {
+    let (rest, expr) = parse_boxed_expression(Span::new("(break)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(*expr.value, Expression::Break);
+    let (_, expr) = parse_boxed_expression(Span::new("(continue)")).unwrap();
+    assert_eq!(*expr.value, Expression::Continue);
+    // breakで始まる関数の呼び出しと区別する
+    let (_, expr) = parse_boxed_expression(Span::new("(breakpoint)")).unwrap();
+    assert!(matches!(*expr.value, Expression::Call(_)));
+}
+
+fn parse_for_clause(input: Span) -> NotLocatedParseResult<Option<LocatedExpr>> {
+    alt((
+        map(pair(lparen, rparen), |_| None),
+        map(parse_boxed_expression, Some),
+    ))(input)
+}
+
+fn parse_for_expression(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        delimited(
+            lparen,
+            tuple((
+                for_token,
+                parse_for_clause,
+                parse_for_clause,
+                parse_for_clause,
+                many0(parse_boxed_expression),
+            )),
+            rparen,
+        ),
+        |(_, init, cond, step, body)| {
+            Expression::For(ForExpr {
+                init,
+                cond,
+                step,
+                body,
+            })
+        },
+    )(input)
+}
+
+#[test]
+fn test_parse_for_expression() {
+    let (rest, expr) = parse_for_expression(Span::new(
+        "(for (:= i 0) (< i 10) (:=< i (+ i 1)) (printf \"%d\" i))",
+    ))
+    .unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match expr {
+        Expression::For(for_expr) => {
+            assert!(matches!(
+                *for_expr.init.unwrap().value,
+                Expression::VariableDecl(_)
+            ));
+            assert!(matches!(
+                *for_expr.cond.unwrap().value,
+                Expression::Binary(_)
+            ));
+            assert!(matches!(
+                *for_expr.step.unwrap().value,
+                Expression::Assignment(_)
+            ));
+            assert_eq!(for_expr.body.len(), 1);
+        }
+        _ => panic!("unexpected expression type"),
+    }
+    let (rest, expr) = parse_for_expression(Span::new("(for () () () (printf \"x\"))")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match expr {
+        Expression::For(for_expr) => {
+            assert!(for_expr.init.is_none());
+            assert!(for_expr.cond.is_none());
+            assert!(for_expr.step.is_none());
+        }
+        _ => panic!("unexpected expression type"),
+    }
+}
+
 fn parse_when_expression(input: Span) -> NotLocatedParseResult<Expression> {
     map(
         delimited(
@@ -241,30 +751,61 @@ fn parse_deref_expression(input: Span) -> NotLocatedParseResult<Expression> {
     })(input)
 }
 
-fn parse_string_literal(input: Span) -> NotLocatedParseResult<Expression> {
-    map(
+// &x, &a[0], &p.x のように左辺値のアドレスを取る。左辺値かどうかはresolverで検査する
+fn parse_address_of_expression(input: Span) -> NotLocatedParseResult<Expression> {
+    map(preceded(ampersand, parse_boxed_expression), |expr| {
+        Expression::AddressOf(AddressOfExpr { target: expr })
+    })(input)
+}
+
+#[test]
+fn test_parse_address_of_expression() {
+    let (rest, expr) = parse_boxed_expression(Span::new("&a[0]")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match *expr.value {
+        Expression::AddressOf(address_of) => {
+            assert!(matches!(
+                *address_of.target.value,
+                Expression::IndexAccess(_)
+            ));
+        }
+        _ => panic!("unexpected expression type"),
+    }
+    // 括弧の中の&はビットAND
+    let (_, expr) = parse_boxed_expression(Span::new("(& a b)")).unwrap();
+    assert!(matches!(*expr.value, Expression::Binary(_)));
+}
+
+// エスケープを展開した文字列リテラルの中身
+pub(super) fn parse_string(input: Span) -> NotLocatedParseResult<String> {
+    delimited(
+        skip0,
         delimited(
-            skip0,
-            delimited(
-                doublequote,
-                map(
-                    many0(alt((
-                        map(tag("\\\""), |_| "\"".to_string()),
-                        map(tag("\\r"), |_| "\r".to_string()),
-                        map(tag("\\n"), |_| "\n".to_string()),
-                        map(tag("\\t"), |_| "\t".to_string()),
-                        map(none_of("\""), |c| c.to_string()),
-                    ))),
-                    |chars| chars.join(""),
-                ),
-                doublequote,
+            doublequote,
+            map(
+                many0(alt((
+                    map(tag("\\\""), |_| "\"".to_string()),
+                    map(tag("\\r"), |_| "\r".to_string()),
+                    map(tag("\\n"), |_| "\n".to_string()),
+                    map(tag("\\t"), |_| "\t".to_string()),
+                    map(tag("\\0"), |_| "\0".to_string()),
+                    map(tag("\\\\"), |_| "\\".to_string()),
+                    map(none_of("\""), |c| c.to_string()),
+                ))),
+                |chars| chars.join(""),
             ),
-            skip0,
+            doublequote,
         ),
-        |value| Expression::StringLiteral(StringLiteralExpr { value }),
+        skip0,
     )(input)
 }
 
+fn parse_string_literal(input: Span) -> NotLocatedParseResult<Expression> {
+    map(parse_string, |value| {
+        Expression::StringLiteral(StringLiteralExpr { value })
+    })(input)
+}
+
 #[test]
 fn test_parse_string_literal() {
     let result = parse_string_literal(Span::new("\"%d\""));
@@ -279,6 +820,65 @@ fn test_parse_string_literal() {
     );
 }
 
+// 'a' のように1バイトの文字だけを書ける
+pub(super) fn parse_char_literal(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        preceded(
+            singlequote,
+            cut(terminated(
+                alt((
+                    map(tag("\\n"), |_| b'\n'),
+                    map(tag("\\t"), |_| b'\t'),
+                    map(tag("\\r"), |_| b'\r'),
+                    map(tag("\\0"), |_| b'\0'),
+                    map(tag("\\\\"), |_| b'\\'),
+                    map(tag("\\'"), |_| b'\''),
+                    map(verify(none_of("'\\"), |c| c.is_ascii()), |c| c as u8),
+                )),
+                char('\''),
+            )),
+        ),
+        |value| Expression::CharLiteral(CharLiteralExpr { value }),
+    )(input)
+}
+
+#[test]
+fn test_parse_char_literal() {
+    for (source, value) in [
+        ("'a'", b'a'),
+        ("'\\n'", b'\n'),
+        ("'\\t'", b'\t'),
+        ("'\\r'", b'\r'),
+        ("'\\0'", b'\0'),
+        ("'\\\\'", b'\\'),
+        ("'\\''", b'\''),
+    ] {
+        let (rest, expr) = parse_char_literal(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "", "{}", source);
+        assert_eq!(
+            expr,
+            Expression::CharLiteral(CharLiteralExpr { value }),
+            "{}",
+            source
+        );
+    }
+    for source in ["'ab'", "''", "'\\x'", "'あ'"] {
+        assert!(parse_char_literal(Span::new(source)).is_err(), "{}", source);
+    }
+}
+
+#[test]
+fn test_parse_string_literal_escapes() {
+    let (rest, expr) = parse_string_literal(Span::new("\"a\\nb\\t\\r\\0\\\\\\\"\"")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(
+        expr,
+        Expression::StringLiteral(StringLiteralExpr {
+            value: "a\nb\t\r\0\\\"".to_string()
+        })
+    );
+}
+
 fn parse_field(input: Span) -> NotLocatedParseResult<(String, LocatedExpr)> {
     context(
         "field",
@@ -297,6 +897,24 @@ fn parse_bool_literal(input: Span) -> NotLocatedParseResult<Expression> {
     })(input)
 }
 
+// nullptrのような識別子と区別するため、識別子として読んでから比較する
+fn parse_null_literal(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        verify(parse_identifier, |name: &String| name == "null"),
+        |_| Expression::NullLiteral,
+    )(input)
+}
+
+#[test]
+fn test_parse_null_literal() {
+    let (rest, expr) = parse_boxed_expression("null".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(*expr.value, Expression::NullLiteral);
+
+    let (_, expr) = parse_boxed_expression("nullable".into()).unwrap();
+    assert!(matches!(*expr.value, Expression::VariableRef(_)));
+}
+
 fn parse_struct_literal(input: Span) -> NotLocatedParseResult<Expression> {
     fn parse_fields(input: Span) -> NotLocatedParseResult<Vec<(String, LocatedExpr)>> {
         let mut fields = Vec::new();
@@ -329,6 +947,14 @@ fn parse_struct_literal(input: Span) -> NotLocatedParseResult<Expression> {
     )(input)
 }
 
+#[test]
+fn test_parse_struct_literal_with_trailing_comma() {
+    let (_, expr) = parse_struct_literal("Point { x: 1, y: 2 }".into()).unwrap();
+    let (rest, expr_with_comma) = parse_struct_literal("Point { x: 1, y: 2, }".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(expr_with_comma, expr);
+}
+
 #[test]
 fn test_parse_struct_literal() {
     let result = parse_boxed_expression(Span::new("Point { x: 1, y: 2, }"));
@@ -343,14 +969,16 @@ fn test_parse_struct_literal() {
         assert_eq!(
             *struct_literal.fields[0].1.value,
             Expression::NumberLiteral(NumberLiteralExpr {
-                value: "1".to_string()
+                value: "1".to_string(),
+                suffix: None
             })
         );
         assert_eq!(struct_literal.fields[1].0, "y");
         assert_eq!(
             *struct_literal.fields[1].1.value,
             Expression::NumberLiteral(NumberLiteralExpr {
-                value: "2".to_string()
+                value: "2".to_string(),
+                suffix: None
             })
         );
     } else {
@@ -378,6 +1006,49 @@ fn test_parse_struct_literal() {
     }
 }
 
+// (1, 2u8)。関数呼び出しと区別するため、最初の要素の直後にカンマが必要
+fn parse_tuple_literal(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        delimited(
+            lparen,
+            pair(
+                terminated(parse_boxed_expression, pair(multispace0, char(','))),
+                many1(parse_boxed_expression),
+            ),
+            rparen,
+        ),
+        |(first, rest)| {
+            let mut elements = vec![first];
+            elements.extend(rest);
+            Expression::TupleLiteral(TupleLiteralExpr { elements })
+        },
+    )(input)
+}
+
+#[test]
+fn test_parse_tuple_literal() {
+    let (rest, expr) = parse_boxed_expression(Span::new("(1, 2u8, (f x),)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    let Expression::TupleLiteral(tuple_literal) = *expr.value else {
+        panic!("unexpected expression type");
+    };
+    assert_eq!(tuple_literal.elements.len(), 3);
+    assert!(matches!(
+        *tuple_literal.elements[2].value,
+        Expression::Call(_)
+    ));
+
+    // カンマが関数名の直後になければ関数呼び出しになる
+    let (_, expr) = parse_boxed_expression(Span::new("(write \"%d\", x)")).unwrap();
+    assert!(matches!(*expr.value, Expression::Call(_)));
+
+    let (_, expr) = parse_boxed_expression(Span::new("pair.1")).unwrap();
+    let Expression::FieldAccess(field_access) = *expr.value else {
+        panic!("unexpected expression type");
+    };
+    assert_eq!(field_access.field_name, "1");
+}
+
 fn parse_sizeof(input: Span) -> NotLocatedParseResult<Expression> {
     map(
         delimited(lparen, preceded(sizeof_token, cut(parse_type)), rparen),
@@ -385,6 +1056,79 @@ fn parse_sizeof(input: Span) -> NotLocatedParseResult<Expression> {
     )(input)
 }
 
+// va_argで始まる関数名を呼び出せるように、型との間に空白を要求する
+fn parse_va_arg(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        delimited(
+            lparen,
+            preceded(pair(va_arg_token, skip1), cut(parse_type)),
+            cut(rparen),
+        ),
+        |ty| Expression::VaArg(VaArgExpr { ty }),
+    )(input)
+}
+
+#[test]
+fn test_parse_va_arg() {
+    let (rest, expr) = parse_va_arg(Span::new("(va_arg i64)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    let Expression::VaArg(va_arg_expr) = expr else {
+        panic!("unexpected expression: {:?}", expr);
+    };
+    assert_eq!(
+        va_arg_expr.ty.value,
+        UnresolvedType::TypeRef(TypeRef {
+            name: "i64".into(),
+            generic_args: None
+        })
+    );
+    assert!(parse_va_arg(Span::new("(va_arg_count)")).is_err());
+}
+
+fn parse_cast_expression(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        delimited(
+            lparen,
+            preceded(
+                cast_token,
+                pair(
+                    verify(parse_generic_arguments, |args: &Vec<_>| args.len() == 1),
+                    cut(parse_boxed_expression),
+                ),
+            ),
+            cut(rparen),
+        ),
+        |(mut target_ty, expr)| {
+            Expression::Cast(CastExpr {
+                target_ty: target_ty.remove(0),
+                expr,
+            })
+        },
+    )(input)
+}
+
+#[test]
+fn test_parse_cast_expression() {
+    let (rest, expr) = parse_boxed_expression(Span::new("(cast<u64> (+ a 1))")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match *expr.value {
+        Expression::Cast(cast_expr) => {
+            assert_eq!(
+                cast_expr.target_ty.value,
+                UnresolvedType::TypeRef(TypeRef {
+                    name: "u64".into(),
+                    generic_args: None
+                })
+            );
+            assert!(matches!(*cast_expr.expr.value, Expression::Binary(_)));
+        }
+        _ => panic!("unexpected expression type"),
+    }
+    // castで始まる名前の関数呼び出しはそのまま
+    let (_, expr) = parse_boxed_expression(Span::new("(caster 1)")).unwrap();
+    assert!(matches!(*expr.value, Expression::Call(_)));
+}
+
 fn parse_asignment(input: Span) -> NotLocatedParseResult<Expression> {
     map(
         delimited(
@@ -415,7 +1159,132 @@ fn test_parse_assignment() {
     assert!(parse_asignment(Span::new("(:=< buf[index] value)")).is_ok());
 }
 
-fn parse_variable_decl(input: Span) -> NotLocatedParseResult<Expression> {
+// 比較演算子以外の二項演算子に = を付けたもの
+fn parse_compound_assignment_op(input: Span) -> NotLocatedParseResult<BinaryOp> {
+    terminated(
+        verify(parse_binary_operator, |op| {
+            !matches!(
+                op,
+                BinaryOp::Equals
+                    | BinaryOp::NotEquals
+                    | BinaryOp::LessThan
+                    | BinaryOp::LessThanOrEquals
+                    | BinaryOp::GreaterThan
+                    | BinaryOp::GreaterThanOrEquals
+            )
+        }),
+        char('='),
+    )(input)
+}
+
+// (+= *p[i] 1) を (:=< *p[i] (+ (*p)[i] 1)) に展開する。
+// インデックスの式は読み出しと書き込みで2回評価される
+fn parse_compound_assignment(input: Span) -> NotLocatedParseResult<Expression> {
+    map(
+        delimited(
+            lparen,
+            tuple((
+                parse_compound_assignment_op,
+                located(many0(asterisk)),
+                located(parse_identifier),
+                opt(index_access),
+                parse_boxed_expression,
+            )),
+            rparen,
+        ),
+        |(op, asterisks, name, index_access, rhs)| {
+            let mut target = Located {
+                range: name.range,
+                value: Box::new(Expression::VariableRef(VariableRefExpr {
+                    name: name.value.clone(),
+                })),
+            };
+            for _ in 0..asterisks.value.len() {
+                target = Located {
+                    range: target.range,
+                    value: Box::new(Expression::DerefExpr(DerefExpr { target })),
+                };
+            }
+            if let Some(index) = &index_access {
+                target = Located {
+                    range: index.range,
+                    value: Box::new(Expression::IndexAccess(IndexAccessExpr {
+                        target,
+                        index: index.clone(),
+                    })),
+                };
+            }
+            Expression::Assignment(AssignExpr {
+                deref_count: asterisks.value.len() as u32,
+                index_access,
+                name: name.value,
+                value: Located {
+                    range: rhs.range,
+                    value: Box::new(Expression::Binary(BinaryExpr {
+                        op,
+                        lhs: target,
+                        rhs,
+                    })),
+                },
+            })
+        },
+    )(input)
+}
+
+#[test]
+fn test_parse_compound_assignment() {
+    for (source, expected_op) in [
+        ("(+= a 1)", BinaryOp::Add),
+        ("(-= a 1)", BinaryOp::Sub),
+        ("(*= a 1)", BinaryOp::Mul),
+        ("(/= a 1)", BinaryOp::Div),
+        ("(%= a 1)", BinaryOp::Mod),
+        ("(&= a 1)", BinaryOp::BitAnd),
+        ("(|= a 1)", BinaryOp::BitOr),
+        ("(^= a 1)", BinaryOp::BitXor),
+        ("(<<= a 1)", BinaryOp::Shl),
+        ("(>>= a 1)", BinaryOp::Shr),
+    ] {
+        let (rest, expr) = parse_boxed_expression(Span::new(source)).unwrap();
+        assert_eq!(rest.to_string().as_str(), "", "{}", source);
+        match *expr.value {
+            Expression::Assignment(assign_expr) => match *assign_expr.value.value {
+                Expression::Binary(binary_expr) => {
+                    assert_eq!(binary_expr.op, expected_op, "{}", source);
+                    assert_eq!(
+                        *binary_expr.lhs.value,
+                        Expression::VariableRef(VariableRefExpr { name: "a".into() })
+                    );
+                }
+                _ => panic!("unexpected value: {}", source),
+            },
+            _ => panic!("unexpected expression type: {}", source),
+        }
+    }
+
+    // デリファレンスとインデックスアクセスは代入先と読み出しの両方に残る
+    let (_, expr) = parse_boxed_expression(Span::new("(+= *buf[i] 1)")).unwrap();
+    match *expr.value {
+        Expression::Assignment(assign_expr) => {
+            assert_eq!(assign_expr.deref_count, 1);
+            assert!(assign_expr.index_access.is_some());
+            let Expression::Binary(binary_expr) = *assign_expr.value.value else {
+                panic!("unexpected value");
+            };
+            let Expression::IndexAccess(index_access) = *binary_expr.lhs.value else {
+                panic!("unexpected lhs");
+            };
+            assert!(matches!(
+                *index_access.target.value,
+                Expression::DerefExpr(_)
+            ));
+        }
+        _ => panic!("unexpected expression type"),
+    }
+}
+
+// トップレベルのグローバル変数の宣言にも使う
+pub(super) fn parse_variable_decls(input: Span) -> NotLocatedParseResult<VariableDeclsExpr> {
     delimited(
         lparen,
         preceded(
@@ -439,67 +1308,162 @@ fn parse_variable_decl(input: Span) -> NotLocatedParseResult<Expression> {
                         value: expression,
                     },
                 ))),
-                |decls| Expression::VariableDecl(VariableDeclsExpr { decls }),
+                |decls| VariableDeclsExpr { decls },
             )),
         ),
         rparen,
     )(input)
 }
 
+fn parse_variable_decl(input: Span) -> NotLocatedParseResult<Expression> {
+    map(parse_variable_decls, Expression::VariableDecl)(input)
+}
+
 pub(super) fn parse_boxed_expression(input: Span) -> ParseResult<Box<Expression>> {
     let (rest, expr) = located(map(
-        alt((
-            context("sizeof", parse_sizeof),
-            context("deref", parse_deref_expression),
-            context("string_literal", parse_string_literal),
-            context("number_literal", parse_number_literal),
-            context("bool_literal", parse_bool_literal),
-            context("struct_literal", parse_struct_literal),
-            context("if", parse_if_expression),
-            context("when", parse_when_expression),
-            context("assignment", parse_asignment),
-            context("variable_decl", parse_variable_decl),
-            context("unary_op", parse_intrinsic_unary_op_expression),
-            context("binop", parse_intrinsic_binop_expression),
-            context("multi_op", parse_intrinsic_multi_op_expression),
-            context("call", parse_function_call_expression),
-            context("variable_ref", parse_variable_ref),
-        )),
+        context(
+            "expression",
+            alt((
+                alt((
+                    context("sizeof", parse_sizeof),
+                    context("va_arg", parse_va_arg),
+                )),
+                context("cast", parse_cast_expression),
+                context("deref", parse_deref_expression),
+                context("address_of", parse_address_of_expression),
+                context("string_literal", parse_string_literal),
+                context("char_literal", parse_char_literal),
+                context("float_literal", parse_float_literal),
+                context("number_literal", parse_number_literal),
+                alt((
+                    context("bool_literal", parse_bool_literal),
+                    context("null_literal", parse_null_literal),
+                )),
+                context("struct_literal", parse_struct_literal),
+                context("tuple_literal", parse_tuple_literal),
+                alt((
+                    context("if", parse_if_expression),
+                    context("when", parse_when_expression),
+                    context("ternary", parse_ternary_expression),
+                )),
+                alt((
+                    context("while", parse_while_expression),
+                    context("for", parse_for_expression),
+                    context("loop_control", parse_loop_control_expression),
+                )),
+                alt((
+                    context("assignment", parse_asignment),
+                    context("compound_assignment", parse_compound_assignment),
+                )),
+                context("variable_decl", parse_variable_decl),
+                context("unary_op", parse_intrinsic_unary_op_expression),
+                context("binop", parse_intrinsic_binop_expression),
+                context("multi_op", parse_intrinsic_multi_op_expression),
+                context("call", parse_function_call_expression),
+                context("variable_ref", parse_variable_ref),
+            )),
+        ),
         Box::new,
     ))(input)?;
 
-    {
-        let (rest, opt_index_expr) = opt(located(index_access))(rest)?;
-        if let Some(index_expr) = opt_index_expr {
-            return Ok((
-                rest,
-                Located {
-                    range: index_expr.range,
-                    value: Box::new(Expression::IndexAccess(IndexAccessExpr {
+    // 後置演算子は a[0].x のように連ねて書ける
+    let mut rest = rest;
+    let mut expr = expr;
+    loop {
+        if let (next, Some(index_expr)) = opt(located(index_access))(rest)? {
+            rest = next;
+            expr = Located {
+                range: index_expr.range,
+                value: Box::new(Expression::IndexAccess(IndexAccessExpr {
+                    target: expr,
+                    index: index_expr.value,
+                })),
+            };
+        } else if let (next, Some(member)) = opt(located(pair(
+            field_access,
+            // x.len() のように、名前の直後に括弧が続けばメソッド呼び出しになる
+            opt(preceded(char('('), terminated(parse_arguments, rparen))),
+        )))(rest)?
+        {
+            rest = next;
+            let (name, args) = member.value;
+            expr = Located {
+                range: member.range,
+                value: Box::new(match args {
+                    Some(args) => Expression::MethodCall(MethodCallExpr {
+                        receiver: expr,
+                        name,
+                        args,
+                    }),
+                    None => Expression::FieldAccess(FieldAccessExpr {
                         target: expr,
-                        index: index_expr.value,
-                    })),
-                },
-            ));
+                        field_name: name,
+                    }),
+                }),
+            };
+        } else {
+            break;
         }
     }
-    {
-        let (rest, opt_field_access) = opt(located(field_access))(rest)?;
-        if let Some(field_access) = opt_field_access {
-            return Ok((
-                rest,
-                Located {
-                    range: field_access.range,
-                    value: Box::new(Expression::FieldAccess(FieldAccessExpr {
-                        target: expr,
-                        field_name: field_access.value,
-                    })),
-                },
+
+    Ok((rest, expr))
+}
+
+#[test]
+fn test_parse_chained_field_access() {
+    let (rest, expr) = parse_boxed_expression(Span::new("line.start.x")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match *expr.value {
+        Expression::FieldAccess(outer) => {
+            assert_eq!(outer.field_name, "x");
+            match *outer.target.value {
+                Expression::FieldAccess(inner) => {
+                    assert_eq!(inner.field_name, "start");
+                    assert_eq!(
+                        *inner.target.value,
+                        Expression::VariableRef(VariableRefExpr {
+                            name: "line".into()
+                        })
+                    );
+                }
+                _ => panic!("unexpected expression type"),
+            }
+        }
+        _ => panic!("unexpected expression type"),
+    }
+    let (_, expr) = parse_boxed_expression(Span::new("points[0].y")).unwrap();
+    match *expr.value {
+        Expression::FieldAccess(field_access) => {
+            assert!(matches!(
+                *field_access.target.value,
+                Expression::IndexAccess(_)
             ));
         }
+        _ => panic!("unexpected expression type"),
     }
+}
 
-    Ok((rest, expr))
+#[test]
+fn test_parse_method_call() {
+    let (rest, expr) = parse_boxed_expression(Span::new("line.start.offset(1 2).x")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    let Expression::FieldAccess(field_access) = *expr.value else {
+        panic!("unexpected expression type");
+    };
+    let Expression::MethodCall(method_call) = *field_access.target.value else {
+        panic!("unexpected expression type");
+    };
+    assert_eq!(method_call.name, "offset");
+    assert_eq!(method_call.args.len(), 2);
+    assert!(matches!(
+        *method_call.receiver.value,
+        Expression::FieldAccess(_)
+    ));
+
+    // 括弧との間に空白があれば、フィールドの後に別の式が続いている
+    let (rest, expr) = parse_boxed_expression(Span::new("p.x (f)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), " (f)");
+    assert!(matches!(*expr.value, Expression::FieldAccess(_)));
 }
 
 #[test]
@@ -520,7 +1484,8 @@ fn test_parse_boxed_expression() {
         .value
         .as_ref(),
         &Expression::NumberLiteral(NumberLiteralExpr {
-            value: "1".to_string()
+            value: "1".to_string(),
+            suffix: None
         })
     )
 }
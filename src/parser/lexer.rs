@@ -0,0 +1,234 @@
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{anychar, char, multispace0, multispace1, none_of, satisfy},
+    combinator::recognize,
+    multi::many0,
+    sequence::{pair, preceded, tuple},
+};
+
+use crate::ast::{Located, Position, Range};
+
+use super::{
+    expression::{parse_char_literal, parse_float_literal, parse_number_literal},
+    util::{block_comment, comment},
+    *,
+};
+
+// 構文解析とは独立したトークン。リテラルは書かれたままの文字列を持つ
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Keyword(&'static str),
+    Identifier(String),
+    Number(String),
+    Float(String),
+    String(String),
+    Char(String),
+    Operator(&'static str),
+    Punctuation(&'static str),
+    // どのトークンにも当てはまらない1文字
+    Unknown(char),
+}
+
+const KEYWORDS: &[&str] = &[
+    "fn",
+    "pub",
+    "struct",
+    "record",
+    "type",
+    "enum",
+    "return",
+    "sizeof",
+    "va_arg",
+    "cast",
+    "const",
+    "import",
+    "if",
+    "when",
+    "while",
+    "for",
+    "break",
+    "continue",
+    "and",
+    "or",
+    "not",
+    "alloc",
+    "salloc",
+    "interface",
+    "impl",
+    "unused",
+    "true",
+    "false",
+    "null",
+];
+
+// 長いものから順に試す。ジェネリクスの<>も演算子として扱う
+const OPERATORS: &[&str] = &[
+    ":=<", "<<=", ">>=", ":=", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=",
+    "/=", "%=", "&=", "|=", "^=", "<", ">", "=", "+", "-", "*", "/", "%", "&", "|", "^", "~", "!",
+];
+
+const PUNCTUATIONS: &[&str] = &[
+    "...", "(", ")", "{", "}", "[", "]", ",", ":", ";", ".", "@", "?", "'", "\"",
+];
+
+fn parse_trivia(input: Span) -> NotLocatedParseResult<()> {
+    map(
+        many0(alt((comment, block_comment, map(multispace1, |_| ())))),
+        |_| (),
+    )(input)
+}
+
+fn parse_word(input: Span) -> NotLocatedParseResult<Token> {
+    // print-i32 や ->bool のような名前も1つの識別子になる
+    let identifier_char = satisfy(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '!' | '?'));
+    map(
+        recognize(pair(
+            alt((
+                tag("->"),
+                recognize(satisfy(|c| c.is_alphabetic() || c == '_')),
+            )),
+            many0(alt((tag("->"), recognize(identifier_char)))),
+        )),
+        |word: Span| match KEYWORDS
+            .iter()
+            .find(|keyword| **keyword == *word.fragment())
+        {
+            Some(keyword) => Token::Keyword(keyword),
+            None => Token::Identifier(word.to_string()),
+        },
+    )(input)
+}
+
+fn parse_string_token(input: Span) -> NotLocatedParseResult<Token> {
+    map(
+        recognize(tuple((
+            char('"'),
+            many0(alt((preceded(char('\\'), anychar), none_of("\"\\")))),
+            char('"'),
+        ))),
+        |s: Span| Token::String(s.to_string()),
+    )(input)
+}
+
+fn parse_symbol<'a>(
+    symbols: &'static [&'static str],
+    f: fn(&'static str) -> Token,
+) -> impl FnMut(Span<'a>) -> NotLocatedParseResult<'a, Token> {
+    move |input: Span<'a>| {
+        for symbol in symbols {
+            if let Ok((rest, _)) = tag::<_, _, VerboseError<Span>>(*symbol)(input) {
+                return Ok((rest, f(symbol)));
+            }
+        }
+        Err(nom::Err::Error(VerboseError {
+            errors: vec![(input, VerboseErrorKind::Context("symbol"))],
+        }))
+    }
+}
+
+fn parse_token(input: Span) -> NotLocatedParseResult<Token> {
+    alt((
+        parse_string_token,
+        map(recognize(parse_char_literal), |s: Span| {
+            Token::Char(s.to_string())
+        }),
+        map(recognize(parse_float_literal), |s: Span| {
+            Token::Float(s.to_string())
+        }),
+        map(recognize(parse_number_literal), |s: Span| {
+            Token::Number(s.to_string())
+        }),
+        parse_word,
+        parse_symbol(OPERATORS, Token::Operator),
+        parse_symbol(PUNCTUATIONS, Token::Punctuation),
+        map(anychar, Token::Unknown),
+    ))(input)
+}
+
+fn position_of(s: Span) -> Position {
+    Position {
+        line: s.location_line(),
+        col: s.get_column(),
+    }
+}
+
+// ソースをトークン列にする。エディタの色付けなどで使うためのもので、空白とコメントは捨てる。
+// 解析できない部分があっても止まらず、1文字ずつUnknownにして続けるので、parse_tokenは空でなければ必ず成功する
+pub fn tokenize(input: &str) -> Vec<Located<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = Span::new(input);
+    loop {
+        // 閉じていないブロックコメントは、その位置からトークンとして読む
+        rest = match parse_trivia(rest) {
+            Ok((s, _)) => s,
+            Err(_) => multispace0::<_, VerboseError<Span>>(rest).unwrap().0,
+        };
+        if rest.is_empty() {
+            break;
+        }
+        let (s, token) = parse_token(rest).unwrap();
+        tokens.push(Located {
+            range: Range {
+                from: position_of(rest),
+                to: position_of(s),
+            },
+            value: token,
+        });
+        rest = s;
+    }
+    tokens
+}
+
+#[test]
+fn test_tokenize() {
+    let tokens = tokenize("fn main(): int { return 1; }");
+    let expected = [
+        (Token::Keyword("fn"), 1, 3),
+        (Token::Identifier("main".into()), 4, 8),
+        (Token::Punctuation("("), 8, 9),
+        (Token::Punctuation(")"), 9, 10),
+        (Token::Punctuation(":"), 10, 11),
+        (Token::Identifier("int".into()), 12, 15),
+        (Token::Punctuation("{"), 16, 17),
+        (Token::Keyword("return"), 18, 24),
+        (Token::Number("1".into()), 25, 26),
+        (Token::Punctuation(";"), 26, 27),
+        (Token::Punctuation("}"), 28, 29),
+    ];
+    assert_eq!(tokens.len(), expected.len(), "{:?}", tokens);
+    for (token, (value, from, to)) in tokens.iter().zip(expected) {
+        assert_eq!(token.value, value);
+        assert_eq!(token.range.from, Position { line: 1, col: from });
+        assert_eq!(token.range.to, Position { line: 1, col: to });
+    }
+}
+
+#[test]
+fn test_tokenize_literals_and_operators() {
+    let tokens = tokenize(
+        "(:=< x, \"a \\\" b\") // comment
+/* block */ (+ 1.5 10u8 'c' print-i32 ->bool) $",
+    );
+    let values = tokens.into_iter().map(|x| x.value).collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![
+            Token::Punctuation("("),
+            Token::Operator(":=<"),
+            Token::Identifier("x".into()),
+            Token::Punctuation(","),
+            Token::String("\"a \\\" b\"".into()),
+            Token::Punctuation(")"),
+            Token::Punctuation("("),
+            Token::Operator("+"),
+            Token::Float("1.5".into()),
+            Token::Number("10u8".into()),
+            Token::Char("'c'".into()),
+            Token::Identifier("print-i32".into()),
+            Token::Identifier("->bool".into()),
+            Token::Punctuation(")"),
+            Token::Unknown('$'),
+        ]
+    );
+}
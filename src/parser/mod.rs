@@ -1,36 +1,193 @@
 mod expression;
+mod lexer;
 mod statement;
 mod token;
 mod toplevel;
 mod ty;
 mod util;
 
-use nom::{combinator::map, error::VerboseError, multi::many1, sequence::delimited, IResult};
+use nom::{
+    combinator::map,
+    error::{convert_error, VerboseError, VerboseErrorKind},
+    multi::many1,
+    sequence::delimited,
+    IResult,
+};
 
 use nom_locate::{position, LocatedSpan};
 
-use crate::ast::{Located, Module};
+use crate::ast::{Located, Module, Position, Range};
 
 use self::{toplevel::parse_toplevel, util::skip0};
 
+pub use self::lexer::tokenize;
+
 pub type Span<'a> = LocatedSpan<&'a str>;
 
 type ParseResult<'a, T> = IResult<Span<'a>, Located<T>, VerboseError<Span<'a>>>;
 type NotLocatedParseResult<'a, T> = IResult<Span<'a>, T, VerboseError<Span<'a>>>;
+// 解析を続けるために読み飛ばした文のエラー
+type RecoveredErrors = Vec<Located<String>>;
+
+// 文の解析のエラーを、位置と失敗した文の構文の名前を含むメッセージにする。
+// contextは内側の構文から順に積まれるので、最後のものが文全体の構文になる
+fn recovered_error(input: Span, err: &VerboseError<Span>) -> Located<String> {
+    let contexts = err
+        .errors
+        .iter()
+        .filter_map(|(_, kind)| match kind {
+            VerboseErrorKind::Context(context) => Some(*context),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let message = match contexts.last() {
+        Some(context) => format!("Invalid statement: failed to parse {}", context),
+        None => "Invalid statement".to_string(),
+    };
+    let position = Position {
+        line: input.location_line(),
+        col: input.get_column(),
+    };
+    Located {
+        range: Range {
+            from: position,
+            to: position,
+        },
+        value: message,
+    }
+}
 
 pub fn parse_module(input: Span<'_>) -> IResult<Span, Module, VerboseError<Span<'_>>> {
     let mut toplevels = Vec::new();
+    let mut errors = Vec::new();
     let mut rest = input;
     loop {
         (rest, _) = skip0(rest)?;
         if rest.is_empty() {
             break;
         }
-        let toplevel;
-        (rest, toplevel) = parse_toplevel(rest)?;
+        let (toplevel, toplevel_errors);
+        (rest, (toplevel, toplevel_errors)) = parse_toplevel(rest)?;
         toplevels.push(toplevel);
+        errors.extend(toplevel_errors);
     }
-    Ok((rest, Module { toplevels }))
+    Ok((rest, Module { toplevels, errors }))
+}
+
+// パースエラーを、失敗した位置の行とキャレット、context(...)で付けた構文の名前を含むメッセージにする
+pub fn format_parse_error(input: &str, err: VerboseError<Span>) -> String {
+    let Some((span, _)) = err.errors.first() else {
+        return "Parse error".into();
+    };
+    let header = format!(
+        "Parse error at line {}, column {}",
+        span.location_line(),
+        span.get_utf8_column()
+    );
+    // using workaround to convert Span -> &str
+    // ref: https://github.com/fflorent/nom_locate/issues/36#issuecomment-1013469728
+    let errors = err
+        .errors
+        .into_iter()
+        .map(|(input, error)| (*input.fragment(), error))
+        .collect();
+    format!(
+        "{}\n{}",
+        header,
+        convert_error(input, VerboseError { errors })
+    )
+}
+
+#[test]
+fn test_format_parse_error() {
+    use nom::Finish;
+
+    let input = "
+fn f(): i32 {
+    1
+}
+(const X: i32 )
+";
+    let err = parse_module(input.into()).finish().unwrap_err();
+    let message = format_parse_error(input, err);
+    assert!(
+        message.starts_with("Parse error at line 5, column 15\n"),
+        "{}",
+        message
+    );
+    assert!(message.contains("(const X: i32 )\n"), "{}", message);
+    assert!(message.contains("in expression"), "{}", message);
+}
+
+#[test]
+fn test_recover_from_broken_statements() {
+    use crate::ast::TopLevel;
+    let input = "
+fn main(): void {
+    (:= x 1)
+    (:= y (+ 1 2)]
+    (printf \"%d\" x)
+    (:= z
+}
+fn f(): void {}
+";
+    let (rest, module) = parse_module(input.into()).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(module.toplevels.len(), 2);
+    assert_eq!(module.errors.len(), 2, "{:?}", module.errors);
+    assert_eq!(module.errors[0].range.from.line, 4);
+    assert_eq!(module.errors[1].range.from.line, 6);
+    // 失敗した構文の名前は、中の識別子などではなく文全体のものにする
+    assert_eq!(
+        module.errors[0].value,
+        "Invalid statement: failed to parse effect_statement"
+    );
+    let TopLevel::Function(main) = &module.toplevels[0].value else {
+        panic!("unexpected toplevel: {:?}", module.toplevels[0]);
+    };
+    assert_eq!(main.body.len(), 2);
+}
+
+#[test]
+fn test_discard_errors_of_backtracked_block() {
+    // implのブロックが閉じていないのでメソッドは読まれず、中で読み飛ばした文のエラーも返さない
+    let (rest, (_, errors)) =
+        parse_toplevel("struct A { v: i32 } impl { fn f(): void { (:= y (+ 1 2)] }".into())
+            .unwrap();
+    assert!(rest.starts_with(" impl"), "{}", rest);
+    assert!(errors.is_empty(), "{:?}", errors);
+}
+
+#[test]
+fn test_parse_empty_function_body() {
+    use crate::ast::TopLevel;
+    use nom::Finish;
+
+    let (rest, module) = parse_module("fn nop(): void {}".into()).finish().unwrap();
+    assert!(rest.is_empty());
+    let TopLevel::Function(nop) = &module.toplevels[0].value else {
+        panic!("unexpected toplevel: {:?}", module.toplevels[0]);
+    };
+    assert!(nop.body.is_empty());
+}
+
+#[test]
+fn test_parse_comment_only_module() {
+    use nom::Finish;
+
+    let (rest, module) = parse_module(
+        "
+// comment
+/* block
+   comment */
+"
+        .into(),
+    )
+    .finish()
+    .unwrap();
+    assert!(rest.is_empty());
+    assert!(module.toplevels.is_empty());
+    assert!(module.errors.is_empty());
 }
 
 #[test]
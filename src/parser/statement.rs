@@ -1,14 +1,19 @@
 use nom::{
     branch::alt,
-    combinator::{map, opt},
+    combinator::{cut, map, not, opt, verify},
     error::context,
-    sequence::tuple,
+    multi::many0,
+    sequence::{delimited, pair, preceded, tuple},
 };
 
-use crate::ast::{EffectStatement, ReturnStatement, Statement};
+use crate::ast::{
+    DoWhileStatement, EffectStatement, Expression, Located, ReturnStatement, Statement,
+    SwitchStatement,
+};
 
 use super::{
-    expression::parse_boxed_expression, token::*, util::*, NotLocatedParseResult, ParseResult, Span,
+    expression::parse_boxed_expression, token::*, toplevel::parse_nested_block, util::*,
+    NotLocatedParseResult, ParseResult, Span,
 };
 
 fn parse_return_statement(input: Span) -> NotLocatedParseResult<Statement> {
@@ -30,9 +35,153 @@ fn parse_effect_statement(input: Span) -> NotLocatedParseResult<Statement> {
     })(input)
 }
 
+fn parse_block_statement(input: Span) -> NotLocatedParseResult<Statement> {
+    map(parse_nested_block, Statement::Block)(input)
+}
+
+fn switch_label<'a>(label: &'static str) -> impl FnMut(Span<'a>) -> NotLocatedParseResult<'a, ()> {
+    map(
+        verify(parse_identifier, move |name: &String| name == label),
+        |_| (),
+    )
+}
+
+// 次のcase、default、または閉じ括弧までの文
+fn parse_switch_body(input: Span) -> NotLocatedParseResult<Vec<Located<Statement>>> {
+    many0(preceded(
+        not(alt((switch_label("case"), switch_label("default")))),
+        parse_statement,
+    ))(input)
+}
+
+fn parse_switch_case(
+    input: Span,
+) -> NotLocatedParseResult<(Located<Expression>, Vec<Located<Statement>>)> {
+    map(
+        tuple((
+            switch_label("case"),
+            cut(parse_boxed_expression),
+            cut(colon),
+            parse_switch_body,
+        )),
+        |(_, label, _, body)| (label.unbox(), body),
+    )(input)
+}
+
+// switchやdo-whileの (x) は引数なしの呼び出しではなく、括弧で囲んだxとして読む
+fn parse_parenthesized_expression(input: Span) -> NotLocatedParseResult<Located<Expression>> {
+    map(
+        alt((
+            delimited(lparen, parse_boxed_expression, rparen),
+            parse_boxed_expression,
+        )),
+        |expr| expr.unbox(),
+    )(input)
+}
+
+// switch (x) { case 1: ... default: ... }
+// 当てはまったcaseの文だけを実行し、次のcaseには落ちない
+fn parse_switch_statement(input: Span) -> NotLocatedParseResult<Statement> {
+    let (s, _) = pair(switch_token, skip1)(input)?;
+    let (s, scrutinee) = cut(parse_parenthesized_expression)(s)?;
+    let (s, _) = cut(lbracket)(s)?;
+    let (s, cases) = many0(parse_switch_case)(s)?;
+    let (s, default) = opt(preceded(
+        pair(switch_label("default"), cut(colon)),
+        parse_switch_body,
+    ))(s)?;
+    let (s, _) = cut(rbracket)(s)?;
+    Ok((
+        s,
+        Statement::Switch(SwitchStatement {
+            scrutinee,
+            cases,
+            default,
+        }),
+    ))
+}
+
+// do { ... } while (cond)
+fn parse_do_while_statement(input: Span) -> NotLocatedParseResult<Statement> {
+    map(
+        tuple((
+            do_token,
+            parse_nested_block,
+            cut(while_token),
+            cut(parse_parenthesized_expression),
+        )),
+        |(_, body, _, cond)| Statement::DoWhile(DoWhileStatement { body, cond }),
+    )(input)
+}
+
 pub(super) fn parse_statement(input: Span) -> ParseResult<Statement> {
     located(alt((
         context("return_statement", parse_return_statement),
+        context("block_statement", parse_block_statement),
+        context("switch_statement", parse_switch_statement),
+        context("do_while_statement", parse_do_while_statement),
         context("effect_statement", parse_effect_statement),
     )))(input)
 }
+
+#[test]
+fn test_parse_block_statement() {
+    let (rest, statement) = parse_statement("{ (:= x 1) { return x } }".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    let Statement::Block(statements) = statement.value else {
+        panic!("unexpected statement: {:?}", statement);
+    };
+    assert_eq!(statements.len(), 2);
+    assert!(matches!(statements[0].value, Statement::Effect(_)));
+    assert!(matches!(&statements[1].value, Statement::Block(inner) if inner.len() == 1));
+}
+
+#[test]
+fn test_parse_switch_statement() {
+    let (rest, statement) = parse_statement(
+        "switch (x) {
+    case 1: (printf \"one\")
+    case (+ ONE 1):
+        (:= y 2)
+        return y
+    default: (printf \"other\")
+}"
+        .into(),
+    )
+    .unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    let Statement::Switch(switch) = statement.value else {
+        panic!("unexpected statement: {:?}", statement);
+    };
+    assert!(matches!(switch.scrutinee.value, Expression::VariableRef(_)));
+    assert_eq!(switch.cases.len(), 2);
+    assert_eq!(switch.cases[0].1.len(), 1);
+    assert!(matches!(switch.cases[1].0.value, Expression::Binary(_)));
+    assert_eq!(switch.cases[1].1.len(), 2);
+    assert_eq!(switch.default.map(|default| default.len()), Some(1));
+
+    // defaultもcaseもなくてよい
+    let (_, statement) = parse_statement("switch (+ x 1) {}".into()).unwrap();
+    assert!(matches!(statement.value, Statement::Switch(switch)
+        if switch.cases.is_empty() && switch.default.is_none()));
+}
+
+#[test]
+fn test_parse_do_while_statement() {
+    let (rest, statement) =
+        parse_statement("do { (:=< i (+ i 1)) (printf \"%d\" i) } while (< i 10)".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    let Statement::DoWhile(do_while) = statement.value else {
+        panic!("unexpected statement: {:?}", statement);
+    };
+    assert_eq!(do_while.body.len(), 2);
+    assert!(matches!(do_while.cond.value, Expression::Binary(_)));
+
+    let (_, statement) = parse_statement("do {} while (done)".into()).unwrap();
+    assert!(matches!(statement.value, Statement::DoWhile(do_while)
+        if matches!(do_while.cond.value, Expression::VariableRef(_))));
+
+    // doで始まる名前の式はdo-whileではない
+    let (_, statement) = parse_statement("done".into()).unwrap();
+    assert!(matches!(statement.value, Statement::Effect(_)));
+}
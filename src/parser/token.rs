@@ -49,37 +49,53 @@ token_char!(langlebracket, '<');
 token_char!(ranglebracket, '>');
 token_char!(comma, ',');
 token_char!(colon, ':');
+token_char!(semicolon, ';');
 token_char!(plus, '+');
 token_char!(minus, '-');
 token_char!(asterisk, '*');
-token_char!(slash, '/');
+token_char!(ampersand, '&');
+token_char!(tilde, '~');
 token_char!(dot, '.');
 token_char!(underscore, '_');
+token_char!(at, '@');
+token_char!(bang, '!');
+token_char!(question, '?');
 token_tag!(fn_token, "fn");
+token_tag!(pub_token, "pub");
 token_tag!(struct_token, "struct");
 token_tag!(record_token, "record");
+token_tag!(type_token, "type");
+token_tag!(enum_token, "enum");
 token_tag!(return_token, "return");
 token_tag!(doublequote, "\"");
+token_char!(singlequote, '\'');
 token_tag!(threedots, "...");
 token_tag!(sizeof_token, "sizeof");
+token_tag!(va_arg_token, "va_arg");
+token_tag!(cast_token, "cast");
+token_tag!(const_token, "const");
+token_tag!(import_token, "import");
 token_tag!(if_token, "if");
 token_tag!(when_token, "when");
+token_tag!(while_token, "while");
+token_tag!(break_token, "break");
+token_tag!(continue_token, "continue");
 token_tag!(var_decl_token, ":=");
 token_tag!(assign_token, ":=<");
 token_tag!(and_token, "and");
 token_tag!(or_token, "or");
+token_tag!(double_ampersand_token, "&&");
+token_tag!(double_pipe_token, "||");
 token_tag!(not_token, "not");
 token_tag!(eq_token, "=");
-token_tag!(neq_token, "!=");
-token_tag!(gte_token, ">=");
-token_tag!(lte_token, "<=");
-token_tag!(gt_token, ">");
-token_tag!(lt_token, "<");
 token_tag!(alloc_token, "alloc");
 token_tag!(salloc_token, "salloc");
 token_tag!(interface_token, "interface");
 token_tag!(impl_token, "impl");
 token_tag!(for_token, "for");
+token_tag!(switch_token, "switch");
+token_tag!(do_token, "do");
+token_tag!(unused_token, "unused");
 
 pub(super) fn parse_identifier(input: Span) -> NotLocatedParseResult<String> {
     let (first_skipped, _) = skip0(input)?;
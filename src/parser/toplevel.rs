@@ -4,13 +4,22 @@ use crate::{
     parser::ty::{parse_generic_argument_decls, parse_type},
 };
 
-use super::{statement::parse_statement, token::*, util::*, *};
+use super::{
+    expression::{parse_boxed_expression, parse_string, parse_variable_decls},
+    statement::parse_statement,
+    token::*,
+    util::*,
+    *,
+};
 
 use nom::{
     branch::alt,
-    combinator::{cut, opt, peek},
+    character::complete::i64,
+    combinator::{cut, not, opt, peek, verify},
     error::context,
-    sequence::{preceded, tuple},
+    multi::many0,
+    sequence::{pair, preceded, terminated, tuple},
+    InputTake,
 };
 
 #[test]
@@ -31,12 +40,46 @@ fn test_parse_generic_arguments() {
     assert_eq!(args[1].value.name, "U");
 }
 
+fn parse_argument_attribute(input: Span) -> NotLocatedParseResult<ArgumentAttribute> {
+    context(
+        "argument_attribute",
+        preceded(at, cut(map(unused_token, |_| ArgumentAttribute::Unused))),
+    )(input)
+}
+
 fn parse_argument(input: Span) -> NotLocatedParseResult<Argument> {
     alt((
         map(threedots, |_| Argument::VarArgs),
+        // メソッドのselfは型を書かない。resolverがレシーバーへのポインタ型にする
         map(
-            tuple((parse_identifier, colon, parse_type)),
-            |(name, _, ty)| Argument::Normal(ty, name),
+            terminated(
+                located(verify(parse_identifier, |name: &str| name == "self")),
+                not(colon),
+            ),
+            |name| Argument::Normal {
+                ty: Located {
+                    range: name.range,
+                    value: UnresolvedType::Infer,
+                },
+                name,
+                attributes: Vec::new(),
+                default: None,
+            },
+        ),
+        map(
+            tuple((
+                many0(parse_argument_attribute),
+                located(parse_identifier),
+                colon,
+                parse_type,
+                opt(preceded(eq_token, parse_boxed_expression)),
+            )),
+            |(attributes, name, _, ty, default)| Argument::Normal {
+                name,
+                ty,
+                attributes,
+                default,
+            },
         ),
     ))(input)
 }
@@ -51,7 +94,12 @@ fn parse_arguments(input: Span) -> NotLocatedParseResult<Vec<Argument>> {
         }
         let arg;
         (rest, arg) = parse_argument(rest)?;
+        let is_var_args = arg == Argument::VarArgs;
         args.push(arg);
+        // ...は最後の引数にしか書けない
+        if is_var_args {
+            break;
+        }
     }
     let (rest, _) = rparen(rest)?;
     Ok((rest, args))
@@ -74,10 +122,10 @@ fn test_parse_single_argument() {
     let (rest, arg) = result.unwrap();
     assert_eq!(rest.to_string().as_str(), ",");
     let (ty, name) = match arg {
-        Argument::Normal(ty, name) => (ty, name),
+        Argument::Normal { ty, name, .. } => (ty, name),
         _ => panic!("unexpected argument type"),
     };
-    assert_eq!(name, "x");
+    assert_eq!(name.value, "x");
     assert_eq!(
         ty.value,
         UnresolvedType::TypeRef(TypeRef {
@@ -95,10 +143,10 @@ fn test_parse_multiple_arguments() {
     let (_, args) = result.unwrap();
     assert_eq!(args.len(), 2);
     let (ty, name) = match &args[0] {
-        Argument::Normal(ty, name) => (ty, name),
+        Argument::Normal { ty, name, .. } => (ty, name),
         _ => panic!("unexpected argument type"),
     };
-    assert_eq!(name, "x");
+    assert_eq!(name.value, "x");
     assert_eq!(
         ty.value,
         UnresolvedType::TypeRef(TypeRef {
@@ -107,10 +155,10 @@ fn test_parse_multiple_arguments() {
         })
     );
     let (ty, name) = match &args[1] {
-        Argument::Normal(ty, name) => (ty, name),
+        Argument::Normal { ty, name, .. } => (ty, name),
         _ => panic!("unexpected argument type"),
     };
-    assert_eq!(name, "y");
+    assert_eq!(name.value, "y");
     assert_eq!(
         ty.value,
         UnresolvedType::TypeRef(TypeRef {
@@ -120,6 +168,65 @@ fn test_parse_multiple_arguments() {
     );
 }
 
+#[test]
+fn test_parse_arguments_with_trailing_comma() {
+    let (_, args) = parse_arguments("(x: i32, y: f64)".into()).unwrap();
+    let (rest, args_with_comma) = parse_arguments("(x: i32, y: f64,)".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(args_with_comma, args);
+
+    let (_, args) = parse_arguments("(format: *u8, ...)".into()).unwrap();
+    let (rest, args_with_comma) = parse_arguments("(format: *u8, ..., )".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(args_with_comma, args);
+}
+
+#[test]
+fn test_parse_argument_with_attribute() {
+    let (rest, arg) = parse_argument("@unused x: i32".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert!(matches!(
+        arg,
+        Argument::Normal { attributes, .. } if attributes == vec![ArgumentAttribute::Unused]
+    ));
+
+    // 未知の属性はエラーにする
+    assert!(parse_argument("@unknown x: i32".into()).is_err());
+}
+
+#[test]
+fn test_parse_default_argument() {
+    let (rest, args) = parse_arguments("(x: i32, y: i32 = 10)".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(args.len(), 2);
+    assert!(matches!(&args[0], Argument::Normal { default: None, .. }));
+    let Argument::Normal {
+        default: Some(default),
+        ..
+    } = &args[1]
+    else {
+        panic!("unexpected argument: {:?}", args[1]);
+    };
+    assert_eq!(
+        *default.value,
+        Expression::NumberLiteral(NumberLiteralExpr {
+            value: "10".into(),
+            suffix: None
+        })
+    );
+}
+
+#[test]
+fn test_parse_var_args() {
+    let (rest, args) = parse_arguments("(count: i32, ...)".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(args.len(), 2);
+    assert_eq!(args[1], Argument::VarArgs);
+
+    // ...の後に引数は書けない
+    assert!(parse_arguments("(..., count: i32)".into()).is_err());
+}
+
 #[test]
 fn test_parse_argument_with_error() {
     let input = "x i32".into();
@@ -134,20 +241,32 @@ fn parse_alloc_mode(input: Span) -> NotLocatedParseResult<AllocMode> {
     ))(input)
 }
 
+fn parse_visibility(input: Span) -> NotLocatedParseResult<Visibility> {
+    map(opt(pub_token), |pub_token| match pub_token {
+        Some(_) => Visibility::Public,
+        None => Visibility::Private,
+    })(input)
+}
+
 fn parse_function_decl(input: Span) -> ParseResult<FunctionDecl> {
     context(
         "function_decl",
         located(map(
             tuple((
+                parse_visibility,
                 opt(parse_alloc_mode),
                 fn_token,
                 parse_identifier,
                 opt(parse_generic_argument_decls),
                 // params
                 parse_arguments,
-                map(tuple((colon, parse_type)), |(_, ty)| ty),
+                // 戻り値の型を省略すると、resolverがreturn文から推論する
+                map(opt(preceded(colon, parse_type)), |ty| {
+                    ty.unwrap_or_else(|| Located::default_from(UnresolvedType::Infer))
+                }),
             )),
-            |(alloc_mode, _, name, generic_args, params, ty)| FunctionDecl {
+            |(visibility, alloc_mode, _, name, generic_args, params, ty)| FunctionDecl {
+                visibility,
                 alloc_mode,
                 name,
                 generic_args,
@@ -159,7 +278,55 @@ fn parse_function_decl(input: Span) -> ParseResult<FunctionDecl> {
     )(input)
 }
 
-pub fn parse_block(input: Span) -> NotLocatedParseResult<Vec<Located<Statement>>> {
+#[test]
+fn test_parse_function_without_return_type() {
+    let (rest, decl) = parse_function_decl("fn add(a: i32, b: i32) {".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), " {");
+    assert_eq!(decl.value.return_type.value, UnresolvedType::Infer);
+}
+
+// 壊れた文を読み飛ばす。文字列の中を除いて括弧の対応を取り、括弧の外の`;`か改行の後、
+// または対応する`{`のない`}`の手前まで進める。閉じていない`(`があっても`}`は越えない。
+// 少なくとも1文字は進めるので、必ず先へ進む
+fn skip_broken_statement(input: Span) -> Span {
+    let mut paren_depth = 0usize;
+    let mut brace_depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut end = input.len();
+    for (i, c) in input.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' => paren_depth += 1,
+            ')' | ']' => paren_depth = paren_depth.saturating_sub(1),
+            '{' => brace_depth += 1,
+            '}' if brace_depth == 0 => {
+                end = i;
+                break;
+            }
+            '}' => brace_depth -= 1,
+            ';' | '\n' if paren_depth == 0 && brace_depth == 0 && i > 0 => {
+                end = i + 1;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let (rest, _) = input.take_split(end.max(1).min(input.len()));
+    rest
+}
+
+// 文の中のブロック。壊れた文を含むブロックは、それを含む本体の文ごと読み飛ばす
+pub fn parse_nested_block(input: Span) -> NotLocatedParseResult<Vec<Located<Statement>>> {
     let (s, _) = skip0(input)?;
     let (s, _) = lbracket(s)?;
     let (s, _) = skip0(s)?;
@@ -174,21 +341,129 @@ pub fn parse_block(input: Span) -> NotLocatedParseResult<Vec<Located<Statement>>
     Ok((s, statements))
 }
 
-fn parse_function(input: Span) -> ParseResult<TopLevel> {
-    located(context(
+// 関数の本体。文の解析に失敗しても読み飛ばして続け、読み飛ばした文のエラーも返す。
+// エラーを結果として返すので、このブロックを読んだ後で外側の構文が失敗してもエラーは残らない
+fn parse_block(input: Span) -> NotLocatedParseResult<(Vec<Located<Statement>>, RecoveredErrors)> {
+    let (s, _) = skip0(input)?;
+    let (s, _) = lbracket(s)?;
+    let (s, _) = skip0(s)?;
+    let mut statements = Vec::new();
+    let mut errors = Vec::new();
+    let mut s = s;
+    while !s.is_empty() && !s.starts_with('}') {
+        let rest = match parse_statement(s) {
+            Ok((rest, stmt)) => {
+                statements.push(stmt);
+                rest
+            }
+            Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+                errors.push(recovered_error(s, &err));
+                skip_broken_statement(s)
+            }
+            Err(err) => return Err(err),
+        };
+        (s, _) = skip0(rest)?;
+    }
+    let (s, _) = rbracket(s)?;
+    Ok((s, (statements, errors)))
+}
+
+fn parse_function_definition(input: Span) -> NotLocatedParseResult<(Function, RecoveredErrors)> {
+    map(
+        tuple((parse_function_decl, skip0, cut(parse_block))),
+        |(decl, _, (body, errors))| {
+            let function = Function {
+                decl: decl.value,
+                body,
+                range: decl.range,
+            };
+            (function, errors)
+        },
+    )(input)
+}
+
+fn parse_function(input: Span) -> NotLocatedParseResult<(Located<TopLevel>, RecoveredErrors)> {
+    located_with_errors(context(
         "function",
-        map(
-            tuple((parse_function_decl, skip0, cut(parse_block))),
-            |(decl, _, body)| {
-                TopLevel::Function(Function {
-                    decl: decl.value,
-                    body,
-                })
-            },
-        ),
+        map(parse_function_definition, |(function, errors)| {
+            (TopLevel::Function(function), errors)
+        }),
     ))(input)
 }
 
+// struct Point { ... } impl { fn len(self): usize { ... } }
+fn parse_methods(input: Span) -> NotLocatedParseResult<(Vec<Function>, RecoveredErrors)> {
+    context("methods", preceded(impl_token, parse_methods_block))(input)
+}
+
+// impl Point { fn len(self): usize { ... } }
+// impl name for Type (...) と区別するため、型名の直後に { が来るものだけを読む
+fn parse_method_impl(input: Span) -> NotLocatedParseResult<(Located<TopLevel>, RecoveredErrors)> {
+    let (s, _) = peek(tuple((impl_token, parse_identifier, lbracket)))(input)?;
+    cut(located_with_errors(map(
+        pair(preceded(impl_token, parse_identifier), parse_methods_block),
+        |(target, (methods, errors))| {
+            (TopLevel::MethodImpl(MethodImpl { target, methods }), errors)
+        },
+    )))(s)
+}
+
+// メソッドごとに読み飛ばした文のエラーをまとめて返す
+fn parse_methods_block(input: Span) -> NotLocatedParseResult<(Vec<Function>, RecoveredErrors)> {
+    map(
+        delimited(lbracket, many0(parse_function_definition), rbracket),
+        |definitions| {
+            let mut errors = Vec::new();
+            let methods = definitions
+                .into_iter()
+                .map(|(method, method_errors)| {
+                    errors.extend(method_errors);
+                    method
+                })
+                .collect();
+            (methods, errors)
+        },
+    )(input)
+}
+
+#[test]
+fn test_parse_methods() {
+    let (rest, (toplevel, _)) = parse_toplevel(
+        "type Point = struct { x: i32, y: i32 } impl {
+    fn sum(self): i32 {
+        (+ self.x self.y)
+    }
+    fn scaled(self, n: i32): i32 {
+        (* self.sum() n)
+    }
+}"
+        .into(),
+    )
+    .unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    let TopLevel::TypeDef(TypeDef {
+        kind: TypeDefKind::StructLike(struct_def),
+        ..
+    }) = toplevel.value
+    else {
+        panic!("unexpected toplevel: {:?}", toplevel.value);
+    };
+    let names = struct_def.methods.iter().map(|x| x.decl.name.as_str());
+    assert_eq!(names.collect::<Vec<_>>(), ["sum", "scaled"]);
+    // selfは型を書かない
+    assert!(matches!(
+        &struct_def.methods[1].decl.args[0],
+        Argument::Normal { name, ty, .. } if name.value == "self" && ty.value == UnresolvedType::Infer
+    ));
+
+    let (_, (toplevel, _)) =
+        parse_toplevel("impl Point { fn x(self): i32 { self.x } }".into()).unwrap();
+    assert!(matches!(
+        toplevel.value,
+        TopLevel::MethodImpl(MethodImpl { target, methods }) if target == "Point" && methods.len() == 1
+    ));
+}
+
 fn parse_interface(input: Span) -> ParseResult<TopLevel> {
     let (s, _) = peek(interface_token)(input)?;
     cut(located(context(
@@ -221,9 +496,9 @@ fn parse_interface_invalid_input() {
     .is_ok());
 }
 
-fn parse_impl(input: Span) -> ParseResult<TopLevel> {
+fn parse_impl(input: Span) -> NotLocatedParseResult<(Located<TopLevel>, RecoveredErrors)> {
     let (s, _) = peek(impl_token)(input)?;
-    cut(located(context(
+    cut(located_with_errors(context(
         "implementation",
         map(
             tuple((
@@ -237,8 +512,18 @@ fn parse_impl(input: Span) -> ParseResult<TopLevel> {
                 preceded(colon, parse_type),
                 parse_block,
             )),
-            |(alloc_mode, _, name, generic_args, _, target_ty, args, return_type, body)| {
-                TopLevel::Implemantation(Implementation {
+            |(
+                alloc_mode,
+                _,
+                name,
+                generic_args,
+                _,
+                target_ty,
+                args,
+                return_type,
+                (body, errors),
+            )| {
+                let implementation = TopLevel::Implemantation(Implementation {
                     decl: ImplementationDecl {
                         alloc_mode,
                         name,
@@ -248,7 +533,8 @@ fn parse_impl(input: Span) -> ParseResult<TopLevel> {
                         return_type,
                     },
                     body,
-                })
+                });
+                (implementation, errors)
             },
         ),
     )))(s)
@@ -275,12 +561,16 @@ fn parse_fields(input: Span) -> NotLocatedParseResult<Vec<(String, Located<Unres
     Ok((rest, fields))
 }
 
-fn parse_struct(input: Span) -> ParseResult<TopLevel> {
-    let (s, _) = peek(alt((struct_token, record_token)))(input)?;
+fn parse_struct(input: Span) -> NotLocatedParseResult<(Located<TopLevel>, RecoveredErrors)> {
+    let (s, _) = peek(preceded(
+        parse_visibility,
+        alt((struct_token, record_token)),
+    ))(input)?;
     context(
         "struct",
-        cut(located(map(
+        cut(located_with_errors(map(
             tuple((
+                parse_visibility,
                 alt((
                     map(struct_token, |_| StructKind::Struct),
                     map(record_token, |_| StructKind::Record),
@@ -288,16 +578,21 @@ fn parse_struct(input: Span) -> ParseResult<TopLevel> {
                 parse_identifier,
                 opt(parse_generic_argument_decls),
                 delimited(lbracket, parse_fields, rbracket),
+                opt(parse_methods),
             )),
-            |(struct_kind, name, generic_args, fields)| {
-                TopLevel::TypeDef(TypeDef {
+            |(visibility, struct_kind, name, generic_args, fields, methods)| {
+                let (methods, errors) = methods.unwrap_or_default();
+                let type_def = TopLevel::TypeDef(TypeDef {
+                    visibility,
                     kind: TypeDefKind::StructLike(StructLikeTypeDef {
                         struct_kind,
                         generic_args,
                         fields,
+                        methods,
                     }),
                     name,
-                })
+                });
+                (type_def, errors)
             },
         ))),
     )(s)
@@ -305,26 +600,267 @@ fn parse_struct(input: Span) -> ParseResult<TopLevel> {
 
 #[test]
 fn test_parse_struct() {
+    let (_, (toplevel, _)) = parse_toplevel("struct Vec<T> { size: i32, data: T }".into()).unwrap();
     assert!(matches!(
-        parse_toplevel("struct Vec<T> { size: i32, data: T }".into())
-            .unwrap()
-            .1
-            .value,
+        toplevel.value,
         TopLevel::TypeDef(TypeDef {
+            visibility: Visibility::Private,
             name: _,
             kind: TypeDefKind::StructLike(StructLikeTypeDef {
                 struct_kind: StructKind::Struct,
                 generic_args: _,
-                fields: _
+                fields: _,
+                methods: _,
             })
         })
     ))
 }
 
-pub(crate) fn parse_toplevel(input: Span) -> ParseResult<TopLevel> {
+fn parse_enum_variant(input: Span) -> NotLocatedParseResult<(String, Option<i64>)> {
+    tuple((
+        parse_identifier,
+        opt(preceded(tuple((eq_token, skip0)), i64)),
+    ))(input)
+}
+
+// type Color = enum { Red, Green = 5, Blue }
+// type Point = struct { x: i32, y: i32 }
+// type MyInt = u64
+fn parse_type_def(input: Span) -> NotLocatedParseResult<(Located<TopLevel>, RecoveredErrors)> {
+    let (s, _) = peek(preceded(parse_visibility, type_token))(input)?;
+    context(
+        "type",
+        cut(located_with_errors(|s| {
+            let (s, (visibility, _, name, _)) =
+                tuple((parse_visibility, type_token, parse_identifier, eq_token))(s)?;
+            let result = alt((
+                map(
+                    preceded(
+                        enum_token,
+                        delimited(lbracket, many0(parse_enum_variant), rbracket),
+                    ),
+                    |variants| {
+                        without_errors(TopLevel::TypeDef(TypeDef {
+                            visibility,
+                            name: name.clone(),
+                            kind: TypeDefKind::Enum(EnumTypeDef { variants }),
+                        }))
+                    },
+                ),
+                // struct Point { ... } と同じ定義になる
+                map(
+                    tuple((
+                        alt((
+                            map(struct_token, |_| StructKind::Struct),
+                            map(record_token, |_| StructKind::Record),
+                        )),
+                        delimited(lbracket, parse_fields, rbracket),
+                        opt(parse_methods),
+                    )),
+                    |(struct_kind, fields, methods)| {
+                        let (methods, errors) = methods.unwrap_or_default();
+                        let type_def = TopLevel::TypeDef(TypeDef {
+                            visibility,
+                            name: name.clone(),
+                            kind: TypeDefKind::StructLike(StructLikeTypeDef {
+                                struct_kind,
+                                generic_args: None,
+                                fields,
+                                methods,
+                            }),
+                        });
+                        (type_def, errors)
+                    },
+                ),
+                map(parse_type, |target| {
+                    without_errors(TopLevel::TypeAlias(TypeAlias {
+                        visibility,
+                        name: name.clone(),
+                        target,
+                    }))
+                }),
+            ))(s);
+            result
+        })),
+    )(s)
+}
+
+#[test]
+fn test_parse_enum() {
+    let (rest, (toplevel, _)) =
+        parse_toplevel("type Color = enum { Red, Green = 5, Blue, Black = -1, }".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(
+        toplevel.value,
+        TopLevel::TypeDef(TypeDef {
+            visibility: Visibility::Private,
+            name: "Color".into(),
+            kind: TypeDefKind::Enum(EnumTypeDef {
+                variants: vec![
+                    ("Red".into(), None),
+                    ("Green".into(), Some(5)),
+                    ("Blue".into(), None),
+                    ("Black".into(), Some(-1)),
+                ]
+            }),
+        })
+    );
+}
+
+#[test]
+fn test_parse_struct_type_def() {
+    let (rest, (toplevel, _)) =
+        parse_toplevel("type Line = struct { start: Point, end: Point }".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match toplevel.value {
+        TopLevel::TypeDef(TypeDef {
+            name,
+            kind: TypeDefKind::StructLike(struct_def),
+            ..
+        }) => {
+            assert_eq!(name, "Line");
+            assert_eq!(struct_def.struct_kind, StructKind::Struct);
+            let field_names = struct_def
+                .fields
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>();
+            assert_eq!(field_names, vec!["start", "end"]);
+        }
+        _ => panic!("unexpected toplevel"),
+    }
+}
+
+#[test]
+fn test_parse_type_alias() {
+    let (rest, (toplevel, _)) = parse_toplevel("type Bytes = *u8".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match toplevel.value {
+        TopLevel::TypeAlias(alias) => {
+            assert_eq!(alias.name, "Bytes");
+            assert!(matches!(alias.target.value, UnresolvedType::Ptr(_)));
+        }
+        _ => panic!("unexpected toplevel"),
+    }
+}
+
+// (:= counter: i32 0) のように関数の中と同じ構文で宣言する
+fn parse_global_variables(input: Span) -> ParseResult<TopLevel> {
+    context(
+        "global_variable",
+        located(map(parse_variable_decls, TopLevel::GlobalVariables)),
+    )(input)
+}
+
+#[test]
+fn test_parse_global_variables() {
+    let (rest, (toplevel, _)) = parse_toplevel("(:= counter: i64 0 limit 10)".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match toplevel.value {
+        TopLevel::GlobalVariables(decls) => {
+            assert_eq!(decls.decls.len(), 2);
+            assert_eq!(decls.decls[0].value.name, "counter");
+            assert!(decls.decls[0].value.ty.is_some());
+            assert_eq!(decls.decls[1].value.name, "limit");
+        }
+        _ => panic!("unexpected toplevel"),
+    }
+}
+
+fn parse_const(input: Span) -> ParseResult<TopLevel> {
+    context(
+        "const",
+        located(map(
+            delimited(
+                lparen,
+                preceded(
+                    const_token,
+                    cut(tuple((
+                        parse_identifier,
+                        opt(preceded(colon, parse_type)),
+                        parse_boxed_expression,
+                    ))),
+                ),
+                rparen,
+            ),
+            |(name, ty, value)| TopLevel::Const(ConstDecl { name, ty, value }),
+        )),
+    )(input)
+}
+
+#[test]
+fn test_parse_const() {
+    let (rest, (toplevel, _)) = parse_toplevel("(const SIZE: usize (* 4 2))".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    match toplevel.value {
+        TopLevel::Const(const_decl) => {
+            assert_eq!(const_decl.name, "SIZE");
+            assert!(const_decl.ty.is_some());
+            assert!(matches!(*const_decl.value.value, Expression::Binary(_)));
+        }
+        _ => panic!("unexpected toplevel"),
+    }
+}
+
+fn parse_import(input: Span) -> ParseResult<TopLevel> {
+    context(
+        "import",
+        located(map(
+            delimited(lparen, preceded(import_token, cut(parse_string)), rparen),
+            |path| TopLevel::Import(Import { path }),
+        )),
+    )(input)
+}
+
+#[test]
+fn test_parse_import() {
+    let (rest, (toplevel, _)) = parse_toplevel("(import \"lib/math.hr\")".into()).unwrap();
+    assert_eq!(rest.to_string().as_str(), "");
+    assert_eq!(
+        toplevel.value,
+        TopLevel::Import(Import {
+            path: "lib/math.hr".into()
+        })
+    );
+}
+
+#[test]
+fn test_parse_visibility() {
+    let (_, (toplevel, _)) = parse_toplevel("pub fn f(): void {}".into()).unwrap();
+    assert!(
+        matches!(&toplevel.value, TopLevel::Function(func) if func.decl.visibility == Visibility::Public)
+    );
+    let (_, (toplevel, _)) = parse_toplevel("fn f(): void {}".into()).unwrap();
+    assert!(
+        matches!(&toplevel.value, TopLevel::Function(func) if func.decl.visibility == Visibility::Private)
+    );
+    let (_, (toplevel, _)) = parse_toplevel("pub struct A { v: i32 }".into()).unwrap();
+    assert!(
+        matches!(&toplevel.value, TopLevel::TypeDef(type_def) if type_def.visibility == Visibility::Public)
+    );
+    let (_, (toplevel, _)) = parse_toplevel("pub type Id = u64".into()).unwrap();
+    assert!(
+        matches!(&toplevel.value, TopLevel::TypeAlias(alias) if alias.visibility == Visibility::Public)
+    );
+}
+
+// トップレベルの要素と、その中の関数の本体で読み飛ばした文のエラー
+pub(crate) fn parse_toplevel(
+    input: Span,
+) -> NotLocatedParseResult<(Located<TopLevel>, RecoveredErrors)> {
     context(
         "toplevel",
-        alt((parse_function, parse_struct, parse_interface, parse_impl)),
+        alt((
+            parse_function,
+            parse_struct,
+            parse_type_def,
+            map(parse_interface, without_errors),
+            parse_method_impl,
+            parse_impl,
+            map(parse_const, without_errors),
+            map(parse_import, without_errors),
+            map(parse_global_variables, without_errors),
+        )),
     )(input)
 }
 
@@ -354,17 +890,17 @@ fn print-i32(s: *u8, n: i32): void {
 
 #[test]
 fn test_parse_record() {
+    let (_, (toplevel, _)) = parse_toplevel(r#"record A { v: i32 }"#.into()).unwrap();
     assert!(matches!(
-        parse_toplevel(r#"record A { v: i32 }"#.into())
-            .unwrap()
-            .1
-            .value,
+        toplevel.value,
         TopLevel::TypeDef(TypeDef {
+            visibility: Visibility::Private,
             name: _,
             kind: TypeDefKind::StructLike(StructLikeTypeDef {
                 struct_kind: StructKind::Record,
                 generic_args: _,
-                fields: _
+                fields: _,
+                methods: _,
             })
         })
     ))
@@ -1,10 +1,11 @@
 use itertools::Itertools;
 use nom::{
     branch::alt,
+    character::complete::i64,
     combinator::opt,
     error::context,
-    multi::separated_list1,
-    sequence::{pair, preceded},
+    multi::{many0, many1, separated_list1},
+    sequence::{pair, preceded, tuple},
 };
 
 use crate::ast::*;
@@ -109,6 +110,94 @@ fn parse_ptr(input: Span) -> ParseResult<UnresolvedType> {
     }))(input)
 }
 
+// [u8; 16]
+fn parse_array(input: Span) -> ParseResult<UnresolvedType> {
+    located(map(
+        delimited(
+            lsqrbracket,
+            tuple((parse_type, semicolon, preceded(skip0, i64))),
+            rsqrbracket,
+        ),
+        |(ty, _, len)| UnresolvedType::Array(Box::new(ty), len),
+    ))(input)
+}
+
+#[test]
+fn test_parse_array_type() {
+    let (rest, ty) = parse_type(Span::new("[u8; 16],")).unwrap();
+    assert_eq!(rest.to_string().as_str(), ",");
+    let UnresolvedType::Array(inner, len) = ty.value else {
+        panic!("unexpected type: {:?}", ty.value);
+    };
+    assert_eq!(len, 16);
+    assert_eq!(
+        inner.value,
+        UnresolvedType::TypeRef(TypeRef {
+            name: "u8".into(),
+            generic_args: None
+        })
+    );
+}
+
+// fn(i32, i32): i32
+fn parse_fn_ptr(input: Span) -> ParseResult<UnresolvedType> {
+    located(map(
+        tuple((
+            fn_token,
+            lparen,
+            many0(parse_type),
+            rparen,
+            colon,
+            parse_type,
+        )),
+        |(_, _, params, _, _, return_type)| UnresolvedType::FnPtr(params, Box::new(return_type)),
+    ))(input)
+}
+
+#[test]
+fn test_parse_fn_ptr_type() {
+    let (rest, ty) = parse_type(Span::new("fn(i32, *u8): void,")).unwrap();
+    assert_eq!(rest.to_string().as_str(), ",");
+    let UnresolvedType::FnPtr(params, return_type) = ty.value else {
+        panic!("unexpected type: {:?}", ty.value);
+    };
+    assert_eq!(
+        params.iter().map(|x| x.value.to_string()).collect_vec(),
+        ["i32", "[u8]"]
+    );
+    assert_eq!(return_type.value.to_string(), "void");
+
+    // 引数のない関数ポインタと、関数ポインタを返す関数ポインタ
+    let (rest, ty) = parse_type(Span::new("fn(): fn(): i32)")).unwrap();
+    assert_eq!(rest.to_string().as_str(), ")");
+    assert_eq!(ty.value.to_string(), "fn(): fn(): i32");
+}
+
+// (i32, u8)。要素は2つ以上
+fn parse_tuple_type(input: Span) -> ParseResult<UnresolvedType> {
+    located(map(
+        delimited(lparen, pair(parse_type, many1(parse_type)), rparen),
+        |(first, rest)| {
+            let mut elements = vec![first];
+            elements.extend(rest);
+            UnresolvedType::Tuple(elements)
+        },
+    ))(input)
+}
+
+#[test]
+fn test_parse_tuple_type() {
+    let (rest, ty) = parse_type(Span::new("(i32, *u8, (bool, u8)) 1")).unwrap();
+    assert_eq!(rest.to_string().as_str(), " 1");
+    let UnresolvedType::Tuple(elements) = &ty.value else {
+        panic!("unexpected type: {:?}", ty.value);
+    };
+    assert_eq!(elements.len(), 3);
+    assert_eq!(ty.value.to_string(), "(i32, [u8], (bool, u8))");
+    // 要素が1つの括弧はタプルではない
+    assert!(parse_type(Span::new("(i32)")).is_err());
+}
+
 fn parse_typeref(input: Span) -> ParseResult<UnresolvedType> {
     located(map(
         pair(parse_identifier, opt(parse_generic_arguments)),
@@ -122,7 +211,17 @@ fn parse_typeref(input: Span) -> ParseResult<UnresolvedType> {
 }
 
 pub(super) fn parse_type(input: Span) -> ParseResult<UnresolvedType> {
-    context("type", alt((parse_infer, parse_ptr, parse_typeref)))(input)
+    context(
+        "type",
+        alt((
+            parse_infer,
+            parse_ptr,
+            parse_array,
+            parse_fn_ptr,
+            parse_tuple_type,
+            parse_typeref,
+        )),
+    )(input)
 }
 
 #[test]
@@ -4,14 +4,14 @@ use super::{expression::parse_boxed_expression, token::*, *};
 use nom::{
     branch::alt,
     bytes::complete::{tag, take_till},
-    character::complete::{line_ending, multispace1},
+    character::complete::{anychar, digit1, line_ending, multispace1},
     combinator::eof,
     multi::many0,
     sequence::{preceded, tuple},
     Parser,
 };
 
-fn comment(s: Span<'_>) -> IResult<Span<'_>, (), VerboseError<Span<'_>>> {
+pub(super) fn comment(s: Span<'_>) -> IResult<Span<'_>, (), VerboseError<Span<'_>>> {
     map(
         tuple((
             tag("//"),
@@ -22,10 +22,48 @@ fn comment(s: Span<'_>) -> IResult<Span<'_>, (), VerboseError<Span<'_>>> {
     )(s)
 }
 
+// /* */ は入れ子にできる。閉じられていない場合はエラーにする
+pub(super) fn block_comment(s: Span<'_>) -> IResult<Span<'_>, (), VerboseError<Span<'_>>> {
+    let (mut rest, _) = tag("/*")(s)?;
+    let mut depth = 1;
+    while depth > 0 {
+        if let Ok((next, _)) = tag::<_, _, VerboseError<Span>>("/*")(rest) {
+            depth += 1;
+            rest = next;
+        } else if let Ok((next, _)) = tag::<_, _, VerboseError<Span>>("*/")(rest) {
+            depth -= 1;
+            rest = next;
+        } else if let Ok((next, _)) = anychar::<_, VerboseError<Span>>(rest) {
+            rest = next;
+        } else {
+            return Err(nom::Err::Failure(VerboseError {
+                errors: vec![(
+                    s,
+                    nom::error::VerboseErrorKind::Context("unterminated block comment"),
+                )],
+            }));
+        }
+    }
+    Ok((rest, ()))
+}
+
+#[test]
+fn test_block_comment() {
+    let (rest, _) = skip0(Span::new("/* a\n b\n */ /* c /* d */ e */ // f\nx")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "x");
+    let (rest, _) = block_comment(Span::new("/**/x")).unwrap();
+    assert_eq!(rest.to_string().as_str(), "x");
+    assert!(matches!(
+        block_comment(Span::new("/* a /* b */")),
+        Err(nom::Err::Failure(_))
+    ));
+}
+
 pub(super) fn skip0(input: Span<'_>) -> IResult<Span<'_>, (), VerboseError<Span<'_>>> {
     map(
         many0(alt((
             comment,
+            block_comment,
             map(tag(","), |_| ()),
             map(multispace1, |_| ()),
         ))),
@@ -35,7 +73,12 @@ pub(super) fn skip0(input: Span<'_>) -> IResult<Span<'_>, (), VerboseError<Span<
 
 pub(super) fn skip1(input: Span<'_>) -> IResult<Span<'_>, (), VerboseError<Span<'_>>> {
     map(
-        many1(alt((comment, map(comma, |_| ()), map(multispace1, |_| ())))),
+        many1(alt((
+            comment,
+            block_comment,
+            map(comma, |_| ()),
+            map(multispace1, |_| ()),
+        ))),
         |_| (),
     )(input)
 }
@@ -69,10 +112,38 @@ pub(super) fn located<'a, O>(
     }
 }
 
+// 読み飛ばした文のエラーと一緒に値を返すparserに位置を付ける。エラーは位置の外に出す
+pub(super) fn located_with_errors<'a, O>(
+    parser: impl Parser<Span<'a>, (O, RecoveredErrors), VerboseError<Span<'a>>>,
+) -> impl FnMut(Span<'a>) -> NotLocatedParseResult<'a, (Located<O>, RecoveredErrors)> {
+    map(located(parser), |located| {
+        let (value, errors) = located.value;
+        (
+            Located {
+                range: located.range,
+                value,
+            },
+            errors,
+        )
+    })
+}
+
+// 読み飛ばす文を持たない構文の結果を、located_with_errorsの結果と同じ形にする
+pub(super) fn without_errors<T>(value: T) -> (T, RecoveredErrors) {
+    (value, Vec::new())
+}
+
 pub(super) fn index_access(input: Span<'_>) -> ParseResult<Box<Expression>> {
     delimited(lsqrbracket, parse_boxed_expression, rsqrbracket)(input)
 }
 
+// タプルの要素は t.0 のように番号で参照する
 pub(super) fn field_access(input: Span<'_>) -> NotLocatedParseResult<String> {
-    preceded(dot, parse_identifier)(input)
+    preceded(
+        dot,
+        alt((
+            parse_identifier,
+            map(digit1, |digits: Span| digits.to_string()),
+        )),
+    )(input)
 }
@@ -1,12 +1,17 @@
-use std::fmt::{Display, Write};
+use std::{
+    fmt::{Display, Write},
+    hash::{Hash, Hasher},
+};
 
 use crate::{
-    ast::{BinaryOp, MultiOp, UnaryOp},
+    ast::{BinaryOp, MultiOp, Range, UnaryOp},
     common::{typename::*, AllocMode},
     concrete_ast::ConcreteType,
 };
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+// ポインタの先で自身を参照するフィールドは、fieldsを持たない型として解決されるので、同じstructかどうかは名前だけで決める。
+// nameはジェネリクスの引数を含むので、名前が同じなら同じ型になる
+#[derive(Debug, Clone)]
 pub struct ResolvedStructType {
     pub name: String,
     pub non_generic_name: String,
@@ -14,6 +19,27 @@ pub struct ResolvedStructType {
     pub generic_args: Option<Vec<ResolvedType>>,
 }
 
+impl ResolvedStructType {
+    // タプルから作ったstructか。structの名前は括弧で始まらない
+    pub fn is_tuple(&self) -> bool {
+        self.name.starts_with('(')
+    }
+}
+
+impl PartialEq for ResolvedStructType {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for ResolvedStructType {}
+
+impl Hash for ResolvedStructType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct InterfaceRestriction {
     pub name: String,
@@ -32,14 +58,22 @@ pub struct ResolvedGenericType {
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum ResolvedType {
+    I8,
+    I16,
     I32,
     I64,
+    U16,
     U32,
     U64,
     USize,
     U8,
+    F32,
+    F64,
     Bool,
     Ptr(Box<ResolvedType>),
+    Array(Box<ResolvedType>, usize),
+    // 引数の型と戻り値の型
+    FnPtr(Vec<ResolvedType>, Box<ResolvedType>),
     Void,
     Unknown,
     StructLike(ResolvedStructType),
@@ -49,13 +83,20 @@ pub enum ResolvedType {
 impl ResolvedType {
     pub fn is_integer_type(&self) -> bool {
         match self {
+            ResolvedType::I8 => true,
+            ResolvedType::I16 => true,
             ResolvedType::I32 => true,
             ResolvedType::USize => true,
             ResolvedType::U8 => true,
+            ResolvedType::U16 => true,
             ResolvedType::U32 => true,
             ResolvedType::I64 => true,
             ResolvedType::U64 => true,
+            ResolvedType::F32 => false,
+            ResolvedType::F64 => false,
             ResolvedType::Ptr(_) => false,
+            ResolvedType::Array(_, _) => false,
+            ResolvedType::FnPtr(_, _) => false,
             ResolvedType::Void => false,
             ResolvedType::Unknown => false,
             ResolvedType::StructLike(_) => false,
@@ -63,6 +104,25 @@ impl ResolvedType {
             ResolvedType::Generics(_) => false,
         }
     }
+    pub fn is_float_type(&self) -> bool {
+        matches!(self, ResolvedType::F32 | ResolvedType::F64)
+    }
+    pub fn integer_bit_width(&self, is_64_bit: bool) -> Option<u32> {
+        match self {
+            ResolvedType::I32 | ResolvedType::U32 => Some(32),
+            ResolvedType::I64 | ResolvedType::U64 => Some(64),
+            ResolvedType::USize => Some(if is_64_bit { 64 } else { 32 }),
+            ResolvedType::I8 | ResolvedType::U8 => Some(8),
+            ResolvedType::I16 | ResolvedType::U16 => Some(16),
+            _ => None,
+        }
+    }
+    pub fn is_signed_integer_type(&self) -> bool {
+        matches!(
+            self,
+            ResolvedType::I8 | ResolvedType::I16 | ResolvedType::I32 | ResolvedType::I64
+        )
+    }
     pub fn is_pointer_type(&self) -> bool {
         if let ResolvedType::Ptr(_) = self {
             true
@@ -71,7 +131,7 @@ impl ResolvedType {
         }
     }
     pub fn can_insert(&self, other: &ResolvedType) -> bool {
-        // void* には任意のポインタ型を代入できる
+        // void* とnullには任意のポインタ型を代入できる
         {
             if let ResolvedType::Ptr(pointee_type) = self {
                 if ResolvedType::Void == **pointee_type {
@@ -79,7 +139,7 @@ impl ResolvedType {
                 }
             }
             if let ResolvedType::Ptr(pointee_type) = other {
-                if ResolvedType::Void == **pointee_type {
+                if let ResolvedType::Void | ResolvedType::Unknown = **pointee_type {
                     return self.is_pointer_type();
                 }
             }
@@ -89,8 +149,11 @@ impl ResolvedType {
     }
     pub fn unwrap_primitive_into_concrete_type(&self, is_64_bit: bool) -> ConcreteType {
         match self {
+            ResolvedType::I8 => ConcreteType::I8,
+            ResolvedType::I16 => ConcreteType::I16,
             ResolvedType::I32 => ConcreteType::I32,
             ResolvedType::I64 => ConcreteType::I64,
+            ResolvedType::U16 => ConcreteType::U16,
             ResolvedType::U32 => ConcreteType::U32,
             ResolvedType::U64 => ConcreteType::U64,
             ResolvedType::USize => {
@@ -101,6 +164,8 @@ impl ResolvedType {
                 }
             }
             ResolvedType::U8 => ConcreteType::U8,
+            ResolvedType::F32 => ConcreteType::F32,
+            ResolvedType::F64 => ConcreteType::F64,
             ResolvedType::Bool => ConcreteType::Bool,
             ResolvedType::Ptr(inner) => ConcreteType::Ptr(Box::new(
                 (*inner).unwrap_primitive_into_concrete_type(is_64_bit),
@@ -128,17 +193,37 @@ impl Display for ResolvedType {
                 f,
                 "{}",
                 match self {
+                    ResolvedType::I8 => I8_TYPE_NAME,
+                    ResolvedType::I16 => I16_TYPE_NAME,
                     ResolvedType::I32 => I32_TYPE_NAME,
                     ResolvedType::I64 => I64_TYPE_NAME,
+                    ResolvedType::U16 => U16_TYPE_NAME,
                     ResolvedType::U32 => U32_TYPE_NAME,
                     ResolvedType::U64 => U64_TYPE_NAME,
                     ResolvedType::USize => USIZE_TYPE_NAME,
                     ResolvedType::U8 => U8_TYPE_NAME,
+                    ResolvedType::F32 => F32_TYPE_NAME,
+                    ResolvedType::F64 => F64_TYPE_NAME,
                     ResolvedType::Bool => BOOL_TYPE_NAME,
                     ResolvedType::Void => VOID_TYPE_NAME,
                     ResolvedType::Ptr(inner) => {
                         return write!(f, "*{}", inner);
                     }
+                    ResolvedType::Array(inner, len) => {
+                        return write!(f, "[{}; {}]", inner, len);
+                    }
+                    ResolvedType::FnPtr(params, return_type) => {
+                        return write!(
+                            f,
+                            "fn({}): {}",
+                            params
+                                .iter()
+                                .map(|x| x.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                            return_type
+                        );
+                    }
                     ResolvedType::Unknown => UNKNOWN_TYPE_NAME,
                     ResolvedType::StructLike(ResolvedStructType {
                         name,
@@ -167,6 +252,19 @@ pub struct CallExpr {
     pub generic_args: Option<Vec<ResolvedType>>,
 }
 
+// 関数ポインタを値に持つ式を呼び出す
+#[derive(Debug, Clone)]
+pub struct IndirectCallExpr {
+    pub callee: Box<ResolvedExpression>,
+    pub args: Vec<ResolvedExpression>,
+}
+
+// &fで得られる関数のアドレス
+#[derive(Debug, Clone)]
+pub struct FunctionRefExpr {
+    pub name: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct VariableRefExpr {
     pub name: String,
@@ -178,6 +276,11 @@ pub struct NumberLiteral {
     pub value: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct FloatLiteral {
+    pub value: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct StringLiteral {
     pub value: String,
@@ -193,6 +296,11 @@ pub struct StructLiteral {
     pub fields: Vec<(String, ResolvedExpression)>,
 }
 
+#[derive(Debug, Clone)]
+pub struct ArrayFill {
+    pub value: Box<ResolvedExpression>,
+}
+
 #[derive(Debug, Clone)]
 pub struct BinaryExpr {
     pub op: BinaryOp,
@@ -217,6 +325,17 @@ pub struct DerefExpr {
     pub target: Box<ResolvedExpression>,
 }
 
+// 変換先の型は式の型として持つ
+#[derive(Debug, Clone)]
+pub struct CastExpr {
+    pub target: Box<ResolvedExpression>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AddressOfExpr {
+    pub target: Box<ResolvedExpression>,
+}
+
 #[derive(Debug, Clone)]
 pub struct IndexAccessExpr {
     pub target: Box<ResolvedExpression>,
@@ -242,23 +361,52 @@ pub struct WhenExpr {
     pub then: Box<ResolvedExpression>,
 }
 
+#[derive(Debug, Clone)]
+pub struct WhileExpr {
+    pub cond: Box<ResolvedExpression>,
+    pub body: Vec<ResolvedExpression>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForExpr {
+    pub init: Option<Box<ResolvedExpression>>,
+    pub cond: Option<Box<ResolvedExpression>>,
+    pub step: Option<Box<ResolvedExpression>>,
+    pub body: Vec<ResolvedExpression>,
+}
+
 #[derive(Debug, Clone)]
 pub enum ExpressionKind {
     SizeOf(ResolvedType),
+    // 読み出す型は式の型と同じ
+    VaArg,
     VariableRef(VariableRefExpr),
     NumberLiteral(NumberLiteral),
+    FloatLiteral(FloatLiteral),
     StringLiteral(StringLiteral),
     StructLiteral(StructLiteral),
+    // 配列の全要素を同じ値で初期化する。(:= a: [u8; 4] 0) の右辺
+    ArrayFill(ArrayFill),
     BoolLiteral(BoolLiteral),
+    // 型は代入先のポインタ型。決まらなければ*unknownになる
+    NullLiteral,
     Binary(BinaryExpr),
     Unary(UnaryExpr),
     Multi(MultiExpr),
     CallExpr(CallExpr),
+    IndirectCall(IndirectCallExpr),
+    FunctionRef(FunctionRefExpr),
     Deref(DerefExpr),
+    AddressOf(AddressOfExpr),
+    Cast(CastExpr),
     IndexAccess(IndexAccessExpr),
     FieldAccess(FieldAccessExpr),
     If(IfExpr),
     When(WhenExpr),
+    While(WhileExpr),
+    For(ForExpr),
+    Break,
+    Continue,
     VariableDecls(VariableDecls),
     Assignment(Assignment),
     Unknown,
@@ -268,6 +416,8 @@ pub enum ExpressionKind {
 pub struct ResolvedExpression {
     pub ty: ResolvedType,
     pub kind: ExpressionKind,
+    // 元になった式の位置。診断とデバッグ情報に使う
+    pub range: Range,
 }
 
 #[derive(Debug, Clone)]
@@ -276,6 +426,8 @@ pub struct Assignment {
     pub value: Box<ResolvedExpression>,
     pub deref_count: usize,
     pub index_access: Option<Box<ResolvedExpression>>,
+    // インデックスで辿る先が配列なら、ポインタをロードせずに変数の領域を直接指す
+    pub indexes_array: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -292,17 +444,99 @@ pub struct VariableDecls {
 #[derive(Debug, Clone)]
 pub struct Return {
     pub expression: Option<ResolvedExpression>,
+    pub range: Range,
 }
 
 #[derive(Debug, Clone)]
 pub struct Effect {
     pub expression: ResolvedExpression,
+    pub range: Range,
+}
+
+// caseのラベルは、scrutineeと同じ型の整数リテラルに畳み込まれている
+#[derive(Debug, Clone)]
+pub struct Switch {
+    pub scrutinee: ResolvedExpression,
+    pub cases: Vec<(ResolvedExpression, Vec<Statement>)>,
+    pub default: Option<Vec<Statement>>,
+    pub range: Range,
+}
+
+#[derive(Debug, Clone)]
+pub struct DoWhile {
+    pub body: Vec<Statement>,
+    pub cond: ResolvedExpression,
+    pub range: Range,
 }
 
 #[derive(Debug, Clone)]
 pub enum Statement {
     Return(Return),
     Effect(Effect),
+    Block(Vec<Statement>),
+    Switch(Switch),
+    DoWhile(DoWhile),
+}
+
+impl Statement {
+    // この文を実行すると必ず関数から戻るか
+    pub fn always_returns(&self) -> bool {
+        match self {
+            Statement::Return(_) => true,
+            Statement::Effect(_) => false,
+            // do-whileの本体は少なくとも1回は実行される
+            Statement::Block(statements)
+            | Statement::DoWhile(DoWhile {
+                body: statements, ..
+            }) => statements.last().is_some_and(Statement::always_returns),
+            // defaultがなければ、どのcaseにも当てはまらずに抜けることがある
+            Statement::Switch(switch) => {
+                let returns = |statements: &Vec<Statement>| {
+                    statements.last().is_some_and(Statement::always_returns)
+                };
+                switch.default.as_ref().is_some_and(returns)
+                    && switch
+                        .cases
+                        .iter()
+                        .all(|(_, statements)| returns(statements))
+            }
+        }
+    }
+
+    // 関数の最後に実行されるReturn
+    pub fn final_return(&self) -> Option<&Return> {
+        match self {
+            Statement::Return(ret) => Some(ret),
+            Statement::Effect(_) | Statement::Switch(_) | Statement::DoWhile(_) => None,
+            Statement::Block(statements) => statements.last().and_then(Statement::final_return),
+        }
+    }
+
+    // この文の中にあるReturnをすべて集める
+    pub fn collect_returns<'a>(&'a self, returns: &mut Vec<&'a Return>) {
+        match self {
+            Statement::Return(ret) => returns.push(ret),
+            Statement::Effect(_) => {}
+            Statement::Block(statements)
+            | Statement::DoWhile(DoWhile {
+                body: statements, ..
+            }) => {
+                for statement in statements {
+                    statement.collect_returns(returns);
+                }
+            }
+            Statement::Switch(switch) => {
+                for statement in switch
+                    .cases
+                    .iter()
+                    .flat_map(|(_, statements)| statements)
+                    .chain(switch.default.iter().flatten())
+                {
+                    statement.collect_returns(returns);
+                }
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -322,6 +556,8 @@ pub struct FunctionDecl {
 pub struct Function {
     pub decl: FunctionDecl,
     pub body: Vec<Statement>,
+    // デバッグ情報に使う宣言部分の位置
+    pub range: Range,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -348,11 +584,19 @@ pub struct Interface {
     pub return_type: ResolvedType,
 }
 
+// 初期値は定数式であることがresolverで保証されている
+#[derive(Debug, Clone)]
+pub struct GlobalVariable {
+    pub name: String,
+    pub value: ResolvedExpression,
+}
+
 #[derive(Debug, Clone)]
 pub enum TopLevel {
     Function(Function),
     Implemantation(Implementation),
     Interface(Interface),
+    GlobalVariable(GlobalVariable),
 }
 
 #[derive(Debug)]
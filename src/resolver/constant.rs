@@ -0,0 +1,449 @@
+use std::cell::Cell;
+
+use crate::{
+    ast::{BinaryOp, MultiOp, UnaryOp},
+    common::binary::get_cast_type,
+    resolved_ast::{
+        BoolLiteral, ExpressionKind, FloatLiteral, NumberLiteral, ResolvedExpression, ResolvedType,
+    },
+};
+
+use super::{
+    error::{CompileWarning, CompileWarningKind},
+    ResolverContext,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ConstantValue {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+}
+
+impl ConstantValue {
+    fn as_f64(self) -> Option<f64> {
+        match self {
+            ConstantValue::Int(n) => Some(n as f64),
+            ConstantValue::Float(n) => Some(n),
+            ConstantValue::Bool(_) => None,
+        }
+    }
+}
+
+// 型のビット幅で切り詰め、実行時と同じようにラップアラウンドさせる
+fn wrap_int(context: &ResolverContext, n: i128, ty: &ResolvedType) -> i128 {
+    let Some(width) = ty.integer_bit_width(context.is_64_bit()) else {
+        return n;
+    };
+    let shift = 128 - width;
+    if ty.is_signed_integer_type() {
+        (n << shift) >> shift
+    } else {
+        ((n as u128) << shift >> shift) as i128
+    }
+}
+
+// 四則演算の結果が型に収まらなければ、ラップアラウンドした上でoverflowedを立てる
+fn wrap_arithmetic(
+    context: &ResolverContext,
+    n: i128,
+    ty: &ResolvedType,
+    overflowed: &Cell<bool>,
+) -> i128 {
+    let wrapped = wrap_int(context, n, ty);
+    if wrapped != n {
+        overflowed.set(true);
+    }
+    wrapped
+}
+
+fn fold_binary(
+    context: &ResolverContext,
+    op: BinaryOp,
+    lhs: ConstantValue,
+    rhs: ConstantValue,
+    ty: &ResolvedType,
+    overflowed: &Cell<bool>,
+) -> Option<ConstantValue> {
+    use ConstantValue::*;
+    let compare = |ordering: Option<std::cmp::Ordering>| -> Option<ConstantValue> {
+        let ordering = ordering?;
+        Some(Bool(match op {
+            BinaryOp::Equals => ordering.is_eq(),
+            BinaryOp::NotEquals => ordering.is_ne(),
+            BinaryOp::LessThan => ordering.is_lt(),
+            BinaryOp::LessThanOrEquals => ordering.is_le(),
+            BinaryOp::GreaterThan => ordering.is_gt(),
+            BinaryOp::GreaterThanOrEquals => ordering.is_ge(),
+            _ => unreachable!(),
+        }))
+    };
+    match op {
+        BinaryOp::Equals
+        | BinaryOp::NotEquals
+        | BinaryOp::LessThan
+        | BinaryOp::LessThanOrEquals
+        | BinaryOp::GreaterThan
+        | BinaryOp::GreaterThanOrEquals => match (lhs, rhs) {
+            (Int(l), Int(r)) => compare(Some(l.cmp(&r))),
+            (Bool(l), Bool(r)) => compare(Some(l.cmp(&r))),
+            _ => compare(lhs.as_f64()?.partial_cmp(&rhs.as_f64()?)),
+        },
+        _ if ty.is_float_type() => {
+            let (l, r) = (lhs.as_f64()?, rhs.as_f64()?);
+            Some(Float(match op {
+                BinaryOp::Add => l + r,
+                BinaryOp::Sub => l - r,
+                BinaryOp::Mul => l * r,
+                BinaryOp::Div => l / r,
+                _ => return None,
+            }))
+        }
+        _ => {
+            let (Int(l), Int(r)) = (lhs, rhs) else {
+                return None;
+            };
+            // 両辺を実行時と同じ型に揃えてから計算する
+            let l = wrap_int(context, l, ty);
+            // シフト量は左辺の型に揃えない
+            let r = match op {
+                BinaryOp::Shl | BinaryOp::Shr => r,
+                _ => wrap_int(context, r, ty),
+            };
+            let value = match op {
+                BinaryOp::Add => wrap_arithmetic(context, l + r, ty, overflowed),
+                BinaryOp::Sub => wrap_arithmetic(context, l - r, ty, overflowed),
+                // u64同士の積はi128にも収まらないことがある
+                BinaryOp::Mul => match l.checked_mul(r) {
+                    Some(value) => wrap_arithmetic(context, value, ty, overflowed),
+                    None => {
+                        overflowed.set(true);
+                        wrap_int(context, l.wrapping_mul(r), ty)
+                    }
+                },
+                // 0除算は定数にできない
+                BinaryOp::Div => wrap_arithmetic(context, l.checked_div(r)?, ty, overflowed),
+                BinaryOp::Mod => l.checked_rem(r)?,
+                BinaryOp::BitAnd => l & r,
+                BinaryOp::BitOr => l | r,
+                BinaryOp::BitXor => l ^ r,
+                BinaryOp::Shl => wrap_int(context, l.checked_shl(u32::try_from(r).ok()?)?, ty),
+                BinaryOp::Shr => l.checked_shr(u32::try_from(r).ok()?)?,
+                _ => unreachable!(),
+            };
+            Some(Int(value))
+        }
+    }
+}
+
+fn fold(
+    context: &ResolverContext,
+    expr: &ResolvedExpression,
+    overflowed: &Cell<bool>,
+) -> Option<ConstantValue> {
+    match &expr.kind {
+        ExpressionKind::NumberLiteral(number_literal) => {
+            if expr.ty.is_float_type() {
+                number_literal.value.parse().ok().map(ConstantValue::Float)
+            } else {
+                number_literal.value.parse().ok().map(ConstantValue::Int)
+            }
+        }
+        ExpressionKind::FloatLiteral(float_literal) => {
+            float_literal.value.parse().ok().map(ConstantValue::Float)
+        }
+        ExpressionKind::BoolLiteral(bool_literal) => Some(ConstantValue::Bool(bool_literal.value)),
+        ExpressionKind::Binary(binary_expr) => {
+            let mut lhs = fold(context, &binary_expr.lhs, overflowed)?;
+            let mut rhs = fold(context, &binary_expr.rhs, overflowed)?;
+            // 型の異なる整数同士の比較は、実行時と同じく共通の型に揃えてから比べる。
+            // (< (- 0 1) 1u32) では-1がu32の最大値になる
+            if let (ConstantValue::Int(l), ConstantValue::Int(r), ResolvedType::Bool) =
+                (lhs, rhs, &expr.ty)
+            {
+                if binary_expr.lhs.ty.is_integer_type() && binary_expr.rhs.ty.is_integer_type() {
+                    let (lhs_cast_type, rhs_cast_type) = get_cast_type(
+                        &binary_expr
+                            .lhs
+                            .ty
+                            .unwrap_primitive_into_concrete_type(context.is_64_bit()),
+                        &binary_expr
+                            .rhs
+                            .ty
+                            .unwrap_primitive_into_concrete_type(context.is_64_bit()),
+                    );
+                    if let Some(ty) = lhs_cast_type {
+                        lhs = ConstantValue::Int(wrap_int(
+                            context,
+                            l,
+                            &ty.unwrap_primitive_into_resolved_type(),
+                        ));
+                    }
+                    if let Some(ty) = rhs_cast_type {
+                        rhs = ConstantValue::Int(wrap_int(
+                            context,
+                            r,
+                            &ty.unwrap_primitive_into_resolved_type(),
+                        ));
+                    }
+                }
+            }
+            fold_binary(context, binary_expr.op, lhs, rhs, &expr.ty, overflowed)
+        }
+        ExpressionKind::Unary(unary_expr) => {
+            match (
+                unary_expr.op,
+                fold(context, &unary_expr.operand, overflowed)?,
+            ) {
+                (UnaryOp::Neg, ConstantValue::Int(n)) => Some(ConstantValue::Int(wrap_arithmetic(
+                    context,
+                    -wrap_int(context, n, &expr.ty),
+                    &expr.ty,
+                    overflowed,
+                ))),
+                (UnaryOp::Neg, ConstantValue::Float(n)) => Some(ConstantValue::Float(-n)),
+                (UnaryOp::BitNot, ConstantValue::Int(n)) => {
+                    Some(ConstantValue::Int(wrap_int(context, !n, &expr.ty)))
+                }
+                (UnaryOp::Not, ConstantValue::Bool(b)) => Some(ConstantValue::Bool(!b)),
+                _ => None,
+            }
+        }
+        ExpressionKind::Multi(multi_expr) => {
+            let mut result = matches!(multi_expr.op, MultiOp::And);
+            for operand in &multi_expr.operands {
+                let ConstantValue::Bool(value) = fold(context, operand, overflowed)? else {
+                    return None;
+                };
+                result = match multi_expr.op {
+                    MultiOp::And => result && value,
+                    MultiOp::Or => result || value,
+                };
+            }
+            Some(ConstantValue::Bool(result))
+        }
+        _ => None,
+    }
+}
+
+// 定数式をコンパイル時に評価し、リテラルに置き換える。定数式でなければNoneを返す。
+// 演算が型の範囲を超えた場合は、rangeに警告を積んだ上でラップアラウンドした値にする
+pub(super) fn evaluate_constant(
+    context: &ResolverContext,
+    expr: &ResolvedExpression,
+) -> Option<ResolvedExpression> {
+    let overflowed = Cell::new(false);
+    let value = fold(context, expr, &overflowed)?;
+    if overflowed.get() {
+        context.warnings.borrow_mut().push(CompileWarning::new(
+            expr.range,
+            CompileWarningKind::ArithmeticOverflow {
+                ty: expr.ty.clone(),
+            },
+        ));
+    }
+    let kind = match value {
+        ConstantValue::Int(n) => ExpressionKind::NumberLiteral(NumberLiteral {
+            value: n.to_string(),
+        }),
+        ConstantValue::Float(n) => ExpressionKind::FloatLiteral(FloatLiteral {
+            value: format!("{:?}", n),
+        }),
+        ConstantValue::Bool(b) => ExpressionKind::BoolLiteral(BoolLiteral { value: b }),
+    };
+    Some(ResolvedExpression {
+        range: expr.range,
+        ty: expr.ty.clone(),
+        kind,
+    })
+}
+
+// 二項演算と単項演算の式が定数だけからなれば、リテラルに畳み込む。
+// 部分式は解決した時点で畳み込まれているので、(* 2 (+ 3 4)) は14になる
+pub(super) fn fold_constant_expression(
+    context: &ResolverContext,
+    expr: ResolvedExpression,
+) -> ResolvedExpression {
+    match expr.kind {
+        ExpressionKind::Binary(_) | ExpressionKind::Unary(_) => {
+            evaluate_constant(context, &expr).unwrap_or(expr)
+        }
+        _ => expr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nom::Finish;
+
+    use crate::{
+        common::target::PointerSizedIntWidth,
+        parser::parse_module,
+        resolved_ast::{Statement, TopLevel},
+        resolver::{error::CompileErrorKind, resolve_module, tests::resolve_source},
+    };
+
+    use super::*;
+
+    // mainが返す式を解決する
+    fn resolve_main_return(source: &str) -> (ResolverContext, ResolvedExpression) {
+        let (_, module) = parse_module(source.into()).finish().unwrap();
+        let context = ResolverContext::new(PointerSizedIntWidth::SixtyFour);
+        let module = resolve_module(&context, &module, true).unwrap();
+        let expression = module
+            .toplevels
+            .into_iter()
+            .find_map(|toplevel| match toplevel {
+                TopLevel::Function(func) if func.decl.name == "main" => {
+                    func.body.into_iter().last()
+                }
+                _ => None,
+            })
+            .and_then(|statement| match statement {
+                Statement::Return(ret) => ret.expression,
+                _ => None,
+            })
+            .unwrap();
+        (context, expression)
+    }
+
+    fn constant_value(context: &ResolverContext, name: &str) -> String {
+        match &context.constants.borrow()[name].kind {
+            ExpressionKind::NumberLiteral(number_literal) => number_literal.value.clone(),
+            ExpressionKind::FloatLiteral(float_literal) => float_literal.value.clone(),
+            ExpressionKind::BoolLiteral(bool_literal) => bool_literal.value.to_string(),
+            kind => panic!("not folded: {:?}", kind),
+        }
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let context = resolve_source(
+            "
+(const SIZE: usize (* 4 2))
+(const DOUBLED: usize (* SIZE 2))
+(const MASK: u8 (~ 0))
+(const WRAPPED: u8 (+ MASK 2))
+(const NEGATIVE (- (<< 1 4)))
+(const HALF (/ 1.0 2.0))
+(const ENABLED (&& (< 1 2) (not false)))
+fn main(): void {
+    (:= buf (malloc DOUBLED))
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+        assert_eq!(constant_value(&context, "SIZE"), "8");
+        assert_eq!(constant_value(&context, "DOUBLED"), "16");
+        assert_eq!(
+            context.constants.borrow()["DOUBLED"].ty,
+            ResolvedType::USize
+        );
+        assert_eq!(constant_value(&context, "MASK"), "255");
+        assert_eq!(constant_value(&context, "WRAPPED"), "1");
+        assert_eq!(constant_value(&context, "NEGATIVE"), "-16");
+        assert_eq!(constant_value(&context, "HALF"), "0.5");
+        assert_eq!(constant_value(&context, "ENABLED"), "true");
+    }
+
+    #[test]
+    fn test_reject_non_constant_initializer() {
+        let context = resolve_source(
+            "
+(const LENGTH (strlen \"abc\"))
+(const INVALID (/ 1 0))
+fn main(): void {}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::NonConstantInitializer {
+                    name: "LENGTH".into()
+                },
+                &CompileErrorKind::NonConstantInitializer {
+                    name: "INVALID".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fold_expression() {
+        let (context, expression) = resolve_main_return(
+            "
+fn main(): i32 {
+    (* 2 (+ 3 4))
+}
+",
+        );
+        assert!(context.warnings.borrow().is_empty());
+        assert_eq!(expression.ty, ResolvedType::I32);
+        assert!(
+            matches!(&expression.kind, ExpressionKind::NumberLiteral(literal) if literal.value == "14"),
+            "{:?}",
+            expression
+        );
+
+        // 実行時と同じく、左辺をu32に変換してから割る
+        let (_, expression) = resolve_main_return(
+            "
+fn main(): u32 {
+    (/ (- 4) 2u32)
+}
+",
+        );
+        assert!(
+            matches!(&expression.kind, ExpressionKind::NumberLiteral(literal) if literal.value == "2147483646"),
+            "{:?}",
+            expression
+        );
+    }
+
+    #[test]
+    fn test_fold_comparison_with_mixed_widths() {
+        let context = resolve_source(
+            "
+(const WIDENED (< 1i64 2))
+(const UNSIGNED (< (- 1) 1u32))
+(const BYTE (== 255u8 255))
+fn main(): void {}
+",
+        );
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+        assert_eq!(constant_value(&context, "WIDENED"), "true");
+        // 実行時と同じく-1をu32に変換してから比べるので、u32の最大値になる
+        assert_eq!(constant_value(&context, "UNSIGNED"), "false");
+        assert_eq!(constant_value(&context, "BYTE"), "true");
+    }
+
+    #[test]
+    fn test_fold_overflow() {
+        let (context, expression) = resolve_main_return(
+            "
+fn main(): u8 {
+    (+ 255u8 1u8)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+        let warnings = context.warnings.borrow();
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|warning| &warning.kind)
+                .collect::<Vec<_>>(),
+            vec![&CompileWarningKind::ArithmeticOverflow {
+                ty: ResolvedType::U8
+            }]
+        );
+        assert_eq!(warnings[0].range.from.line, 3);
+        assert!(
+            matches!(&expression.kind, ExpressionKind::NumberLiteral(literal) if literal.value == "0"),
+            "{:?}",
+            expression
+        );
+    }
+}
@@ -4,10 +4,38 @@ use std::fmt::Display;
 use thiserror::Error;
 
 use crate::{
-    ast::{Range, UnresolvedType},
+    ast::{BinaryOp, Range, UnaryOp, UnresolvedType},
     resolved_ast::ResolvedType,
 };
 
+// InvalidOperandで、二項演算子と単項演算子をまとめて扱う
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Binary(BinaryOp),
+    Unary(UnaryOp),
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operator::Binary(op) => op.fmt(f),
+            Operator::Unary(op) => op.fmt(f),
+        }
+    }
+}
+
+impl From<BinaryOp> for Operator {
+    fn from(op: BinaryOp) -> Self {
+        Operator::Binary(op)
+    }
+}
+
+impl From<UnaryOp> for Operator {
+    fn from(op: UnaryOp) -> Self {
+        Operator::Unary(op)
+    }
+}
+
 #[derive(Debug, Error, PartialEq)]
 pub enum CompileErrorKind {
     #[error("Variable `{name:?}` is not found in this scope.")]
@@ -26,6 +54,8 @@ pub enum CompileErrorKind {
     IsNotVariable { name: String },
     #[error("Invalid operand. Expected numeric value, but got `{actual:?}`")]
     InvalidNumericOperand { actual: ResolvedType },
+    #[error("Invalid operand. Operator `{op}` cannot be applied to `{actual}`")]
+    InvalidOperand { op: Operator, actual: ResolvedType },
     #[error("Invalid argument.")]
     InvalidArgument,
     #[error("Type does not match. expected `{expected}`, but got `{actual}`")]
@@ -33,23 +63,56 @@ pub enum CompileErrorKind {
         expected: ResolvedType,
         actual: ResolvedType,
     },
+    #[error("`{fn_name}` does not return a value on every path")]
+    MissingReturn { fn_name: String },
     #[error("Return value does not match. expected `{expected}`, but got `{actual}`")]
     ReturnTypeMismatch {
         expected: ResolvedType,
         actual: ResolvedType,
     },
+    #[error(
+        "Cannot infer the return type of `{fn_name}`. It returns both `{first}` and `{second}`"
+    )]
+    ConflictingReturnTypes {
+        fn_name: String,
+        first: ResolvedType,
+        second: ResolvedType,
+    },
+    #[error("Cannot infer the return type of `{fn_name}` because it is used in its own body. Add a return type annotation")]
+    CannotInferRecursiveReturnType { fn_name: String },
     #[error("Cannot deref {name} for {deref_count:?} times.")]
     InvalidDeref { name: String, deref_count: u32 },
+    #[error("Cannot take the address of a value that is not a variable, index access, field access or deref.")]
+    InvalidAddressOf,
+    #[error("Integer literal `{value}` is out of range for `{ty}`")]
+    IntegerLiteralOutOfRange { value: String, ty: ResolvedType },
+    #[error("Initializer of `{name}` must be a constant expression")]
+    NonConstantInitializer { name: String },
+    #[error("Cannot cast `{from}` to `{to}`")]
+    InvalidCast {
+        from: ResolvedType,
+        to: ResolvedType,
+    },
     #[error("Cannot access {ty} by index.")]
     InvalidIndexAccess { ty: ResolvedType },
     #[error("`{ty}` has no field named `{name}`")]
     InvalidFieldAccess { ty: ResolvedType, name: String },
     #[error("Array index must be an integer value")]
     InvalidArrayIndex,
+    #[error("Cannot infer the pointer type of `{name}` from `null`. Add a type annotation")]
+    CannotInferNullType { name: String },
+    #[error("Array length must be greater than 0, but got {len}")]
+    InvalidArrayLength { len: i64 },
     #[error("Cannot find type name {name}")]
     TypeNotFound { name: String },
-    #[error("Mismatch generic args privided. `{name}` requires {expected} generic arguments, but got {actual}")]
-    MismatchGenericArgCount {
+    #[error("Too many generic arguments for `{name}`. It takes {expected}, but got {actual}")]
+    TooManyGenericArgs {
+        name: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Too few generic arguments for `{name}`. It takes {expected}, but got {actual}")]
+    TooFewGenericArgs {
         name: String,
         expected: usize,
         actual: usize,
@@ -69,22 +132,136 @@ pub enum CompileErrorKind {
         field_name: String,
         type_name: String,
     },
+    #[error("No method named `{name}` found for type `{type_name}`")]
+    MethodNotFound { name: String, type_name: String },
+    #[error("`self` can only be used as the first argument of a method")]
+    SelfOutsideMethod,
+    #[error("Missing field `{field_name}` in literal of `{type_name}`")]
+    MissingField {
+        field_name: String,
+        type_name: String,
+    },
+    #[error("Field `{field_name}` is specified more than once in literal of `{type_name}`")]
+    DuplicateField {
+        field_name: String,
+        type_name: String,
+    },
+    #[error("Function `{name}` is defined more than once{}", previous_definition(.previous))]
+    DuplicateFunction {
+        name: String,
+        // 組み込み関数と衝突した場合はNone
+        previous: Option<Range>,
+    },
+    #[error("No overload of `{name}` accepts arguments of type ({})", .arg_types.iter().map(|ty| ty.to_string()).collect::<Vec<_>>().join(", "))]
+    NoMatchingOverload {
+        name: String,
+        arg_types: Vec<ResolvedType>,
+    },
+    #[error("Unknown format specifier `{specifier}`")]
+    InvalidFormatSpecifier { specifier: String },
+    #[error("Format string expects {expected} arguments, but got {actual}")]
+    FormatArgCountMismatch { expected: usize, actual: usize },
+    #[error("`{specifier}` cannot format a value of type `{actual}`")]
+    FormatArgTypeMismatch {
+        specifier: String,
+        actual: ResolvedType,
+    },
+    #[error("Call to `{name}` matches more than one overload")]
+    AmbiguousCall { name: String },
+    #[error("Parameter `{name}` must have a default value because a previous parameter has one")]
+    RequiredArgumentAfterDefault { name: String },
+    #[error("`break` can only be used inside a loop")]
+    BreakOutsideLoop,
+    #[error("`continue` can only be used inside a loop")]
+    ContinueOutsideLoop,
+    #[error("Cannot switch on a value of type `{ty}`. Expected an integer")]
+    InvalidSwitchScrutinee { ty: ResolvedType },
+    #[error("Case label must be a constant integer")]
+    NonConstantCaseLabel,
+    #[error("Case label `{value}` is used more than once")]
+    DuplicateCaseLabel { value: String },
+    #[error("Switch on enum `{enum_name}` does not handle {}. Add the missing cases or a default", .variants.iter().map(|variant| format!("`{}`", variant)).collect::<Vec<_>>().join(", "))]
+    NonExhaustiveSwitch {
+        enum_name: String,
+        variants: Vec<String>,
+    },
+    #[error("`va_arg` can only be used inside a function with `...` arguments")]
+    VaArgOutsideVarArgsFunction,
+    #[error("Cannot read a value of type `{ty}` with `va_arg`")]
+    InvalidVaArgType { ty: ResolvedType },
+    #[error("Struct `{name}` contains itself without a pointer, so its size is infinite")]
+    RecursiveStruct { name: String },
+    #[error("Type alias `{name}` refers to itself")]
+    CyclicTypeAlias { name: String },
+    #[error("Cannot take the address of function `{name}`. Generic, overloaded and `...` functions cannot be used as function pointers")]
+    InvalidFunctionRef { name: String },
+    #[error("`{name}` of type `{ty}` is not a function pointer")]
+    NotCallable { name: String, ty: ResolvedType },
+    #[error("Cannot find variant `{variant_name}` in enum `{enum_name}`")]
+    VariantNotFound {
+        variant_name: String,
+        enum_name: String,
+    },
     #[error("Generic args length mismatch. expected {expected}, but got {actual}")]
     InvalidGenericArgsLength { expected: usize, actual: usize },
     #[error("Cannot infer generic argument of function `{name}`. {message}")]
     CannotInferGenericArgs { name: String, message: String },
+    #[error(
+        "Cannot infer `{generic_arg}` of `{name}`. It is inferred as both `{first}` and `{second}`"
+    )]
+    ConflictingGenericArgs {
+        name: String,
+        generic_arg: String,
+        first: ResolvedType,
+        second: ResolvedType,
+    },
     #[error("{message}")]
     NotImplemented { message: String },
     #[error("Cannot implement interface for pointer type")]
     ImplForPointerIsInvalid,
     #[error("Cannot implement interface for inference type")]
     ImplForInferenceIsInvalid,
+    #[error("Generated LLVM module is invalid: {message}")]
+    InvalidModule { message: String },
+    #[error("Optimization failed: {message}")]
+    OptimizationFailed { message: String },
+    #[error("Cannot emit code for the target: {message}")]
+    CannotEmitCode { message: String },
+    #[error("Cannot run the program: {message}")]
+    CannotRunJit { message: String },
 }
 
 #[derive(Debug, Error, PartialEq)]
 pub struct CompileError {
-    range: Range,
-    kind: CompileErrorKind,
+    pub(crate) range: Range,
+    pub(crate) kind: CompileErrorKind,
+    // 診断を出したファイルの番号。解決が終わるまでに付ける
+    pub(crate) file: Option<usize>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CompileWarningKind {
+    #[error("Parameter `{name}` is never used. Prefix it with `_` or mark it `@unused` if this is intentional.")]
+    UnusedParameter { name: String },
+    #[error("Variable `{name}` is never used. Prefix it with `_` if this is intentional.")]
+    UnusedVariable { name: String },
+    #[error("Unreachable code.")]
+    UnreachableCode,
+    #[error("Shift amount {amount} is not less than the bit width of `{ty}` ({width}).")]
+    ShiftAmountTooLarge {
+        amount: u64,
+        ty: ResolvedType,
+        width: u32,
+    },
+    #[error("This arithmetic operation overflows `{ty}` and will wrap around.")]
+    ArithmeticOverflow { ty: ResolvedType },
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub struct CompileWarning {
+    pub(crate) range: Range,
+    pub(crate) kind: CompileWarningKind,
+    pub(crate) file: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -92,7 +269,16 @@ pub struct FaitalError(pub String);
 
 impl CompileError {
     pub fn new(range: Range, kind: CompileErrorKind) -> Self {
-        CompileError { kind, range }
+        CompileError {
+            kind,
+            range,
+            file: None,
+        }
+    }
+
+    // resolve_modulesに渡したファイルのうち、この診断の位置を含むものの番号
+    pub fn file(&self) -> usize {
+        self.file.unwrap_or(0)
     }
 }
 
@@ -103,45 +289,108 @@ impl CompileError {
         path: &str,
         source: &str,
     ) -> std::io::Result<()> {
-        let mut display_lines = Vec::new();
-        let mut lines = source.lines();
-        let mut line = lines.next().unwrap();
-        // self.rangeから表示する行を切り取る
-        let mut line_number = 1;
-        while line_number < self.range.from.line {
-            line = lines.next().unwrap();
-            line_number += 1;
-        }
-        while line_number <= self.range.to.line {
-            display_lines.push((line_number, line));
-            line = lines.next().unwrap();
-            line_number += 1;
-        }
-        writeln!(
-            f,
-            "error: {}
-  in {}:{}:{}\n{}\n",
-            self.kind,
-            path,
-            self.range.from.line,
-            self.range.from.col,
-            display_lines
-                .iter()
-                .map(|(line_number, line)| format!("{:4} |{}", line_number, line))
-                .collect::<Vec<_>>()
-                .join("\n")
-        )?;
-        Ok(())
+        fmt_diagnostic_with_source(f, "error", &self.kind, self.range, path, source)
     }
 }
 
 impl Display for CompileError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}", self.kind)?;
-        Ok(())
+        fmt_diagnostic(f, &self.kind, self.range)
+    }
+}
+
+impl CompileWarning {
+    pub fn new(range: Range, kind: CompileWarningKind) -> Self {
+        CompileWarning {
+            kind,
+            range,
+            file: None,
+        }
+    }
+
+    pub fn file(&self) -> usize {
+        self.file.unwrap_or(0)
+    }
+
+    pub fn fmt_with_source(
+        &self,
+        f: &mut impl std::io::Write,
+        path: &str,
+        source: &str,
+    ) -> std::io::Result<()> {
+        fmt_diagnostic_with_source(f, "warning", &self.kind, self.range, path, source)
+    }
+}
+
+impl Display for CompileWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_diagnostic(f, &self.kind, self.range)
+    }
+}
+
+fn previous_definition(previous: &Option<Range>) -> String {
+    match previous {
+        Some(range) => format!(
+            " (previously defined at {}:{})",
+            range.from.line, range.from.col
+        ),
+        None => " (conflicts with a builtin function)".into(),
     }
 }
 
+// 行:列: メッセージ の形式で出力する。コード生成のエラーのように位置を持たないものはメッセージだけにする
+fn fmt_diagnostic(
+    f: &mut std::fmt::Formatter<'_>,
+    message: &impl Display,
+    range: Range,
+) -> std::fmt::Result {
+    if range == Range::default() {
+        write!(f, "{}", message)
+    } else {
+        write!(f, "{}:{}: {}", range.from.line, range.from.col, message)
+    }
+}
+
+fn fmt_diagnostic_with_source(
+    f: &mut impl std::io::Write,
+    label: &str,
+    message: &impl Display,
+    range: Range,
+    path: &str,
+    source: &str,
+) -> std::io::Result<()> {
+    let mut display_lines = Vec::new();
+    let mut lines = source.lines();
+    let mut line = lines.next().unwrap_or_default();
+    // rangeから表示する行を切り取る
+    let mut line_number = 1;
+    while line_number < range.from.line {
+        line = lines.next().unwrap_or_default();
+        line_number += 1;
+    }
+    while line_number <= range.to.line {
+        display_lines.push((line_number, line));
+        line = lines.next().unwrap_or_default();
+        line_number += 1;
+    }
+    writeln!(
+        f,
+        "{}: {}
+  in {}:{}:{}\n{}\n",
+        label,
+        message,
+        path,
+        range.from.line,
+        range.from.col,
+        display_lines
+            .iter()
+            .map(|(line_number, line)| format!("{:4} |{}", line_number, line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )?;
+    Ok(())
+}
+
 #[macro_export]
 macro_rules! error_context {
     ( $context_type:expr, $generator_block:expr ) => {
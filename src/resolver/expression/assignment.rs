@@ -7,9 +7,74 @@ pub(super) fn resolve_assignment(
     context: &ResolverContext,
     assignment_expr: &Located<&AssignExpr>,
 ) -> Result<ResolvedExpression, FaitalError> {
-    let resolved_expr =
-        resolve_expression(context, assignment_expr.value.value.as_inner_deref(), None)?;
+    // 代入先の型を、デリファレンスとインデックスアクセスを辿って求める
+    let variable_ty = context.scopes.borrow().get(&assignment_expr.name).cloned();
+    let mut indexes_array = false;
+    let target_ty = if let Some(mut ty) = variable_ty {
+        for _ in 0..assignment_expr.deref_count {
+            ty = match ty {
+                ResolvedType::Ptr(inner) => *inner,
+                ResolvedType::Unknown => break,
+                _ => {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        assignment_expr.range,
+                        CompileErrorKind::InvalidDeref {
+                            name: assignment_expr.name.clone(),
+                            deref_count: assignment_expr.deref_count,
+                        },
+                    ));
+                    ResolvedType::Unknown
+                }
+            };
+        }
+        if assignment_expr.index_access.is_some() {
+            ty = match ty {
+                ResolvedType::Ptr(inner) => *inner,
+                ResolvedType::Array(inner, _) => {
+                    indexes_array = true;
+                    *inner
+                }
+                ResolvedType::Unknown => ResolvedType::Unknown,
+                _ => {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        assignment_expr.range,
+                        CompileErrorKind::InvalidIndexAccess { ty },
+                    ));
+                    ResolvedType::Unknown
+                }
+            };
+        }
+        ty
+    } else {
+        context.errors.borrow_mut().push(CompileError::new(
+            assignment_expr.range,
+            CompileErrorKind::VariableNotFound {
+                name: assignment_expr.name.clone(),
+            },
+        ));
+        ResolvedType::Unknown
+    };
+
+    let resolved_expr = resolve_expression(
+        context,
+        assignment_expr.value.value.as_inner_deref(),
+        Some(&target_ty),
+    )?;
+    // どちらかが既にエラーになっている場合は重ねて報告しない
+    if target_ty != ResolvedType::Unknown
+        && resolved_expr.ty != ResolvedType::Unknown
+        && !target_ty.can_insert(&resolved_expr.ty)
+    {
+        context.errors.borrow_mut().push(CompileError::new(
+            assignment_expr.value.value.range,
+            CompileErrorKind::TypeMismatch {
+                expected: target_ty,
+                actual: resolved_expr.ty.clone(),
+            },
+        ));
+    }
     Ok(ResolvedExpression {
+        range: assignment_expr.range,
         ty: ResolvedType::Void,
         kind: ExpressionKind::Assignment(resolved_ast::Assignment {
             name: assignment_expr.name.clone(),
@@ -23,6 +88,76 @@ pub(super) fn resolve_assignment(
                 })
                 .transpose()?
                 .map(Box::new),
+            indexes_array,
         }),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::resolver::{error::CompileErrorKind, tests::resolve_source};
+
+    use super::*;
+
+    #[test]
+    fn test_assignment_checks_pointee_type() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= x: u8 1)
+    (:= p &x)
+    (:=< *p 5)
+    (:=< *p (< 1 2))
+    (:= buf \"abc\")
+    (:=< buf[2] 7)
+    (:=< x[0] 1)
+    (:=< **p 1)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::TypeMismatch {
+                    expected: ResolvedType::U8,
+                    actual: ResolvedType::Bool,
+                },
+                &CompileErrorKind::InvalidIndexAccess {
+                    ty: ResolvedType::U8
+                },
+                &CompileErrorKind::InvalidDeref {
+                    name: "p".into(),
+                    deref_count: 2,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment_through_pointer() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= total: i64 0)
+    (:= p &total)
+    (+= *p 10)
+    (<<= *p 2)
+    (:= buf \"abc\")
+    (-= buf[1] 1)
+    (+= total (< 1 2))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        // 読み出し側もデリファレンスした先の型になるので、最後の不正な加算だけが報告される
+        assert_eq!(
+            kinds,
+            vec![&CompileErrorKind::InvalidNumericOperand {
+                actual: ResolvedType::Bool
+            }]
+        );
+    }
+}
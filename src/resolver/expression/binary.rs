@@ -4,45 +4,228 @@ use self::ast::BinaryExpr;
 
 use super::*;
 
-pub(super) fn resolve_binary_expression(
+// ptr ± int と int + ptr はポインタに、同じ型のポインタ同士の差は要素数になる
+// ポインタ演算でなければNoneを返す
+fn pointer_arithmetic_type(
     context: &ResolverContext,
     bin_expr: &Located<&BinaryExpr>,
-) -> Result<ResolvedExpression, FaitalError> {
-    let lhs = resolve_expression(context, bin_expr.lhs.as_deref(), None)?;
-    let rhs = resolve_expression(context, bin_expr.rhs.as_deref(), None)?;
-    match bin_expr.op {
-        BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div => {
-            if !lhs.ty.is_integer_type() {
+    lhs: &ResolvedType,
+    rhs: &ResolvedType,
+) -> Option<ResolvedType> {
+    match (bin_expr.op, lhs, rhs) {
+        (BinaryOp::Add | BinaryOp::Sub, ResolvedType::Ptr(_), rhs) if rhs.is_integer_type() => {
+            Some(lhs.clone())
+        }
+        (BinaryOp::Add, lhs, ResolvedType::Ptr(_)) if lhs.is_integer_type() => Some(rhs.clone()),
+        (BinaryOp::Sub, ResolvedType::Ptr(_), ResolvedType::Ptr(_)) => {
+            if lhs != rhs {
                 context.errors.borrow_mut().push(CompileError::new(
                     bin_expr.range,
-                    CompileErrorKind::InvalidNumericOperand {
-                        actual: lhs.ty.clone(),
+                    CompileErrorKind::TypeMismatch {
+                        expected: lhs.clone(),
+                        actual: rhs.clone(),
                     },
                 ));
+                return Some(ResolvedType::Unknown);
             }
-            if !rhs.ty.is_integer_type() {
+            Some(ResolvedType::USize)
+        }
+        _ => None,
+    }
+}
+
+// 数値同士の演算で両辺を揃える型を返す。どちらかが数値でなければNone
+fn common_numeric_type(
+    context: &ResolverContext,
+    lhs: &ResolvedType,
+    rhs: &ResolvedType,
+) -> Option<ResolvedType> {
+    let is_numeric = |ty: &ResolvedType| ty.is_integer_type() || ty.is_float_type();
+    if !is_numeric(lhs) || !is_numeric(rhs) {
+        return None;
+    }
+    // 同じ型同士ならusizeのような別名の型もそのまま残す
+    if lhs == rhs {
+        return Some(lhs.clone());
+    }
+    let ty = match get_cast_type(
+        &lhs.unwrap_primitive_into_concrete_type(context.is_64_bit()),
+        &rhs.unwrap_primitive_into_concrete_type(context.is_64_bit()),
+    ) {
+        (None, None) => lhs.unwrap_primitive_into_concrete_type(context.is_64_bit()),
+        (None, Some(t)) => t,
+        (Some(t), None) => t,
+        (Some(_), Some(t)) => t,
+    };
+    Some(ty.unwrap_primitive_into_resolved_type())
+}
+
+// 比較ではリテラルの型をもう一方の辺の型から決め、(< x 2) のxがf64でも比べられるようにする。
+// (< 2 x) のように左辺だけがリテラルなら、右辺から先に解決する
+fn resolve_comparison_operands(
+    context: &ResolverContext,
+    bin_expr: &Located<&BinaryExpr>,
+) -> Result<(ResolvedExpression, ResolvedExpression), FaitalError> {
+    let is_literal = |expr: &Expression| {
+        matches!(
+            expr,
+            Expression::NumberLiteral(_) | Expression::FloatLiteral(_)
+        )
+    };
+    let lhs_is_literal = is_literal(&bin_expr.lhs.value);
+    let rhs_is_literal = is_literal(&bin_expr.rhs.value);
+    if lhs_is_literal && !rhs_is_literal {
+        let rhs = resolve_expression(context, bin_expr.rhs.as_deref(), None)?;
+        let lhs = resolve_expression(context, bin_expr.lhs.as_deref(), Some(&rhs.ty))?;
+        return Ok((lhs, rhs));
+    }
+    let lhs = resolve_expression(context, bin_expr.lhs.as_deref(), None)?;
+    let rhs_annotation = rhs_is_literal.then_some(&lhs.ty);
+    let rhs = resolve_expression(context, bin_expr.rhs.as_deref(), rhs_annotation)?;
+    Ok((lhs, rhs))
+}
+
+pub(super) fn resolve_binary_expression(
+    context: &ResolverContext,
+    bin_expr: &Located<&BinaryExpr>,
+    annotation: Option<&ResolvedType>,
+) -> Result<ResolvedExpression, FaitalError> {
+    // (:= x: usize (* 4 2)) のように、算術演算ではリテラルの型を注釈から決められるようにする
+    let (lhs_annotation, rhs_annotation) = match bin_expr.op {
+        BinaryOp::Add
+        | BinaryOp::Sub
+        | BinaryOp::Mul
+        | BinaryOp::Div
+        | BinaryOp::Mod
+        | BinaryOp::BitAnd
+        | BinaryOp::BitOr
+        | BinaryOp::BitXor => (annotation, annotation),
+        // シフト量は左辺の型と関係ない
+        BinaryOp::Shl | BinaryOp::Shr => (annotation, None),
+        _ => (None, None),
+    };
+    // ポインタ演算の整数側にポインタの注釈を付けない
+    let (lhs_annotation, rhs_annotation) = match annotation {
+        Some(ty) if ty.is_pointer_type() => (None, None),
+        _ => (lhs_annotation, rhs_annotation),
+    };
+    let is_comparison = matches!(
+        bin_expr.op,
+        BinaryOp::Equals
+            | BinaryOp::NotEquals
+            | BinaryOp::LessThan
+            | BinaryOp::LessThanOrEquals
+            | BinaryOp::GreaterThan
+            | BinaryOp::GreaterThanOrEquals
+    );
+    let (lhs, rhs) = if is_comparison {
+        resolve_comparison_operands(context, bin_expr)?
+    } else {
+        (
+            resolve_expression(context, bin_expr.lhs.as_deref(), lhs_annotation)?,
+            resolve_expression(context, bin_expr.rhs.as_deref(), rhs_annotation)?,
+        )
+    };
+    match bin_expr.op {
+        BinaryOp::Add
+        | BinaryOp::Sub
+        | BinaryOp::Mul
+        | BinaryOp::Div
+        | BinaryOp::Mod
+        | BinaryOp::BitAnd
+        | BinaryOp::BitOr
+        | BinaryOp::BitXor => {
+            if let Some(ty) = pointer_arithmetic_type(context, bin_expr, &lhs.ty, &rhs.ty) {
+                return Ok(resolved_ast::ResolvedExpression {
+                    range: bin_expr.range,
+                    kind: resolved_ast::ExpressionKind::Binary(resolved_ast::BinaryExpr {
+                        op: bin_expr.op,
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                    }),
+                    ty,
+                });
+            }
+            // 浮動小数点数は四則演算にだけ使える
+            let accepts_float = matches!(
+                bin_expr.op,
+                BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div
+            );
+            let is_valid_operand =
+                |ty: &ResolvedType| ty.is_integer_type() || (accepts_float && ty.is_float_type());
+            // 1つの式には1つだけ報告する。左辺から順に調べ、最初の不正な辺で打ち切る
+            if let Some(operand) = [&lhs, &rhs]
+                .into_iter()
+                .find(|operand| !is_valid_operand(&operand.ty))
+            {
+                let kind = if operand.ty.is_pointer_type() || operand.ty.is_float_type() {
+                    CompileErrorKind::InvalidOperand {
+                        op: bin_expr.op.into(),
+                        actual: operand.ty.clone(),
+                    }
+                } else {
+                    CompileErrorKind::InvalidNumericOperand {
+                        actual: operand.ty.clone(),
+                    }
+                };
+                context
+                    .errors
+                    .borrow_mut()
+                    .push(CompileError::new(bin_expr.range, kind));
+                return Ok(resolved_ast::ResolvedExpression {
+                    range: bin_expr.range,
+                    kind: resolved_ast::ExpressionKind::Unknown,
+                    ty: ResolvedType::Unknown,
+                });
+            }
+            let ty = common_numeric_type(context, &lhs.ty, &rhs.ty).unwrap();
+            Ok(resolved_ast::ResolvedExpression {
+                range: bin_expr.range,
+                kind: resolved_ast::ExpressionKind::Binary(resolved_ast::BinaryExpr {
+                    op: bin_expr.op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                }),
+                ty,
+            })
+        }
+        BinaryOp::Shl | BinaryOp::Shr => {
+            if let Some(operand) = [&lhs, &rhs]
+                .into_iter()
+                .find(|operand| !operand.ty.is_integer_type())
+            {
                 context.errors.borrow_mut().push(CompileError::new(
                     bin_expr.range,
                     CompileErrorKind::InvalidNumericOperand {
-                        actual: rhs.ty.clone(),
+                        actual: operand.ty.clone(),
                     },
                 ));
+                return Ok(resolved_ast::ResolvedExpression {
+                    range: bin_expr.range,
+                    kind: resolved_ast::ExpressionKind::Unknown,
+                    ty: ResolvedType::Unknown,
+                });
             }
-            let ty: ResolvedType = match get_cast_type(
-                &lhs.ty
-                    .unwrap_primitive_into_concrete_type(context.is_64_bit()),
-                &rhs.ty
-                    .unwrap_primitive_into_concrete_type(context.is_64_bit()),
-            ) {
-                (None, None) => lhs
-                    .ty
-                    .unwrap_primitive_into_concrete_type(context.is_64_bit()),
-                (None, Some(t)) => t,
-                (Some(t), None) => t,
-                (Some(_), Some(t)) => t,
+            // ビット幅以上のシフトはLLVMではpoisonになる
+            if let resolved_ast::ExpressionKind::NumberLiteral(amount) = &rhs.kind {
+                let width = lhs.ty.integer_bit_width(context.is_64_bit()).unwrap();
+                if let Ok(amount) = amount.value.parse::<u64>() {
+                    if amount >= width as u64 {
+                        context.warnings.borrow_mut().push(CompileWarning::new(
+                            bin_expr.rhs.range,
+                            CompileWarningKind::ShiftAmountTooLarge {
+                                amount,
+                                ty: lhs.ty.clone(),
+                                width,
+                            },
+                        ));
+                    }
+                }
             }
-            .unwrap_primitive_into_resolved_type();
+            // シフトの結果は左辺の型になる
+            let ty = lhs.ty.clone();
             Ok(resolved_ast::ResolvedExpression {
+                range: bin_expr.range,
                 kind: resolved_ast::ExpressionKind::Binary(resolved_ast::BinaryExpr {
                     op: bin_expr.op,
                     lhs: Box::new(lhs),
@@ -57,7 +240,12 @@ pub(super) fn resolve_binary_expression(
         | BinaryOp::LessThanOrEquals
         | BinaryOp::GreaterThan
         | BinaryOp::GreaterThanOrEquals => {
-            if lhs.ty != rhs.ty {
+            // 数値同士は算術演算と同じ型に揃えて比べる。揃える際のキャストはbuilderで行う
+            // nullや*voidはどのポインタとも比較できる
+            if common_numeric_type(context, &lhs.ty, &rhs.ty).is_none()
+                && !lhs.ty.can_insert(&rhs.ty)
+                && !rhs.ty.can_insert(&lhs.ty)
+            {
                 context.errors.borrow_mut().push(CompileError::new(
                     bin_expr.range,
                     CompileErrorKind::TypeMismatch {
@@ -67,6 +255,7 @@ pub(super) fn resolve_binary_expression(
                 ));
             }
             Ok(resolved_ast::ResolvedExpression {
+                range: bin_expr.range,
                 kind: resolved_ast::ExpressionKind::Binary(resolved_ast::BinaryExpr {
                     op: bin_expr.op,
                     lhs: Box::new(lhs),
@@ -77,3 +266,238 @@ pub(super) fn resolve_binary_expression(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::resolver::{
+        error::{CompileErrorKind, CompileWarningKind},
+        tests::resolve_source,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_binary_expression_keeps_range() {
+        let context = resolve_source(
+            "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn main(): void {
+    (add 1 2)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+        let resolved_functions = context.resolved_functions.borrow();
+        let Some(resolved_ast::Statement::Return(resolved_ast::Return {
+            expression: Some(expression),
+            ..
+        })) = resolved_functions["add"].body.last()
+        else {
+            panic!("unexpected body: {:?}", resolved_functions["add"].body);
+        };
+        assert_eq!(expression.range.from.line, 3);
+        assert_eq!(expression.range.from.col, 5);
+        let ExpressionKind::Binary(binary) = &expression.kind else {
+            panic!("unexpected expression: {:?}", expression);
+        };
+        assert_eq!(binary.lhs.range.from.col, 8);
+        assert_eq!(binary.rhs.range.from.col, 10);
+    }
+
+    #[test]
+    fn test_mod_rejects_pointer_operand() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= s \"abc\")
+    (:= r (% s 2))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::InvalidOperand {
+                op: BinaryOp::Mod.into(),
+                actual: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_pointer_arithmetic() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= x: i32 1)
+    (:= p &x)
+    (:= q: *i32 (+ p 1))
+    (:= r (+ 2 q))
+    (:= _n: usize (- r p))
+    (:= _s (+ p q))
+}
+",
+        );
+        // 両辺がポインタでも、式ごとに1つだけ報告する
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].range.from.line, 8);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::InvalidOperand {
+                op: BinaryOp::Add.into(),
+                actual: ResolvedType::Ptr(Box::new(ResolvedType::I32)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_bitwise_requires_integer_operands() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a (| (& 1 2) 4))
+    (:= b (^ (< 1 2) 1))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::InvalidNumericOperand {
+                actual: ResolvedType::Bool,
+            }
+        );
+    }
+
+    #[test]
+    fn test_shift_amount_too_large() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a: u8 1)
+    (:= _b (<< a 7))
+    (:= _c (<< a 8))
+    (:= _d (>> 1 32))
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+        let warnings = context.warnings.borrow();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[0].kind,
+            CompileWarningKind::ShiftAmountTooLarge {
+                amount: 8,
+                ty: ResolvedType::U8,
+                width: 8,
+            }
+        );
+        assert_eq!(
+            warnings[1].kind,
+            CompileWarningKind::ShiftAmountTooLarge {
+                amount: 32,
+                ty: ResolvedType::I32,
+                width: 32,
+            }
+        );
+    }
+
+    #[test]
+    fn test_float_arithmetic() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a (+ 1.0 2))
+    (:= b: f32 1)
+    (:= c (* b 2))
+    (:= d (+ b 1.5))
+    (:= e (% 1.5 2))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::InvalidOperand {
+                op: BinaryOp::Mod.into(),
+                actual: ResolvedType::F64,
+            }
+        );
+    }
+
+    #[test]
+    fn test_compare_integer_with_float() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= x: f64 1.5)
+    (:= y: f32 2)
+    (:= n: i32 3)
+    (:= _a (< 1.0 2))
+    (:= _b (< x 2))
+    (:= _c (>= 2 x))
+    (:= _d (== y n))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        // 比較の相手がf64なので、整数リテラルもf64になる
+        let main = &context.resolved_functions.borrow()["main"];
+        for (index, literal_is_lhs) in [(4, false), (5, true)] {
+            let resolved_ast::Statement::Effect(statement) = &main.body[index] else {
+                panic!("unexpected statement: {:?}", main.body[index]);
+            };
+            let ExpressionKind::VariableDecls(decls) = &statement.expression.kind else {
+                panic!("unexpected expression: {:?}", statement.expression);
+            };
+            let ExpressionKind::Binary(binary) = &decls.decls[0].value.kind else {
+                panic!("unexpected expression: {:?}", decls.decls[0].value);
+            };
+            let literal = if literal_is_lhs {
+                &binary.lhs
+            } else {
+                &binary.rhs
+            };
+            assert_eq!(literal.ty, ResolvedType::F64);
+        }
+    }
+
+    #[test]
+    fn test_compare_integers_with_different_widths() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a: i64 1)
+    (:= b: i32 2)
+    (:= c: u8 3)
+    (:= _d (< a b))
+    (:= _e (< c 2))
+    (:= _f (>= b c))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        // 比較の相手がu8なので、リテラルもu8になる
+        let main = &context.resolved_functions.borrow()["main"];
+        let resolved_ast::Statement::Effect(statement) = &main.body[4] else {
+            panic!("unexpected statement: {:?}", main.body[4]);
+        };
+        let ExpressionKind::VariableDecls(decls) = &statement.expression.kind else {
+            panic!("unexpected expression: {:?}", statement.expression);
+        };
+        let ExpressionKind::Binary(binary) = &decls.decls[0].value.kind else {
+            panic!("unexpected expression: {:?}", decls.decls[0].value);
+        };
+        assert_eq!(binary.rhs.ty, ResolvedType::U8);
+    }
+}
@@ -1,223 +1,232 @@
-use std::collections::HashSet;
-
-use itertools::Itertools;
-
 use crate::{
     ast::UnresolvedType,
-    resolver::{generics::check_generic_bounds, ResolverContext},
+    common::builtin::ArithmeticBuiltin,
+    resolver::{
+        format::check_printf_format,
+        generics::{check_generic_bounds, generic_arg_count_error, unify_generic_arg},
+        method_name,
+        overload::select_overload,
+        ResolverContext,
+    },
 };
 
 use super::*;
 
+// ジェネリック関数を型引数ごとに特殊化して解決する。型引数を省略した呼び出しでは、引数とアノテーションから推論する。
+// 型引数の名前と解決した型の組を返す。型引数が正しくなければエラーを積んでNoneを返す
 pub fn resolve_call_with_generic_args(
     context: &ResolverContext,
     call_expr: &Located<&ast::CallExpr>,
     callee: &ast::Function,
-    // 推論に成功した場合のみtrueを返す
-) -> Result<bool, FaitalError> {
-    if let Some(generic_args) = &callee.decl.generic_args {
-        // スコープに解決したジェネリックの型を追加する
-        if let Some(actual_generic_args) = &call_expr.generic_args {
-            if generic_args.len() != actual_generic_args.len() {
-                context.errors.borrow_mut().push(CompileError::new(
-                    call_expr.range,
-                    CompileErrorKind::MismatchGenericArgCount {
-                        name: call_expr.name.to_owned(),
-                        expected: generic_args.len(),
-                        actual: actual_generic_args.len(),
-                    },
-                ));
-                return Ok(false);
-            };
-
-            in_global_scope!(context.scopes, {
-                in_global_scope!(context.types, {
-                    // 正常系
-                    for i in 0..generic_args.len() {
-                        let generic_arg = &generic_args[i];
-                        let actual_arg = &actual_generic_args[i];
-                        let resolved_type = resolve_type(context, actual_arg)?;
-                        context
-                            .types
-                            .borrow_mut()
-                            .add(generic_arg.name.clone(), resolved_type);
-                    }
-                    resolve_function(context, callee)?;
-                });
-            });
-            return Ok(true);
+    annotation: Option<&ResolvedType>,
+) -> Result<Option<Vec<(String, ResolvedType)>>, FaitalError> {
+    let Some(generic_args) = &callee.decl.generic_args else {
+        return Ok(Some(Vec::new()));
+    };
+    let resolved_generic_args = match &call_expr.generic_args {
+        Some(actual_generic_args) => {
+            if let Some(error) = generic_arg_count_error(
+                &call_expr.name,
+                generic_args.len(),
+                actual_generic_args.len(),
+            ) {
+                context
+                    .errors
+                    .borrow_mut()
+                    .push(CompileError::new(call_expr.range, error));
+                return Ok(None);
+            }
+            // 型引数は呼び出し元のスコープで解決する
+            let mut resolved_generic_args = Vec::new();
+            for actual_arg in actual_generic_args {
+                resolved_generic_args.push(resolve_type(context, actual_arg)?);
+            }
+            resolved_generic_args
         }
-    }
-    Ok(false)
+        None => match infer_generic_args(context, call_expr, callee, generic_args, annotation)? {
+            Some(inferred_generic_args) => inferred_generic_args,
+            None => return Ok(None),
+        },
+    };
+    let generic_bindings = generic_args
+        .iter()
+        .map(|generic_arg| generic_arg.name.clone())
+        .zip(resolved_generic_args)
+        .collect::<Vec<_>>();
+    // 特殊化した関数はマングルした名前で登録されるので、同じ型引数で2回解決することはない
+    in_global_scope!(context.scopes, {
+        in_global_scope!(context.types, {
+            in_new_scope!(context.types, {
+                for (name, ty) in &generic_bindings {
+                    context.types.borrow_mut().add(name.clone(), ty.clone());
+                }
+                resolve_function(context, callee)
+            })
+        })
+    })?;
+    Ok(Some(generic_bindings))
 }
 
-fn infer_generic_args_recursively(
-    tmp_errors: &mut Vec<CompileError>,
+// 型引数を省略した呼び出しで、宣言された引数の型と実際の引数の型を突き合わせて型引数を推論する。
+// 引数から決まらない型引数は、宣言された戻り値の型とアノテーションを突き合わせて推論する。
+// 推論できなければエラーを積んでNoneを返す
+fn infer_generic_args(
     context: &ResolverContext,
+    call_expr: &Located<&ast::CallExpr>,
     callee: &ast::Function,
-    current_callee_return_ty: &UnresolvedType,
-    current_annotation: &ResolvedType,
-) -> Result<bool, FaitalError> {
-    let callee_generic_args = callee.decl.generic_args.as_ref().unwrap();
-    match current_callee_return_ty {
-        UnresolvedType::TypeRef(return_ty_typeref) => {
-            if return_ty_typeref.generic_args.is_none() {
-                if let Some(generic_arg) = callee_generic_args
-                    .iter()
-                    .find(|x| x.value.name == return_ty_typeref.name)
-                {
-                    context
-                        .types
-                        .borrow_mut()
-                        .add(generic_arg.value.name.clone(), current_annotation.clone());
-                    return Ok(true);
-                } else {
-                    return Ok(false);
-                }
+    generic_args: &[Located<ast::GenericArgument>],
+    annotation: Option<&ResolvedType>,
+) -> Result<Option<Vec<ResolvedType>>, FaitalError> {
+    // 型を調べるためだけに引数を解決する。型引数を当てはめて解決し直すので、ここで積んだエラーは捨てる
+    let error_count = context.errors.borrow().len();
+    let warning_count = context.warnings.borrow().len();
+    let mut arg_types = Vec::new();
+    for arg in &call_expr.args {
+        arg_types.push(resolve_expression(context, arg.as_inner_deref(), None)?.ty);
+    }
+    context.errors.borrow_mut().truncate(error_count);
+    context.warnings.borrow_mut().truncate(warning_count);
+
+    // 接尾辞のない整数リテラルは他の引数やアノテーションに合わせられるので、それらから推論してから突き合わせる。
+    // nullはどのポインタにもなれるので推論には使わない
+    let is_int_literal = |arg: &ast::LocatedExpr| {
+        matches!(
+            arg.value.as_ref(),
+            Expression::NumberLiteral(ast::NumberLiteralExpr { suffix: None, .. })
+        )
+    };
+    let mut inferred = vec![None; generic_args.len()];
+    for int_literal_pass in [false, true] {
+        if int_literal_pass {
+            unify_return_type_with_annotation(
+                call_expr,
+                callee,
+                generic_args,
+                annotation,
+                &mut inferred,
+            );
+        }
+        for ((callee_arg, arg), arg_ty) in
+            callee.decl.args.iter().zip(&call_expr.args).zip(&arg_types)
+        {
+            let ast::Argument::Normal { ty, .. } = callee_arg else {
+                continue;
+            };
+            if matches!(arg.value.as_ref(), Expression::NullLiteral)
+                || is_int_literal(arg) != int_literal_pass
+            {
+                continue;
             }
-            if let Some(generic_args) = &return_ty_typeref.generic_args {
-                match current_annotation {
-                    ResolvedType::StructLike(resolved_struct) => {
-                        if resolved_struct.non_generic_name == return_ty_typeref.name {
-                            let mut generic_arg_inferred = false;
-                            for (i, resolved_generic_ty) in
-                                resolved_struct.generic_args.iter().enumerate()
-                            {
-                                if infer_generic_args_recursively(
-                                    tmp_errors,
-                                    context,
-                                    callee,
-                                    &generic_args[i],
-                                    &resolved_generic_ty[i],
-                                )? {
-                                    generic_arg_inferred = true;
-                                }
-                            }
-                            if generic_arg_inferred {
-                                return Ok(true);
-                            }
-                        } else {
-                            return Ok(false);
-                        }
-                    }
-                    _ => {
-                        return Ok(false);
-                    }
-                }
+            if int_literal_pass && infers_integer(generic_args, ty, &inferred) {
+                continue;
             }
-        }
-        UnresolvedType::Ptr(return_ty_pointer_ty) => {
-            if let ResolvedType::Ptr(inner) = current_annotation {
-                if infer_generic_args_recursively(
-                    tmp_errors,
-                    context,
-                    callee,
-                    return_ty_pointer_ty,
-                    inner,
-                )? {
-                    return Ok(true);
-                }
+            if let Err(error) =
+                unify_generic_arg(&call_expr.name, generic_args, ty, arg_ty, &mut inferred)
+            {
+                context
+                    .errors
+                    .borrow_mut()
+                    .push(CompileError::new(arg.range, error));
+                return Ok(None);
             }
         }
-        UnresolvedType::Infer => {
-            return Ok(false);
-        }
     }
-    Ok(false)
+
+    let mut inferred_generic_args = Vec::new();
+    for (generic_arg, inferred) in generic_args.iter().zip(inferred) {
+        let Some(inferred) = inferred else {
+            context.errors.borrow_mut().push(CompileError::new(
+                call_expr.range,
+                CompileErrorKind::CannotInferGenericArgs {
+                    name: call_expr.name.to_owned(),
+                    message: format!(
+                        "`{}` can be inferred from neither the argument types nor the annotation. Specify the type arguments like `({}<...> ...)`.",
+                        generic_arg.name, call_expr.name
+                    ),
+                },
+            ));
+            return Ok(None);
+        };
+        inferred_generic_args.push(inferred);
+    }
+    Ok(Some(inferred_generic_args))
 }
-// 関数のジェネリック引数を引数から推論する関数
-pub fn resolve_infer_generic_from_arguments(
-    context: &ResolverContext,
+
+// (:= v: Vec<i32> (vec)) のように引数から決まらない型引数を、戻り値の型とアノテーションを突き合わせて決める。
+// 整数リテラルの引数より先に突き合わせるので、(:= c: u64 (id 5)) は u64 で特殊化される。
+// アノテーションと戻り値の型が食い違う場合は何も決めず、代入先で型の不一致として扱う
+fn unify_return_type_with_annotation(
     call_expr: &Located<&ast::CallExpr>,
     callee: &ast::Function,
-    resolved_args: &[ResolvedExpression], // 推論に成功した場合のみtrueを返す
-) -> Result<Vec<usize>, FaitalError> {
-    todo!()
-    // ジェネリック引数が存在しない場合は、推論を行わない
-    // if call_expr.generic_args.is_some() || callee.decl.generic_args.is_none() {
-    //     return Ok(false);
-    // }
-    // // 可変長引数を持つ関数は、推論を行わない
-    // if callee
-    //     .decl
-    //     .args
-    //     .iter()
-    //     .any(|x| matches!(x, ast::Argument::VarArgs))
-    // {
-    //     return Ok(false);
-    // }
-    // let mut inferred = false;
-    // let mut temp_errors = Vec::new();
-    // // グローバルスコープで処理を行う
-    // in_global_scope!(context.scopes, {
-    //     in_global_scope!(context.types, {
-    //         for (arg_idx, _arg) in call_expr.args.iter().enumerate() {
-    //             let callee_arg = &callee.decl.args[arg_idx];
-    //             // 引数にジェネリクスを含まない場合は推論しない
-    //             // 含む場合、unknownになるはずなので推論する
-    //             match callee_arg {
-    //                 ast::Argument::VarArgs => unreachable!(),
-    //                 ast::Argument::Normal(callee_ty, _name) => {
-    //                     if infer_generic_args_recursively(
-    //                         &mut temp_errors,
-    //                         context,
-    //                         callee,
-    //                         callee_ty,
-    //                         &resolved_args[arg_idx].ty.clone(),
-    //                     )? {
-    //                         inferred = true;
-    //                     }
-    //                 }
-    //             }
-    //         }
-    //         // 関数の解決を試みる
-    //         resolve_function(context, callee)?;
-    //     });
-    // });
+    generic_args: &[Located<ast::GenericArgument>],
+    annotation: Option<&ResolvedType>,
+    inferred: &mut [Option<ResolvedType>],
+) {
+    let Some(annotation) = annotation else {
+        return;
+    };
+    let mut from_annotation = inferred.to_vec();
+    if unify_generic_arg(
+        &call_expr.name,
+        generic_args,
+        &callee.decl.return_type,
+        annotation,
+        &mut from_annotation,
+    )
+    .is_ok()
+    {
+        inferred.clone_from_slice(&from_annotation);
+    }
+}
 
-    // // 推論が成功した場合、一時エラーを追加する
-    // if inferred {
-    //     context.errors.borrow_mut().extend(temp_errors);
-    // }
+// 引数の型が型引数そのもので、すでに整数型が推論されているか
+fn infers_integer(
+    generic_args: &[Located<ast::GenericArgument>],
+    ty: &Located<UnresolvedType>,
+    inferred: &[Option<ResolvedType>],
+) -> bool {
+    let UnresolvedType::TypeRef(type_ref) = &ty.value else {
+        return false;
+    };
+    generic_args
+        .iter()
+        .position(|generic_arg| generic_arg.name == type_ref.name)
+        .and_then(|i| inferred[i].as_ref())
+        .is_some_and(ResolvedType::is_integer_type)
+}
 
-    // Ok(inferred)
+// 呼び出し先の引数や戻り値の型を、ジェネリックの型引数を当てはめて解決する
+fn resolve_callee_type(
+    context: &ResolverContext,
+    ty: &Located<UnresolvedType>,
+    generic_bindings: &[(String, ResolvedType)],
+) -> Result<ResolvedType, FaitalError> {
+    in_new_scope!(context.types, {
+        for (name, ty) in generic_bindings {
+            context.types.borrow_mut().add(name.clone(), ty.clone());
+        }
+        resolve_type(context, ty)
+    })
 }
 
-// 関数のジェネリック引数をアノテーションから推論する関数
-pub fn resolve_infer_generic_from_annotation(
+// 戻り値の型を省略した関数は、本体を解決したときに推論した型を使う。
+// 解決中の関数(再帰呼び出し)はまだ戻り値の型が決まっていないのでエラーにする
+fn inferred_return_type(
     context: &ResolverContext,
-    call_expr: &Located<&ast::CallExpr>,
-    callee: &ast::Function,
-    annotation: Option<&ResolvedType>,
-    // 推論に成功したジェネリクス引数のインデックスを返す
-) -> Result<Vec<usize>, FaitalError> {
-    // ジェネリック引数が存在しない場合は、推論を行わない
-    if call_expr.generic_args.is_some() || callee.decl.generic_args.is_none() {
-        return Ok(vec![]);
-    }
-    // アノテーションが存在する場合、推論を試みる
-    if let Some(annotation) = &annotation {
-        in_global_scope!(context.scopes, {
-            in_global_scope!(context.types, {
-                let mut temp_errors = Vec::new();
-                let inferred = infer_generic_args_recursively(
-                    &mut temp_errors,
-                    context,
-                    callee,
-                    &callee.decl.return_type.value,
-                    annotation,
-                )?;
-                // 推論が成功した場合、一時エラーを追加し、関数の解決を試みる
-                if inferred {
-                    context.errors.borrow_mut().extend(temp_errors);
-                    resolve_function(context, callee)?;
-                }
-                Ok((0..callee.decl.generic_args.as_ref().unwrap().len()).collect_vec())
-            })
-        })
-    } else {
-        Ok(vec![])
+    resolved_name: &str,
+    fn_name: &str,
+    range: ast::Range,
+) -> ResolvedType {
+    if let Some(function) = context.resolved_functions.borrow().get(resolved_name) {
+        return function.decl.return_type.clone();
     }
+    context.errors.borrow_mut().push(CompileError::new(
+        range,
+        CompileErrorKind::CannotInferRecursiveReturnType {
+            fn_name: fn_name.to_string(),
+        },
+    ));
+    ResolvedType::Unknown
 }
 
 // ジェネリック引数を持たない関数の解決を試みる関数
@@ -245,57 +254,71 @@ fn resolve_function_call_expr(
     callee: &ast::Function,
     annotation: Option<&ResolvedType>,
 ) -> Result<ResolvedExpression, FaitalError> {
+    // pubでない関数は、定義したファイルの外からは見つからないものとする
+    if !context.is_function_visible(&callee.decl.name) {
+        context.errors.borrow_mut().push(CompileError::new(
+            call_expr.range,
+            CompileErrorKind::FunctionNotFound {
+                name: call_expr.name.to_owned(),
+            },
+        ));
+        return Ok(ResolvedExpression {
+            range: call_expr.range,
+            ty: ResolvedType::Unknown,
+            kind: ExpressionKind::Unknown,
+        });
+    }
     {
-        // ジェネリック引数を持たない関数、ジェネリック引数を持つ関数、およびアノテーションからの推論を試みる
-        if let Some(callee_generic_args) = &callee.decl.generic_args {
-            let mut inferred_args_indices = HashSet::new();
-            if let Some(annotation) = annotation {
-                inferred_args_indices.extend(resolve_infer_generic_from_annotation(
-                    context,
-                    call_expr,
-                    callee,
-                    Some(annotation),
-                )?);
+        // ジェネリック関数は、型引数ごとに特殊化した関数を解決する
+        let generic_bindings = if callee.decl.generic_args.is_some() {
+            match resolve_call_with_generic_args(context, call_expr, callee, annotation)? {
+                Some(generic_bindings) => generic_bindings,
+                None => {
+                    return Ok(ResolvedExpression {
+                        range: call_expr.range,
+                        ty: ResolvedType::Unknown,
+                        kind: ExpressionKind::Unknown,
+                    })
+                }
             }
-            if inferred_args_indices.len() == callee_generic_args.len() {}
-            // if !resolve_call_with_generic_args(context, call_expr, callee)? {
-            //     inferred = false;
-            // }
+        } else if call_expr.generic_args.is_some() {
+            context.errors.borrow_mut().push(CompileError::new(
+                call_expr.range,
+                CompileErrorKind::UnnecessaryGenericArgs {
+                    name: call_expr.name.to_owned(),
+                },
+            ));
+            return Ok(ResolvedExpression {
+                range: call_expr.range,
+                ty: ResolvedType::Unknown,
+                kind: ExpressionKind::Unknown,
+            });
         } else {
             resolve_non_generic_function(context, callee)?;
-        }
-        // 推論が失敗した場合、引数からの推論を試みる
-        // if !inferred
-        //     && !resolve_infer_generic_from_arguments(context, call_expr, callee, &resolved_args)?
-        // {
-        //     context.errors.borrow_mut().push(CompileError::new(
-        //         call_expr.range,
-        //         CompileErrorKind::CannotInferGenericArgs {
-        //             name: call_expr.name.to_owned(),
-        //             message: "".to_string(),
-        //         },
-        //     ));
-        //     return Ok(ResolvedExpression {
-        //         ty: resolve_type(context, &callee.decl.return_type)?,
-        //         kind: ExpressionKind::Unknown,
-        //     });
-        // }
-
+            Vec::new()
+        };
         // 引数の解決を試みる
         let mut resolved_args = Vec::new();
-        let has_var_args = callee.decl.args.last() == Some(&ast::Argument::VarArgs);
+        let has_var_args = callee.decl.is_var_args();
 
-        // 可変長引数を持たない場合、引数の数が一致しなければエラーを返す
-        if !has_var_args && callee.decl.args.len() != call_expr.args.len() {
+        // 既定値を持たない引数が足りない場合と、可変長引数を持たずに引数が多すぎる場合はエラーを返す
+        let required_arg_count = callee.decl.required_arg_count();
+        let too_many_args = !has_var_args && call_expr.args.len() > callee.decl.args.len();
+        if call_expr.args.len() < required_arg_count || too_many_args {
             context.errors.borrow_mut().push(CompileError::new(
                 call_expr.range,
                 CompileErrorKind::MismatchFunctionArgCount {
                     name: call_expr.name.to_owned(),
-                    expected: callee.decl.args.len(),
+                    expected: if too_many_args {
+                        callee.decl.args.len()
+                    } else {
+                        required_arg_count
+                    },
                     actual: call_expr.args.len(),
                 },
             ));
             return Ok(ResolvedExpression {
+                range: call_expr.range,
                 ty: ResolvedType::Unknown,
                 kind: ExpressionKind::Unknown,
             });
@@ -312,8 +335,8 @@ fn resolve_function_call_expr(
                 ast::Argument::VarArgs => {
                     resolved_args.push(resolve_expression(context, arg.as_inner_deref(), None)?);
                 }
-                ast::Argument::Normal(ty, _name) => {
-                    let resolved_ty = resolve_type(context, ty)?;
+                ast::Argument::Normal { ty, .. } => {
+                    let resolved_ty = resolve_callee_type(context, ty, &generic_bindings)?;
                     let resolved_arg =
                         resolve_expression(context, arg.as_inner_deref(), Some(&resolved_ty))?;
                     if !resolved_ty.can_insert(&resolved_arg.ty) {
@@ -330,9 +353,66 @@ fn resolve_function_call_expr(
                 }
             }
         }
+        if callee.decl.is_intrinsic && callee.decl.name == "printf" {
+            check_printf_format(context, call_expr, &resolved_args);
+        }
+
+        // 省略された引数を既定値で補う。既定値は呼び出し元のローカル変数が見えないように、呼び出し先と同じグローバルスコープで解決する
+        for callee_arg in callee.decl.args.iter().skip(call_expr.args.len()) {
+            let ast::Argument::Normal {
+                ty,
+                default: Some(default),
+                ..
+            } = callee_arg
+            else {
+                continue;
+            };
+            let resolved_ty = resolve_callee_type(context, ty, &generic_bindings)?;
+            let resolved_default = in_global_scope!(context.scopes, {
+                in_global_scope!(context.types, {
+                    resolve_expression(context, default.as_inner_deref(), Some(&resolved_ty))
+                })
+            })?;
+            if !resolved_ty.can_insert(&resolved_default.ty) {
+                context.errors.borrow_mut().push(CompileError::new(
+                    default.range,
+                    CompileErrorKind::TypeMismatch {
+                        expected: resolved_ty.clone(),
+                        actual: resolved_default.ty.clone(),
+                    },
+                ));
+            }
+            resolved_args.push(resolved_default);
+        }
 
         // 戻り値の型を解決する
-        let mut resolved_return_ty = resolve_type(context, &callee.decl.return_type)?;
+        let callee_return_ty =
+            resolve_callee_type(context, &callee.decl.return_type, &generic_bindings)?;
+        // 特殊化した関数の名前は、resolve_functionと同じく型引数を当てはめた引数と戻り値の型から作る
+        let (callee_name, generic_args) = if callee.decl.generic_args.is_some() {
+            let mut param_types = Vec::new();
+            for callee_arg in &callee.decl.args {
+                if let ast::Argument::Normal { ty, .. } = callee_arg {
+                    param_types.push(resolve_callee_type(context, ty, &generic_bindings)?);
+                }
+            }
+            let name = mangle_fn_name(
+                &callee.decl.name,
+                &param_types.iter().collect::<Vec<_>>(),
+                &callee_return_ty,
+            );
+            let generic_args = generic_bindings.into_iter().map(|(_, ty)| ty).collect();
+            (name, Some(generic_args))
+        } else {
+            (callee.decl.name.clone(), None)
+        };
+
+        // 戻り値の型を省略した関数は、解決した本体から推論した型を返す
+        let mut resolved_return_ty = if let UnresolvedType::Infer = callee.decl.return_type.value {
+            inferred_return_type(context, &callee_name, &callee.decl.name, call_expr.range)
+        } else {
+            callee_return_ty
+        };
         // void* はアノテーションがあればその型として扱う
         if let Some(annotation) = annotation {
             if let ResolvedType::Ptr(inner) = &resolved_return_ty {
@@ -343,7 +423,7 @@ fn resolve_function_call_expr(
         };
 
         // varargsはintrinsicでしか定義しないので、引数の最後に来ないケースは想定しない。
-        if call_expr.args.len() != callee.decl.args.len() && !has_var_args {
+        if resolved_args.len() != callee.decl.args.len() && !has_var_args {
             context.errors.borrow_mut().push(CompileError::new(
                 call_expr.range,
                 CompileErrorKind::MismatchFunctionArgCount {
@@ -353,36 +433,17 @@ fn resolve_function_call_expr(
                 },
             ));
             return Ok(ResolvedExpression {
+                range: call_expr.range,
                 ty: resolved_return_ty,
                 kind: ExpressionKind::Unknown,
             });
         }
 
-        let mut generic_args = None;
-        if let Some(unresolved_generic_args) = &call_expr.generic_args {
-            let mut resolved_generic_args = Vec::new();
-            for generic_arg in unresolved_generic_args {
-                resolved_generic_args.push(resolve_type(context, generic_arg)?);
-            }
-            generic_args = Some(resolved_generic_args)
-        }
-
         // 解決された式を返す
         return Ok(resolved_ast::ResolvedExpression {
+            range: call_expr.range,
             kind: resolved_ast::ExpressionKind::CallExpr(resolved_ast::CallExpr {
-                callee: if callee.decl.generic_args.is_some() {
-                    mangle_fn_name(
-                        &call_expr.name,
-                        resolved_args
-                            .iter()
-                            .map(|x| &x.ty)
-                            .collect::<Vec<_>>()
-                            .as_slice(),
-                        &resolved_return_ty,
-                    )
-                } else {
-                    call_expr.name.clone()
-                },
+                callee: callee_name,
                 args: resolved_args,
                 generic_args,
             }),
@@ -400,17 +461,228 @@ pub fn resolve_interface_call_expr(
     todo!()
 }
 
+// &fのfが変数でもconstでもなく関数を指していれば、その関数のアドレスを返す
+pub fn resolve_function_ref(
+    context: &ResolverContext,
+    target: &ast::LocatedExpr,
+    range: ast::Range,
+) -> Result<Option<ResolvedExpression>, FaitalError> {
+    let Expression::VariableRef(variable_ref) = target.value.as_ref() else {
+        return Ok(None);
+    };
+    let name = &variable_ref.name;
+    if context.scopes.borrow().get(name).is_some() || context.constants.borrow().contains_key(name)
+    {
+        return Ok(None);
+    }
+    let invalid_function_ref = || {
+        context.errors.borrow_mut().push(CompileError::new(
+            range,
+            CompileErrorKind::InvalidFunctionRef { name: name.clone() },
+        ));
+        Ok(Some(ResolvedExpression {
+            range,
+            ty: ResolvedType::Unknown,
+            kind: ExpressionKind::Unknown,
+        }))
+    };
+    let is_overloaded = context.overloads_by_name.borrow().contains_key(name);
+    let callee = context.function_by_name.borrow().get(name).cloned();
+    let callee = match callee {
+        Some(callee) if context.is_function_visible(name) => callee,
+        // 関数でもなければ、変数が見つからないエラーとして扱う
+        _ if !is_overloaded => return Ok(None),
+        // オーバーロードされた関数は、どれを指すか決められない
+        _ => return invalid_function_ref(),
+    };
+    if callee.decl.generic_args.is_some() || callee.decl.is_var_args() {
+        return invalid_function_ref();
+    }
+    // 呼び出しと同じく、アドレスを取られた関数も出力に含める
+    resolve_non_generic_function(context, &callee)?;
+    // 引数と戻り値の型は、関数を定義したファイルから見えるものとして解決する
+    let ty = context.in_module_of_function(name, || -> Result<ResolvedType, FaitalError> {
+        let mut param_types = Vec::new();
+        for arg in &callee.decl.args {
+            if let ast::Argument::Normal { ty, .. } = arg {
+                param_types.push(resolve_type(context, ty)?);
+            }
+        }
+        let return_type = if let UnresolvedType::Infer = callee.decl.return_type.value {
+            inferred_return_type(context, name, name, range)
+        } else {
+            resolve_type(context, &callee.decl.return_type)?
+        };
+        Ok(ResolvedType::FnPtr(param_types, Box::new(return_type)))
+    })?;
+    Ok(Some(ResolvedExpression {
+        range,
+        ty,
+        kind: ExpressionKind::FunctionRef(resolved_ast::FunctionRefExpr { name: name.clone() }),
+    }))
+}
+
+// 関数ポインタ型の変数を呼び出す。既定値や可変長引数は持たないので、引数の数は型と一致しなければならない
+fn resolve_indirect_call_expr(
+    context: &ResolverContext,
+    call_expr: &Located<&ast::CallExpr>,
+    callee_ty: ResolvedType,
+) -> Result<ResolvedExpression, FaitalError> {
+    let ResolvedType::FnPtr(param_types, return_type) = &callee_ty else {
+        unreachable!()
+    };
+    if call_expr.args.len() != param_types.len() {
+        context.errors.borrow_mut().push(CompileError::new(
+            call_expr.range,
+            CompileErrorKind::MismatchFunctionArgCount {
+                name: call_expr.name.to_owned(),
+                expected: param_types.len(),
+                actual: call_expr.args.len(),
+            },
+        ));
+        return Ok(ResolvedExpression {
+            range: call_expr.range,
+            ty: ResolvedType::Unknown,
+            kind: ExpressionKind::Unknown,
+        });
+    }
+    let mut resolved_args = Vec::new();
+    for (arg, param_ty) in call_expr.args.iter().zip(param_types) {
+        let resolved_arg = resolve_expression(context, arg.as_inner_deref(), Some(param_ty))?;
+        if !param_ty.can_insert(&resolved_arg.ty) {
+            context.errors.borrow_mut().push(CompileError::new(
+                arg.range,
+                CompileErrorKind::TypeMismatch {
+                    expected: param_ty.clone(),
+                    actual: resolved_arg.ty.clone(),
+                },
+            ));
+        }
+        resolved_args.push(resolved_arg);
+    }
+    Ok(ResolvedExpression {
+        range: call_expr.range,
+        ty: (**return_type).clone(),
+        kind: ExpressionKind::IndirectCall(resolved_ast::IndirectCallExpr {
+            callee: Box::new(ResolvedExpression {
+                range: call_expr.range,
+                ty: callee_ty.clone(),
+                kind: ExpressionKind::VariableRef(resolved_ast::VariableRefExpr {
+                    name: call_expr.name.clone(),
+                }),
+            }),
+            args: resolved_args,
+        }),
+    })
+}
+
+// 両辺が同じ整数型であることを検査し、その型を結果の型にする。
+// 2つ目の引数は1つ目の型を注釈にするので、(wrapping_add x 1) の1はxの型になる
+fn resolve_arithmetic_builtin(
+    context: &ResolverContext,
+    call_expr: &Located<&ast::CallExpr>,
+    annotation: Option<&ResolvedType>,
+) -> Result<ResolvedExpression, FaitalError> {
+    let unknown = ResolvedExpression {
+        range: call_expr.range,
+        ty: ResolvedType::Unknown,
+        kind: ExpressionKind::Unknown,
+    };
+    let [lhs, rhs] = call_expr.args.as_slice() else {
+        context.errors.borrow_mut().push(CompileError::new(
+            call_expr.range,
+            CompileErrorKind::MismatchFunctionArgCount {
+                name: call_expr.name.to_owned(),
+                expected: 2,
+                actual: call_expr.args.len(),
+            },
+        ));
+        return Ok(unknown);
+    };
+    let annotation = annotation.filter(|ty| ty.is_integer_type());
+    let lhs = resolve_expression(context, lhs.as_inner_deref(), annotation)?;
+    let rhs_annotation = Some(&lhs.ty).filter(|ty| ty.is_integer_type());
+    let rhs_range = rhs.range;
+    let rhs = resolve_expression(context, rhs.as_inner_deref(), rhs_annotation)?;
+    if lhs.ty == ResolvedType::Unknown || rhs.ty == ResolvedType::Unknown {
+        return Ok(unknown);
+    }
+    for operand in [&lhs, &rhs] {
+        if !operand.ty.is_integer_type() {
+            context.errors.borrow_mut().push(CompileError::new(
+                operand.range,
+                CompileErrorKind::InvalidNumericOperand {
+                    actual: operand.ty.clone(),
+                },
+            ));
+            return Ok(unknown);
+        }
+    }
+    // 通常の二項演算と違い、型を揃える暗黙の変換はしない
+    if lhs.ty != rhs.ty {
+        context.errors.borrow_mut().push(CompileError::new(
+            rhs_range,
+            CompileErrorKind::TypeMismatch {
+                expected: lhs.ty.clone(),
+                actual: rhs.ty.clone(),
+            },
+        ));
+        return Ok(unknown);
+    }
+    Ok(ResolvedExpression {
+        range: call_expr.range,
+        ty: lhs.ty.clone(),
+        kind: ExpressionKind::CallExpr(resolved_ast::CallExpr {
+            callee: call_expr.name.clone(),
+            args: vec![lhs, rhs],
+            generic_args: None,
+        }),
+    })
+}
+
 // 関数呼び出し式の解決を試みる関数
 pub fn resolve_call_expr(
     context: &ResolverContext,
     call_expr: &Located<&ast::CallExpr>,
     annotation: Option<&ResolvedType>,
 ) -> Result<ResolvedExpression, FaitalError> {
+    // 関数ポインタ型の変数は、同名の関数より優先して呼び出す
+    let variable_ty = context.scopes.borrow().get(&call_expr.name).cloned();
+    if let Some(ty @ ResolvedType::FnPtr(_, _)) = variable_ty {
+        return resolve_indirect_call_expr(context, call_expr, ty);
+    }
+    // 同名の関数を定義していなければ、整数演算の組み込み関数として解決する。
+    // オーバーロードした関数はfunction_by_nameから元の名前が消えているので、overloads_by_nameも見る
+    if ArithmeticBuiltin::from_name(&call_expr.name).is_some()
+        && !context
+            .function_by_name
+            .borrow()
+            .contains_key(&call_expr.name)
+        && !context
+            .overloads_by_name
+            .borrow()
+            .contains_key(&call_expr.name)
+    {
+        return resolve_arithmetic_builtin(context, call_expr, annotation);
+    }
     // 関数名から関数を取得し、見つからない場合はエラーを返す
     let function_by_name = context.function_by_name.borrow();
     let interface_by_name = context.interface_by_name.borrow();
     let impls_by_name = context.impls_by_name.borrow();
-    if let Some(callee) = function_by_name.get(&call_expr.name) {
+    if context
+        .overloads_by_name
+        .borrow()
+        .contains_key(&call_expr.name)
+    {
+        match select_overload(context, call_expr)? {
+            Some(callee) => resolve_function_call_expr(context, call_expr, &callee, annotation),
+            None => Ok(ResolvedExpression {
+                ty: ResolvedType::Unknown,
+                kind: ExpressionKind::Unknown,
+                range: call_expr.range,
+            }),
+        }
+    } else if let Some(callee) = function_by_name.get(&call_expr.name) {
         resolve_function_call_expr(context, call_expr, callee, annotation)
     } else if let Some(interface) = interface_by_name.get(&call_expr.name) {
         let mut resolved_arg_types = vec![];
@@ -450,6 +722,7 @@ pub fn resolve_call_expr(
                     },
                 ));
                 Ok(ResolvedExpression {
+                    range: call_expr.range,
                     ty: ResolvedType::Unknown,
                     kind: ExpressionKind::Unknown,
                 })
@@ -458,15 +731,103 @@ pub fn resolve_call_expr(
             todo!()
         }
     } else {
-        context.errors.borrow_mut().push(CompileError::new(
-            call_expr.range,
-            CompileErrorKind::FunctionNotFound {
+        let kind = match variable_ty {
+            Some(ty) => CompileErrorKind::NotCallable {
                 name: call_expr.name.to_owned(),
+                ty,
             },
-        ));
+            None => CompileErrorKind::FunctionNotFound {
+                name: call_expr.name.to_owned(),
+            },
+        };
+        context
+            .errors
+            .borrow_mut()
+            .push(CompileError::new(call_expr.range, kind));
         Ok(ResolvedExpression {
+            range: call_expr.range,
             ty: ResolvedType::Unknown,
             kind: ExpressionKind::Unknown,
         })
     }
 }
+
+// x.len(a) を X.len(&x a) の呼び出しとして解決する。レシーバーがポインタならそのまま渡す
+pub fn resolve_method_call_expr(
+    context: &ResolverContext,
+    method_call: &Located<&ast::MethodCallExpr>,
+    annotation: Option<&ResolvedType>,
+) -> Result<ResolvedExpression, FaitalError> {
+    // 型を調べるためだけにレシーバーを解決する。呼び出しの引数として解決し直すので、ここで積んだエラーは捨てる
+    let error_count = context.errors.borrow().len();
+    let warning_count = context.warnings.borrow().len();
+    let receiver_ty = resolve_expression(context, method_call.receiver.as_inner_deref(), None)?.ty;
+    context.errors.borrow_mut().truncate(error_count);
+    context.warnings.borrow_mut().truncate(warning_count);
+
+    let (struct_type, is_ptr) = match &receiver_ty {
+        ResolvedType::StructLike(struct_type) => (struct_type, false),
+        ResolvedType::Ptr(inner) => match inner.as_ref() {
+            ResolvedType::StructLike(struct_type) => (struct_type, true),
+            _ => return Ok(method_not_found(context, method_call, &receiver_ty)),
+        },
+        // レシーバーのエラーは解決し直したときに積む
+        ResolvedType::Unknown => {
+            resolve_expression(context, method_call.receiver.as_inner_deref(), None)?;
+            return Ok(ResolvedExpression {
+                range: method_call.range,
+                ty: ResolvedType::Unknown,
+                kind: ExpressionKind::Unknown,
+            });
+        }
+        _ => return Ok(method_not_found(context, method_call, &receiver_ty)),
+    };
+    let name = method_name(&struct_type.non_generic_name, &method_call.name);
+    if !context.function_by_name.borrow().contains_key(&name) {
+        return Ok(method_not_found(context, method_call, &receiver_ty));
+    }
+    let self_arg = if is_ptr {
+        method_call.receiver.clone()
+    } else {
+        Located {
+            range: method_call.receiver.range,
+            value: Box::new(Expression::AddressOf(ast::AddressOfExpr {
+                target: method_call.receiver.clone(),
+            })),
+        }
+    };
+    let call_expr = ast::CallExpr {
+        name,
+        generic_args: None,
+        args: std::iter::once(self_arg)
+            .chain(method_call.args.iter().cloned())
+            .collect(),
+    };
+    resolve_call_expr(
+        context,
+        &Located {
+            range: method_call.range,
+            value: &call_expr,
+        },
+        annotation,
+    )
+}
+
+fn method_not_found(
+    context: &ResolverContext,
+    method_call: &Located<&ast::MethodCallExpr>,
+    receiver_ty: &ResolvedType,
+) -> ResolvedExpression {
+    context.errors.borrow_mut().push(CompileError::new(
+        method_call.range,
+        CompileErrorKind::MethodNotFound {
+            name: method_call.name.clone(),
+            type_name: receiver_ty.to_string(),
+        },
+    ));
+    ResolvedExpression {
+        range: method_call.range,
+        ty: ResolvedType::Unknown,
+        kind: ExpressionKind::Unknown,
+    }
+}
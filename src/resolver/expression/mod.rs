@@ -4,22 +4,145 @@ mod call;
 mod variable_decl;
 
 use crate::ast::{Expression, Located, TypeDefKind};
-use crate::resolved_ast::{
-    ExpressionKind, IndexAccessExpr, ResolvedExpression, ResolvedStructType, ResolvedType,
-};
+use crate::resolved_ast::{ExpressionKind, IndexAccessExpr, ResolvedExpression, ResolvedType};
 use crate::resolver::ty::resolve_type;
 use crate::{ast, in_global_scope, in_new_scope, resolved_ast};
 
 use self::assignment::resolve_assignment;
 use self::binary::resolve_binary_expression;
-use self::call::resolve_call_expr;
+use self::call::{resolve_call_expr, resolve_function_ref, resolve_method_call_expr};
 use self::variable_decl::resolve_variable_decl;
 
-use super::ty::get_resolved_struct_name;
+use super::constant::fold_constant_expression;
+use super::ty::{complete_struct_type, enum_underlying_type, enum_variant_values, tuple_type};
 use super::{
     error::*, mangle_fn_name, resolve_function, BinaryOp, MultiOp, ResolverContext, UnaryOp,
 };
 
+// ループの本体を解決する間だけbreakとcontinueを許可する
+fn resolve_loop_body(
+    context: &ResolverContext,
+    body: &[ast::LocatedExpr],
+) -> Result<Vec<ResolvedExpression>, FaitalError> {
+    *context.loop_depth.borrow_mut() += 1;
+    let body = in_new_scope!(context.scopes, {
+        let mut resolved_body = Vec::new();
+        let mut diverged = false;
+        for expr in body {
+            let resolved_expr = resolve_expression(context, expr.as_deref(), None)?;
+            if diverged {
+                continue;
+            }
+            diverged = diverges(&resolved_expr);
+            resolved_body.push(resolved_expr);
+            if diverged && resolved_body.len() < body.len() {
+                context.warnings.borrow_mut().push(CompileWarning::new(
+                    body[resolved_body.len()].range,
+                    CompileWarningKind::UnreachableCode,
+                ));
+            }
+        }
+        Ok(resolved_body)
+    });
+    *context.loop_depth.borrow_mut() -= 1;
+    body
+}
+
+// break/continueのように、評価すると必ずその先へ進まない式かどうか
+fn diverges(expr: &ResolvedExpression) -> bool {
+    match &expr.kind {
+        ExpressionKind::Break | ExpressionKind::Continue => true,
+        ExpressionKind::If(if_expr) => diverges(&if_expr.then) && diverges(&if_expr.els),
+        _ => false,
+    }
+}
+
+fn resolve_for_expression(
+    context: &ResolverContext,
+    for_expr: &ast::ForExpr,
+) -> Result<resolved_ast::ForExpr, FaitalError> {
+    let init = for_expr
+        .init
+        .as_ref()
+        .map(|init| resolve_expression(context, init.as_deref(), None).map(Box::new))
+        .transpose()?;
+    let cond = match &for_expr.cond {
+        Some(cond) => {
+            let condition_expr =
+                resolve_expression(context, cond.as_deref(), Some(&ResolvedType::Bool))?;
+            if !matches!(condition_expr.ty, ResolvedType::Bool) {
+                context.errors.borrow_mut().push(CompileError::new(
+                    cond.range,
+                    CompileErrorKind::TypeMismatch {
+                        expected: ResolvedType::Bool,
+                        actual: condition_expr.ty.clone(),
+                    },
+                ));
+            }
+            Some(Box::new(condition_expr))
+        }
+        None => None,
+    };
+    let body = resolve_loop_body(context, &for_expr.body)?;
+    let step = for_expr
+        .step
+        .as_ref()
+        .map(|step| resolve_expression(context, step.as_deref(), None).map(Box::new))
+        .transpose()?;
+    Ok(resolved_ast::ForExpr {
+        init,
+        cond,
+        step,
+        body,
+    })
+}
+
+// Color.Red のようなバリアントの参照を整数リテラルに置き換える。enumの参照でなければNoneを返す
+fn resolve_enum_variant(
+    context: &ResolverContext,
+    range: ast::Range,
+    field_access_expr: &ast::FieldAccessExpr,
+) -> Option<ResolvedExpression> {
+    let Expression::VariableRef(variable_ref) = field_access_expr.target.value.as_ref() else {
+        return None;
+    };
+    // 同名の変数があればそちらを優先する
+    if context.scopes.borrow().get(&variable_ref.name).is_some()
+        || !context.is_type_visible(&variable_ref.name)
+    {
+        return None;
+    }
+    let type_defs = context.type_defs.borrow();
+    let TypeDefKind::Enum(enum_def) = &type_defs.get(&variable_ref.name)?.kind else {
+        return None;
+    };
+    let ty = enum_underlying_type(enum_def);
+    let Some((_, value)) = enum_variant_values(enum_def)
+        .into_iter()
+        .find(|(name, _)| *name == field_access_expr.field_name)
+    else {
+        context.errors.borrow_mut().push(CompileError::new(
+            range,
+            CompileErrorKind::VariantNotFound {
+                variant_name: field_access_expr.field_name.clone(),
+                enum_name: variable_ref.name.clone(),
+            },
+        ));
+        return Some(ResolvedExpression {
+            ty,
+            kind: ExpressionKind::Unknown,
+            range,
+        });
+    };
+    Some(ResolvedExpression {
+        range,
+        ty,
+        kind: ExpressionKind::NumberLiteral(resolved_ast::NumberLiteral {
+            value: value.to_string(),
+        }),
+    })
+}
+
 pub(crate) fn resolve_expression(
     context: &ResolverContext,
     loc_expr: Located<&ast::Expression>,
@@ -32,17 +155,19 @@ pub(crate) fn resolve_expression(
                     name: variable_ref.name.clone(),
                 });
 
+            // 変数の型はアノテーションで上書きせず、代入先で型の不一致として扱う
             if let Some(ty) = context.scopes.borrow().get(&variable_ref.name) {
-                let resolved_type = if let Some(annotation) = annotation {
-                    annotation
-                } else {
-                    ty
-                };
-
                 Ok(resolved_ast::ResolvedExpression {
-                    ty: resolved_type.clone(),
+                    range: loc_expr.range,
+                    ty: ty.clone(),
                     kind: expr_kind,
                 })
+            } else if let Some(value) = context.constants.borrow().get(&variable_ref.name) {
+                // constは評価済みの値を、参照した位置に埋め込む
+                Ok(ResolvedExpression {
+                    range: loc_expr.range,
+                    ..value.clone()
+                })
             } else {
                 context.errors.borrow_mut().push(CompileError::new(
                     loc_expr.range,
@@ -51,6 +176,7 @@ pub(crate) fn resolve_expression(
                     },
                 ));
                 Ok(ResolvedExpression {
+                    range: loc_expr.range,
                     ty: ResolvedType::Unknown,
                     kind: expr_kind,
                 })
@@ -60,41 +186,138 @@ pub(crate) fn resolve_expression(
             let kind = resolved_ast::ExpressionKind::NumberLiteral(resolved_ast::NumberLiteral {
                 value: number_literal.value.clone(),
             });
-            let ty = if let Some(annotation) = annotation {
+            // 接尾辞があれば文脈によらずその型に固定する
+            // 数値型以外のアノテーションは無視し、代入先で型の不一致として扱う
+            // 整数リテラルは浮動小数点型にも変換できる
+            let ty = if let Some(suffix) = &number_literal.suffix {
+                context.types.borrow().get(suffix).cloned().unwrap()
+            } else if let Some(annotation) =
+                annotation.filter(|ty| ty.is_integer_type() || ty.is_float_type())
+            {
                 annotation.clone()
             } else if number_literal.value.parse::<i32>().is_ok() {
                 ResolvedType::I32
             } else if number_literal.value.parse::<i64>().is_ok() {
                 ResolvedType::I64
-            } else if number_literal.value.parse::<u64>().is_ok() {
-                ResolvedType::U64
             } else {
-                unreachable!()
+                // u64にも収まらない値は下で範囲外として報告する
+                ResolvedType::U64
             };
 
-            Ok(ResolvedExpression { ty, kind })
-        }
-        Expression::Binary(bin_expr) => {
-            resolve_binary_expression(context, &Located::transfer(loc_expr, bin_expr))
-        }
-        Expression::Unary(unary_expr) => {
-            let operand = resolve_expression(context, unary_expr.operand.as_deref(), None)?;
-            if matches!(unary_expr.op, UnaryOp::Not) && !matches!(operand.ty, ResolvedType::Bool) {
+            if !integer_literal_fits(&number_literal.value, &ty, context.is_64_bit()) {
                 context.errors.borrow_mut().push(CompileError::new(
                     loc_expr.range,
-                    CompileErrorKind::TypeMismatch {
-                        expected: ResolvedType::Bool,
-                        actual: operand.ty.clone(),
+                    CompileErrorKind::IntegerLiteralOutOfRange {
+                        value: number_literal.value.clone(),
+                        ty: ty.clone(),
                     },
                 ));
+                return Ok(ResolvedExpression {
+                    range: loc_expr.range,
+                    ty,
+                    kind: resolved_ast::ExpressionKind::Unknown,
+                });
             }
-            Ok(resolved_ast::ResolvedExpression {
+
+            Ok(ResolvedExpression {
+                ty,
+                kind,
+                range: loc_expr.range,
+            })
+        }
+        // 文字リテラルはu8の数値リテラルとして扱う
+        Expression::CharLiteral(char_literal) => Ok(ResolvedExpression {
+            range: loc_expr.range,
+            ty: ResolvedType::U8,
+            kind: resolved_ast::ExpressionKind::NumberLiteral(resolved_ast::NumberLiteral {
+                value: char_literal.value.to_string(),
+            }),
+        }),
+        Expression::FloatLiteral(float_literal) => {
+            let kind = resolved_ast::ExpressionKind::FloatLiteral(resolved_ast::FloatLiteral {
+                value: float_literal.value.clone(),
+            });
+            let ty = annotation
+                .filter(|ty| ty.is_float_type())
+                .cloned()
+                .unwrap_or(ResolvedType::F64);
+            Ok(ResolvedExpression {
+                ty,
+                kind,
+                range: loc_expr.range,
+            })
+        }
+        Expression::Binary(bin_expr) => {
+            let error_count = context.errors.borrow().len();
+            let resolved = resolve_binary_expression(
+                context,
+                &Located::transfer(loc_expr, bin_expr),
+                annotation,
+            )?;
+            // エラーのある式は畳み込まず、同じ箇所に警告を重ねない
+            if context.errors.borrow().len() > error_count {
+                return Ok(resolved);
+            }
+            Ok(fold_constant_expression(context, resolved))
+        }
+        Expression::Unary(unary_expr) => {
+            let error_count = context.errors.borrow().len();
+            let ty = match unary_expr.op {
+                UnaryOp::Not => None,
+                // (:= x: i64 (- 5)) のようにリテラルの型を注釈から決められるようにする
+                UnaryOp::Neg | UnaryOp::BitNot => annotation,
+            };
+            let operand = resolve_expression(context, unary_expr.operand.as_deref(), ty)?;
+            let ty = match unary_expr.op {
+                UnaryOp::Not => {
+                    if !matches!(operand.ty, ResolvedType::Bool) {
+                        context.errors.borrow_mut().push(CompileError::new(
+                            loc_expr.range,
+                            CompileErrorKind::TypeMismatch {
+                                expected: ResolvedType::Bool,
+                                actual: operand.ty.clone(),
+                            },
+                        ));
+                    }
+                    ResolvedType::Bool
+                }
+                UnaryOp::Neg => {
+                    if !operand.ty.is_signed_integer_type() {
+                        context.errors.borrow_mut().push(CompileError::new(
+                            loc_expr.range,
+                            CompileErrorKind::InvalidOperand {
+                                op: unary_expr.op.into(),
+                                actual: operand.ty.clone(),
+                            },
+                        ));
+                    }
+                    operand.ty.clone()
+                }
+                UnaryOp::BitNot => {
+                    if !operand.ty.is_integer_type() {
+                        context.errors.borrow_mut().push(CompileError::new(
+                            loc_expr.range,
+                            CompileErrorKind::InvalidOperand {
+                                op: unary_expr.op.into(),
+                                actual: operand.ty.clone(),
+                            },
+                        ));
+                    }
+                    operand.ty.clone()
+                }
+            };
+            let resolved = resolved_ast::ResolvedExpression {
                 kind: resolved_ast::ExpressionKind::Unary(resolved_ast::UnaryExpr {
                     op: unary_expr.op,
                     operand: Box::new(operand),
                 }),
-                ty: ResolvedType::Bool,
-            })
+                ty,
+                range: loc_expr.range,
+            };
+            if context.errors.borrow().len() > error_count {
+                return Ok(resolved);
+            }
+            Ok(fold_constant_expression(context, resolved))
         }
         Expression::Multi(multi_expr) => {
             let mut resolved_operands = Vec::new();
@@ -116,6 +339,7 @@ pub(crate) fn resolve_expression(
                         }
                     }
                     Ok(resolved_ast::ResolvedExpression {
+                        range: loc_expr.range,
                         kind: resolved_ast::ExpressionKind::Multi(resolved_ast::MultiExpr {
                             op: multi_expr.op,
                             operands: resolved_operands,
@@ -128,13 +352,79 @@ pub(crate) fn resolve_expression(
         Expression::Call(call_expr) => {
             resolve_call_expr(context, &Located::transfer(loc_expr, call_expr), annotation)
         }
+        Expression::MethodCall(method_call) => resolve_method_call_expr(
+            context,
+            &Located::transfer(loc_expr, method_call),
+            annotation,
+        ),
         Expression::DerefExpr(deref_expr) => {
             let target = resolve_expression(context, deref_expr.target.as_deref(), None)?;
+            // ポインタ以外のデリファレンスは従来通りi32として扱う
+            let ty = match &target.ty {
+                ResolvedType::Ptr(inner) if **inner != ResolvedType::Void => (**inner).clone(),
+                ResolvedType::Unknown => ResolvedType::Unknown,
+                _ => ResolvedType::I32,
+            };
             Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
                 kind: resolved_ast::ExpressionKind::Deref(resolved_ast::DerefExpr {
                     target: Box::new(target),
                 }),
-                ty: ResolvedType::I32,
+                ty,
+            })
+        }
+        Expression::AddressOf(address_of_expr) => {
+            if let Some(function_ref) =
+                resolve_function_ref(context, &address_of_expr.target, loc_expr.range)?
+            {
+                return Ok(function_ref);
+            }
+            let target = resolve_expression(context, address_of_expr.target.as_deref(), None)?;
+            let is_lvalue = match address_of_expr.target.value.as_ref() {
+                // constは値に置き換わっているのでアドレスを持たない
+                Expression::VariableRef(_) => {
+                    matches!(target.kind, resolved_ast::ExpressionKind::VariableRef(_))
+                }
+                Expression::IndexAccess(_)
+                | Expression::FieldAccess(_)
+                | Expression::DerefExpr(_) => true,
+                _ => false,
+            };
+            let resolved_ty = if is_lvalue {
+                ResolvedType::Ptr(Box::new(target.ty.clone()))
+            } else {
+                context.errors.borrow_mut().push(CompileError::new(
+                    loc_expr.range,
+                    CompileErrorKind::InvalidAddressOf,
+                ));
+                ResolvedType::Unknown
+            };
+            Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
+                kind: resolved_ast::ExpressionKind::AddressOf(resolved_ast::AddressOfExpr {
+                    target: Box::new(target),
+                }),
+                ty: resolved_ty,
+            })
+        }
+        Expression::Cast(cast_expr) => {
+            let target_ty = resolve_type(context, &cast_expr.target_ty)?;
+            let target = resolve_expression(context, cast_expr.expr.as_deref(), None)?;
+            if !is_valid_cast(&target.ty, &target_ty) {
+                context.errors.borrow_mut().push(CompileError::new(
+                    loc_expr.range,
+                    CompileErrorKind::InvalidCast {
+                        from: target.ty.clone(),
+                        to: target_ty.clone(),
+                    },
+                ));
+            }
+            Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
+                kind: resolved_ast::ExpressionKind::Cast(resolved_ast::CastExpr {
+                    target: Box::new(target),
+                }),
+                ty: target_ty,
             })
         }
         Expression::IndexAccess(index_access_expr) => {
@@ -144,18 +434,20 @@ pub(crate) fn resolve_expression(
                 index_access_expr.index.as_deref(),
                 Some(&ResolvedType::USize),
             )?;
-            let resolved_ty = if let ResolvedType::Ptr(ptr) = &target.ty {
-                *ptr.clone()
-            } else {
-                context.errors.borrow_mut().push(CompileError::new(
-                    loc_expr.range,
-                    CompileErrorKind::InvalidIndexAccess {
-                        ty: target.clone().ty,
-                    },
-                ));
-                ResolvedType::Unknown
-            };
+            let resolved_ty =
+                if let ResolvedType::Ptr(inner) | ResolvedType::Array(inner, _) = &target.ty {
+                    *inner.clone()
+                } else {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        loc_expr.range,
+                        CompileErrorKind::InvalidIndexAccess {
+                            ty: target.clone().ty,
+                        },
+                    ));
+                    ResolvedType::Unknown
+                };
             Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
                 kind: resolved_ast::ExpressionKind::IndexAccess(IndexAccessExpr {
                     target: Box::new(target),
                     index: Box::new(index),
@@ -164,8 +456,26 @@ pub(crate) fn resolve_expression(
             })
         }
         Expression::FieldAccess(field_access_expr) => {
-            let target = resolve_expression(context, field_access_expr.target.as_deref(), None)?;
+            if let Some(variant) = resolve_enum_variant(context, loc_expr.range, field_access_expr)
+            {
+                return Ok(variant);
+            }
+            let mut target =
+                resolve_expression(context, field_access_expr.target.as_deref(), None)?;
+            // メソッドのselfのようなstructへのポインタは、暗黙にデリファレンスしてフィールドを読む
+            if let ResolvedType::Ptr(inner) = &target.ty {
+                if let ResolvedType::StructLike(_) = inner.as_ref() {
+                    target = resolved_ast::ResolvedExpression {
+                        range: target.range,
+                        ty: (**inner).clone(),
+                        kind: resolved_ast::ExpressionKind::Deref(resolved_ast::DerefExpr {
+                            target: Box::new(target),
+                        }),
+                    };
+                }
+            }
             let resolved_ty = if let ResolvedType::StructLike(struct_ty) = &target.ty {
+                let struct_ty = complete_struct_type(context, struct_ty);
                 if let Some((_name, ty)) = struct_ty
                     .fields
                     .iter()
@@ -193,6 +503,7 @@ pub(crate) fn resolve_expression(
                 ResolvedType::Unknown
             };
             Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
                 kind: resolved_ast::ExpressionKind::FieldAccess(resolved_ast::FieldAccessExpr {
                     target: Box::new(target),
                     field_name: field_access_expr.field_name.clone(),
@@ -201,25 +512,38 @@ pub(crate) fn resolve_expression(
             })
         }
         Expression::StringLiteral(str_literal) => Ok(resolved_ast::ResolvedExpression {
+            range: loc_expr.range,
             kind: resolved_ast::ExpressionKind::StringLiteral(resolved_ast::StringLiteral {
                 value: str_literal.value.clone(),
             }),
             ty: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
         }),
         Expression::BoolLiteral(bool_literal) => Ok(resolved_ast::ResolvedExpression {
+            range: loc_expr.range,
             kind: resolved_ast::ExpressionKind::BoolLiteral(resolved_ast::BoolLiteral {
                 value: bool_literal.value,
             }),
             ty: ResolvedType::Bool,
         }),
+        // ポインタ型の注釈があればその型にする
+        Expression::NullLiteral => Ok(resolved_ast::ResolvedExpression {
+            range: loc_expr.range,
+            kind: resolved_ast::ExpressionKind::NullLiteral,
+            ty: match annotation {
+                Some(ty) if ty.is_pointer_type() => ty.clone(),
+                _ => ResolvedType::Ptr(Box::new(ResolvedType::Unknown)),
+            },
+        }),
         Expression::StructLiteral(struct_literal_expr) => {
             let mut resolved_fields = Vec::new();
-            let mut resolved_generic_args = Vec::new();
 
+            // enumと、他のファイルのpubでない構造体は構造体リテラルで作れない
             let typedef = context
                 .type_defs
                 .borrow()
                 .get(&struct_literal_expr.name)
+                .filter(|typedef| matches!(typedef.kind, TypeDefKind::StructLike(_)))
+                .filter(|_| context.is_type_visible(&struct_literal_expr.name))
                 .cloned();
             if typedef.is_none() {
                 context.errors.borrow_mut().push(CompileError::new(
@@ -229,6 +553,7 @@ pub(crate) fn resolve_expression(
                     },
                 ));
                 return Ok(resolved_ast::ResolvedExpression {
+                    range: loc_expr.range,
                     ty: ResolvedType::Unknown,
                     kind: resolved_ast::ExpressionKind::StructLiteral(
                         resolved_ast::StructLiteral { fields: Vec::new() },
@@ -236,95 +561,125 @@ pub(crate) fn resolve_expression(
                 });
             };
             let typedef = typedef.unwrap();
-            let TypeDefKind::StructLike(struct_def) = &typedef.kind;
-
-            in_new_scope!(context.types, {
-                if let Some(generic_args_in_def) = &struct_def.generic_args {
-                    for (i, generic_arg) in generic_args_in_def.iter().enumerate() {
-                        let resolved_generic_arg = resolve_type(
-                            context,
-                            &struct_literal_expr.generic_args.as_ref().unwrap()[i],
-                        )?;
-                        resolved_generic_args.push(resolved_generic_arg.clone());
-                        context
-                            .types
-                            .borrow_mut()
-                            .add(generic_arg.name.clone(), resolved_generic_arg);
-                    }
-                }
-                for (field_name, ty) in &struct_def.fields {
-                    let field_in_expr = if let Some(expr) = struct_literal_expr
-                        .fields
-                        .iter()
-                        .find(|x| &x.0 == field_name)
-                    {
-                        expr
-                    } else {
-                        context.errors.borrow_mut().push(CompileError::new(
-                            loc_expr.range,
-                            CompileErrorKind::FieldNotFound {
-                                field_name: field_name.clone(),
-                                type_name: struct_literal_expr.name.clone(),
-                            },
-                        ));
-                        continue;
-                    };
-                    if let Some(generic_args) = &struct_def.generic_args {
-                        for (i, generic_arg) in generic_args.iter().enumerate() {
-                            let resolved_type = resolve_type(
-                                context,
-                                &struct_literal_expr.generic_args.as_ref().unwrap()[i],
-                            )?;
-                            context
-                                .types
-                                .borrow_mut()
-                                .add(generic_arg.name.clone(), resolved_type);
-                        }
-                    }
+            let TypeDefKind::StructLike(struct_def) = &typedef.kind else {
+                unreachable!()
+            };
 
-                    let expected_ty = resolve_type(context, ty)?;
-                    let resolved_field = resolve_expression(
-                        context,
-                        field_in_expr.1.as_deref(),
-                        Some(&expected_ty.clone()),
-                    )?;
+            // 定義にないフィールドと、同じフィールドの重複を報告する
+            for (i, (field_name, field_expr)) in struct_literal_expr.fields.iter().enumerate() {
+                if !struct_def.fields.iter().any(|(name, _)| name == field_name) {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        field_expr.range,
+                        CompileErrorKind::FieldNotFound {
+                            field_name: field_name.clone(),
+                            type_name: struct_literal_expr.name.clone(),
+                        },
+                    ));
+                } else if struct_literal_expr.fields[..i]
+                    .iter()
+                    .any(|(name, _)| name == field_name)
+                {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        field_expr.range,
+                        CompileErrorKind::DuplicateField {
+                            field_name: field_name.clone(),
+                            type_name: struct_literal_expr.name.clone(),
+                        },
+                    ));
+                }
+            }
 
-                    if !expected_ty.can_insert(&resolved_field.ty) {
-                        context.errors.borrow_mut().push(CompileError::new(
-                            loc_expr.range,
-                            CompileErrorKind::TypeMismatch {
-                                expected: expected_ty.clone(),
-                                actual: resolved_field.ty.clone(),
-                            },
-                        ));
-                    }
+            // 型引数の検査と、型引数を当てはめたフィールドの型の解決は、型を参照するときと同じにする
+            let struct_ty = resolve_type(
+                context,
+                &Located {
+                    range: loc_expr.range,
+                    value: ast::UnresolvedType::TypeRef(ast::TypeRef {
+                        name: struct_literal_expr.name.clone(),
+                        generic_args: struct_literal_expr.generic_args.clone(),
+                    }),
+                },
+            )?;
+            let ResolvedType::StructLike(struct_ty) = struct_ty else {
+                return Ok(resolved_ast::ResolvedExpression {
+                    range: loc_expr.range,
+                    ty: ResolvedType::Unknown,
+                    kind: resolved_ast::ExpressionKind::StructLiteral(
+                        resolved_ast::StructLiteral { fields: Vec::new() },
+                    ),
+                });
+            };
+            // フィールドの値は、struct側の型引数が見えない呼び出し元のスコープで解決する
+            for (field_name, expected_ty) in &struct_ty.fields {
+                let field_in_expr = if let Some(expr) = struct_literal_expr
+                    .fields
+                    .iter()
+                    .find(|x| &x.0 == field_name)
+                {
+                    expr
+                } else {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        loc_expr.range,
+                        CompileErrorKind::MissingField {
+                            field_name: field_name.clone(),
+                            type_name: struct_literal_expr.name.clone(),
+                        },
+                    ));
+                    continue;
+                };
+                let resolved_field =
+                    resolve_expression(context, field_in_expr.1.as_deref(), Some(expected_ty))?;
 
-                    resolved_fields.push((field_name.clone(), resolved_field));
+                if !expected_ty.can_insert(&resolved_field.ty) {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        field_in_expr.1.range,
+                        CompileErrorKind::TypeMismatch {
+                            expected: expected_ty.clone(),
+                            actual: resolved_field.ty.clone(),
+                        },
+                    ));
                 }
-            });
 
-            let struct_name = get_resolved_struct_name(
-                &struct_literal_expr.name,
-                if struct_def.generic_args.is_some() {
-                    Some(&resolved_generic_args)
-                } else {
-                    None
-                },
-            );
+                resolved_fields.push((field_name.clone(), resolved_field));
+            }
+
             Ok(resolved_ast::ResolvedExpression {
-                ty: ResolvedType::StructLike(ResolvedStructType {
-                    name: struct_name,
-                    non_generic_name: typedef.name.clone(),
-                    fields: resolved_fields
-                        .iter()
-                        .map(|(name, expr)| (name.clone(), expr.ty.clone()))
-                        .collect(),
-                    generic_args: if resolved_generic_args.is_empty() {
-                        None
-                    } else {
-                        Some(resolved_generic_args)
-                    },
+                range: loc_expr.range,
+                ty: ResolvedType::StructLike(struct_ty),
+                kind: resolved_ast::ExpressionKind::StructLiteral(resolved_ast::StructLiteral {
+                    fields: resolved_fields,
                 }),
+            })
+        }
+        // タプルは要素をフィールドに持つstructのリテラルにする
+        Expression::TupleLiteral(tuple_literal_expr) => {
+            let element_annotations = match annotation {
+                Some(ResolvedType::StructLike(struct_ty))
+                    if struct_ty.is_tuple()
+                        && struct_ty.fields.len() == tuple_literal_expr.elements.len() =>
+                {
+                    struct_ty.fields.iter().map(|(_, ty)| Some(ty)).collect()
+                }
+                _ => vec![None; tuple_literal_expr.elements.len()],
+            };
+            let mut resolved_fields = Vec::new();
+            for (i, (element, element_annotation)) in tuple_literal_expr
+                .elements
+                .iter()
+                .zip(element_annotations)
+                .enumerate()
+            {
+                let resolved_element =
+                    resolve_expression(context, element.as_deref(), element_annotation)?;
+                resolved_fields.push((i.to_string(), resolved_element));
+            }
+            let ty = tuple_type(
+                context,
+                resolved_fields.iter().map(|(_, x)| x.ty.clone()).collect(),
+            );
+            Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
+                ty,
                 kind: resolved_ast::ExpressionKind::StructLiteral(resolved_ast::StructLiteral {
                     fields: resolved_fields,
                 }),
@@ -333,10 +688,35 @@ pub(crate) fn resolve_expression(
         Expression::SizeOf(sizeof_expr) => {
             let resolved_ty = resolve_type(context, &sizeof_expr.ty)?;
             Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
                 kind: resolved_ast::ExpressionKind::SizeOf(resolved_ty),
                 ty: ResolvedType::USize,
             })
         }
+        Expression::VaArg(va_arg_expr) => {
+            let resolved_ty = resolve_type(context, &va_arg_expr.ty)?;
+            if !*context.in_var_args_function.borrow() {
+                context.errors.borrow_mut().push(CompileError::new(
+                    loc_expr.range,
+                    CompileErrorKind::VaArgOutsideVarArgsFunction,
+                ));
+            } else if matches!(
+                resolved_ty,
+                ResolvedType::StructLike(_) | ResolvedType::Void
+            ) {
+                context.errors.borrow_mut().push(CompileError::new(
+                    loc_expr.range,
+                    CompileErrorKind::InvalidVaArgType {
+                        ty: resolved_ty.clone(),
+                    },
+                ));
+            }
+            Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
+                kind: resolved_ast::ExpressionKind::VaArg,
+                ty: resolved_ty,
+            })
+        }
         Expression::If(if_expr) => {
             let condition_expr =
                 resolve_expression(context, if_expr.cond.as_deref(), Some(&ResolvedType::Bool))?;
@@ -361,6 +741,7 @@ pub(crate) fn resolve_expression(
                 ));
             }
             Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
                 ty: then_expr.ty.clone(),
                 kind: resolved_ast::ExpressionKind::If(resolved_ast::IfExpr {
                     cond: Box::new(condition_expr),
@@ -386,6 +767,7 @@ pub(crate) fn resolve_expression(
             }
             let then_expr = resolve_expression(context, when_expr.then.as_deref(), annotation)?;
             Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
                 ty: ResolvedType::Void,
                 kind: resolved_ast::ExpressionKind::When(resolved_ast::WhenExpr {
                     cond: Box::new(condition_expr),
@@ -393,6 +775,64 @@ pub(crate) fn resolve_expression(
                 }),
             })
         }
+        Expression::While(while_expr) => {
+            let condition_expr = resolve_expression(
+                context,
+                while_expr.cond.as_deref(),
+                Some(&ResolvedType::Bool),
+            )?;
+            if !matches!(condition_expr.ty, ResolvedType::Bool) {
+                context.errors.borrow_mut().push(CompileError::new(
+                    while_expr.cond.range,
+                    CompileErrorKind::TypeMismatch {
+                        expected: ResolvedType::Bool,
+                        actual: condition_expr.ty.clone(),
+                    },
+                ));
+            }
+            let body = resolve_loop_body(context, &while_expr.body)?;
+            Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
+                ty: ResolvedType::Void,
+                kind: resolved_ast::ExpressionKind::While(resolved_ast::WhileExpr {
+                    cond: Box::new(condition_expr),
+                    body,
+                }),
+            })
+        }
+        Expression::For(for_expr) => {
+            // initで宣言した変数はfor全体のスコープに入る
+            let for_expr = in_new_scope!(context.scopes, {
+                resolve_for_expression(context, for_expr)
+            })?;
+            Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
+                ty: ResolvedType::Void,
+                kind: resolved_ast::ExpressionKind::For(for_expr),
+            })
+        }
+        Expression::Break | Expression::Continue => {
+            let is_break = matches!(loc_expr.value, Expression::Break);
+            if *context.loop_depth.borrow() == 0 {
+                context.errors.borrow_mut().push(CompileError::new(
+                    loc_expr.range,
+                    if is_break {
+                        CompileErrorKind::BreakOutsideLoop
+                    } else {
+                        CompileErrorKind::ContinueOutsideLoop
+                    },
+                ));
+            }
+            Ok(resolved_ast::ResolvedExpression {
+                range: loc_expr.range,
+                ty: ResolvedType::Void,
+                kind: if is_break {
+                    resolved_ast::ExpressionKind::Break
+                } else {
+                    resolved_ast::ExpressionKind::Continue
+                },
+            })
+        }
         Expression::Assignment(assign_expr) => {
             resolve_assignment(context, &Located::transfer(loc_expr, assign_expr))
         }
@@ -401,3 +841,925 @@ pub(crate) fn resolve_expression(
         }
     }
 }
+
+// 整数リテラルが型の範囲に収まるか。浮動小数点型には常に変換できる
+fn integer_literal_fits(value: &str, ty: &ResolvedType, is_64_bit: bool) -> bool {
+    let Some(width) = ty.integer_bit_width(is_64_bit) else {
+        return true;
+    };
+    let Ok(n) = value.parse::<i128>() else {
+        return false;
+    };
+    if ty.is_signed_integer_type() {
+        let max = (1i128 << (width - 1)) - 1;
+        (-max - 1..=max).contains(&n)
+    } else {
+        (0..1i128 << width).contains(&n)
+    }
+}
+
+// 数値型同士、boolから整数、ポインタ同士、ポインタと整数の間だけ変換できる
+fn is_valid_cast(from: &ResolvedType, to: &ResolvedType) -> bool {
+    let is_numeric = |ty: &ResolvedType| ty.is_integer_type() || ty.is_float_type();
+    from == to
+        || matches!(from, ResolvedType::Unknown)
+        || matches!(to, ResolvedType::Unknown)
+        || (is_numeric(from) && is_numeric(to))
+        || (matches!(from, ResolvedType::Bool) && to.is_integer_type())
+        || (from.is_pointer_type() && (to.is_pointer_type() || to.is_integer_type()))
+        || (from.is_integer_type() && to.is_pointer_type())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resolver::{error::CompileErrorKind, tests::resolve_source};
+
+    use super::*;
+
+    #[test]
+    fn test_while_condition_must_be_bool() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (while 1 (:= x 0))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::TypeMismatch {
+                expected: ResolvedType::Bool,
+                actual: ResolvedType::I32,
+            }
+        );
+    }
+
+    #[test]
+    fn test_negation_requires_signed_integer() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a (- 5))
+    (:= b: i64 (- (- 5)))
+    (:= c: u32 3)
+    (:= d (- c))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::InvalidOperand {
+                op: UnaryOp::Neg.into(),
+                actual: ResolvedType::U32,
+            }
+        );
+    }
+
+    #[test]
+    fn test_bit_not_rejects_bool_and_pointer() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a: u8 (~ 0))
+    (:= s \"abc\")
+    (:= b (~ s))
+    (:= c (~ (< 1 2)))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::InvalidOperand {
+                op: UnaryOp::BitNot.into(),
+                actual: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+            }
+        );
+        assert_eq!(
+            errors[1].kind,
+            CompileErrorKind::InvalidOperand {
+                op: UnaryOp::BitNot.into(),
+                actual: ResolvedType::Bool,
+            }
+        );
+    }
+
+    #[test]
+    fn test_field_access_errors() {
+        let context = resolve_source(
+            "
+struct Point {
+    x: i32,
+    y: i32,
+}
+fn main(): void {
+    (:= p Point { x: 1, y: 2 })
+    (:= a p.x)
+    (:= b p.z)
+    (:= n 1)
+    (:= c n.x)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::FieldNotFound {
+                field_name: "z".into(),
+                type_name: "Point".into(),
+            }
+        );
+        assert_eq!(
+            errors[1].kind,
+            CompileErrorKind::InvalidFieldAccess {
+                ty: ResolvedType::I32,
+                name: "x".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_struct_literal_field_errors() {
+        let context = resolve_source(
+            "
+struct Point {
+    x: i32,
+    y: i32,
+}
+fn main(): void {
+    (:= missing Point { x: 1 })
+    (:= extra Point { x: 1, y: 2, z: 3 })
+    (:= duplicated Point { x: 1, x: 2, y: 3 })
+    (:= wrong_type Point { x: 1, y: (< 1 2) })
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::MissingField {
+                    field_name: "y".into(),
+                    type_name: "Point".into(),
+                },
+                &CompileErrorKind::FieldNotFound {
+                    field_name: "z".into(),
+                    type_name: "Point".into(),
+                },
+                &CompileErrorKind::DuplicateField {
+                    field_name: "x".into(),
+                    type_name: "Point".into(),
+                },
+                &CompileErrorKind::TypeMismatch {
+                    expected: ResolvedType::I32,
+                    actual: ResolvedType::Bool,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuple() {
+        let context = resolve_source(
+            "
+fn divmod(a: i32, b: i32): (i32, i32) {
+    return ((/ a b), (% a b))
+}
+fn main(): void {
+    (:= t (1, 2u8))
+    (:= second: u8 t.1)
+    (:= wide: (i64, u8) (1, 2u8))
+    (:= result (divmod 7 2))
+    (printf \"%d %d %ld\" second result.0 wide.0)
+    (:= out_of_range t.2)
+    (:= wrong: (i32, i32) t)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::FieldNotFound {
+                field_name: "2".into(),
+                type_name: "(i32, u8)".into(),
+            }
+        );
+        assert_eq!(
+            errors[1].kind.to_string(),
+            "Type does not match. expected `(i32, i32)`, but got `(i32, u8)`"
+        );
+
+        // タプルは要素をフィールドに持つstructになり、注釈があれば要素の型を合わせる
+        let resolved_structs = context.resolved_structs.borrow();
+        assert_eq!(
+            resolved_structs["(i64, u8)"].fields,
+            vec![
+                ("0".to_string(), ResolvedType::I64),
+                ("1".to_string(), ResolvedType::U8)
+            ]
+        );
+        let divmod = &context.resolved_functions.borrow()["divmod"];
+        assert_eq!(divmod.decl.return_type.to_string(), "(i32, i32)");
+    }
+
+    #[test]
+    fn test_method_call() {
+        let context = resolve_source(
+            "
+type Point = struct { x: i32, y: i32 } impl {
+    fn sum(self): i32 {
+        (+ self.x self.y)
+    }
+}
+impl Point {
+    fn scaled(self, n: i32): i32 {
+        (* self.sum() n)
+    }
+}
+fn main(): void {
+    (:= p Point { x: 1, y: 2 })
+    (:= q &p)
+    (printf \"%d %d\" p.scaled(3) q.sum())
+    p.len()
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::MethodNotFound {
+                name: "len".into(),
+                type_name: "Point".into(),
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 16);
+
+        // メソッドはselfにレシーバーへのポインタを受け取る関数になる
+        let resolved_functions = context.resolved_functions.borrow();
+        let scaled = &resolved_functions["Point.scaled"];
+        let resolved_ast::Argument::Normal(self_ty, name) = &scaled.decl.args[0] else {
+            panic!("unexpected argument: {:?}", scaled.decl.args[0]);
+        };
+        assert_eq!(name, "self");
+        assert!(
+            matches!(self_ty, ResolvedType::Ptr(inner) if matches!(inner.as_ref(), ResolvedType::StructLike(point) if point.name == "Point")),
+            "{:?}",
+            self_ty
+        );
+        assert!(resolved_functions.contains_key("Point.sum"));
+    }
+
+    #[test]
+    fn test_self_outside_method() {
+        let context = resolve_source(
+            "
+fn main(self): void {}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].kind, CompileErrorKind::SelfOutsideMethod);
+        assert_eq!(errors[0].range.from.line, 2);
+    }
+
+    #[test]
+    fn test_arithmetic_builtins() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a 200u8)
+    (:= sum (saturating_add a 100))
+    (:= product: u8 (wrapping_mul a 2))
+    (:= b: i32 1)
+    (:= mixed (wrapping_add a b))
+    (:= float (saturating_sub 1.0 2.0))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        // 通常の二項演算と違い、u8とi32を揃えない
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::TypeMismatch {
+                expected: ResolvedType::U8,
+                actual: ResolvedType::I32,
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 7);
+        assert_eq!(
+            errors[1].kind,
+            CompileErrorKind::InvalidNumericOperand {
+                actual: ResolvedType::F64,
+            }
+        );
+
+        // 2つ目の引数のリテラルは1つ目の型になる
+        let main = &context.resolved_functions.borrow()["main"];
+        let resolved_ast::Statement::Effect(statement) = &main.body[1] else {
+            panic!("unexpected statement: {:?}", main.body[1]);
+        };
+        let resolved_ast::ExpressionKind::VariableDecls(decls) = &statement.expression.kind else {
+            panic!("unexpected expression: {:?}", statement.expression);
+        };
+        assert_eq!(decls.decls[0].value.ty, ResolvedType::U8);
+    }
+
+    #[test]
+    fn test_overloaded_function_shadows_arithmetic_builtin() {
+        let context = resolve_source(
+            "
+fn wrapping_add(a: f64, b: f64): f64 {
+    (+ a b)
+}
+fn wrapping_add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn main(): void {
+    (:= x (wrapping_add 1.0 2.0))
+    (:= _y: f64 x)
+}
+",
+        );
+        // 組み込み関数ではなくオーバーロードした関数を呼ぶので、f64の引数でもエラーにならない
+        let errors = context.errors.borrow();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let main = &context.resolved_functions.borrow()["main"];
+        let resolved_ast::Statement::Effect(statement) = &main.body[0] else {
+            panic!("unexpected statement: {:?}", main.body[0]);
+        };
+        let resolved_ast::ExpressionKind::VariableDecls(decls) = &statement.expression.kind else {
+            panic!("unexpected expression: {:?}", statement.expression);
+        };
+        let ExpressionKind::CallExpr(call_expr) = &decls.decls[0].value.kind else {
+            panic!("unexpected expression: {:?}", decls.decls[0].value);
+        };
+        assert_ne!(call_expr.callee, "wrapping_add");
+    }
+
+    #[test]
+    fn test_address_of_requires_lvalue() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= x 1)
+    (:= p &x)
+    (:= q: *i32 p)
+    (:= r &5)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, CompileErrorKind::InvalidAddressOf);
+    }
+
+    #[test]
+    fn test_invalid_casts() {
+        let context = resolve_source(
+            "
+struct Point {
+    x: i32,
+    y: i32,
+}
+fn main(): void {
+    (:= a (cast<u8> 300))
+    (:= b (cast<f64> a))
+    (:= c (cast<i32> (< 1 2)))
+    (:= s \"abc\")
+    (:= d (cast<u64> s))
+    (:= e (cast<*u8> d))
+    (:= p Point { x: 1, y: 2 })
+    (:= f (cast<i32> p))
+    (:= g (cast<bool> 1))
+    (:= h (cast<f64> s))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(kinds.len(), 3, "{:?}", kinds);
+        assert!(matches!(
+            kinds[0],
+            CompileErrorKind::InvalidCast {
+                from: ResolvedType::StructLike(_),
+                to: ResolvedType::I32,
+            }
+        ));
+        assert_eq!(
+            kinds[1],
+            &CompileErrorKind::InvalidCast {
+                from: ResolvedType::I32,
+                to: ResolvedType::Bool,
+            }
+        );
+        assert_eq!(
+            kinds[2],
+            &CompileErrorKind::InvalidCast {
+                from: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+                to: ResolvedType::F64,
+            }
+        );
+    }
+
+    #[test]
+    fn test_enum_variant_values() {
+        let context = resolve_source(
+            "
+type Color = enum { Red, Green = 5, Blue }
+type Big = enum { Small, Large = 5000000000 }
+(const RED Color.Red)
+(const GREEN Color.Green)
+(const BLUE Color.Blue)
+(const LARGE Big.Large)
+fn main(): void {
+    (:= c: Color Color.Blue)
+    (:= is_blue (== c Color.Blue))
+    (:= unknown Color.Purple)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::VariantNotFound {
+                variant_name: "Purple".into(),
+                enum_name: "Color".into(),
+            }
+        );
+        let constants = context.constants.borrow();
+        let value_of = |name: &str| match &constants[name].kind {
+            ExpressionKind::NumberLiteral(number_literal) => number_literal.value.clone(),
+            kind => panic!("unexpected expression: {:?}", kind),
+        };
+        // 値を省略したバリアントは直前の値+1になる
+        assert_eq!(value_of("RED"), "0");
+        assert_eq!(value_of("GREEN"), "5");
+        assert_eq!(value_of("BLUE"), "6");
+        assert_eq!(constants["BLUE"].ty, ResolvedType::I32);
+        // i32に収まらない値があればi64になる
+        assert_eq!(value_of("LARGE"), "5000000000");
+        assert_eq!(constants["LARGE"].ty, ResolvedType::I64);
+    }
+
+    #[test]
+    fn test_loop_control_outside_loop() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (while true (break))
+    (for (:= i 0) (< i 10) (:=< i (+ i 1))
+        (when (== i 5) (continue)))
+    (break)
+    (when true (continue))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::BreakOutsideLoop,
+                &CompileErrorKind::ContinueOutsideLoop,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_integer_literal_out_of_range() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a: u8 255)
+    (:= b: u8 256)
+    (:= c 2147483648)
+    (:= d 99999999999999999999999)
+    (:= e: i64 9223372036854775808)
+    (:= f: f64 99999999999999999999999)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::IntegerLiteralOutOfRange {
+                    value: "256".into(),
+                    ty: ResolvedType::U8,
+                },
+                &CompileErrorKind::IntegerLiteralOutOfRange {
+                    value: "99999999999999999999999".into(),
+                    ty: ResolvedType::U64,
+                },
+                &CompileErrorKind::IntegerLiteralOutOfRange {
+                    value: "9223372036854775808".into(),
+                    ty: ResolvedType::I64,
+                },
+            ]
+        );
+        // 範囲外のリテラルはspanを持つ
+        assert_eq!(errors[0].range.from.line, 4);
+    }
+
+    #[test]
+    fn test_small_signed_integer_types() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= _a: i8 127)
+    (:= _b: i8 128)
+    (:= _c: i16 32767)
+    (:= _d: i16 32768)
+    (:= _e 1i8)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::IntegerLiteralOutOfRange {
+                    value: "128".into(),
+                    ty: ResolvedType::I8,
+                },
+                &CompileErrorKind::IntegerLiteralOutOfRange {
+                    value: "32768".into(),
+                    ty: ResolvedType::I16,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_integer_literal_suffix() {
+        let context = resolve_source(
+            "
+(const WIDE 5i64)
+(const NARROW: u64 (+ 1 5u64))
+fn main(): void {
+    (:= a 256u8)
+    (:= b: i32 5i64)
+}
+",
+        );
+        let constants = context.constants.borrow();
+        assert_eq!(constants["WIDE"].ty, ResolvedType::I64);
+        assert_eq!(constants["NARROW"].ty, ResolvedType::U64);
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        // 接尾辞の型はアノテーションで上書きされない
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::IntegerLiteralOutOfRange {
+                    value: "256".into(),
+                    ty: ResolvedType::U8,
+                },
+                &CompileErrorKind::TypeMismatch {
+                    expected: ResolvedType::I32,
+                    actual: ResolvedType::I64,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_va_arg() {
+        let context = resolve_source(
+            "
+fn first(): i32 {
+    (va_arg i32)
+}
+fn sum(count: i32, ...): i64 {
+    (first)
+    (:= total: i64 0)
+    (for (:= i 0) (< i count) (+= i 1)
+        (+= total (va_arg i64)))
+    total
+}
+fn main(): void {
+    (sum 2 1 2)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(kinds, vec![&CompileErrorKind::VaArgOutsideVarArgsFunction]);
+        // 可変長引数の関数の中から呼んでも、呼び出し先では使えない
+        assert_eq!(errors[0].range.from.line, 3);
+    }
+
+    #[test]
+    fn test_default_argument() {
+        let context = resolve_source(
+            "
+fn f(x: i32, y: i32 = 10): i32 {
+    (* x y)
+}
+fn g(x: i32 = 1, y: i32): i32 {
+    (+ x y)
+}
+fn main(): void {
+    (:= y 2)
+    (f 1)
+    (f 1 y)
+    (f)
+    (g 1 2)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::MismatchFunctionArgCount {
+                    name: "f".into(),
+                    expected: 1,
+                    actual: 0,
+                },
+                &CompileErrorKind::RequiredArgumentAfterDefault { name: "y".into() },
+            ]
+        );
+
+        let resolved_functions = context.resolved_functions.borrow();
+        let calls = resolved_functions["main"]
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                resolved_ast::Statement::Effect(resolved_ast::Effect {
+                    expression:
+                        ResolvedExpression {
+                            kind: ExpressionKind::CallExpr(call_expr),
+                            ..
+                        },
+                    ..
+                }) => Some(call_expr),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // 省略した引数は既定値で補われる
+        assert_eq!(calls[0].args.len(), 2);
+        assert!(
+            matches!(&calls[0].args[1].kind, ExpressionKind::NumberLiteral(literal) if literal.value == "10")
+        );
+        assert_eq!(calls[1].args.len(), 2);
+        assert!(matches!(
+            &calls[1].args[1].kind,
+            ExpressionKind::VariableRef(_)
+        ));
+    }
+
+    #[test]
+    fn test_generic_function_specialization() {
+        let context = resolve_source(
+            "
+fn id<T>(x: T): T {
+    x
+}
+fn fact(n: i32): i32 {
+    (if (== n 0) 1 (* n (fact (- n 1))))
+}
+fn main(): void {
+    (id<i32> 5)
+    (id<u64> 5)
+    (id<i32> 6)
+    (id<i32, u8> 7)
+    (fact 5)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![&CompileErrorKind::TooManyGenericArgs {
+                name: "id".into(),
+                expected: 1,
+                actual: 2,
+            }]
+        );
+
+        // 同じ型引数の呼び出しは1つの特殊化を共有する
+        let resolved_functions = context.resolved_functions.borrow();
+        let mut names = resolved_functions.keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["fact", "id(i32)->i32", "id(u64)->u64", "main"]);
+        let u64_id = &resolved_functions["id(u64)->u64"];
+        assert_eq!(
+            u64_id.decl.args,
+            vec![resolved_ast::Argument::Normal(
+                ResolvedType::U64,
+                "x".into()
+            )]
+        );
+        assert_eq!(u64_id.decl.return_type, ResolvedType::U64);
+
+        let callees = resolved_functions["main"]
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                resolved_ast::Statement::Effect(resolved_ast::Effect {
+                    expression:
+                        ResolvedExpression {
+                            kind: ExpressionKind::CallExpr(call_expr),
+                            ..
+                        },
+                    ..
+                }) => Some(call_expr.callee.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            callees,
+            vec!["id(i32)->i32", "id(u64)->u64", "id(i32)->i32", "fact"]
+        );
+    }
+
+    #[test]
+    fn test_generic_arg_count() {
+        let context = resolve_source(
+            "
+struct Pair<T, U> {
+    first: T,
+    second: U,
+}
+fn id<T>(x: T): T {
+    x
+}
+fn square(x: i32): i32 {
+    (* x x)
+}
+fn main(): void {
+    (id<i32, u8> 1)
+    (id<> 1)
+    (square<i32> 2)
+    (:= p: Pair<i32> Pair<i32, u8, u8> { first: 1, second: 2 })
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::TooManyGenericArgs {
+                    name: "id".into(),
+                    expected: 1,
+                    actual: 2,
+                },
+                &CompileErrorKind::TooFewGenericArgs {
+                    name: "id".into(),
+                    expected: 1,
+                    actual: 0,
+                },
+                &CompileErrorKind::UnnecessaryGenericArgs {
+                    name: "square".into(),
+                },
+                &CompileErrorKind::TooFewGenericArgs {
+                    name: "Pair".into(),
+                    expected: 2,
+                    actual: 1,
+                },
+                &CompileErrorKind::TooManyGenericArgs {
+                    name: "Pair".into(),
+                    expected: 2,
+                    actual: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_infer_generic_args() {
+        let context = resolve_source(
+            "
+struct Pair<T, U> {
+    first: T,
+    second: U,
+}
+fn id<T>(x: T): T {
+    x
+}
+fn max<T>(a: T, b: T): T {
+    (if (> a b) a b)
+}
+fn first<T, U>(p: Pair<T, U>): T {
+    p.first
+}
+fn pair<T>(a: T, b: T): T {
+    a
+}
+fn zero<T>(): i32 {
+    0
+}
+fn main(): void {
+    (:= x: u8 3)
+    (:= a (id 5))
+    (:= b (max 10 x))
+    (:= c (first Pair<u8, i32> { first: 1, second: 2 }))
+    (printf \"%d %d %d\\n\" a b c)
+    (pair 1 \"s\")
+    (zero)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        // 後から来た整数リテラルが、文字列から推論した型と食い違う
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::ConflictingGenericArgs {
+                name: "pair".into(),
+                generic_arg: "T".into(),
+                first: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+                second: ResolvedType::I32,
+            }
+        );
+        assert!(
+            matches!(
+                &errors[1].kind,
+                CompileErrorKind::CannotInferGenericArgs { name, .. } if name == "zero"
+            ),
+            "{:?}",
+            errors[1]
+        );
+
+        let resolved_functions = context.resolved_functions.borrow();
+        for name in ["id(i32)->i32", "max(u8,u8)->u8", "first(Pair<u8, i32>)->u8"] {
+            assert!(
+                resolved_functions.contains_key(name),
+                "{:?}",
+                resolved_functions.keys()
+            );
+        }
+    }
+
+    #[test]
+    fn test_infer_generic_args_with_annotation() {
+        let context = resolve_source(
+            "
+fn id<T>(x: T): T {
+    x
+}
+fn zero<T>(): T {
+    (:= z: T 0)
+    z
+}
+fn main(): void {
+    (:= c: u64 (id 5))
+    (:= z: u8 (zero))
+    (printf \"%lu %d\\n\" c z)
+    (zero)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        // アノテーションのない呼び出しでは、戻り値にしか現れない型引数を推論できない
+        assert!(
+            matches!(
+                &errors[0].kind,
+                CompileErrorKind::CannotInferGenericArgs { name, .. } if name == "zero"
+            ),
+            "{:?}",
+            errors[0]
+        );
+        let resolved_functions = context.resolved_functions.borrow();
+        for name in ["id(u64)->u64", "zero()->u8"] {
+            assert!(
+                resolved_functions.contains_key(name),
+                "{:?}",
+                resolved_functions.keys()
+            );
+        }
+    }
+
+    #[test]
+    fn test_infer_generic_args_from_annotation() {
+        // (:= v2 : Vec<i32> (vec)) のように、戻り値の型にしか現れない型引数をアノテーションから推論する
+        let context = resolve_source(include_str!("../../../sample/sample.hr"));
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+        let resolved_functions = context.resolved_functions.borrow();
+        for name in ["vec()->Vec<i32>", "vec()->Vec<Vec<i32>>"] {
+            assert!(
+                resolved_functions.contains_key(name),
+                "{:?}",
+                resolved_functions.keys()
+            );
+        }
+    }
+}
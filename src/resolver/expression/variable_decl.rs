@@ -1,4 +1,7 @@
-use crate::{resolved_ast::VariableDecls, resolver::ResolverContext};
+use crate::{
+    resolved_ast::VariableDecls,
+    resolver::{ResolverContext, Variable, VariableKind},
+};
 
 use super::*;
 use ast::*;
@@ -15,13 +18,32 @@ pub(super) fn resolve_variable_decl(
                 .clone()
                 .map(|unresolved_ty| resolve_type(context, &unresolved_ty))
                 .transpose()?;
-            let resolved_expr = resolve_expression(
+            // 配列型の変数は、要素の型の値1つで全要素を初期化する
+            let element_annotation = match &resolved_annotation {
+                Some(ResolvedType::Array(element_ty, _)) => Some(element_ty.as_ref()),
+                _ => resolved_annotation.as_ref(),
+            };
+            let mut resolved_expr = resolve_expression(
                 context,
                 variable_decl_expr.value.value.as_deref(),
-                resolved_annotation.as_ref(),
+                element_annotation,
             )?;
+            if let Some(array_ty @ ResolvedType::Array(element_ty, _)) = &resolved_annotation {
+                if *array_ty != resolved_expr.ty && element_ty.can_insert(&resolved_expr.ty) {
+                    resolved_expr = ResolvedExpression {
+                        range: resolved_expr.range,
+                        ty: array_ty.clone(),
+                        kind: ExpressionKind::ArrayFill(resolved_ast::ArrayFill {
+                            value: Box::new(resolved_expr),
+                        }),
+                    };
+                }
+            }
             if let Some(resolved_annotation) = resolved_annotation {
-                if !resolved_annotation.can_insert(&resolved_expr.ty) {
+                // 注釈の型の解決に失敗していれば、そこでエラーを報告済みなので重ねない
+                if resolved_annotation != ResolvedType::Unknown
+                    && !resolved_annotation.can_insert(&resolved_expr.ty)
+                {
                     context.errors.borrow_mut().push(CompileError::new(
                         variable_decl_expr.range,
                         CompileErrorKind::TypeMismatch {
@@ -30,19 +52,257 @@ pub(super) fn resolve_variable_decl(
                         },
                     ));
                 }
+            } else if resolved_expr.ty == ResolvedType::Ptr(Box::new(ResolvedType::Unknown)) {
+                // nullだけからはポインタの型を決められない
+                context.errors.borrow_mut().push(CompileError::new(
+                    variable_decl_expr.range,
+                    CompileErrorKind::CannotInferNullType {
+                        name: variable_decl_expr.name.clone(),
+                    },
+                ));
             }
-            context
-                .scopes
-                .borrow_mut()
-                .add(variable_decl_expr.name.clone(), resolved_expr.ty.clone());
+            context.scopes.borrow_mut().add(
+                variable_decl_expr.name.clone(),
+                Variable::new(
+                    resolved_expr.ty.clone(),
+                    VariableKind::Local,
+                    variable_decl_expr.range,
+                ),
+            );
             decls.push(resolved_ast::VariableDecl {
                 name: variable_decl_expr.name.clone(),
                 value: Box::new(resolved_expr),
             });
         }
         Ok(ResolvedExpression {
+            range: variable_decls_expr.range,
             ty: ResolvedType::Void,
             kind: ExpressionKind::VariableDecls(VariableDecls { decls }),
         })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::resolver::{error::CompileErrorKind, tests::resolve_source};
+
+    use super::*;
+
+    #[test]
+    fn test_bool_variable_decl() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= b: bool (< 1 2))
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_integer_literal_cannot_be_assigned_to_bool() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= b: bool 1)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::TypeMismatch {
+                expected: ResolvedType::Bool,
+                actual: ResolvedType::I32,
+            }
+        );
+    }
+
+    #[test]
+    fn test_float_variable_decl() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= f: f32 1)
+    (:= d: f64 2)
+    (:= g: f32 \"abc\")
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::TypeMismatch {
+                expected: ResolvedType::F32,
+                actual: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_annotation_does_not_override_variable_type() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= x: i64 1)
+    (:= y: i32 x)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::TypeMismatch {
+                expected: ResolvedType::I32,
+                actual: ResolvedType::I64,
+            }
+        );
+    }
+
+    #[test]
+    fn test_cyclic_annotation_reports_once() {
+        let context = resolve_source(
+            "
+type A = B
+type B = A
+fn main(): void {
+    (:= _x: A 1)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![&CompileErrorKind::CyclicTypeAlias { name: "A".into() }]
+        );
+    }
+
+    #[test]
+    fn test_array_variable_decl() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= a: [u8; 4] 0)
+    (:=< a[1] 5)
+    (:= _b: u8 a[1])
+    (:= _c: [u8; 4] a)
+    (:= _d: [u8; 4] true)
+    (:= _e: [u8; 0] 0)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        // 長さが不正な配列は、初期化式の型の不一致を重ねて報告しない
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::TypeMismatch {
+                    expected: ResolvedType::Array(Box::new(ResolvedType::U8), 4),
+                    actual: ResolvedType::Bool,
+                },
+                &CompileErrorKind::InvalidArrayLength { len: 0 },
+            ]
+        );
+
+        let main = &context.resolved_functions.borrow()["main"];
+        let resolved_ast::Statement::Effect(statement) = &main.body[0] else {
+            panic!("unexpected statement: {:?}", main.body[0]);
+        };
+        let ExpressionKind::VariableDecls(decls) = &statement.expression.kind else {
+            panic!("unexpected expression: {:?}", statement.expression);
+        };
+        let ExpressionKind::ArrayFill(array_fill) = &decls.decls[0].value.kind else {
+            panic!("unexpected expression: {:?}", decls.decls[0].value);
+        };
+        assert_eq!(array_fill.value.ty, ResolvedType::U8);
+    }
+
+    #[test]
+    fn test_null_literal() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= p: *i32 null)
+    (:= _q: *u8 null)
+    (:= _n: i32 null)
+    (:= _r null)
+    (:= _b (== p null))
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].kind,
+            CompileErrorKind::TypeMismatch {
+                expected: ResolvedType::I32,
+                actual: ResolvedType::Ptr(Box::new(ResolvedType::Unknown)),
+            }
+        );
+        assert_eq!(
+            errors[1].kind,
+            CompileErrorKind::CannotInferNullType { name: "_r".into() }
+        );
+    }
+
+    #[test]
+    fn test_infer_variable_type_from_initializer() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= n (+ 5 3))
+    (:= p &n)
+    (:=< n 10)
+    (:=< n \"s\")
+    (:=< p n)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        // 推論した型と合わない値は代入できない
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::TypeMismatch {
+                    expected: ResolvedType::I32,
+                    actual: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+                },
+                &CompileErrorKind::TypeMismatch {
+                    expected: ResolvedType::Ptr(Box::new(ResolvedType::I32)),
+                    actual: ResolvedType::I32,
+                },
+            ]
+        );
+
+        let resolved_functions = context.resolved_functions.borrow();
+        let decl_types = resolved_functions["main"]
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                resolved_ast::Statement::Effect(resolved_ast::Effect {
+                    expression:
+                        ResolvedExpression {
+                            kind: ExpressionKind::VariableDecls(VariableDecls { decls }),
+                            ..
+                        },
+                    ..
+                }) => Some(decls[0].value.ty.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            decl_types,
+            vec![
+                ResolvedType::I32,
+                ResolvedType::Ptr(Box::new(ResolvedType::I32)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt::Display;
+
+use crate::{
+    ast::{self, Located},
+    resolved_ast::{ExpressionKind, ResolvedExpression, ResolvedType},
+};
+
+use super::{
+    error::{CompileError, CompileErrorKind},
+    ResolverContext,
+};
+
+// 書式文字列の%から始まる変換指定。フラグ、幅、精度は型の検査に関係ないので読み飛ばす
+struct FormatSpecifier {
+    // hやlなどの長さ修飾子
+    length: String,
+    conversion: char,
+}
+
+impl FormatSpecifier {
+    fn accepts(&self, ty: &ResolvedType, is_64_bit: bool) -> bool {
+        match self.conversion {
+            // 可変長引数ではi32より小さい整数はi32に拡張される
+            'd' | 'i' | 'u' | 'x' | 'X' | 'o' | 'c' => match ty.integer_bit_width(is_64_bit) {
+                Some(width) if self.length.contains(['l', 'z', 'j']) => {
+                    width == 64 || *ty == ResolvedType::USize
+                }
+                Some(width) => width <= 32,
+                None => false,
+            },
+            's' => *ty == ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+            'p' => ty.is_pointer_type(),
+            _ => ty.is_float_type(),
+        }
+    }
+}
+
+impl Display for FormatSpecifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "%{}{}", self.length, self.conversion)
+    }
+}
+
+// 未知の変換指定があれば、その指定をErrで返す
+fn parse_format_specifiers(format: &str) -> Result<Vec<FormatSpecifier>, String> {
+    let mut specifiers = Vec::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        while chars.next_if(|c| "-+ #0123456789.".contains(*c)).is_some() {}
+        let mut length = String::new();
+        while let Some(c) = chars.next_if(|c| "hlzjt".contains(*c)) {
+            length.push(c);
+        }
+        match chars.next() {
+            Some('%') if length.is_empty() => {}
+            Some(conversion) if "diuxXocspfFeEgG".contains(conversion) => {
+                specifiers.push(FormatSpecifier { length, conversion })
+            }
+            Some(conversion) => return Err(format!("%{}{}", length, conversion)),
+            None => return Err(format!("%{}", length)),
+        }
+    }
+    Ok(specifiers)
+}
+
+// 書式文字列がリテラルなら、変換指定と可変長引数の数と型が合っているかを検査する
+pub(super) fn check_printf_format(
+    context: &ResolverContext,
+    call_expr: &Located<&ast::CallExpr>,
+    resolved_args: &[ResolvedExpression],
+) {
+    let Some(ResolvedExpression {
+        kind: ExpressionKind::StringLiteral(format),
+        ..
+    }) = resolved_args.first()
+    else {
+        return;
+    };
+    let specifiers = match parse_format_specifiers(&format.value) {
+        Ok(specifiers) => specifiers,
+        Err(specifier) => {
+            context.errors.borrow_mut().push(CompileError::new(
+                call_expr.args[0].range,
+                CompileErrorKind::InvalidFormatSpecifier { specifier },
+            ));
+            return;
+        }
+    };
+    let var_args = &resolved_args[1..];
+    if specifiers.len() != var_args.len() {
+        context.errors.borrow_mut().push(CompileError::new(
+            call_expr.range,
+            CompileErrorKind::FormatArgCountMismatch {
+                expected: specifiers.len(),
+                actual: var_args.len(),
+            },
+        ));
+        return;
+    }
+    for ((specifier, arg), loc_arg) in specifiers.iter().zip(var_args).zip(&call_expr.args[1..]) {
+        // 既にエラーになっている引数は重ねて報告しない
+        if arg.ty == ResolvedType::Unknown || specifier.accepts(&arg.ty, context.is_64_bit()) {
+            continue;
+        }
+        context.errors.borrow_mut().push(CompileError::new(
+            loc_arg.range,
+            CompileErrorKind::FormatArgTypeMismatch {
+                specifier: specifier.to_string(),
+                actual: arg.ty.clone(),
+            },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resolver::{error::CompileErrorKind, tests::resolve_source};
+
+    use super::*;
+
+    #[test]
+    fn test_printf_format() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= name \"Bob\")
+    (:= age: u8 42)
+    (printf \"%s is %3d years old. %c %ld %p %.2f %%\\n\" name age 'x' 10i64 name 1.5)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_printf_format_mismatch() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (printf \"%d\" \"str\")
+    (printf \"%d %d\\n\" 1)
+    (printf \"%d\" 1i64)
+    (printf \"%q\" 1)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &CompileErrorKind::FormatArgTypeMismatch {
+                    specifier: "%d".into(),
+                    actual: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+                },
+                &CompileErrorKind::FormatArgCountMismatch {
+                    expected: 2,
+                    actual: 1,
+                },
+                &CompileErrorKind::FormatArgTypeMismatch {
+                    specifier: "%d".into(),
+                    actual: ResolvedType::I64,
+                },
+                &CompileErrorKind::InvalidFormatSpecifier {
+                    specifier: "%q".into(),
+                },
+            ]
+        );
+        assert_eq!(errors[0].range.from.line, 3);
+    }
+}
@@ -1,7 +1,108 @@
+use std::cmp::Ordering;
+
 use self::error::CompileErrorKind;
 
 use super::*;
 
+// 型引数の数を宣言と比べ、合わなければ多すぎるか少なすぎるかのエラーを返す
+pub(crate) fn generic_arg_count_error(
+    name: &str,
+    expected: usize,
+    actual: usize,
+) -> Option<CompileErrorKind> {
+    let name = name.to_string();
+    match actual.cmp(&expected) {
+        Ordering::Greater => Some(CompileErrorKind::TooManyGenericArgs {
+            name,
+            expected,
+            actual,
+        }),
+        Ordering::Less => Some(CompileErrorKind::TooFewGenericArgs {
+            name,
+            expected,
+            actual,
+        }),
+        Ordering::Equal => None,
+    }
+}
+
+// 宣言された引数の型に現れる型引数を、実際の引数の型と突き合わせて推論する。
+// 同じ型引数に別々の型が推論されたらエラーを返す
+pub(crate) fn unify_generic_arg(
+    name: &str,
+    declared_generic_args: &[Located<ast::GenericArgument>],
+    declared: &ast::UnresolvedType,
+    actual: &ResolvedType,
+    inferred: &mut [Option<ResolvedType>],
+) -> Result<(), CompileErrorKind> {
+    match (declared, actual) {
+        (_, ResolvedType::Unknown) => Ok(()),
+        (ast::UnresolvedType::TypeRef(type_ref), _) => {
+            let position = declared_generic_args
+                .iter()
+                .position(|generic_arg| generic_arg.name == type_ref.name);
+            if let (Some(i), None) = (position, &type_ref.generic_args) {
+                return match &inferred[i] {
+                    None => {
+                        inferred[i] = Some(actual.clone());
+                        Ok(())
+                    }
+                    Some(first) if first == actual => Ok(()),
+                    Some(first) => Err(CompileErrorKind::ConflictingGenericArgs {
+                        name: name.to_string(),
+                        generic_arg: type_ref.name.clone(),
+                        first: first.clone(),
+                        second: actual.clone(),
+                    }),
+                };
+            }
+            // Pair<T, U> のような型は、同じstructの型引数どうしを突き合わせる
+            match (&type_ref.generic_args, actual) {
+                (Some(generic_args), ResolvedType::StructLike(struct_ty))
+                    if struct_ty.non_generic_name == type_ref.name =>
+                {
+                    for (declared, actual) in generic_args
+                        .iter()
+                        .zip(struct_ty.generic_args.iter().flatten())
+                    {
+                        unify_generic_arg(name, declared_generic_args, declared, actual, inferred)?;
+                    }
+                    Ok(())
+                }
+                _ => Ok(()),
+            }
+        }
+        (ast::UnresolvedType::Ptr(declared), ResolvedType::Ptr(actual))
+        | (ast::UnresolvedType::Array(declared, _), ResolvedType::Array(actual, _)) => {
+            unify_generic_arg(name, declared_generic_args, declared, actual, inferred)
+        }
+        (ast::UnresolvedType::Tuple(declared_elements), ResolvedType::StructLike(struct_ty))
+            if struct_ty.is_tuple() =>
+        {
+            for (declared, (_, actual)) in declared_elements.iter().zip(&struct_ty.fields) {
+                unify_generic_arg(name, declared_generic_args, declared, actual, inferred)?;
+            }
+            Ok(())
+        }
+        (
+            ast::UnresolvedType::FnPtr(declared_params, declared_return),
+            ResolvedType::FnPtr(actual_params, actual_return),
+        ) => {
+            for (declared, actual) in declared_params.iter().zip(actual_params) {
+                unify_generic_arg(name, declared_generic_args, declared, actual, inferred)?;
+            }
+            unify_generic_arg(
+                name,
+                declared_generic_args,
+                declared_return,
+                actual_return,
+                inferred,
+            )
+        }
+        _ => Ok(()),
+    }
+}
+
 pub(crate) fn try_implements_interface(
     context: &ResolverContext,
     ty: &ResolvedType,
@@ -1,13 +1,10 @@
 use std::collections::HashMap;
 
-use nom::{
-    error::{convert_error, VerboseError},
-    Finish,
-};
+use nom::Finish;
 
 use crate::{
     ast::{self, Function, FunctionDecl},
-    parser::parse_module,
+    parser::{format_parse_error, parse_module},
     resolved_ast::ResolvedType,
 };
 
@@ -15,7 +12,8 @@ use super::TypeScopes;
 
 const INTRINSIC_DECLS: &str = r#"
 fn malloc(size: usize) : *void {}
-fn free(ptr: *void) : *void {}
+fn realloc(ptr: *void, size: usize) : *void {}
+fn free(ptr: *void) : void {}
 fn memcpy(dst: *void, src: *void, size: usize) : void {}
 fn memset(dst: *void, value: *void, size: usize) : void {}
 fn strlen(s: *u8) : usize {}
@@ -29,15 +27,7 @@ fn printf(s: *u8, ...) : i32 {}
 pub(super) fn register_intrinsic_functions(function_by_name: &mut HashMap<String, Function>) {
     let result = parse_module(INTRINSIC_DECLS.into()).finish();
     if let Err(err) = result {
-        // using workaround to convert Span -> &str
-        // ref: https://github.com/fflorent/nom_locate/issues/36#issuecomment-1013469728
-        let errors = err
-            .errors
-            .into_iter()
-            .map(|(input, error)| (*input.fragment(), error))
-            .collect();
-
-        let error_message = convert_error(INTRINSIC_DECLS, VerboseError { errors });
+        let error_message = format_parse_error(INTRINSIC_DECLS, err);
         println!("{}", error_message);
         return;
     }
@@ -55,12 +45,18 @@ pub(super) fn register_intrinsic_functions(function_by_name: &mut HashMap<String
                             ..function.decl
                         },
                         body: function.body,
+                        range: function.range,
                     },
                 );
             }
             ast::TopLevel::TypeDef(_) => {}
             ast::TopLevel::Implemantation(_) => unreachable!(),
             ast::TopLevel::Interface(_) => unreachable!(),
+            ast::TopLevel::GlobalVariables(_) => unreachable!(),
+            ast::TopLevel::Const(_) => unreachable!(),
+            ast::TopLevel::TypeAlias(_) => unreachable!(),
+            ast::TopLevel::Import(_) => unreachable!(),
+            ast::TopLevel::MethodImpl(_) => unreachable!(),
         }
     }
 }
@@ -78,11 +74,17 @@ Void,
 Unknown, */
 
 pub(super) fn register_intrinsic_types(types: &mut TypeScopes) {
+    types.add("i8".into(), ResolvedType::I8);
+    types.add("i16".into(), ResolvedType::I16);
     types.add("i32".into(), ResolvedType::I32);
     types.add("i64".into(), ResolvedType::I64);
+    types.add("u16".into(), ResolvedType::U16);
     types.add("u32".into(), ResolvedType::U32);
     types.add("u64".into(), ResolvedType::U64);
     types.add("usize".into(), ResolvedType::USize);
     types.add("u8".into(), ResolvedType::U8);
+    types.add("f32".into(), ResolvedType::F32);
+    types.add("f64".into(), ResolvedType::F64);
+    types.add("bool".into(), ResolvedType::Bool);
     types.add("void".into(), ResolvedType::Void);
 }
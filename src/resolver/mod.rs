@@ -1,9 +1,15 @@
-mod error;
+mod constant;
+pub(crate) mod error;
 mod expression;
+mod format;
 mod generics;
 mod intrinsic;
+mod overload;
+#[cfg(feature = "color")]
+pub(crate) mod report;
 mod statement;
 mod ty;
+mod unused;
 
 use std::{cell::RefCell, collections::HashMap, ops::DerefMut, rc::Rc};
 
@@ -15,9 +21,12 @@ use crate::{
 };
 
 use self::{
-    error::{CompileError, FaitalError},
+    error::{CompileError, CompileWarning, FaitalError},
     intrinsic::{register_intrinsic_functions, register_intrinsic_types},
-    statement::resolve_statement,
+    overload::{register_overload, registered_function, Overload},
+    statement::resolve_statements,
+    ty::{check_struct_def, resolve_type_alias},
+    unused::report_unused_variables,
 };
 
 pub(crate) type Result<T, E = FaitalError> = std::result::Result<T, E>;
@@ -26,13 +35,37 @@ use crate::ast::*;
 
 pub struct ResolverContext {
     pub errors: Rc<RefCell<Vec<CompileError>>>,
+    pub warnings: Rc<RefCell<Vec<CompileWarning>>>,
     pub types: Rc<RefCell<TypeScopes>>,
     pub scopes: Rc<RefCell<VariableScopes>>,
     pub type_defs: Rc<RefCell<HashMap<String, ast::TypeDef>>>,
+    pub type_aliases: Rc<RefCell<HashMap<String, ast::TypeAlias>>>,
+    // 解決中の型エイリアス。循環の検出に使う
+    pub resolving_type_aliases: Rc<RefCell<Vec<String>>>,
+    // フィールドを解決中のstructと、そこへポインタを経由して辿り着いたか。自身を含むstructの検出に使う
+    pub resolving_structs: Rc<RefCell<Vec<(String, bool)>>>,
+    // 解決済みのstruct。ポインタの先で自身を参照するフィールドは、フィールドを持たない型になっているので、ここから補う
+    pub resolved_structs: Rc<RefCell<HashMap<String, resolved_ast::ResolvedStructType>>>,
     pub function_by_name: Rc<RefCell<HashMap<String, ast::Function>>>,
+    // オーバーロードされた関数。function_by_nameにはマングルした名前で登録する
+    pub overloads_by_name: Rc<RefCell<HashMap<String, Vec<Overload>>>>,
     pub interface_by_name: Rc<RefCell<HashMap<String, ast::Interface>>>,
     pub impls_by_name: Rc<RefCell<HashMap<String, Vec<Implementation>>>>,
     pub resolved_functions: Rc<RefCell<HashMap<String, resolved_ast::Function>>>,
+    // 本体を解決中の関数。再帰呼び出しで同じ関数を解決し直さないようにする
+    pub resolving_functions: Rc<RefCell<Vec<String>>>,
+    // 解決中の式を囲んでいるループの数。breakとcontinueの検査に使う
+    pub loop_depth: Rc<RefCell<usize>>,
+    // 解決中の関数が可変長引数を取るか。va_argの検査に使う
+    pub in_var_args_function: Rc<RefCell<bool>>,
+    // constの値。評価済みのリテラルを持ち、参照箇所にそのまま埋め込む
+    pub constants: Rc<RefCell<HashMap<String, resolved_ast::ResolvedExpression>>>,
+    // resolve_modulesで解決する場合の、関数と型を定義したファイルの番号と公開範囲。
+    // 関数はオーバーロードのマングルした名前でも引ける。ここにない名前はどこからでも見える
+    pub function_modules: Rc<RefCell<HashMap<String, (usize, Visibility)>>>,
+    pub type_modules: Rc<RefCell<HashMap<String, (usize, Visibility)>>>,
+    // 解決中の関数や型を定義したファイルの番号
+    pub current_module: Rc<RefCell<usize>>,
     pub ptr_sized_int_type: PointerSizedIntWidth,
 }
 
@@ -40,11 +73,24 @@ impl ResolverContext {
     pub fn new(ptr_sized_int_type: PointerSizedIntWidth) -> Self {
         Self {
             errors: Default::default(),
+            warnings: Default::default(),
             types: Rc::new(RefCell::new(TypeScopes::new())),
             scopes: Rc::new(RefCell::new(VariableScopes::new())),
             type_defs: Default::default(),
+            type_aliases: Default::default(),
+            resolving_type_aliases: Default::default(),
+            resolving_structs: Default::default(),
+            resolved_structs: Default::default(),
             function_by_name: Default::default(),
+            overloads_by_name: Default::default(),
             resolved_functions: Default::default(),
+            resolving_functions: Default::default(),
+            loop_depth: Default::default(),
+            in_var_args_function: Default::default(),
+            constants: Default::default(),
+            function_modules: Default::default(),
+            type_modules: Default::default(),
+            current_module: Default::default(),
             ptr_sized_int_type,
             interface_by_name: Default::default(),
             impls_by_name: Default::default(),
@@ -53,6 +99,66 @@ impl ResolverContext {
     pub fn is_64_bit(&self) -> bool {
         self.ptr_sized_int_type == PointerSizedIntWidth::SixtyFour
     }
+
+    fn is_visible(
+        &self,
+        modules: &RefCell<HashMap<String, (usize, Visibility)>>,
+        name: &str,
+    ) -> bool {
+        match modules.borrow().get(name) {
+            Some((module, visibility)) => {
+                *visibility == Visibility::Public || *module == *self.current_module.borrow()
+            }
+            None => true,
+        }
+    }
+
+    // 解決中のファイルから関数が見えるか。nameはオーバーロードのマングルした名前でもよい
+    pub(crate) fn is_function_visible(&self, name: &str) -> bool {
+        self.is_visible(&self.function_modules, name)
+    }
+
+    pub(crate) fn is_type_visible(&self, name: &str) -> bool {
+        self.is_visible(&self.type_modules, name)
+    }
+
+    // nameを定義したファイルの中にいるものとしてfを呼ぶ
+    fn in_module_of<T>(
+        &self,
+        modules: &RefCell<HashMap<String, (usize, Visibility)>>,
+        name: &str,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        let module = modules.borrow().get(name).map(|(module, _)| *module);
+        let Some(module) = module else {
+            return f();
+        };
+        self.in_file(module, f)
+    }
+
+    // fileの番号のファイルの中にいるものとしてfを呼び、その間に出たファイルの決まっていない診断にfileを付ける
+    pub(crate) fn in_file<T>(&self, file: usize, f: impl FnOnce() -> T) -> T {
+        let outer_module = self.current_module.replace(file);
+        let error_count = self.errors.borrow().len();
+        let warning_count = self.warnings.borrow().len();
+        let result = f();
+        self.current_module.replace(outer_module);
+        for error in self.errors.borrow_mut().iter_mut().skip(error_count) {
+            error.file.get_or_insert(file);
+        }
+        for warning in self.warnings.borrow_mut().iter_mut().skip(warning_count) {
+            warning.file.get_or_insert(file);
+        }
+        result
+    }
+
+    pub(crate) fn in_module_of_function<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        self.in_module_of(&self.function_modules, name, f)
+    }
+
+    pub(crate) fn in_module_of_type<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        self.in_module_of(&self.type_modules, name, f)
+    }
 }
 
 pub(crate) fn mangle_fn_name(
@@ -79,9 +185,32 @@ pub(crate) fn mangle_fn_name(
     mangled_name
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableKind {
+    Parameter,
+    Local,
+    Global,
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub ty: ResolvedType,
+    pub kind: VariableKind,
+    pub range: Range,
+}
+
+impl Variable {
+    pub fn new(ty: ResolvedType, kind: VariableKind, range: Range) -> Self {
+        Self { ty, kind, range }
+    }
+}
+
+// 同じスコープで同名の変数が再宣言された場合は、後の宣言が優先される
+type VariableScope = Vec<(String, Variable)>;
+
 #[derive(Debug, Clone)]
 pub struct VariableScopes {
-    scopes: Vec<HashMap<String, ResolvedType>>,
+    scopes: Vec<VariableScope>,
 }
 
 impl<'a> VariableScopes {
@@ -90,25 +219,25 @@ impl<'a> VariableScopes {
     }
 
     fn push_new(&mut self) {
-        self.scopes.push(HashMap::new());
+        self.scopes.push(Vec::new());
     }
 
-    fn push(&mut self, scope: HashMap<String, ResolvedType>) {
+    fn push(&mut self, scope: VariableScope) {
         self.scopes.push(scope);
     }
 
-    fn pop(&mut self) -> HashMap<String, ResolvedType> {
+    fn pop(&mut self) -> VariableScope {
         self.scopes.pop().unwrap()
     }
 
-    fn add(&mut self, name: String, ty: ResolvedType) {
-        self.scopes.last_mut().unwrap().insert(name, ty);
+    fn add(&mut self, name: String, variable: Variable) {
+        self.scopes.last_mut().unwrap().push((name, variable));
     }
 
     fn get(&'a self, name: &str) -> Option<&ResolvedType> {
         for scope in self.scopes.iter().rev() {
-            if let Some(ty) = scope.get(name) {
-                return Some(ty);
+            if let Some((_, variable)) = scope.iter().rev().find(|(n, _)| n == name) {
+                return Some(&variable.ty);
             }
         }
         None
@@ -145,6 +274,10 @@ impl<'a> TypeScopes {
         self.scopes.last_mut().unwrap().insert(name, ty);
     }
 
+    fn add_global(&mut self, name: String, ty: ResolvedType) {
+        self.scopes.first_mut().unwrap().insert(name, ty);
+    }
+
     fn get(&'a self, name: &str) -> Option<&ResolvedType> {
         for scope in self.scopes.iter().rev() {
             if let Some(ty) = scope.get(name) {
@@ -191,21 +324,71 @@ fn resolve_function(
     context: &ResolverContext,
     current_fn: &ast::Function,
 ) -> Result<(), FaitalError> {
-    let result_type = resolve_type(context, &current_fn.decl.return_type)?;
+    // 関数の中の名前は、その関数を定義したファイルから見えるものに限る
+    context.in_module_of_function(&current_fn.decl.name, || {
+        let result_type = resolve_type(context, &current_fn.decl.return_type)?;
+        // 呼び出し先の関数は呼び出し元の途中で解決されるので、関数ごとの状態を退避しておく
+        let outer_loop_depth = context.loop_depth.replace(0);
+        let outer_in_var_args_function = context
+            .in_var_args_function
+            .replace(current_fn.decl.is_var_args());
+        let result = resolve_function_body(context, current_fn, result_type);
+        context.loop_depth.replace(outer_loop_depth);
+        context
+            .in_var_args_function
+            .replace(outer_in_var_args_function);
+        result
+    })
+}
+
+fn resolve_function_body(
+    context: &ResolverContext,
+    current_fn: &ast::Function,
+    result_type: ResolvedType,
+) -> Result<(), FaitalError> {
     in_new_scope!(context.scopes, {
         let mut resolved_args: Vec<resolved_ast::Argument> = Vec::new();
+        let mut has_default = false;
         for arg in &current_fn.decl.args {
             match arg {
                 Argument::VarArgs => {
                     resolved_args.push(resolved_ast::Argument::VarArgs);
                 }
-                Argument::Normal(arg_ty, arg_name) => {
+                Argument::Normal {
+                    name: arg_name,
+                    ty: arg_ty,
+                    default,
+                    ..
+                } => {
+                    // 省略できる引数は末尾にまとめる
+                    if default.is_some() {
+                        has_default = true;
+                    } else if has_default {
+                        context.errors.borrow_mut().push(CompileError::new(
+                            arg_name.range,
+                            error::CompileErrorKind::RequiredArgumentAfterDefault {
+                                name: arg_name.value.clone(),
+                            },
+                        ));
+                    }
+                    // 型を省略できるのはメソッドのselfだけ
+                    if arg_ty.value == UnresolvedType::Infer {
+                        context.errors.borrow_mut().push(CompileError::new(
+                            arg_name.range,
+                            error::CompileErrorKind::SelfOutsideMethod,
+                        ));
+                    }
                     let arg_type = resolve_type(context, arg_ty)?;
+                    let variable =
+                        Variable::new(arg_type.clone(), VariableKind::Parameter, arg_name.range);
                     context
                         .scopes
                         .borrow_mut()
-                        .add(arg_name.clone(), arg_type.clone());
-                    resolved_args.push(resolved_ast::Argument::Normal(arg_type, arg_name.clone()));
+                        .add(arg_name.value.clone(), variable);
+                    resolved_args.push(resolved_ast::Argument::Normal(
+                        arg_type,
+                        arg_name.value.clone(),
+                    ));
                 }
             }
         }
@@ -223,19 +406,53 @@ fn resolve_function(
             current_fn.decl.name.clone()
         };
 
-        if context.resolved_functions.borrow().contains_key(&name) {
+        // ジェネリック関数は型引数ごとに名前が変わるので、同じ型引数の特殊化は1つにまとまる
+        if context.resolved_functions.borrow().contains_key(&name)
+            || context.resolving_functions.borrow().contains(&name)
+        {
             return Ok(());
         }
 
-        let mut resolved_statements = Vec::new();
-        for statement in &current_fn.body {
-            resolved_statements.push(resolve_statement(context, statement)?);
+        context.resolving_functions.borrow_mut().push(name.clone());
+        let resolved_statements = resolve_statements(context, &current_fn.body);
+        context.resolving_functions.borrow_mut().pop();
+        let mut resolved_statements = resolved_statements?;
+        // 戻り値の型を省略した関数は、return文の型から推論する
+        let result_type = if let UnresolvedType::Infer = current_fn.decl.return_type.value {
+            infer_return_type(context, current_fn, &resolved_statements)
+        } else {
+            result_type
+        };
+        if !current_fn.decl.is_intrinsic {
+            report_unused_variables(context, current_fn);
         }
-        // 必ずReturnするための特別な処理
+        // voidの関数のreturnは値を持てない
+        if result_type == ResolvedType::Void {
+            let mut returns = Vec::new();
+            for statement in &resolved_statements {
+                statement.collect_returns(&mut returns);
+            }
+            for ret in returns {
+                let Some(expression) = &ret.expression else {
+                    continue;
+                };
+                if !matches!(expression.ty, ResolvedType::Void | ResolvedType::Unknown) {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        ret.range,
+                        error::CompileErrorKind::ReturnTypeMismatch {
+                            expected: ResolvedType::Void,
+                            actual: expression.ty.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+        // 必ずReturnするための特別な処理。補ったReturnは直前の文の位置を持つ
         if !current_fn.decl.is_intrinsic {
             if resolved_statements.is_empty() {
                 resolved_statements.push(resolved_ast::Statement::Return(resolved_ast::Return {
                     expression: None,
+                    range: current_fn.range,
                 }));
             } else {
                 let last_stmt = resolved_statements.pop().unwrap();
@@ -248,15 +465,35 @@ fn resolve_function(
                             resolved_statements.push(resolved_ast::Statement::Effect(
                                 resolved_ast::Effect {
                                     expression: effect.expression.clone(),
+                                    range: effect.range,
                                 },
                             ));
                             resolved_statements.push(resolved_ast::Statement::Return(
-                                resolved_ast::Return { expression: None },
+                                resolved_ast::Return {
+                                    expression: None,
+                                    range: effect.range,
+                                },
                             ));
                         } else {
                             resolved_statements.push(resolved_ast::Statement::Return(
                                 resolved_ast::Return {
                                     expression: Some(effect.expression.clone()),
+                                    range: effect.range,
+                                },
+                            ));
+                        }
+                    }
+                    // ブロックやswitchなどは値を持たないので、voidの関数でだけReturnを補う
+                    resolved_ast::Statement::Block(_)
+                    | resolved_ast::Statement::Switch(_)
+                    | resolved_ast::Statement::DoWhile(_) => {
+                        let returns = last_stmt.always_returns();
+                        resolved_statements.push(last_stmt);
+                        if !returns && result_type == ResolvedType::Void {
+                            resolved_statements.push(resolved_ast::Statement::Return(
+                                resolved_ast::Return {
+                                    expression: None,
+                                    range: current_fn.body.last().unwrap().range,
                                 },
                             ));
                         }
@@ -266,17 +503,40 @@ fn resolve_function(
         }
 
         if ResolvedType::Void != result_type && !current_fn.decl.is_intrinsic {
-            let return_stmt = resolved_statements.last().unwrap();
-            let actual_return_ty = match return_stmt {
-                resolved_ast::Statement::Return(ret) => &ret.expression.as_ref().unwrap().ty,
-                _ => unreachable!(),
+            let return_expr = match resolved_statements
+                .last()
+                .and_then(resolved_ast::Statement::final_return)
+            {
+                Some(resolved_ast::Return {
+                    expression: Some(expression),
+                    ..
+                }) if yields_value(expression) => Some(expression),
+                _ => None,
             };
-            if !result_type.can_insert(actual_return_ty) {
+            // すべてのcaseとdefaultがreturnするswitchなどで終わる場合は、それぞれのreturnで値を返している
+            let ends_with_returning_statement = matches!(
+                resolved_statements.last(),
+                Some(
+                    statement @ (resolved_ast::Statement::Switch(_)
+                    | resolved_ast::Statement::DoWhile(_))
+                ) if statement.always_returns()
+            );
+            if let Some(return_expr) = return_expr {
+                let actual_return_ty = &return_expr.ty;
+                if !result_type.can_insert(actual_return_ty) {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        current_fn.body.last().unwrap().range,
+                        crate::resolver::error::CompileErrorKind::TypeMismatch {
+                            expected: result_type.clone(),
+                            actual: actual_return_ty.clone(),
+                        },
+                    ));
+                }
+            } else if !ends_with_returning_statement {
                 context.errors.borrow_mut().push(CompileError::new(
-                    current_fn.body.last().unwrap().range,
-                    crate::resolver::error::CompileErrorKind::TypeMismatch {
-                        expected: result_type.clone(),
-                        actual: actual_return_ty.clone(),
+                    current_fn.range,
+                    crate::resolver::error::CompileErrorKind::MissingReturn {
+                        fn_name: current_fn.decl.name.clone(),
                     },
                 ));
             }
@@ -289,6 +549,7 @@ fn resolve_function(
                 return_type: result_type,
             },
             body: resolved_statements,
+            range: current_fn.range,
         };
 
         context
@@ -299,10 +560,179 @@ fn resolve_function(
     Ok(())
 }
 
+// すべてのreturn文と最後の式が同じ型を返していればその型にする。どちらもなければvoidになる
+fn infer_return_type(
+    context: &ResolverContext,
+    current_fn: &ast::Function,
+    statements: &[resolved_ast::Statement],
+) -> ResolvedType {
+    // 値を返す最後の式は、後でreturn文に置き換わる
+    let trailing_return = match statements.last() {
+        Some(resolved_ast::Statement::Effect(effect)) if yields_value(&effect.expression) => {
+            Some(resolved_ast::Return {
+                expression: Some(effect.expression.clone()),
+                range: effect.range,
+            })
+        }
+        _ => None,
+    };
+    let mut returns = Vec::new();
+    for statement in statements {
+        statement.collect_returns(&mut returns);
+    }
+    returns.extend(trailing_return.as_ref());
+    let mut inferred: Option<ResolvedType> = None;
+    for ret in returns {
+        let ty = ret
+            .expression
+            .as_ref()
+            .map_or(ResolvedType::Void, |expression| expression.ty.clone());
+        // 型が分からない式は、既にエラーを積んでいる
+        if ty == ResolvedType::Unknown {
+            continue;
+        }
+        match &inferred {
+            None => inferred = Some(ty),
+            Some(first) if first.can_insert(&ty) => {}
+            Some(first) => context.errors.borrow_mut().push(CompileError::new(
+                ret.range,
+                error::CompileErrorKind::ConflictingReturnTypes {
+                    fn_name: current_fn.decl.name.clone(),
+                    first: first.clone(),
+                    second: ty,
+                },
+            )),
+        }
+    }
+    inferred.unwrap_or(ResolvedType::Void)
+}
+
+// 最後の式が値を返すかどうか。ifはどちらの分岐も値を返す必要がある
+fn yields_value(expression: &resolved_ast::ResolvedExpression) -> bool {
+    match &expression.kind {
+        resolved_ast::ExpressionKind::If(if_expr) => {
+            yields_value(&if_expr.then) && yields_value(&if_expr.els)
+        }
+        _ => expression.ty != ResolvedType::Void,
+    }
+}
+
+// 初期値を解決してコンパイル時に評価する。評価できなければエラーを積み、型だけを持つ式を返す
+fn resolve_constant_initializer(
+    context: &ResolverContext,
+    name: &str,
+    ty: Option<&Located<UnresolvedType>>,
+    value: Located<&Expression>,
+) -> Result<resolved_ast::ResolvedExpression, FaitalError> {
+    let annotation = ty.map(|ty| resolve_type(context, ty)).transpose()?;
+    let range = value.range;
+    let resolved_value = expression::resolve_expression(context, value, annotation.as_ref())?;
+    if let Some(annotation) = &annotation {
+        if !annotation.can_insert(&resolved_value.ty) {
+            context.errors.borrow_mut().push(CompileError::new(
+                range,
+                error::CompileErrorKind::TypeMismatch {
+                    expected: annotation.clone(),
+                    actual: resolved_value.ty.clone(),
+                },
+            ));
+        }
+    }
+    if let Some(folded) = constant::evaluate_constant(context, &resolved_value) {
+        return Ok(folded);
+    }
+    context.errors.borrow_mut().push(CompileError::new(
+        range,
+        error::CompileErrorKind::NonConstantInitializer {
+            name: name.to_string(),
+        },
+    ));
+    Ok(resolved_ast::ResolvedExpression {
+        range,
+        ty: resolved_value.ty,
+        kind: resolved_ast::ExpressionKind::Unknown,
+    })
+}
+
+fn resolve_const(context: &ResolverContext, decl: &ConstDecl) -> Result<(), FaitalError> {
+    let value =
+        resolve_constant_initializer(context, &decl.name, decl.ty.as_ref(), decl.value.as_deref())?;
+    context
+        .constants
+        .borrow_mut()
+        .insert(decl.name.clone(), value);
+    Ok(())
+}
+
+fn resolve_global_variable(
+    context: &ResolverContext,
+    decl: &Located<VariableDecl>,
+) -> Result<resolved_ast::GlobalVariable, FaitalError> {
+    let value = resolve_constant_initializer(
+        context,
+        &decl.name,
+        decl.ty.as_ref(),
+        decl.value.value.as_deref(),
+    )?;
+    let variable = Variable::new(value.ty.clone(), VariableKind::Global, decl.range);
+    context.scopes.borrow_mut().add(decl.name.clone(), variable);
+    Ok(resolved_ast::GlobalVariable {
+        name: decl.name.clone(),
+        value,
+    })
+}
+
+// メソッドを解決した後の関数名。呼び出し側もこの名前で引く
+pub(crate) fn method_name(type_name: &str, name: &str) -> String {
+    format!("{}.{}", type_name, name)
+}
+
+// メソッドは、selfにレシーバーへのポインタを受け取る普通の関数として登録する
+fn register_methods(context: &ResolverContext, type_name: &str, methods: &[Function]) {
+    let mut function_by_name = context.function_by_name.borrow_mut();
+    for method in methods {
+        let mut method = method.clone();
+        method.decl.name = method_name(type_name, &method.decl.name);
+        if let Some(Argument::Normal { name, ty, .. }) = method.decl.args.first_mut() {
+            if name.value == "self" && ty.value == UnresolvedType::Infer {
+                ty.value = UnresolvedType::Ptr(Box::new(Located {
+                    range: ty.range,
+                    value: UnresolvedType::TypeRef(TypeRef {
+                        name: type_name.to_string(),
+                        generic_args: None,
+                    }),
+                }));
+            }
+        }
+        if let Some(previous) = function_by_name.get(&method.decl.name) {
+            context.errors.borrow_mut().push(CompileError::new(
+                method.range,
+                error::CompileErrorKind::DuplicateFunction {
+                    name: method.decl.name.clone(),
+                    previous: Some(previous.range),
+                },
+            ));
+            continue;
+        }
+        function_by_name.insert(method.decl.name.clone(), method);
+    }
+}
+
 pub(crate) fn resolve_module(
     context: &ResolverContext,
     module: &crate::ast::Module,
     is_build_only: bool,
+) -> Result<crate::resolved_ast::ResolvedModule, FaitalError> {
+    let files = vec![0; module.toplevels.len()];
+    resolve_toplevels(context, module, &files, is_build_only)
+}
+
+// filesはmoduleのトップレベルの要素ごとの、それを書いたファイルの番号
+fn resolve_toplevels(
+    context: &ResolverContext,
+    module: &crate::ast::Module,
+    files: &[usize],
+    is_build_only: bool,
 ) -> Result<crate::resolved_ast::ResolvedModule, FaitalError> {
     context.scopes.borrow_mut().push_new();
     context.types.borrow_mut().push_new();
@@ -313,14 +743,30 @@ pub(crate) fn resolve_module(
     }
     register_intrinsic_types(context.types.borrow_mut().deref_mut());
 
-    for toplevel in &module.toplevels {
+    // 同名の関数の2つ目以降の定義。引数の型を解決できるようになってからオーバーロードとして登録する
+    let mut overloading_functions = Vec::new();
+    for (toplevel, &file) in module.toplevels.iter().zip(files) {
         match &toplevel.value {
             // 関数を名前で引けるようにしておく
             TopLevel::Function(func) => {
-                context
-                    .function_by_name
-                    .borrow_mut()
-                    .insert(func.decl.name.clone(), func.clone());
+                let mut function_by_name = context.function_by_name.borrow_mut();
+                if let Some(previous) = function_by_name.get(&func.decl.name) {
+                    if previous.decl.is_intrinsic {
+                        context.in_file(file, || {
+                            context.errors.borrow_mut().push(CompileError::new(
+                                func.range,
+                                error::CompileErrorKind::DuplicateFunction {
+                                    name: func.decl.name.clone(),
+                                    previous: None,
+                                },
+                            ))
+                        });
+                    } else {
+                        overloading_functions.push((func, file));
+                    }
+                    continue;
+                }
+                function_by_name.insert(func.decl.name.clone(), func.clone());
             }
             // 型定義を名前で引けるようにしておく
             TopLevel::TypeDef(typedef) => {
@@ -328,6 +774,12 @@ pub(crate) fn resolve_module(
                     .type_defs
                     .borrow_mut()
                     .insert(typedef.name.clone(), typedef.clone());
+                if let TypeDefKind::StructLike(struct_def) = &typedef.kind {
+                    register_methods(context, &typedef.name, &struct_def.methods);
+                }
+            }
+            TopLevel::MethodImpl(method_impl) => {
+                register_methods(context, &method_impl.target, &method_impl.methods);
             }
             TopLevel::Interface(interface) => {
                 context
@@ -335,14 +787,40 @@ pub(crate) fn resolve_module(
                     .borrow_mut()
                     .insert(interface.name.clone(), interface.clone());
             }
+            TopLevel::TypeAlias(alias) => {
+                context
+                    .type_aliases
+                    .borrow_mut()
+                    .insert(alias.name.clone(), alias.clone());
+            }
             TopLevel::Implemantation(_) => (),
+            // importしたファイルはresolve_modulesでまとめて渡される
+            TopLevel::GlobalVariables(_) | TopLevel::Const(_) | TopLevel::Import(_) => (),
         }
     }
 
-    // TypeDefが登録された後、Implentationの登録に必要な型を解決する
+    // 使われていないエイリアスの循環も報告するため、全てのエイリアスを先に解決しておく
     for toplevel in &module.toplevels {
+        if let TopLevel::TypeAlias(alias) = &toplevel.value {
+            resolve_type_alias(context, &alias.name)?;
+        }
+    }
+
+    // 使われていないstructが自身を含んでいても報告するため、全てのstructを先に解決しておく
+    for (toplevel, &file) in module.toplevels.iter().zip(files) {
+        if let TopLevel::TypeDef(typedef) = &toplevel.value {
+            context.in_file(file, || check_struct_def(context, toplevel.range, typedef))?;
+        }
+    }
+
+    for (func, file) in overloading_functions {
+        context.in_file(file, || register_overload(context, func))?;
+    }
+
+    // TypeDefが登録された後、Implentationの登録に必要な型を解決する
+    for (toplevel, &file) in module.toplevels.iter().zip(files) {
         match &toplevel.value {
-            TopLevel::Implemantation(implementation) => {
+            TopLevel::Implemantation(implementation) => context.in_file(file, || {
                 let mut impls_by_name = context.impls_by_name.borrow_mut();
                 let mut impl_by_target = impls_by_name
                     .entry(implementation.decl.name.clone())
@@ -350,7 +828,7 @@ pub(crate) fn resolve_module(
 
                 if let Ok(resolved_ty) = resolve_type(context, &implementation.decl.target_ty) {
                     impl_by_target.push(implementation.clone());
-                    continue;
+                    return;
                 }
 
                 match &implementation.decl.target_ty.value {
@@ -373,7 +851,10 @@ pub(crate) fn resolve_module(
                             ));
                         }
                     }
-                    UnresolvedType::Ptr(_) => {
+                    UnresolvedType::Ptr(_)
+                    | UnresolvedType::Array(_, _)
+                    | UnresolvedType::FnPtr(_, _)
+                    | UnresolvedType::Tuple(_) => {
                         context.errors.borrow_mut().push(CompileError::new(
                             implementation.decl.target_ty.range,
                             crate::resolver::error::CompileErrorKind::ImplForPointerIsInvalid,
@@ -386,18 +867,39 @@ pub(crate) fn resolve_module(
                         ));
                     }
                 }
-            }
+            }),
             _ => {}
         }
     }
 
+    let resolved_toplevels = RefCell::new(Vec::new());
+
+    // constとグローバル変数は関数より先に、宣言された順に解決する
+    for (toplevel, &file) in module.toplevels.iter().zip(files) {
+        context.in_file(file, || -> Result<(), FaitalError> {
+            match &toplevel.value {
+                TopLevel::GlobalVariables(decls) => {
+                    for decl in &decls.decls {
+                        let global_variable = resolve_global_variable(context, decl)?;
+                        resolved_toplevels
+                            .borrow_mut()
+                            .push(resolved_ast::TopLevel::GlobalVariable(global_variable));
+                    }
+                }
+                TopLevel::Const(const_decl) => {
+                    resolve_const(context, const_decl)?;
+                }
+                _ => {}
+            }
+            Ok(())
+        })?;
+    }
+
     let function_by_name = context.function_by_name.borrow();
     let main_fn = function_by_name
         .get("main")
         .ok_or_else(|| FaitalError("No main function found".into()))?;
 
-    let resolved_toplevels = RefCell::new(Vec::new());
-
     // main関数から辿れる関数を全て解決する
     resolve_function(&context, main_fn)?;
 
@@ -417,14 +919,25 @@ pub(crate) fn resolve_module(
                         // TODO: この部分で出来ない解析は別の場所で行う
                         continue;
                     }
-                    resolve_function(&context, unresolved_function)?;
+                    // オーバーロードはマングルした名前で解決する
+                    let Some(unresolved_function) =
+                        registered_function(context, unresolved_function)
+                    else {
+                        continue;
+                    };
+                    resolve_function(&context, &unresolved_function)?;
                     for resolved_function in context.resolved_functions.borrow().values() {
                         resolved_toplevels
                             .borrow_mut()
                             .push(resolved_ast::TopLevel::Function(resolved_function.clone()));
                     }
                 }
-                TopLevel::TypeDef(_) => {}
+                TopLevel::TypeDef(_)
+                | TopLevel::TypeAlias(_)
+                | TopLevel::Import(_)
+                | TopLevel::GlobalVariables(_)
+                | TopLevel::Const(_)
+                | TopLevel::MethodImpl(_) => {}
                 TopLevel::Implemantation(_) => todo!(),
                 TopLevel::Interface(_) => todo!(),
             }
@@ -435,3 +948,861 @@ pub(crate) fn resolve_module(
         toplevels: resolved_toplevels.into_inner(),
     })
 }
+
+// ファイルごとのモジュールを1つにまとめて解決する。pubを付けた関数と型は他のファイルから使える。
+// ファイルをまたいで同じ名前の関数を定義するとエラーにし、後のファイルの定義は捨てる。
+// Rangeはファイルを区別しないので、previousは別のファイルの位置を指すことがある
+pub(crate) fn resolve_modules(
+    context: &ResolverContext,
+    modules: Vec<crate::ast::Module>,
+    is_build_only: bool,
+) -> Result<crate::resolved_ast::ResolvedModule, FaitalError> {
+    // 関数名と、それを最初に定義したファイルの番号と位置
+    let mut defined_in: HashMap<String, (usize, Range)> = HashMap::new();
+    let mut toplevels = Vec::new();
+    let mut files = Vec::new();
+    for (file_index, module) in modules.into_iter().enumerate() {
+        for toplevel in module.toplevels {
+            if let TopLevel::Function(func) = &toplevel.value {
+                match defined_in.get(&func.decl.name) {
+                    Some((previous_file, previous)) if *previous_file != file_index => {
+                        context.in_file(file_index, || {
+                            context.errors.borrow_mut().push(CompileError::new(
+                                func.range,
+                                error::CompileErrorKind::DuplicateFunction {
+                                    name: func.decl.name.clone(),
+                                    previous: Some(*previous),
+                                },
+                            ))
+                        });
+                        continue;
+                    }
+                    Some(_) => {}
+                    None => {
+                        defined_in.insert(func.decl.name.clone(), (file_index, func.range));
+                    }
+                }
+            }
+            let (name, visibility, modules) = match &toplevel.value {
+                TopLevel::Function(func) => (
+                    &func.decl.name,
+                    func.decl.visibility,
+                    &context.function_modules,
+                ),
+                TopLevel::TypeDef(type_def) => {
+                    (&type_def.name, type_def.visibility, &context.type_modules)
+                }
+                TopLevel::TypeAlias(alias) => {
+                    (&alias.name, alias.visibility, &context.type_modules)
+                }
+                _ => {
+                    toplevels.push(toplevel);
+                    files.push(file_index);
+                    continue;
+                }
+            };
+            // オーバーロードのどれかがpubなら、その名前は他のファイルから見える
+            let mut modules = modules.borrow_mut();
+            let entry = modules
+                .entry(name.clone())
+                .or_insert((file_index, visibility));
+            if visibility == Visibility::Public {
+                entry.1 = Visibility::Public;
+            }
+            drop(modules);
+            toplevels.push(toplevel);
+            files.push(file_index);
+        }
+    }
+    let module = crate::ast::Module {
+        toplevels,
+        errors: Vec::new(),
+    };
+    resolve_toplevels(context, &module, &files, is_build_only)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use nom::Finish;
+
+    use super::*;
+    use crate::{parser::parse_module, resolver::error::CompileWarningKind};
+
+    pub(crate) fn resolve_source(source: &str) -> ResolverContext {
+        let (_, module) = parse_module(source.into()).finish().unwrap();
+        let context = ResolverContext::new(PointerSizedIntWidth::SixtyFour);
+        resolve_module(&context, &module, true).unwrap();
+        context
+    }
+
+    #[test]
+    fn test_unused_parameter_warning() {
+        let context = resolve_source(
+            "
+fn f(x: i32, _y: i32, @unused z: i32, w: i32): i32 {
+    w
+}
+fn main(): void {
+    (f 1 2 3 4)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+        let warnings = context.warnings.borrow();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].kind,
+            CompileWarningKind::UnusedParameter { name: "x".into() }
+        );
+        assert_eq!(warnings[0].range.from.line, 2);
+        assert_eq!(warnings[0].range.from.col, 6);
+    }
+
+    #[test]
+    fn test_unused_variable_warning() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= x 1 y 2 _z 3)
+    (while (< y 10)
+        (:= w y)
+        (:=< y (+ y 1)))
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+        assert_eq!(context.resolved_functions.borrow().len(), 1);
+        let warnings = context.warnings.borrow();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(
+            warnings[0].kind,
+            CompileWarningKind::UnusedVariable { name: "w".into() }
+        );
+        assert_eq!(warnings[0].range.from.line, 5);
+        assert_eq!(
+            warnings[1].kind,
+            CompileWarningKind::UnusedVariable { name: "x".into() }
+        );
+        assert_eq!(warnings[1].range.from.line, 3);
+    }
+
+    #[test]
+    fn test_parameter_used_through_pointer_assignment() {
+        let context = resolve_source(
+            "
+fn set(p: *i32): void {
+    (:=< *p 1)
+}
+fn main(): void {
+    (:= _x: i32 0)
+    (set (malloc 4))
+}
+",
+        );
+        assert!(context.warnings.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_global_variable_requires_constant_initializer() {
+        let context = resolve_source(
+            "
+(:= limit: u8 10)
+(:= doubled (* limit 2))
+fn main(): void {
+    (:= x limit)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::NonConstantInitializer {
+                name: "doubled".into()
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 3);
+    }
+
+    #[test]
+    fn test_error_reports_location() {
+        let context = resolve_source(
+            "
+fn main(): void {
+    (:= x 1)
+    (:=< x  undefined)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::VariableNotFound {
+                name: "undefined".into()
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 4);
+        assert_eq!(errors[0].range.from.col, 13);
+        assert_eq!(
+            errors[0].to_string(),
+            "4:13: Variable `\"undefined\"` is not found in this scope."
+        );
+    }
+
+    #[test]
+    fn test_missing_return() {
+        let context = resolve_source(
+            "
+fn f(x: i32): i32 {
+    (when (> x 0) x)
+}
+fn main(): void {
+    (f 1)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::MissingReturn {
+                fn_name: "f".into()
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 2);
+    }
+
+    #[test]
+    fn test_missing_return_in_one_branch() {
+        let context = resolve_source(
+            "
+fn f(x: i32): i32 {
+    (:= y 0)
+    (if (> x 0) x (:=< y 1))
+}
+fn main(): void {
+    (f 1)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert!(errors.iter().any(|error| error.kind
+            == error::CompileErrorKind::MissingReturn {
+                fn_name: "f".into()
+            }));
+    }
+
+    #[test]
+    fn test_duplicate_function() {
+        let context = resolve_source(
+            "
+fn main(): void {
+}
+fn main(): void {
+}
+fn printf(s: *u8): i32 {
+    0
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2);
+        // 組み込み関数との衝突は関数を登録する時点で見つかる
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::DuplicateFunction {
+                name: "printf".into(),
+                previous: None,
+            }
+        );
+        let error::CompileErrorKind::DuplicateFunction { name, previous } = &errors[1].kind else {
+            panic!("unexpected error: {:?}", errors[1]);
+        };
+        assert_eq!(name, "main");
+        assert_eq!(previous.unwrap().from.line, 2);
+        assert_eq!(errors[1].range.from.line, 4);
+    }
+
+    #[test]
+    fn test_invalid_array_length() {
+        let context = resolve_source(
+            "
+type Bytes = [u8; 16]
+type Empty = [u8; 0]
+type Negative = *[i32; -1]
+fn main(): void {
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::InvalidArrayLength { len: 0 }
+        );
+        assert_eq!(errors[0].range.from.line, 3);
+        assert_eq!(
+            errors[1].kind,
+            error::CompileErrorKind::InvalidArrayLength { len: -1 }
+        );
+        assert_eq!(errors[1].range.from.line, 4);
+    }
+
+    fn resolve_sources(sources: &[&str]) -> (ResolverContext, crate::resolved_ast::ResolvedModule) {
+        let modules = sources
+            .iter()
+            .map(|source| parse_module((*source).into()).finish().unwrap().1)
+            .collect();
+        let context = ResolverContext::new(PointerSizedIntWidth::SixtyFour);
+        let module = resolve_modules(&context, modules, true).unwrap();
+        (context, module)
+    }
+
+    #[test]
+    fn test_resolve_multiple_files() {
+        let main = "
+fn main(): i32 {
+    (helper 21)
+}
+";
+        let lib = "
+pub fn helper(x: i32): i32 {
+    (* x 2)
+}
+";
+        let (context, module) = resolve_sources(&[main, lib]);
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+        let names = module
+            .toplevels
+            .iter()
+            .filter_map(|toplevel| match toplevel {
+                resolved_ast::TopLevel::Function(func) => Some(func.decl.name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(names.contains(&"main"), "{:?}", names);
+        assert!(names.contains(&"helper"), "{:?}", names);
+
+        // ファイルをまたいだ同名の関数はオーバーロードにしない
+        let other_lib = "
+fn helper(x: i64): i64 {
+    x
+}
+";
+        let (context, _) = resolve_sources(&[main, lib, other_lib]);
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        let error::CompileErrorKind::DuplicateFunction { name, previous } = &errors[0].kind else {
+            panic!("unexpected error: {:?}", errors[0]);
+        };
+        assert_eq!(name, "helper");
+        assert_eq!(previous.unwrap().from.line, 2);
+        assert_eq!(errors[0].file(), 2);
+    }
+
+    #[test]
+    fn test_diagnostics_have_file_index() {
+        let main = "
+fn main(): i32 {
+    (:= unused 1)
+    (helper 1 true)
+}
+";
+        let lib = "
+(const LIMIT: i32 (+ 1 true))
+pub fn helper(x: i32, y: bool): i32 {
+    (:= unused 1)
+    (+ x y)
+}
+";
+        let (context, _) = resolve_sources(&[main, lib]);
+        // 診断の位置は、それを出したファイルの中の位置になる
+        let errors = context.errors.borrow();
+        let mut files = errors
+            .iter()
+            .map(|error| (error.file(), error.range.from.line))
+            .collect::<Vec<_>>();
+        files.dedup();
+        assert_eq!(files, vec![(1, 2), (1, 5)], "{:?}", errors);
+        let warnings = context.warnings.borrow();
+        let mut files = warnings
+            .iter()
+            .map(|warning| (warning.file(), warning.range.from.line))
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, vec![(0, 3), (1, 4)], "{:?}", warnings);
+    }
+
+    #[test]
+    fn test_overloads_across_files() {
+        let lib = "
+fn show(n: i32): void {
+    (printf \"%d\" n)
+}
+pub fn show(s: *u8): void {
+    (printf s)
+}
+";
+        let main = "
+fn main(): void {
+    (show \"hello\")
+    (show 1)
+}
+";
+        let (context, _) = resolve_sources(&[main, lib]);
+        // オーバーロードはそれぞれを書いたファイルと公開範囲で登録する
+        let function_modules = context.function_modules.borrow();
+        for overload in &context.overloads_by_name.borrow()["show"] {
+            assert_eq!(function_modules[&overload.mangled_name].0, 1);
+        }
+        let errors = context.errors.borrow();
+        // pubでない方は他のファイルから呼べない
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::FunctionNotFound {
+                name: "show".into()
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 4);
+    }
+
+    #[test]
+    fn test_private_items_across_files() {
+        let lib = "
+struct Counter {
+    count: i32,
+}
+fn private_helper(x: i32): i32 {
+    (:= counter Counter { count: x })
+    (+ counter.count 1)
+}
+pub fn helper(x: i32): i32 {
+    (private_helper x)
+}
+";
+        // pubな関数からは同じファイルのpubでない関数と型を使える
+        let main = "
+fn main(): i32 {
+    (helper 1)
+}
+";
+        let (context, _) = resolve_sources(&[main, lib]);
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+
+        let main = "
+fn main(): void {
+    (:= counter: Counter Counter { count: 1 })
+    (private_helper 1)
+}
+";
+        let (context, _) = resolve_sources(&[main, lib]);
+        let errors = context.errors.borrow();
+        let kinds = errors.iter().map(|error| &error.kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                &error::CompileErrorKind::TypeNotFound {
+                    name: "Counter".into()
+                },
+                &error::CompileErrorKind::TypeNotFound {
+                    name: "Counter".into()
+                },
+                &error::CompileErrorKind::FunctionNotFound {
+                    name: "private_helper".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overload_by_argument_types() {
+        let context = resolve_source(
+            "
+fn print(n: i32): void {
+    (printf \"%d\" n)
+}
+fn print(s: *u8): void {
+    (printf s)
+}
+fn print(n: i32): i32 {
+    n
+}
+fn main(): void {
+    (print 1)
+    (print \"hello\")
+    (print 1.0)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 2);
+        let error::CompileErrorKind::DuplicateFunction { name, previous } = &errors[0].kind else {
+            panic!("unexpected error: {:?}", errors[0]);
+        };
+        assert_eq!(name, "print");
+        assert_eq!(previous.unwrap().from.line, 2);
+        assert_eq!(
+            errors[1].kind,
+            error::CompileErrorKind::NoMatchingOverload {
+                name: "print".into(),
+                arg_types: vec![ResolvedType::F64],
+            }
+        );
+
+        let resolved_functions = context.resolved_functions.borrow();
+        let main = &resolved_functions["main"];
+        let callees = main
+            .body
+            .iter()
+            .filter_map(|statement| match statement {
+                resolved_ast::Statement::Effect(resolved_ast::Effect {
+                    expression:
+                        resolved_ast::ResolvedExpression {
+                            kind: resolved_ast::ExpressionKind::CallExpr(call_expr),
+                            ..
+                        },
+                    ..
+                }) => Some(call_expr.callee.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(callees, vec!["print(i32)->void", "print([u8])->void"]);
+        assert!(resolved_functions.contains_key("print(i32)->void"));
+        assert!(resolved_functions.contains_key("print([u8])->void"));
+    }
+
+    #[test]
+    fn test_unreachable_code_after_return() {
+        let context = resolve_source(
+            "
+fn f(): i32 {
+    (:= x 0)
+    return 1
+    (:=< x 2)
+    x
+}
+fn main(): void {
+    (:= i 0)
+    (while (< i 10)
+        (if (< i 5) (break) (continue))
+        (:=< i (+ i 1)))
+    (f)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+        let warnings = context.warnings.borrow();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].kind, CompileWarningKind::UnreachableCode);
+        assert_eq!(warnings[0].range.from.line, 12);
+        assert_eq!(warnings[1].kind, CompileWarningKind::UnreachableCode);
+        assert_eq!(warnings[1].range.from.line, 5);
+
+        let resolved_functions = context.resolved_functions.borrow();
+        let f = &resolved_functions["f"];
+        assert_eq!(f.body.len(), 2);
+        assert!(matches!(
+            f.body.last(),
+            Some(resolved_ast::Statement::Return(_))
+        ));
+    }
+
+    #[test]
+    fn test_switch_case_labels() {
+        let context = resolve_source(
+            "
+(const TWO: i32 2)
+fn f(x: i32, y: i32, p: *u8): void {
+    switch (x) {
+        case 1: (printf \"one\")
+        case (+ 1 1): (printf \"two\")
+        case TWO: (printf \"two\")
+        case y: (printf \"y\")
+        case 3u8: (printf \"three\")
+    }
+    switch (p) {}
+}
+fn main(): void {
+    (f 1 2 \"p\")
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(
+            errors.iter().map(|error| &error.kind).collect::<Vec<_>>(),
+            vec![
+                &error::CompileErrorKind::DuplicateCaseLabel { value: "2".into() },
+                &error::CompileErrorKind::NonConstantCaseLabel,
+                &error::CompileErrorKind::TypeMismatch {
+                    expected: ResolvedType::I32,
+                    actual: ResolvedType::U8,
+                },
+                &error::CompileErrorKind::InvalidSwitchScrutinee {
+                    ty: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+                },
+            ]
+        );
+        assert_eq!(errors[0].range.from.line, 7);
+    }
+
+    #[test]
+    fn test_non_exhaustive_enum_switch() {
+        let context = resolve_source(
+            "
+type Color = enum { Red, Green, Blue }
+fn name(c: Color): void {
+    switch (c) {
+        case Color.Red: (printf \"red\")
+        case Color.Blue: (printf \"blue\")
+    }
+}
+fn main(): void {
+    (name Color.Red)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::NonExhaustiveSwitch {
+                enum_name: "Color".into(),
+                variants: vec!["Green".into()],
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 4);
+    }
+
+    #[test]
+    fn test_exhaustive_enum_switch() {
+        let context = resolve_source(
+            "
+type Color = enum { Red, Green, Blue }
+fn name(c: Color): void {
+    switch (c) {
+        case Color.Red: (printf \"red\")
+        case Color.Green: (printf \"green\")
+        case Color.Blue: (printf \"blue\")
+    }
+    // defaultがあれば全てのバリアントを書かなくてよい
+    switch (c) {
+        case Color.Red: (printf \"red\")
+        default: (printf \"other\")
+    }
+}
+fn main(): void {
+    (name Color.Red)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+    }
+
+    #[test]
+    fn test_function_pointer() {
+        let context = resolve_source(
+            "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn main(): void {
+    (:= op: fn(i32, i32): i32 &add)
+    (:= sum: i32 (op 1 2))
+    (op 1)
+    (op 1 true)
+    (:= wrong: fn(i32): i32 &add)
+    (:= print &printf)
+    (sum 1)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let fn_ptr =
+            |params: Vec<ResolvedType>| ResolvedType::FnPtr(params, Box::new(ResolvedType::I32));
+        assert_eq!(
+            errors.iter().map(|error| &error.kind).collect::<Vec<_>>(),
+            vec![
+                &error::CompileErrorKind::MismatchFunctionArgCount {
+                    name: "op".into(),
+                    expected: 2,
+                    actual: 1,
+                },
+                &error::CompileErrorKind::TypeMismatch {
+                    expected: ResolvedType::I32,
+                    actual: ResolvedType::Bool,
+                },
+                &error::CompileErrorKind::TypeMismatch {
+                    expected: fn_ptr(vec![ResolvedType::I32]),
+                    actual: fn_ptr(vec![ResolvedType::I32, ResolvedType::I32]),
+                },
+                &error::CompileErrorKind::InvalidFunctionRef {
+                    name: "printf".into()
+                },
+                &error::CompileErrorKind::NotCallable {
+                    name: "sum".into(),
+                    ty: ResolvedType::I32,
+                },
+            ]
+        );
+        // アドレスを取られただけの関数も出力に含まれる
+        assert!(context.resolved_functions.borrow().contains_key("add"));
+    }
+
+    #[test]
+    fn test_switch_returning_from_every_case() {
+        let context = resolve_source(
+            "
+fn name(x: i32): i32 {
+    switch (x) {
+        case 0:
+            (:= zero 10)
+            return zero
+        default: return 20
+    }
+}
+fn main(): void {
+    (name 0)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty());
+        assert!(context.warnings.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_block_scope() {
+        let context = resolve_source(
+            "
+fn f(x: i32): i32 {
+    {
+        (:= x: i64 1)
+        (:= inner 2)
+        (printf \"%ld %d\" x inner)
+    }
+    (printf \"%d\" inner)
+    x
+}
+fn main(): void {
+    (f 1)
+}
+",
+        );
+        // ブロック内の宣言は外に漏れず、ブロックを抜けると外側のxが見える
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::VariableNotFound {
+                name: "inner".into()
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 8);
+    }
+
+    #[test]
+    fn test_infer_return_type() {
+        let context = resolve_source(
+            "
+fn add(a: i32, b: i32) {
+    return (+ a b)
+}
+fn show(x: i32) {
+    (when (!= x 0) (printf \"%d\" x))
+}
+fn succ(x: i32) {
+    (+ x 1)
+}
+fn pick(x: i32) {
+    switch (x) {
+        case 0: return 0u8
+    }
+    (if (> x 0) 1u8 2u8)
+}
+fn name(x: i32) {
+    switch (x) {
+        case 0: return 1
+        default: return \"many\"
+    }
+}
+fn fact(n: i32) {
+    return (fact (- n 1))
+}
+fn main(): void {
+    (:= sum: i32 (add 1 2))
+    (show sum)
+    (:= next: i32 (succ sum))
+    (:= picked: u8 (pick sum))
+    (name sum)
+    (fact 3)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &error::CompileErrorKind::ConflictingReturnTypes {
+                    fn_name: "name".into(),
+                    first: ResolvedType::I32,
+                    second: ResolvedType::Ptr(Box::new(ResolvedType::U8)),
+                },
+                &error::CompileErrorKind::CannotInferRecursiveReturnType {
+                    fn_name: "fact".into(),
+                },
+            ]
+        );
+        assert_eq!(errors[0].range.from.line, 20);
+
+        let resolved_functions = context.resolved_functions.borrow();
+        assert_eq!(
+            resolved_functions["add"].decl.return_type,
+            ResolvedType::I32
+        );
+        // 値を返す最後の式も、return文と同じく戻り値の型になる
+        assert_eq!(
+            resolved_functions["succ"].decl.return_type,
+            ResolvedType::I32
+        );
+        assert_eq!(
+            resolved_functions["pick"].decl.return_type,
+            ResolvedType::U8
+        );
+        // return文がなく、最後の式も値を返さなければvoidになる
+        assert_eq!(
+            resolved_functions["show"].decl.return_type,
+            ResolvedType::Void
+        );
+    }
+
+    #[test]
+    fn test_return_value_from_void_function() {
+        let context = resolve_source(
+            "
+fn nop(): void {}
+fn log(x: i32): void {
+    (printf \"%d\" x)
+    (when (< x 0) (printf \"negative\"))
+    return (nop)
+}
+fn main(): void {
+    (log 1)
+    return 0
+}
+",
+        );
+        // voidの式を返すのはよいが、値は返せない
+        let errors = context.errors.borrow();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(
+            errors[0].kind,
+            error::CompileErrorKind::ReturnTypeMismatch {
+                expected: ResolvedType::Void,
+                actual: ResolvedType::I32,
+            }
+        );
+        assert_eq!(errors[0].range.from.line, 10);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::{
+    ast::{self, Located, Range},
+    resolved_ast::ResolvedType,
+};
+
+use super::{
+    error::{CompileError, CompileErrorKind, FaitalError},
+    expression::resolve_expression,
+    mangle_fn_name,
+    ty::resolve_type,
+    ResolverContext,
+};
+
+// 同じ名前で引数の型が異なる関数の一つ
+#[derive(Debug, Clone)]
+pub struct Overload {
+    pub param_types: Vec<ResolvedType>,
+    pub is_var_args: bool,
+    // 既定値を持たない引数の数
+    pub required_arg_count: usize,
+    // function_by_nameのキー。LLVMのシンボル名にもなる
+    pub mangled_name: String,
+}
+
+fn resolve_param_types(
+    context: &ResolverContext,
+    func: &ast::Function,
+) -> Result<Vec<ResolvedType>, FaitalError> {
+    func.decl
+        .args
+        .iter()
+        .filter_map(|arg| match arg {
+            ast::Argument::VarArgs => None,
+            ast::Argument::Normal { ty, .. } => Some(resolve_type(context, ty)),
+        })
+        .collect()
+}
+
+fn register(context: &ResolverContext, func: &ast::Function) -> Result<(), FaitalError> {
+    let param_types = resolve_param_types(context, func)?;
+    let return_type = resolve_type(context, &func.decl.return_type)?;
+    let mangled_name = mangle_fn_name(
+        &func.decl.name,
+        &param_types.iter().collect::<Vec<_>>(),
+        &return_type,
+    );
+    let mut overloads_by_name = context.overloads_by_name.borrow_mut();
+    let overloads = overloads_by_name.entry(func.decl.name.clone()).or_default();
+    // 戻り値の型だけが異なる関数は呼び分けられないので重複とする
+    if let Some(previous) = overloads
+        .iter()
+        .find(|overload| overload.param_types == param_types)
+    {
+        let previous = context.function_by_name.borrow()[&previous.mangled_name].range;
+        push_duplicate_function_error(context, func, previous);
+        return Ok(());
+    }
+    overloads.push(Overload {
+        param_types,
+        is_var_args: func.decl.is_var_args(),
+        required_arg_count: func.decl.required_arg_count(),
+        mangled_name: mangled_name.clone(),
+    });
+    // マングルした名前でも定義したファイルと公開範囲を引けるようにする。funcは解決中のファイルにある
+    let mut function_modules = context.function_modules.borrow_mut();
+    if function_modules.contains_key(&func.decl.name) {
+        let module = *context.current_module.borrow();
+        function_modules.insert(mangled_name.clone(), (module, func.decl.visibility));
+    }
+    drop(function_modules);
+    let mut func = func.clone();
+    func.decl.name = mangled_name.clone();
+    context
+        .function_by_name
+        .borrow_mut()
+        .insert(mangled_name, func);
+    Ok(())
+}
+
+// 同名の関数の2つ目以降の定義を登録する。型を解決するので、型定義を登録した後に、funcを書いたファイルの中で呼ぶ
+pub(super) fn register_overload(
+    context: &ResolverContext,
+    func: &ast::Function,
+) -> Result<(), FaitalError> {
+    let name = &func.decl.name;
+    let first = context.function_by_name.borrow().get(name).cloned();
+    let Some(first) = first else {
+        // 既にオーバーロードとして登録し直してある
+        if func.decl.generic_args.is_some() {
+            let overloads_by_name = context.overloads_by_name.borrow();
+            let first_overload = &overloads_by_name[name][0];
+            let previous = context.function_by_name.borrow()[&first_overload.mangled_name].range;
+            push_duplicate_function_error(context, func, previous);
+            return Ok(());
+        }
+        return register(context, func);
+    };
+    // ジェネリック関数とmain関数はオーバーロードできない。引数の型が同じ場合も重複とする
+    if first.decl.generic_args.is_some()
+        || func.decl.generic_args.is_some()
+        || name == "main"
+        || resolve_param_types(context, &first)? == resolve_param_types(context, func)?
+    {
+        push_duplicate_function_error(context, func, first.range);
+        return Ok(());
+    }
+    // 最初の定義も、それを書いたファイルの中でマングルした名前で登録し直す
+    context.function_by_name.borrow_mut().remove(name);
+    context.in_module_of_function(name, || register(context, &first))?;
+    register(context, func)
+}
+
+fn push_duplicate_function_error(context: &ResolverContext, func: &ast::Function, previous: Range) {
+    context.errors.borrow_mut().push(CompileError::new(
+        func.range,
+        CompileErrorKind::DuplicateFunction {
+            name: func.decl.name.clone(),
+            previous: Some(previous),
+        },
+    ));
+}
+
+// 関数の定義から、登録されている関数を引く。重複して登録されなかった定義にはNoneを返す
+pub(super) fn registered_function(
+    context: &ResolverContext,
+    func: &ast::Function,
+) -> Option<ast::Function> {
+    let function_by_name = context.function_by_name.borrow();
+    let registered = match context.overloads_by_name.borrow().get(&func.decl.name) {
+        Some(overloads) => overloads
+            .iter()
+            .map(|overload| &function_by_name[&overload.mangled_name])
+            .find(|registered| registered.range == func.range),
+        None => function_by_name
+            .get(&func.decl.name)
+            .filter(|registered| registered.range == func.range),
+    };
+    registered.cloned()
+}
+
+// 引数の型から呼び出す関数を選ぶ。型が完全に一致するものを優先する
+pub(super) fn select_overload(
+    context: &ResolverContext,
+    call_expr: &Located<&ast::CallExpr>,
+) -> Result<Option<ast::Function>, FaitalError> {
+    let overloads = context.overloads_by_name.borrow()[&call_expr.name].clone();
+    // 選んだ関数の引数の型で改めて解決するので、ここで積んだエラーは捨てる
+    let error_count = context.errors.borrow().len();
+    let warning_count = context.warnings.borrow().len();
+    let mut arg_types = Vec::new();
+    for arg in &call_expr.args {
+        arg_types.push(resolve_expression(context, arg.as_inner_deref(), None)?.ty);
+    }
+    context.errors.borrow_mut().truncate(error_count);
+    context.warnings.borrow_mut().truncate(warning_count);
+
+    let accepts = |overload: &&Overload, exact: bool| {
+        let arity_matches = arg_types.len() >= overload.required_arg_count
+            && (overload.is_var_args || arg_types.len() <= overload.param_types.len());
+        arity_matches
+            && overload
+                .param_types
+                .iter()
+                .zip(&arg_types)
+                .all(|(param, arg)| {
+                    if exact {
+                        param == arg
+                    } else {
+                        param.can_insert(arg)
+                    }
+                })
+    };
+    let mut candidates = overloads
+        .iter()
+        .filter(|overload| accepts(overload, true))
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        candidates = overloads
+            .iter()
+            .filter(|overload| accepts(overload, false))
+            .collect();
+    }
+    match candidates.as_slice() {
+        [overload] => Ok(Some(
+            context.function_by_name.borrow()[&overload.mangled_name].clone(),
+        )),
+        [] => {
+            context.errors.borrow_mut().push(CompileError::new(
+                call_expr.range,
+                CompileErrorKind::NoMatchingOverload {
+                    name: call_expr.name.clone(),
+                    arg_types,
+                },
+            ));
+            Ok(None)
+        }
+        _ => {
+            context.errors.borrow_mut().push(CompileError::new(
+                call_expr.range,
+                CompileErrorKind::AmbiguousCall {
+                    name: call_expr.name.clone(),
+                },
+            ));
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::io::Write;
+
+use crate::ast::Range;
+
+use super::error::{CompileError, CompileErrorKind, CompileWarning};
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+
+// 診断をソースの該当行と下線つきで表示する。colorがtrueならANSIエスケープで色を付ける
+pub struct DiagnosticRenderer {
+    color: bool,
+}
+
+// 下線を引く位置と、その下に添える説明
+struct Label<'a> {
+    range: Range,
+    underline: char,
+    message: &'a str,
+}
+
+impl DiagnosticRenderer {
+    pub fn new(color: bool) -> Self {
+        Self { color }
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn render_error(
+        &self,
+        f: &mut impl Write,
+        error: &CompileError,
+        path: &str,
+        source: &str,
+    ) -> std::io::Result<()> {
+        let mut labels = vec![Label {
+            range: error.range,
+            underline: '^',
+            message: "",
+        }];
+        // 重複した定義は、前の定義の位置も表示する
+        if let CompileErrorKind::DuplicateFunction {
+            previous: Some(previous),
+            ..
+        } = &error.kind
+        {
+            labels.push(Label {
+                range: *previous,
+                underline: '-',
+                message: "previously defined here",
+            });
+        }
+        self.render(f, ("error", RED), &error.kind, &labels, path, source)
+    }
+
+    pub fn render_warning(
+        &self,
+        f: &mut impl Write,
+        warning: &CompileWarning,
+        path: &str,
+        source: &str,
+    ) -> std::io::Result<()> {
+        let labels = [Label {
+            range: warning.range,
+            underline: '^',
+            message: "",
+        }];
+        self.render(f, ("warning", YELLOW), &warning.kind, &labels, path, source)
+    }
+
+    fn render(
+        &self,
+        f: &mut impl Write,
+        (level, style): (&str, &str),
+        message: &impl std::fmt::Display,
+        labels: &[Label],
+        path: &str,
+        source: &str,
+    ) -> std::io::Result<()> {
+        writeln!(f, "{}: {}", self.paint(style, level), message)?;
+        let primary = labels[0].range;
+        // コード生成のエラーのように位置を持たないものはメッセージだけにする
+        if primary == Range::default() {
+            return writeln!(f);
+        }
+        let gutter_width = labels
+            .iter()
+            .map(|label| label.range.to.line.max(label.range.from.line))
+            .max()
+            .unwrap_or_default()
+            .to_string()
+            .len();
+        let gutter = self.paint(BLUE, &format!("{:width$} |", "", width = gutter_width));
+        writeln!(
+            f,
+            "{:width$}{} {}:{}:{}",
+            "",
+            self.paint(BLUE, "-->"),
+            path,
+            primary.from.line,
+            primary.from.col,
+            width = gutter_width
+        )?;
+        let lines = source.lines().collect::<Vec<_>>();
+        for label in labels {
+            writeln!(f, "{}", gutter)?;
+            let label_style = if label.underline == '^' { style } else { BLUE };
+            let last_line = label.range.to.line.max(label.range.from.line);
+            for line_number in label.range.from.line..=last_line {
+                let line = lines
+                    .get(line_number as usize - 1)
+                    .copied()
+                    .unwrap_or_default();
+                writeln!(
+                    f,
+                    "{} {}",
+                    self.paint(
+                        BLUE,
+                        &format!("{:width$} |", line_number, width = gutter_width)
+                    ),
+                    line
+                )?;
+                // 複数行にまたがる場合、途中の行は字下げの後から行末までに下線を引く
+                let start = if line_number == label.range.from.line {
+                    label.range.from.col - 1
+                } else {
+                    line.len() - line.trim_start().len()
+                };
+                let end = if line_number == label.range.to.line {
+                    label.range.to.col - 1
+                } else {
+                    line.len()
+                };
+                let underline = label
+                    .underline
+                    .to_string()
+                    .repeat(end.saturating_sub(start).max(1));
+                let mut marker = format!("{}{}", " ".repeat(start), underline);
+                if line_number == last_line && !label.message.is_empty() {
+                    marker = format!("{} {}", marker, label.message);
+                }
+                writeln!(f, "{} {}", gutter, self.paint(label_style, &marker))?;
+            }
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::tests::resolve_source;
+
+    fn render_errors(source: &str) -> String {
+        let context = resolve_source(source);
+        let renderer = DiagnosticRenderer::new(false);
+        let mut output = Vec::new();
+        for error in context.errors.borrow().iter() {
+            renderer
+                .render_error(&mut output, error, "main.hr", source)
+                .unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_render_error() {
+        let output = render_errors(
+            "
+fn main(): void {
+    (:= x 1)
+    (:=< x  undefined)
+}
+",
+        );
+        assert_eq!(
+            output,
+            "\
+error: Variable `\"undefined\"` is not found in this scope.
+ --> main.hr:4:13
+  |
+4 |     (:=< x  undefined)
+  |             ^^^^^^^^^
+
+"
+        );
+    }
+
+    #[test]
+    fn test_render_duplicate_function() {
+        let output = render_errors(
+            "
+fn f(): void {}
+fn f(): void {}
+fn main(): void {
+    (f)
+}
+",
+        );
+        assert!(output.contains("3 | fn f(): void {}\n  | ^"), "{}", output);
+        assert!(output.contains("- previously defined here"), "{}", output);
+        assert!(!output.contains('\x1b'), "{}", output);
+    }
+}
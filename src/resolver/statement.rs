@@ -1,26 +1,204 @@
-use crate::ast::{Located, Statement};
-use crate::resolved_ast::{self};
+use std::collections::HashSet;
 
-use super::error::FaitalError;
+use crate::ast::{Expression, Located, Range, Statement, SwitchStatement, TypeDefKind};
+use crate::resolved_ast::{self, ExpressionKind, ResolvedType};
+
+use crate::in_new_scope;
+
+use super::constant::evaluate_constant;
+use super::error::{
+    CompileError, CompileErrorKind, CompileWarning, CompileWarningKind, FaitalError,
+};
 use super::expression::resolve_expression;
+use super::ty::enum_variant_values;
 use super::ResolverContext;
 
 pub fn resolve_statement(
     context: &ResolverContext,
     loc_statement: &Located<Statement>,
 ) -> Result<resolved_ast::Statement, FaitalError> {
+    let range = loc_statement.range;
     Ok(match &loc_statement.value {
         Statement::Return(ret) => {
             if let Some(expr) = &ret.expression {
                 resolved_ast::Statement::Return(resolved_ast::Return {
                     expression: Some(resolve_expression(context, expr.as_ref().into(), None)?),
+                    range,
                 })
             } else {
-                resolved_ast::Statement::Return(resolved_ast::Return { expression: None })
+                resolved_ast::Statement::Return(resolved_ast::Return {
+                    expression: None,
+                    range,
+                })
             }
         }
         Statement::Effect(effect) => resolved_ast::Statement::Effect(resolved_ast::Effect {
             expression: resolve_expression(context, effect.expression.as_ref(), None)?,
+            range,
         }),
+        Statement::Block(statements) => {
+            let resolved_statements =
+                in_new_scope!(context.scopes, { resolve_statements(context, statements) })?;
+            resolved_ast::Statement::Block(resolved_statements)
+        }
+        Statement::Switch(switch) => {
+            resolved_ast::Statement::Switch(resolve_switch(context, switch, range)?)
+        }
+        Statement::DoWhile(do_while) => {
+            // 本体の中でだけbreakとcontinueを使える
+            *context.loop_depth.borrow_mut() += 1;
+            let body = in_new_scope!(context.scopes, {
+                resolve_statements(context, &do_while.body)
+            });
+            *context.loop_depth.borrow_mut() -= 1;
+            let cond =
+                resolve_expression(context, do_while.cond.as_ref(), Some(&ResolvedType::Bool))?;
+            if !matches!(cond.ty, ResolvedType::Bool) {
+                context.errors.borrow_mut().push(CompileError::new(
+                    do_while.cond.range,
+                    CompileErrorKind::TypeMismatch {
+                        expected: ResolvedType::Bool,
+                        actual: cond.ty.clone(),
+                    },
+                ));
+            }
+            resolved_ast::Statement::DoWhile(resolved_ast::DoWhile {
+                body: body?,
+                cond,
+                range,
+            })
+        }
+    })
+}
+
+// caseのラベルはscrutineeと同じ型の整数の定数でなければならず、同じ値を2回書くこともできない
+// caseのラベルが全て同じenumのバリアント(Color.Red など)なら、そのenumの名前を返す。
+// enumは整数として解決されるので、enumに対するswitchかどうかはラベルから判断する
+fn switched_enum_name(context: &ResolverContext, switch: &SwitchStatement) -> Option<String> {
+    let mut enum_name = None;
+    for (label, _) in &switch.cases {
+        let Expression::FieldAccess(field_access) = &label.value else {
+            return None;
+        };
+        let Expression::VariableRef(variable_ref) = field_access.target.value.as_ref() else {
+            return None;
+        };
+        // 同名の変数があれば、バリアントではなくフィールドの参照になる
+        if context.scopes.borrow().get(&variable_ref.name).is_some()
+            || enum_name.is_some_and(|name| name != &variable_ref.name)
+        {
+            return None;
+        }
+        enum_name = Some(&variable_ref.name);
+    }
+    let enum_name = enum_name?;
+    let type_defs = context.type_defs.borrow();
+    matches!(type_defs.get(enum_name)?.kind, TypeDefKind::Enum(_)).then(|| enum_name.clone())
+}
+
+fn resolve_switch(
+    context: &ResolverContext,
+    switch: &SwitchStatement,
+    range: Range,
+) -> Result<resolved_ast::Switch, FaitalError> {
+    let enum_name = switched_enum_name(context, switch);
+    let scrutinee = resolve_expression(context, switch.scrutinee.as_ref(), None)?;
+    // 型が分からない場合は、既にエラーが報告されている
+    let check_labels = scrutinee.ty != ResolvedType::Unknown;
+    if check_labels && !scrutinee.ty.is_integer_type() {
+        context.errors.borrow_mut().push(CompileError::new(
+            switch.scrutinee.range,
+            CompileErrorKind::InvalidSwitchScrutinee {
+                ty: scrutinee.ty.clone(),
+            },
+        ));
+    }
+    let mut labels = HashSet::new();
+    let mut cases = Vec::new();
+    for (label, statements) in &switch.cases {
+        let resolved_label = resolve_expression(context, label.as_ref(), Some(&scrutinee.ty))?;
+        let resolved_label = evaluate_constant(context, &resolved_label).unwrap_or(resolved_label);
+        let error = match &resolved_label.kind {
+            _ if !check_labels => None,
+            ExpressionKind::NumberLiteral(_) if resolved_label.ty != scrutinee.ty => {
+                Some(CompileErrorKind::TypeMismatch {
+                    expected: scrutinee.ty.clone(),
+                    actual: resolved_label.ty.clone(),
+                })
+            }
+            ExpressionKind::NumberLiteral(number_literal) => {
+                let value = number_literal.value.parse::<i128>().ok();
+                (!labels.insert(value)).then(|| CompileErrorKind::DuplicateCaseLabel {
+                    value: number_literal.value.clone(),
+                })
+            }
+            _ => Some(CompileErrorKind::NonConstantCaseLabel),
+        };
+        if let Some(error) = error {
+            context
+                .errors
+                .borrow_mut()
+                .push(CompileError::new(label.range, error));
+        }
+        let resolved_statements =
+            in_new_scope!(context.scopes, { resolve_statements(context, statements) })?;
+        cases.push((resolved_label, resolved_statements));
+    }
+    let default = match &switch.default {
+        Some(statements) => Some(in_new_scope!(context.scopes, {
+            resolve_statements(context, statements)
+        })?),
+        None => None,
+    };
+    // defaultのないenumのswitchは、どのcaseの値にもならないバリアントを報告する
+    if let (Some(enum_name), None) = (enum_name, &default) {
+        let type_defs = context.type_defs.borrow();
+        let TypeDefKind::Enum(enum_def) = &type_defs[&enum_name].kind else {
+            unreachable!()
+        };
+        let variants = enum_variant_values(enum_def)
+            .into_iter()
+            .filter(|(_, value)| !labels.contains(&Some(i128::from(*value))))
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        if !variants.is_empty() {
+            context.errors.borrow_mut().push(CompileError::new(
+                range,
+                CompileErrorKind::NonExhaustiveSwitch {
+                    enum_name,
+                    variants,
+                },
+            ));
+        }
+    }
+    Ok(resolved_ast::Switch {
+        scrutinee,
+        cases,
+        default,
+        range,
     })
 }
+
+// 文の並びを解決する。return以降の文もエラーの検出のために解決はするが、コードは生成しない
+pub fn resolve_statements(
+    context: &ResolverContext,
+    statements: &[Located<Statement>],
+) -> Result<Vec<resolved_ast::Statement>, FaitalError> {
+    let mut resolved_statements = Vec::new();
+    let mut returned = false;
+    for statement in statements {
+        let resolved_statement = resolve_statement(context, statement)?;
+        if returned {
+            continue;
+        }
+        returned = resolved_statement.always_returns();
+        resolved_statements.push(resolved_statement);
+        if returned && resolved_statements.len() < statements.len() {
+            context.warnings.borrow_mut().push(CompileWarning::new(
+                statements[resolved_statements.len()].range,
+                CompileWarningKind::UnreachableCode,
+            ));
+        }
+    }
+    Ok(resolved_statements)
+}
@@ -1,87 +1,90 @@
 use crate::{in_new_scope, resolved_ast::ResolvedType};
 
-use self::resolved_ast::ResolvedStructType;
+use self::{generics::generic_arg_count_error, resolved_ast::ResolvedStructType};
 
 use super::*;
 
 pub(super) fn resolve_type(
     context: &ResolverContext,
     loc_ty: &Located<ast::UnresolvedType>,
+) -> Result<ResolvedType> {
+    resolve_type_behind_pointer(context, loc_ty, false)
+}
+
+// behind_pointerは、ポインタの先の型を解決しているかどうか。
+// ポインタの先であれば、フィールドを解決中のstruct自身を参照してもよい
+fn resolve_type_behind_pointer(
+    context: &ResolverContext,
+    loc_ty: &Located<ast::UnresolvedType>,
+    behind_pointer: bool,
 ) -> Result<ResolvedType> {
     match &loc_ty.value {
         UnresolvedType::TypeRef(typ_ref) => {
+            // pubでない型は、定義したファイルの外からは見つからないものとする
+            if !context.is_type_visible(&typ_ref.name) {
+                context.errors.borrow_mut().push(CompileError::new(
+                    loc_ty.range,
+                    error::CompileErrorKind::TypeNotFound {
+                        name: typ_ref.name.clone(),
+                    },
+                ));
+                return Ok(ResolvedType::Unknown);
+            }
             if let Some(type_def) = context.type_defs.borrow().get(&typ_ref.name) {
                 match &type_def.kind {
                     TypeDefKind::StructLike(struct_def) => {
                         let mut resolved_generic_args = Vec::new();
                         if let Some(generic_args) = &typ_ref.generic_args {
                             if let Some(generic_args_in_def) = &struct_def.generic_args {
-                                if generic_args.len() != generic_args_in_def.len() {
-                                    context.errors.borrow_mut().push(CompileError::new(
-                                        loc_ty.range,
-                                        error::CompileErrorKind::MismatchGenericArgCount {
-                                            name: typ_ref.name.clone(),
-                                            expected: generic_args_in_def.len(),
-                                            actual: generic_args.len(),
-                                        },
-                                    ));
+                                if let Some(error) = generic_arg_count_error(
+                                    &typ_ref.name,
+                                    generic_args_in_def.len(),
+                                    generic_args.len(),
+                                ) {
+                                    context
+                                        .errors
+                                        .borrow_mut()
+                                        .push(CompileError::new(loc_ty.range, error));
                                     Ok(ResolvedType::Unknown)
                                 } else {
+                                    // 型引数は、定義の型引数を当てはめる前のスコープで解決する
+                                    for generic_arg in generic_args {
+                                        if matches!(generic_arg.value, UnresolvedType::Infer) {
+                                            context.errors.borrow_mut().push(CompileError::new(
+                                                loc_ty.range,
+                                                error::CompileErrorKind::CannotInferGenericArgs {
+                                                    name: typ_ref.name.clone(),
+                                                    message: "Generic inference here is not supported yet.".into()
+                                                },
+                                            ));
+                                            return Ok(ResolvedType::Unknown);
+                                        }
+                                        resolved_generic_args
+                                            .push(resolve_type(context, generic_arg)?);
+                                    }
                                     in_new_scope!(context.types, {
-                                        for (i, generic_arg) in generic_args.iter().enumerate() {
-                                            if matches!(generic_arg.value, UnresolvedType::Infer) {
-                                                context
-                                                    .errors
-                                                    .borrow_mut()
-                                                    .push(CompileError::new(
-                                                    loc_ty.range,
-                                                    error::CompileErrorKind::CannotInferGenericArgs {
-                                                        name: typ_ref.name.clone(),
-                                                        message: "Generic inference here is not supported yet.".into()
-                                                    },
-                                                ));
-                                                return Ok(ResolvedType::Unknown);
-                                            }
-
-                                            let resolved_generic_arg =
-                                                resolve_type(context, generic_arg)?;
-                                            resolved_generic_args
-                                                .push(resolved_generic_arg.clone());
+                                        for (generic_arg_in_def, resolved_generic_arg) in
+                                            generic_args_in_def.iter().zip(&resolved_generic_args)
+                                        {
                                             context.types.borrow_mut().add(
-                                                generic_args_in_def[i].name.clone(),
+                                                generic_arg_in_def.name.clone(),
                                                 resolved_generic_arg.clone(),
                                             )
                                         }
-                                        Ok(ResolvedType::StructLike(ResolvedStructType {
-                                            name: get_resolved_struct_name(
-                                                &type_def.name,
-                                                Some(&resolved_generic_args),
-                                            ),
-                                            fields: struct_def
-                                                .fields
-                                                .iter()
-                                                .map(|(name, unresolved_ty)| {
-                                                    match resolve_type(context, unresolved_ty) {
-                                                        Ok(resolved_ty) => {
-                                                            Ok((name.clone(), resolved_ty.clone()))
-                                                        }
-                                                        Err(err) => Err(err),
-                                                    }
-                                                })
-                                                .collect::<Result<Vec<_>>>()?,
-                                            generic_args: if struct_def.generic_args.is_some() {
-                                                Some(resolved_generic_args)
-                                            } else {
-                                                None
-                                            },
-                                            non_generic_name: type_def.name.clone(),
-                                        }))
+                                        resolve_struct_type(
+                                            context,
+                                            loc_ty.range,
+                                            &type_def.name,
+                                            struct_def,
+                                            Some(resolved_generic_args),
+                                            behind_pointer,
+                                        )
                                     })
                                 }
                             } else {
                                 context.errors.borrow_mut().push(CompileError::new(
                                     loc_ty.range,
-                                    error::CompileErrorKind::NoGenericArgs {
+                                    error::CompileErrorKind::UnnecessaryGenericArgs {
                                         name: typ_ref.name.clone(),
                                     },
                                 ));
@@ -96,52 +99,257 @@ pub(super) fn resolve_type(
                             ));
                             Ok(ResolvedType::Unknown)
                         } else {
-                            Ok(ResolvedType::StructLike(ResolvedStructType {
-                                name: get_resolved_struct_name(&type_def.name, None),
-                                fields: struct_def
-                                    .fields
-                                    .iter()
-                                    .map(|(name, unresolved_ty)| {
-                                        match resolve_type(context, unresolved_ty) {
-                                            Ok(resolved_ty) => {
-                                                Ok((name.clone(), resolved_ty.clone()))
-                                            }
-                                            Err(err) => Err(err),
-                                        }
-                                    })
-                                    .collect::<Result<Vec<_>>>()?,
-                                generic_args: None,
-                                non_generic_name: type_def.name.clone(),
-                            }))
+                            resolve_struct_type(
+                                context,
+                                loc_ty.range,
+                                &type_def.name,
+                                struct_def,
+                                None,
+                                behind_pointer,
+                            )
                         }
                     }
+                    TypeDefKind::Enum(enum_def) => Ok(enum_underlying_type(enum_def)),
                 }
             } else {
-                let resolved_type = context
-                    .types
-                    .borrow()
-                    .get(&typ_ref.name)
-                    .cloned()
-                    .unwrap_or_else(|| {
-                        context.errors.borrow_mut().push(CompileError::new(
-                            loc_ty.range,
-                            error::CompileErrorKind::TypeNotFound {
-                                name: typ_ref.name.clone(),
-                            },
-                        ));
-                        ResolvedType::Unknown
-                    });
-                Ok(resolved_type.clone())
+                let resolved_type = context.types.borrow().get(&typ_ref.name).cloned();
+                if let Some(resolved_type) = resolved_type {
+                    Ok(resolved_type)
+                } else if context.type_aliases.borrow().contains_key(&typ_ref.name) {
+                    resolve_type_alias(context, &typ_ref.name)
+                } else {
+                    context.errors.borrow_mut().push(CompileError::new(
+                        loc_ty.range,
+                        error::CompileErrorKind::TypeNotFound {
+                            name: typ_ref.name.clone(),
+                        },
+                    ));
+                    Ok(ResolvedType::Unknown)
+                }
             }
         }
         UnresolvedType::Ptr(inner_type) => {
-            let inner_type: ResolvedType = resolve_type(context, inner_type)?;
+            let inner_type: ResolvedType = resolve_type_behind_pointer(context, inner_type, true)?;
             Ok(ResolvedType::Ptr(Box::new(inner_type)))
         }
+        UnresolvedType::Array(inner_type, len) => {
+            let inner_type = resolve_type_behind_pointer(context, inner_type, behind_pointer)?;
+            if *len <= 0 {
+                context.errors.borrow_mut().push(CompileError::new(
+                    loc_ty.range,
+                    error::CompileErrorKind::InvalidArrayLength { len: *len },
+                ));
+                return Ok(ResolvedType::Unknown);
+            }
+            Ok(ResolvedType::Array(Box::new(inner_type), *len as usize))
+        }
+        UnresolvedType::FnPtr(params, return_type) => {
+            let params = params
+                .iter()
+                .map(|param| resolve_type_behind_pointer(context, param, true))
+                .collect::<Result<Vec<_>>>()?;
+            let return_type = resolve_type_behind_pointer(context, return_type, true)?;
+            Ok(ResolvedType::FnPtr(params, Box::new(return_type)))
+        }
+        UnresolvedType::Tuple(elements) => {
+            let elements = elements
+                .iter()
+                .map(|element| resolve_type_behind_pointer(context, element, behind_pointer))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(tuple_type(context, elements))
+        }
         UnresolvedType::Infer => Ok(ResolvedType::Unknown),
     }
 }
 
+// タプルは、要素を0, 1, ...という名前のフィールドに持つ名前のないstructにする。
+// 同じ要素の型の組は、(i32, u8) のような同じ名前のstructを共有する
+pub(super) fn tuple_type(context: &ResolverContext, elements: Vec<ResolvedType>) -> ResolvedType {
+    let name = format!(
+        "({})",
+        elements
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let struct_ty = ResolvedStructType {
+        name: name.clone(),
+        non_generic_name: name.clone(),
+        fields: elements
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| (i.to_string(), ty))
+            .collect(),
+        generic_args: None,
+    };
+    context
+        .resolved_structs
+        .borrow_mut()
+        .insert(name, struct_ty.clone());
+    ResolvedType::StructLike(struct_ty)
+}
+
+// structの型を作る。フィールドの解決中に同じstructへ戻ってきた場合、途中でポインタを経由していれば
+// フィールドを持たない型を返し、そうでなければ大きさが決まらないのでエラーにする
+fn resolve_struct_type(
+    context: &ResolverContext,
+    range: Range,
+    type_name: &str,
+    struct_def: &StructLikeTypeDef,
+    generic_args: Option<Vec<ResolvedType>>,
+    behind_pointer: bool,
+) -> Result<ResolvedType> {
+    let name = get_resolved_struct_name(type_name, generic_args.as_deref());
+    // structは型引数の組ごとに1度だけフィールドを解決する。
+    // ジェネリックでないstructは宣言時に解決するので、フィールドのエラーを参照のたびに重ねない
+    if let Some(resolved) = context.resolved_structs.borrow().get(&name) {
+        return Ok(ResolvedType::StructLike(resolved.clone()));
+    }
+    let mut struct_ty = ResolvedStructType {
+        name: name.clone(),
+        non_generic_name: type_name.to_string(),
+        fields: vec![],
+        generic_args,
+    };
+    let position = context
+        .resolving_structs
+        .borrow()
+        .iter()
+        .position(|(resolving, _)| *resolving == name);
+    if let Some(position) = position {
+        // struct A { b: *B } と struct B { a: A } のように、間のstructへポインタで辿り着いていてもよい
+        let through_pointer = behind_pointer
+            || context.resolving_structs.borrow()[position + 1..]
+                .iter()
+                .any(|(_, behind_pointer)| *behind_pointer);
+        if through_pointer {
+            return Ok(ResolvedType::StructLike(struct_ty));
+        }
+        context.errors.borrow_mut().push(CompileError::new(
+            range,
+            error::CompileErrorKind::RecursiveStruct { name },
+        ));
+        return Ok(ResolvedType::Unknown);
+    }
+    context
+        .resolving_structs
+        .borrow_mut()
+        .push((name.clone(), behind_pointer));
+    let fields = resolve_fields(context, type_name, struct_def);
+    context.resolving_structs.borrow_mut().pop();
+    struct_ty.fields = fields?;
+    context
+        .resolved_structs
+        .borrow_mut()
+        .insert(name, struct_ty.clone());
+    Ok(ResolvedType::StructLike(struct_ty))
+}
+
+// 使われていないstructも含めて、大きさが決まらない定義を報告する。
+// ジェネリックなstructは型引数が決まるまでフィールドを解決できないので、使われた時に検査する
+pub(super) fn check_struct_def(
+    context: &ResolverContext,
+    range: Range,
+    type_def: &TypeDef,
+) -> Result<()> {
+    if let TypeDefKind::StructLike(struct_def) = &type_def.kind {
+        if struct_def.generic_args.is_none() {
+            resolve_struct_type(context, range, &type_def.name, struct_def, None, false)?;
+        }
+    }
+    Ok(())
+}
+
+// ポインタの先で自身を参照するフィールドの型は、フィールドを持たないので、解決済みのstructから補う
+pub(super) fn complete_struct_type(
+    context: &ResolverContext,
+    struct_ty: &ResolvedStructType,
+) -> ResolvedStructType {
+    if struct_ty.fields.is_empty() {
+        if let Some(resolved) = context.resolved_structs.borrow().get(&struct_ty.name) {
+            return resolved.clone();
+        }
+    }
+    struct_ty.clone()
+}
+
+// フィールドの型は、その型を定義したファイルから見えるものとして解決する
+fn resolve_fields(
+    context: &ResolverContext,
+    name: &str,
+    struct_def: &StructLikeTypeDef,
+) -> Result<Vec<(String, ResolvedType)>> {
+    context.in_module_of_type(name, || {
+        struct_def
+            .fields
+            .iter()
+            .map(|(name, unresolved_ty)| Ok((name.clone(), resolve_type(context, unresolved_ty)?)))
+            .collect()
+    })
+}
+
+// エイリアスを辿って解決し、結果をグローバルな型のスコープに登録する
+pub(super) fn resolve_type_alias(context: &ResolverContext, name: &str) -> Result<ResolvedType> {
+    let resolved_type = context.types.borrow().get(name).cloned();
+    if let Some(resolved_type) = resolved_type {
+        return Ok(resolved_type);
+    }
+    let alias = context.type_aliases.borrow()[name].clone();
+    if context
+        .resolving_type_aliases
+        .borrow()
+        .iter()
+        .any(|resolving| resolving == name)
+    {
+        context.errors.borrow_mut().push(CompileError::new(
+            alias.target.range,
+            error::CompileErrorKind::CyclicTypeAlias {
+                name: name.to_string(),
+            },
+        ));
+        return Ok(ResolvedType::Unknown);
+    }
+    context
+        .resolving_type_aliases
+        .borrow_mut()
+        .push(name.to_string());
+    let resolved_type = context.in_module_of_type(name, || resolve_type(context, &alias.target));
+    context.resolving_type_aliases.borrow_mut().pop();
+    let resolved_type = resolved_type?;
+    context
+        .types
+        .borrow_mut()
+        .add_global(name.to_string(), resolved_type.clone());
+    Ok(resolved_type)
+}
+
+// 各バリアントの値を決める。値を省略したバリアントは直前の値+1で、先頭なら0になる
+pub(super) fn enum_variant_values(enum_def: &EnumTypeDef) -> Vec<(String, i64)> {
+    let mut next = 0i64;
+    enum_def
+        .variants
+        .iter()
+        .map(|(name, value)| {
+            let value = value.unwrap_or(next);
+            next = value.wrapping_add(1);
+            (name.clone(), value)
+        })
+        .collect()
+}
+
+// enumは整数として扱う。全てのバリアントがi32に収まらなければi64にする
+pub(super) fn enum_underlying_type(enum_def: &EnumTypeDef) -> ResolvedType {
+    if enum_variant_values(enum_def)
+        .iter()
+        .all(|(_, value)| i32::try_from(*value).is_ok())
+    {
+        ResolvedType::I32
+    } else {
+        ResolvedType::I64
+    }
+}
+
 pub(crate) fn get_resolved_struct_name(
     name: &str,
     generic_args: Option<&[ResolvedType]>,
@@ -172,6 +380,7 @@ mod tests {
         context.type_defs.borrow_mut().insert(
             "Vec".to_string(),
             TypeDef {
+                visibility: Visibility::Private,
                 name: "Vec".to_string(),
                 kind: TypeDefKind::StructLike(StructLikeTypeDef {
                     struct_kind: StructKind::Struct,
@@ -200,6 +409,7 @@ mod tests {
                             restrictions: vec![],
                         },
                     }]),
+                    methods: Vec::new(),
                 }),
             },
         );
@@ -239,6 +449,222 @@ mod tests {
                 ],
                 generic_args: Some(vec![ResolvedType::I32]),
             })
-        )
+        );
+        // structは名前だけで比べるので、フィールドは別に確かめる
+        let ResolvedType::StructLike(struct_ty) = resolved_ty else {
+            unreachable!()
+        };
+        assert_eq!(
+            struct_ty.fields,
+            vec![
+                (
+                    "ptr".to_string(),
+                    ResolvedType::Ptr(Box::new(ResolvedType::I32))
+                ),
+                ("len".to_string(), ResolvedType::USize),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_type_alias() {
+        let context = crate::resolver::tests::resolve_source(
+            "
+type Bytes = *Byte
+type Byte = u8
+type Size = usize
+fn copy(dst: Bytes, src: Bytes, n: Size): Bytes {
+    dst
+}
+fn main(): void {
+    (:= p: Bytes \"abc\")
+    (:= b: Byte 1)
+    (copy p p 3)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+        let types = context.types.borrow();
+        assert_eq!(
+            types.get("Bytes"),
+            Some(&ResolvedType::Ptr(Box::new(ResolvedType::U8)))
+        );
+        assert_eq!(types.get("Size"), Some(&ResolvedType::USize));
+    }
+
+    #[test]
+    fn test_cyclic_type_alias() {
+        let context = crate::resolver::tests::resolve_source(
+            "
+type A = B
+type B = A
+type C = C
+fn main(): void {}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &error::CompileErrorKind::CyclicTypeAlias { name: "A".into() },
+                &error::CompileErrorKind::CyclicTypeAlias { name: "C".into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_recursive_struct_through_pointer() {
+        let context = crate::resolver::tests::resolve_source(
+            "
+struct Node {
+    value: i32,
+    next: *Node,
+}
+fn second(head: *Node): i32 {
+    (:= next head[0].next)
+    next[0].value
+}
+fn main(): void {
+    (:= tail Node { value: 2, next: null })
+    (:= head Node { value: 1, next: &tail })
+    (second &head)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+        let structs = context.resolved_structs.borrow();
+        let next_ty = &structs["Node"].fields[1].1;
+        let ResolvedType::Ptr(inner) = next_ty else {
+            panic!("unexpected type: {:?}", next_ty);
+        };
+        // ポインタの先の自身はフィールドを持たない型になる
+        let ResolvedType::StructLike(inner) = inner.as_ref() else {
+            panic!("unexpected type: {:?}", inner);
+        };
+        assert_eq!(inner.name, "Node");
+        assert!(inner.fields.is_empty());
+    }
+
+    #[test]
+    fn test_struct_containing_itself() {
+        let context = crate::resolver::tests::resolve_source(
+            "
+struct Node {
+    value: i32,
+    next: Node,
+}
+struct Pair {
+    nodes: [Pair; 2],
+}
+fn main(): void {
+    (:= node: *Node null)
+    (:= pair: *Pair null)
+}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &error::CompileErrorKind::RecursiveStruct {
+                    name: "Node".into()
+                },
+                &error::CompileErrorKind::RecursiveStruct {
+                    name: "Pair".into()
+                },
+            ]
+        );
+        assert_eq!(errors[0].range.from.line, 4);
+    }
+
+    #[test]
+    fn test_unused_struct_containing_itself() {
+        let context = crate::resolver::tests::resolve_source(
+            "
+struct Bad {
+    me: Bad,
+}
+fn main(): void {}
+",
+        );
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![&error::CompileErrorKind::RecursiveStruct { name: "Bad".into() }]
+        );
+        assert_eq!(errors[0].range.from.line, 3);
+    }
+
+    #[test]
+    fn test_structs_containing_each_other_through_pointer() {
+        let context = crate::resolver::tests::resolve_source(
+            "
+struct A {
+    b: *B,
+}
+struct B {
+    a: A,
+}
+struct C {
+    x: Missing,
+}
+fn main(): void {
+    (:= _a: *A null)
+    (:= _c: *C null)
+    (:= _d: *C null)
+}
+",
+        );
+        // フィールドの型のエラーは、structを参照した回数によらず1つだけ報告する
+        let errors = context.errors.borrow();
+        let kinds: Vec<_> = errors.iter().map(|error| &error.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![&error::CompileErrorKind::TypeNotFound {
+                name: "Missing".into()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_generic_struct_instance() {
+        let context = crate::resolver::tests::resolve_source(
+            "
+struct Pair<T, U> {
+    first: T,
+    second: U,
+}
+fn swap<T, U>(p: Pair<T, U>): Pair<U, T> {
+    Pair<U, T> { first: p.second, second: p.first }
+}
+fn main(): void {
+    (:= p Pair<i32, u8> { first: 1, second: 2 })
+    (:= q (swap<i32, u8> p))
+    (:= x: u8 q.first)
+    (:= y: i32 q.second)
+}
+",
+        );
+        assert!(context.errors.borrow().is_empty(), "{:?}", context.errors);
+        let structs = context.resolved_structs.borrow();
+        assert_eq!(
+            structs["Pair<i32, u8>"].fields,
+            vec![
+                ("first".to_string(), ResolvedType::I32),
+                ("second".to_string(), ResolvedType::U8),
+            ]
+        );
+        // 型引数はswapの中の T と U を当てはめてから入れ替える
+        assert_eq!(
+            structs["Pair<u8, i32>"].fields,
+            vec![
+                ("first".to_string(), ResolvedType::U8),
+                ("second".to_string(), ResolvedType::I32),
+            ]
+        );
+        assert!(!structs.contains_key("Pair<u8, u8>"));
     }
 }
@@ -0,0 +1,177 @@
+use crate::ast::{
+    visit::{walk_expression, walk_function, walk_statement, walk_statements, Visitor},
+    Argument, ArgumentAttribute, Expression, Function, Located, Range, Statement,
+};
+
+use super::{
+    error::{CompileWarning, CompileWarningKind},
+    ResolverContext, VariableKind,
+};
+
+struct Declaration {
+    name: String,
+    kind: VariableKind,
+    range: Range,
+    allow_unused: bool,
+    is_used: bool,
+}
+
+// 関数の本体を辿り、スコープを抜けるときにそのスコープで宣言されて一度も読まれなかった変数を警告する。
+// スコープの区切りはresolverと同じで、ブロック文、while/forの本体、for全体がそれぞれスコープを持つ
+#[derive(Default)]
+struct UnusedVariableChecker {
+    scopes: Vec<Vec<Declaration>>,
+    warnings: Vec<CompileWarning>,
+}
+
+impl UnusedVariableChecker {
+    fn declare(&mut self, name: &str, kind: VariableKind, range: Range, allow_unused: bool) {
+        // 同じスコープで再宣言された変数も、前の宣言の使用状況を追えるように残しておく
+        self.scopes.last_mut().unwrap().push(Declaration {
+            name: name.to_string(),
+            kind,
+            range,
+            allow_unused,
+            is_used: false,
+        });
+    }
+
+    // 関数の外で宣言された名前(グローバル変数など)は何もしない
+    fn mark_used(&mut self, name: &str) {
+        let declaration = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|decl| decl.name == name));
+        if let Some(declaration) = declaration {
+            declaration.is_used = true;
+        }
+    }
+
+    fn in_new_scope(&mut self, f: impl FnOnce(&mut Self)) {
+        self.scopes.push(Vec::new());
+        f(self);
+        let scope = self.scopes.pop().unwrap();
+        for declaration in scope {
+            if declaration.is_used || declaration.allow_unused || declaration.name.starts_with('_')
+            {
+                continue;
+            }
+            let name = declaration.name;
+            let kind = match declaration.kind {
+                VariableKind::Parameter => CompileWarningKind::UnusedParameter { name },
+                VariableKind::Local => CompileWarningKind::UnusedVariable { name },
+                VariableKind::Global => continue,
+            };
+            self.warnings
+                .push(CompileWarning::new(declaration.range, kind));
+        }
+    }
+}
+
+impl Visitor for UnusedVariableChecker {
+    fn visit_function(&mut self, func: &Function) {
+        self.in_new_scope(|checker| {
+            for arg in &func.decl.args {
+                if let Argument::Normal {
+                    name, attributes, ..
+                } = arg
+                {
+                    let allow_unused = attributes.contains(&ArgumentAttribute::Unused);
+                    checker.declare(
+                        &name.value,
+                        VariableKind::Parameter,
+                        name.range,
+                        allow_unused,
+                    );
+                }
+            }
+            walk_function(checker, func);
+        });
+    }
+
+    fn visit_statement(&mut self, statement: &Located<Statement>) {
+        match &statement.value {
+            Statement::Block(_) => self.in_new_scope(|checker| walk_statement(checker, statement)),
+            // caseごとにスコープを持つ
+            Statement::Switch(switch) => {
+                self.visit_expression(switch.scrutinee.as_ref());
+                for (label, statements) in &switch.cases {
+                    self.visit_expression(label.as_ref());
+                    self.in_new_scope(|checker| walk_statements(checker, statements));
+                }
+                if let Some(statements) = &switch.default {
+                    self.in_new_scope(|checker| walk_statements(checker, statements));
+                }
+            }
+            Statement::DoWhile(do_while) => {
+                self.in_new_scope(|checker| walk_statements(checker, &do_while.body));
+                self.visit_expression(do_while.cond.as_ref());
+            }
+            _ => walk_statement(self, statement),
+        }
+    }
+
+    fn visit_expression(&mut self, expr: Located<&Expression>) {
+        match expr.value {
+            Expression::VariableRef(variable_ref) => self.mark_used(&variable_ref.name),
+            // 関数ポインタ型の変数は、名前で呼び出すことで使われる
+            Expression::Call(call) => {
+                self.mark_used(&call.name);
+                walk_expression(self, expr);
+            }
+            Expression::VariableDecl(variable_decls) => {
+                // 初期値の中では、まだ自身を参照できない
+                for decl in &variable_decls.decls {
+                    self.visit_expression(decl.value.value.as_inner_deref());
+                    self.declare(&decl.name, VariableKind::Local, decl.range, false);
+                }
+            }
+            Expression::Assignment(assignment) => {
+                // ポインタ経由の代入は変数の値を読んでいるので使用扱いにする
+                if assignment.deref_count > 0 || assignment.index_access.is_some() {
+                    self.mark_used(&assignment.name);
+                }
+                walk_expression(self, expr);
+            }
+            Expression::While(while_expr) => {
+                self.visit_expression(while_expr.cond.as_inner_deref());
+                self.in_new_scope(|checker| {
+                    for body_expr in &while_expr.body {
+                        checker.visit_expression(body_expr.as_inner_deref());
+                    }
+                });
+            }
+            Expression::For(for_expr) => {
+                // initで宣言した変数はfor全体のスコープに入る
+                self.in_new_scope(|checker| {
+                    for expr in [&for_expr.init, &for_expr.cond, &for_expr.step]
+                        .into_iter()
+                        .flatten()
+                    {
+                        checker.visit_expression(expr.as_inner_deref());
+                    }
+                    checker.in_new_scope(|checker| {
+                        for body_expr in &for_expr.body {
+                            checker.visit_expression(body_expr.as_inner_deref());
+                        }
+                    });
+                });
+            }
+            _ => walk_expression(self, expr),
+        }
+    }
+}
+
+// 関数の中で使われていない引数とローカル変数を警告する
+pub(super) fn report_unused_variables(context: &ResolverContext, func: &Function) {
+    let mut checker = UnusedVariableChecker::default();
+    checker.visit_function(func);
+    // ジェネリック関数は特殊化ごとに本体を解決するので、同じ警告を2回積まない
+    let mut warnings = context.warnings.borrow_mut();
+    for warning in checker.warnings {
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::{path::PathBuf, process::Command};
+
+// 一時ディレクトリにソースを書き出し、そのパスを返す
+fn write_source(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn compiler() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_hirou_compiler"))
+}
+
+#[test]
+fn test_emit_ir_to_stdout() {
+    let path = write_source(
+        "hirou_compiler_cli_emit_ir.hr",
+        "
+fn add(a: i32, b: i32): i32 {
+    (+ a b)
+}
+fn main(): i32 {
+    (add 40 2)
+}
+",
+    );
+    let output = compiler()
+        .arg(&path)
+        .args(["--emit", "ir", "-O1"])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("define i32 @add(i32 %a, i32 %b)"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("define i32 @main()"), "{}", stdout);
+}
+
+#[test]
+fn test_emit_obj() {
+    let path = write_source(
+        "hirou_compiler_cli_emit_obj.hr",
+        "
+fn main(): void {}
+",
+    );
+    let object_path = std::env::temp_dir().join("hirou_compiler_cli_emit_obj.o");
+    let output = compiler()
+        .arg(&path)
+        .args([
+            "--emit",
+            "obj",
+            "--triple",
+            "x86_64-unknown-linux-gnu",
+            "-o",
+        ])
+        .arg(&object_path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let object = std::fs::read(&object_path).unwrap();
+    std::fs::remove_file(&object_path).unwrap();
+    assert_eq!(&object[..4], b"\x7fELF");
+}
+
+#[test]
+fn test_report_errors() {
+    let path = write_source(
+        "hirou_compiler_cli_report_errors.hr",
+        "
+fn main(): void {
+    (:= x: i32 undefined)
+}
+",
+    );
+    let output = compiler()
+        .arg(&path)
+        .args(["--emit", "ir"])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(!output.status.success(), "{:?}", output);
+    // 診断は標準エラーに出し、IRは出力しない
+    assert!(output.stdout.is_empty(), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("undefined"), "{}", stderr);
+}